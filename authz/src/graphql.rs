@@ -0,0 +1,34 @@
+use crate::{require, Action, Actor, Denied, Resource};
+use async_graphql::{Context, Error, ErrorExtensions};
+use context::{Scope, User as UserContext};
+
+impl From<Denied> for Error {
+    fn from(denied: Denied) -> Self {
+        let (message, code) = match denied {
+            Denied::Unauthenticated => ("unauthorized", "UNAUTHORIZED"),
+            Denied::Forbidden => ("forbidden", "FORBIDDEN"),
+        };
+
+        Error::new(message).extend_with(|_, extensions| extensions.set("code", code))
+    }
+}
+
+impl Actor {
+    /// Derive the actor from the request's context
+    pub fn from_graphql(ctx: &Context<'_>) -> Self {
+        let scope = ctx.data_unchecked::<Scope>();
+        let user = ctx.data_unchecked::<UserContext>();
+        Actor::from_context(scope, user)
+    }
+}
+
+/// Guard a field using the policy, with the actor derived from the request's context
+pub fn guard(
+    action: Action,
+    resource: Resource,
+) -> impl Fn(&Context<'_>) -> async_graphql::Result<()> + Send + Sync {
+    move |ctx| {
+        let actor = Actor::from_graphql(ctx);
+        require(&actor, action, &resource).map_err(Into::into)
+    }
+}
@@ -0,0 +1,460 @@
+use context::{Scope, User as UserContext, UserRole};
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::warn;
+
+#[cfg(feature = "graphql")]
+mod graphql;
+
+#[cfg(feature = "graphql")]
+pub use graphql::guard;
+
+/// Who is attempting to perform an action
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Actor {
+    /// The ID of the user, if they are authenticated
+    pub id: Option<i32>,
+    /// Whether the user has administrator privileges
+    pub is_admin: bool,
+    /// The role the user has within the organization or event owning the resource
+    pub role: Option<UserRole>,
+}
+
+impl Actor {
+    /// Derive the actor from the gateway-provided context. Administrator privileges only apply
+    /// within the admin scope.
+    pub fn from_context(scope: &Scope, user: &UserContext) -> Self {
+        match user {
+            UserContext::Authenticated(user) => Actor {
+                id: Some(user.id),
+                is_admin: user.is_admin && matches!(scope, Scope::Admin),
+                role: user.role,
+            },
+            _ => Actor::default(),
+        }
+    }
+
+    /// Check if the actor is the specified user
+    fn is(&self, id: i32) -> bool {
+        self.id == Some(id)
+    }
+
+    /// Check if the actor has at least the role
+    fn has_at_least(&self, role: UserRole) -> bool {
+        self.role.is_some_and(|actual| rank(actual) >= rank(role))
+    }
+}
+
+/// Something that can be done to a resource
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    /// View the publicly visible details
+    Read,
+    /// View details that are only visible to those managing the resource
+    ReadPrivate,
+    Create,
+    Update,
+    Archive,
+    Restore,
+    /// Extend when write-access expires
+    Extend,
+    Delete,
+    /// Add or remove organizers
+    ManageMembers,
+    /// Add or remove participants
+    ManageParticipants,
+    TransferOwnership,
+    /// Grant or revoke administrator privileges
+    GrantAdmin,
+}
+
+/// What an action is being performed on, along with the facts needed to make a decision
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resource {
+    Provider,
+    User {
+        id: i32,
+    },
+    Session {
+        user_id: i32,
+    },
+    Organization {
+        owner_id: Option<i32>,
+    },
+    Event,
+    Participant {
+        user_id: i32,
+    },
+    Webhook,
+    OidcClient,
+    FeatureFlag,
+    AllowedRedirectDomain,
+    /// Whether users are banned or suspended, and the notes kept about them
+    Moderation,
+    /// The labels used to group events and organizations
+    Label,
+    /// The previous versions of users and events
+    History,
+    /// The email domains whose users automatically join an organization
+    EmailDomain,
+    /// Every user, organization, and event, rather than only those the actor is part of
+    Directory,
+}
+
+/// The reason an action was denied
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Denied {
+    /// We do not know who the user is
+    Unauthenticated,
+    /// The user does not have permission
+    Forbidden,
+}
+
+/// The outcome of evaluating the policy, along with the rule that produced it
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Decision {
+    /// Whether the action is allowed
+    pub allowed: bool,
+    /// A human-readable description of the rule that matched
+    pub rule: &'static str,
+}
+
+impl Decision {
+    fn new(allowed: bool, rule: &'static str) -> Self {
+        Decision { allowed, rule }
+    }
+}
+
+/// Evaluate the policy for the actor performing the action on the resource
+pub fn decide(actor: &Actor, action: Action, resource: &Resource) -> Decision {
+    if actor.is_admin {
+        return Decision::new(true, "admins can do anything");
+    }
+
+    match (resource, action) {
+        (Resource::Provider, Action::Read) => Decision::new(true, "providers are public"),
+        (Resource::Provider, _) => Decision::new(false, "only admins can manage providers"),
+
+        (Resource::User { .. }, Action::GrantAdmin) => {
+            Decision::new(false, "only admins can grant admin")
+        }
+        (
+            Resource::User { id },
+            Action::Read | Action::ReadPrivate | Action::Update | Action::Delete,
+        ) => Decision::new(actor.is(*id), "users can only manage themselves"),
+        (Resource::User { .. }, _) => Decision::new(false, "unsupported action on users"),
+
+        (Resource::Session { user_id }, Action::Read | Action::Delete) => Decision::new(
+            actor.is(*user_id),
+            "users can only manage their own sessions",
+        ),
+        (Resource::Session { .. }, _) => Decision::new(false, "unsupported action on sessions"),
+
+        (Resource::Organization { .. }, Action::Read | Action::ReadPrivate) => Decision::new(
+            actor.has_at_least(UserRole::Organizer),
+            "organizations are visible to organizers",
+        ),
+        (Resource::Organization { .. }, Action::Update | Action::ManageMembers) => Decision::new(
+            actor.has_at_least(UserRole::Director),
+            "organizations are managed by directors",
+        ),
+        (Resource::Organization { owner_id }, Action::TransferOwnership) => Decision::new(
+            owner_id.is_some_and(|owner_id| actor.is(owner_id)),
+            "only the owner can transfer an organization",
+        ),
+        (Resource::Organization { .. }, _) => {
+            Decision::new(false, "only admins can create or delete organizations")
+        }
+
+        (Resource::Event, Action::Read) => Decision::new(
+            actor.has_at_least(UserRole::Participant),
+            "events are visible to participants",
+        ),
+        (Resource::Event, Action::ReadPrivate) => Decision::new(
+            actor.has_at_least(UserRole::Organizer),
+            "event details are visible to organizers",
+        ),
+        (Resource::Event, Action::Create | Action::Update | Action::Archive | Action::Delete) => {
+            Decision::new(
+                actor.has_at_least(UserRole::Director),
+                "events are managed by directors",
+            )
+        }
+        (Resource::Event, Action::ManageParticipants) => Decision::new(
+            actor.has_at_least(UserRole::Manager),
+            "participants are managed by managers",
+        ),
+        (Resource::Event, Action::Restore | Action::Extend) => {
+            Decision::new(false, "only admins can restore or extend events")
+        }
+        (Resource::Event, _) => Decision::new(false, "unsupported action on events"),
+
+        (Resource::Participant { user_id }, Action::Read | Action::Update) => Decision::new(
+            actor.is(*user_id) || actor.has_at_least(UserRole::Organizer),
+            "participants are visible to themselves and organizers",
+        ),
+        (Resource::Participant { .. }, _) => {
+            Decision::new(false, "unsupported action on participants")
+        }
+
+        (Resource::Webhook, _) => Decision::new(false, "only admins can manage webhooks"),
+
+        (Resource::OidcClient, _) => {
+            Decision::new(false, "only admins can manage openid connect clients")
+        }
+
+        (Resource::FeatureFlag, _) => Decision::new(false, "only admins can manage feature flags"),
+
+        (Resource::AllowedRedirectDomain, _) => {
+            Decision::new(false, "only admins can manage allowed redirect domains")
+        }
+
+        (Resource::Moderation, _) => Decision::new(false, "only admins can moderate users"),
+
+        (Resource::Label, _) => Decision::new(false, "only admins can manage labels"),
+
+        (Resource::History, _) => Decision::new(false, "only admins can view history"),
+
+        // Claiming a domain enrolls everyone with an email there, so ownership must be vetted
+        (Resource::EmailDomain, _) => {
+            Decision::new(false, "only admins can manage organization email domains")
+        }
+
+        (Resource::Directory, _) => Decision::new(false, "only admins can browse the directory"),
+    }
+}
+
+/// Check whether the actor can perform the action on the resource
+pub fn can(actor: &Actor, action: Action, resource: &Resource) -> bool {
+    decide(actor, action, resource).allowed
+}
+
+/// Require that the actor can perform the action on the resource
+///
+/// When [audit mode](set_audit) is enabled, denials are logged along with the rule that caused
+/// them.
+pub fn require(actor: &Actor, action: Action, resource: &Resource) -> Result<(), Denied> {
+    let decision = decide(actor, action, resource);
+    if decision.allowed {
+        return Ok(());
+    }
+
+    if AUDIT.load(Ordering::Relaxed) {
+        warn!(
+            target: "authz::audit",
+            actor.id,
+            actor.is_admin,
+            actor.role = ?actor.role,
+            ?action,
+            ?resource,
+            rule = decision.rule,
+            "authorization denied"
+        );
+    }
+
+    if actor.id.is_none() {
+        Err(Denied::Unauthenticated)
+    } else {
+        Err(Denied::Forbidden)
+    }
+}
+
+/// Whether authorization denials should be logged
+static AUDIT: AtomicBool = AtomicBool::new(false);
+
+/// Enable or disable logging every authorization denial
+pub fn set_audit(enabled: bool) {
+    AUDIT.store(enabled, Ordering::Relaxed);
+}
+
+/// The relative privilege of a role
+fn rank(role: UserRole) -> u8 {
+    match role {
+        UserRole::Director => 3,
+        UserRole::Manager => 2,
+        UserRole::Organizer => 1,
+        UserRole::Participant => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: i32, role: Option<UserRole>) -> Actor {
+        Actor {
+            id: Some(id),
+            is_admin: false,
+            role,
+        }
+    }
+
+    #[test]
+    fn admins_can_do_anything() {
+        let admin = Actor {
+            id: Some(1),
+            is_admin: true,
+            role: None,
+        };
+
+        assert!(can(&admin, Action::Restore, &Resource::Event));
+        assert!(can(&admin, Action::GrantAdmin, &Resource::User { id: 2 }));
+        assert!(can(&admin, Action::Create, &Resource::Provider));
+    }
+
+    #[test]
+    fn event_roles() {
+        let director = user(1, Some(UserRole::Director));
+        let manager = user(2, Some(UserRole::Manager));
+        let organizer = user(3, Some(UserRole::Organizer));
+        let participant = user(4, Some(UserRole::Participant));
+
+        assert!(can(&director, Action::Update, &Resource::Event));
+        assert!(!can(&manager, Action::Update, &Resource::Event));
+        assert!(can(&manager, Action::ManageParticipants, &Resource::Event));
+        assert!(!can(
+            &organizer,
+            Action::ManageParticipants,
+            &Resource::Event
+        ));
+        assert!(can(&organizer, Action::ReadPrivate, &Resource::Event));
+        assert!(!can(&participant, Action::ReadPrivate, &Resource::Event));
+        assert!(!can(&director, Action::Restore, &Resource::Event));
+    }
+
+    #[test]
+    fn users_can_only_manage_themselves() {
+        let actor = user(1, None);
+
+        assert!(can(&actor, Action::Update, &Resource::User { id: 1 }));
+        assert!(!can(&actor, Action::Update, &Resource::User { id: 2 }));
+        assert!(!can(&actor, Action::GrantAdmin, &Resource::User { id: 1 }));
+        assert!(can(
+            &actor,
+            Action::Delete,
+            &Resource::Session { user_id: 1 }
+        ));
+        assert!(!can(
+            &actor,
+            Action::Delete,
+            &Resource::Session { user_id: 2 }
+        ));
+    }
+
+    #[test]
+    fn only_the_owner_can_transfer_an_organization() {
+        let owner = user(1, Some(UserRole::Director));
+        let director = user(2, Some(UserRole::Director));
+        let resource = Resource::Organization { owner_id: Some(1) };
+
+        assert!(can(&owner, Action::TransferOwnership, &resource));
+        assert!(!can(&director, Action::TransferOwnership, &resource));
+    }
+
+    #[test]
+    fn only_admins_can_manage_webhooks() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::Webhook));
+        assert!(!can(&director, Action::Create, &Resource::Webhook));
+    }
+
+    #[test]
+    fn only_admins_can_manage_oidc_clients() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::OidcClient));
+        assert!(!can(&director, Action::Delete, &Resource::OidcClient));
+    }
+
+    #[test]
+    fn only_admins_can_manage_feature_flags() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::FeatureFlag));
+        assert!(!can(&director, Action::Update, &Resource::FeatureFlag));
+    }
+
+    #[test]
+    fn only_admins_can_manage_allowed_redirect_domains() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(
+            &director,
+            Action::Read,
+            &Resource::AllowedRedirectDomain
+        ));
+        assert!(!can(
+            &director,
+            Action::Create,
+            &Resource::AllowedRedirectDomain
+        ));
+        assert!(!can(
+            &director,
+            Action::Delete,
+            &Resource::AllowedRedirectDomain
+        ));
+    }
+
+    #[test]
+    fn only_admins_can_moderate_users() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::Moderation));
+        assert!(!can(&director, Action::Update, &Resource::Moderation));
+    }
+
+    #[test]
+    fn only_admins_can_manage_labels() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Create, &Resource::Label));
+        assert!(!can(&director, Action::Delete, &Resource::Label));
+    }
+
+    #[test]
+    fn only_admins_can_view_history() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::History));
+    }
+
+    #[test]
+    fn only_admins_can_manage_email_domains() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Create, &Resource::EmailDomain));
+        assert!(!can(&director, Action::Delete, &Resource::EmailDomain));
+    }
+
+    #[test]
+    fn only_admins_can_browse_the_directory() {
+        let director = user(1, Some(UserRole::Director));
+
+        assert!(!can(&director, Action::Read, &Resource::Directory));
+        assert!(!can(&Actor::default(), Action::Read, &Resource::Directory));
+    }
+
+    #[test]
+    fn decisions_include_the_matching_rule() {
+        let actor = user(1, Some(UserRole::Organizer));
+        let decision = decide(&actor, Action::Update, &Resource::Event);
+
+        assert!(!decision.allowed);
+        assert_eq!(decision.rule, "events are managed by directors");
+    }
+
+    #[test]
+    fn denials_distinguish_unauthenticated_users() {
+        let anonymous = Actor::default();
+        let actor = user(1, None);
+
+        assert_eq!(
+            require(&anonymous, Action::Read, &Resource::Event),
+            Err(Denied::Unauthenticated)
+        );
+        assert_eq!(
+            require(&actor, Action::Read, &Resource::Event),
+            Err(Denied::Forbidden)
+        );
+    }
+}
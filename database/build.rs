@@ -0,0 +1,4 @@
+fn main() {
+    // Re-embed the migrations whenever they change
+    println!("cargo:rerun-if-changed=../migrations");
+}
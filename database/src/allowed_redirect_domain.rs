@@ -0,0 +1,81 @@
+use crate::Result;
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, Executor};
+use tracing::instrument;
+
+/// A domain the OAuth flow is allowed to return to, in addition to those from the configuration
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct AllowedRedirectDomain {
+    /// The domain, which may contain globs
+    pub pattern: String,
+    /// Why the domain is allowed
+    pub description: String,
+    /// When the domain was allowed
+    pub created_at: DateTime<Utc>,
+}
+
+impl AllowedRedirectDomain {
+    /// Get all the domains
+    #[instrument(name = "AllowedRedirectDomain::all", skip_all)]
+    pub async fn all<'c, 'e, E>(db: E) -> Result<Vec<AllowedRedirectDomain>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let domains = query_as!(
+            AllowedRedirectDomain,
+            "SELECT * FROM allowed_redirect_domains ORDER BY pattern"
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(domains)
+    }
+
+    /// Allow a new domain
+    #[instrument(name = "AllowedRedirectDomain::create", skip(db))]
+    pub async fn create<'c, 'e, E>(
+        pattern: &str,
+        description: &str,
+        db: E,
+    ) -> Result<AllowedRedirectDomain>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let domain = query_as!(
+            AllowedRedirectDomain,
+            r#"
+            INSERT INTO allowed_redirect_domains (pattern, description)
+            VALUES ($1, $2)
+            RETURNING *
+            "#,
+            pattern,
+            description,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(domain)
+    }
+
+    /// Stop allowing a domain, returning whether it was allowed
+    #[instrument(name = "AllowedRedirectDomain::delete", skip(db))]
+    pub async fn delete<'c, 'e, E>(pattern: &str, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            "DELETE FROM allowed_redirect_domains WHERE pattern = $1",
+            pattern
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,73 @@
+use crate::Result;
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{query_as, Executor};
+use tracing::instrument;
+
+/// A record of a sensitive action that was performed
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuditLogEntry {
+    /// A unique ID
+    pub id: i64,
+    /// The user who performed the action, if any
+    pub actor_id: Option<i32>,
+    /// What was done, i.e. `organization.delete`
+    pub action: String,
+    /// The resource that was acted upon, i.e. `organization:1`
+    pub target: String,
+    /// Any additional context about the action
+    pub details: Value,
+    /// When the action was performed
+    pub created_at: DateTime<Utc>,
+}
+
+impl AuditLogEntry {
+    /// Get all the entries for a target, newest first
+    #[instrument(name = "AuditLogEntry::for_target", skip(db))]
+    pub async fn for_target<'c, 'e, E>(target: &str, db: E) -> Result<Vec<AuditLogEntry>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let entries = query_as!(
+            AuditLogEntry,
+            "SELECT * FROM audit_log WHERE target = $1 ORDER BY created_at DESC",
+            target
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Record that an action was performed
+    #[instrument(name = "AuditLogEntry::record", skip(details, db))]
+    pub async fn record<'c, 'e, E>(
+        actor_id: Option<i32>,
+        action: &str,
+        target: &str,
+        details: Value,
+        db: E,
+    ) -> Result<AuditLogEntry>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let entry = query_as!(
+            AuditLogEntry,
+            r#"
+            INSERT INTO audit_log (actor_id, action, target, details) 
+            VALUES ($1, $2, $3, $4) 
+            RETURNING *
+            "#,
+            actor_id,
+            action,
+            target,
+            details
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(entry)
+    }
+}
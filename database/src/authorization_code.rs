@@ -0,0 +1,229 @@
+use crate::{
+    oidc_client::{generate, hash},
+    Result,
+};
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{query, query_as, Executor};
+use tracing::instrument;
+
+/// How long a code can be exchanged for tokens
+const LIFETIME_MINUTES: i64 = 5;
+
+/// A single-use code issued by the OpenID Connect provider once a user authorizes a client
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AuthorizationCode {
+    /// The client the code was issued to
+    pub client_id: String,
+    /// The user who authorized the client
+    pub user_id: i32,
+    /// Where the user was returned to
+    pub redirect_uri: String,
+    /// The space-separated scopes that were granted
+    pub scope: String,
+    /// The value to include in the ID token, if requested
+    pub nonce: Option<String>,
+    /// The PKCE challenge the client must prove it knows the verifier for
+    pub code_challenge: Option<String>,
+    /// When the code can no longer be exchanged
+    pub expires_at: DateTime<Utc>,
+}
+
+impl AuthorizationCode {
+    /// Issue a new code, returning the value to send to the client
+    #[instrument(name = "AuthorizationCode::issue", skip(db))]
+    pub async fn issue<'c, 'e, E>(
+        client_id: &str,
+        user_id: i32,
+        redirect_uri: &str,
+        scope: &str,
+        nonce: Option<&str>,
+        code_challenge: Option<&str>,
+        db: E,
+    ) -> Result<String>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let code = generate(32);
+        let expires_at = Utc::now() + Duration::try_minutes(LIFETIME_MINUTES).unwrap();
+
+        query!(
+            r#"
+            INSERT INTO oidc_authorization_codes
+                (code_hash, client_id, user_id, redirect_uri, scope, nonce, code_challenge, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            "#,
+            hash(&code),
+            client_id,
+            user_id,
+            redirect_uri,
+            scope,
+            nonce,
+            code_challenge,
+            expires_at,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(code)
+    }
+
+    /// Consume the code, returning its details if it exists, has not expired, and was issued to the
+    /// client for the redirect URI. Codes issued with a PKCE challenge also require the matching
+    /// challenge to be computed from the client's verifier. A code can only be redeemed once, and a
+    /// request that doesn't match leaves the code untouched.
+    #[instrument(name = "AuthorizationCode::redeem", skip_all, fields(client.id = client_id))]
+    pub async fn redeem<'c, 'e, E>(
+        code: &str,
+        client_id: &str,
+        redirect_uri: &str,
+        code_challenge: Option<&str>,
+        db: E,
+    ) -> Result<Option<AuthorizationCode>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let code = query_as!(
+            AuthorizationCode,
+            r#"
+            DELETE FROM oidc_authorization_codes
+            WHERE code_hash = $1 AND client_id = $2 AND redirect_uri = $3
+                AND (code_challenge IS NULL OR code_challenge = $4)
+                AND expires_at > now()
+            RETURNING client_id, user_id, redirect_uri, scope, nonce, code_challenge, expires_at
+            "#,
+            hash(code),
+            client_id,
+            redirect_uri,
+            code_challenge,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(code)
+    }
+
+    /// Remove the codes that expired without being redeemed, returning how many were removed
+    #[instrument(name = "AuthorizationCode::purge_expired", skip_all)]
+    pub async fn purge_expired<'c, 'e, E>(db: E) -> Result<u64>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!("DELETE FROM oidc_authorization_codes WHERE expires_at <= now()")
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AuthorizationCode;
+    use crate::{OidcClient, User};
+    use sqlx::PgPool;
+
+    const REDIRECT_URI: &str = "https://client.example.com/callback";
+
+    async fn issue(challenge: Option<&str>, db: &PgPool) -> (String, String) {
+        let (client, _) = OidcClient::create("Client", &[REDIRECT_URI.to_owned()], db)
+            .await
+            .unwrap();
+        let user = User::create("Ada", "Lovelace", "ada@example.com", db)
+            .await
+            .unwrap();
+
+        let code = AuthorizationCode::issue(
+            &client.id,
+            user.id,
+            REDIRECT_URI,
+            "openid",
+            None,
+            challenge,
+            db,
+        )
+        .await
+        .unwrap();
+        (client.id, code)
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn redeem_once(db: PgPool) {
+        let (client_id, code) = issue(None, &db).await;
+
+        let redeemed = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, None, &db)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(redeemed.client_id, client_id);
+        assert_eq!(redeemed.scope, "openid");
+
+        let again = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, None, &db)
+            .await
+            .unwrap();
+        assert!(again.is_none());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn mismatched_requests_leave_code(db: PgPool) {
+        let (client_id, code) = issue(None, &db).await;
+
+        let other_client = AuthorizationCode::redeem(&code, "other", REDIRECT_URI, None, &db)
+            .await
+            .unwrap();
+        assert!(other_client.is_none());
+
+        let other_redirect = AuthorizationCode::redeem(
+            &code,
+            &client_id,
+            "https://attacker.example.com/callback",
+            None,
+            &db,
+        )
+        .await
+        .unwrap();
+        assert!(other_redirect.is_none());
+
+        let redeemed = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, None, &db)
+            .await
+            .unwrap();
+        assert!(redeemed.is_some());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn requires_matching_challenge(db: PgPool) {
+        let (client_id, code) = issue(Some("challenge"), &db).await;
+
+        let missing = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, None, &db)
+            .await
+            .unwrap();
+        assert!(missing.is_none());
+
+        let wrong = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, Some("wrong"), &db)
+            .await
+            .unwrap();
+        assert!(wrong.is_none());
+
+        let redeemed =
+            AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, Some("challenge"), &db)
+                .await
+                .unwrap();
+        assert!(redeemed.is_some());
+    }
+
+    #[sqlx::test(migrations = "../migrations")]
+    async fn expired_code_is_rejected(db: PgPool) {
+        let (client_id, code) = issue(None, &db).await;
+        sqlx::query("UPDATE oidc_authorization_codes SET expires_at = now()")
+            .execute(&db)
+            .await
+            .unwrap();
+
+        let redeemed = AuthorizationCode::redeem(&code, &client_id, REDIRECT_URI, None, &db)
+            .await
+            .unwrap();
+        assert!(redeemed.is_none());
+    }
+}
@@ -0,0 +1,61 @@
+use crate::Result;
+use serde::Deserialize;
+use sqlx::postgres::{PgListener, PgPool};
+use tracing::{instrument, warn};
+
+/// The channel that changes to cached tables are announced on
+const CHANNEL: &str = "cache_invalidation";
+
+/// A table whose rows are cached in memory or in the cache
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CachedTable {
+    /// The redirect domains allowed at runtime, keyed by pattern
+    AllowedRedirectDomains,
+    /// The custom domains of events, keyed by name
+    CustomDomains,
+    /// The feature flags, keyed by key
+    FeatureFlags,
+    /// The authentication providers, keyed by slug
+    Providers,
+}
+
+/// A row in a cached table that was created, updated, or deleted
+#[derive(Clone, Debug, Deserialize)]
+pub struct Change {
+    /// The table the row is in
+    pub table: CachedTable,
+    /// The key identifying the row
+    pub key: String,
+}
+
+/// Receives the changes made to cached tables, no matter which instance made them
+pub struct ChangeListener(PgListener);
+
+impl ChangeListener {
+    /// Start listening for changes on a dedicated connection
+    #[instrument(name = "ChangeListener::connect", skip_all)]
+    pub async fn connect(db: &PgPool) -> Result<ChangeListener> {
+        let mut listener = PgListener::connect_with(db).await?;
+        listener.listen(CHANNEL).await?;
+
+        Ok(ChangeListener(listener))
+    }
+
+    /// Wait for the next change. Returns `None` if the connection was lost, so changes may have
+    /// been missed. Listening resumes on the next call.
+    pub async fn recv(&mut self) -> Result<Option<Change>> {
+        loop {
+            let Some(notification) = self.0.try_recv().await? else {
+                return Ok(None);
+            };
+
+            match serde_json::from_str(notification.payload()) {
+                Ok(change) => return Ok(Some(change)),
+                Err(error) => {
+                    warn!(%error, payload = notification.payload(), "invalid change notification")
+                }
+            }
+        }
+    }
+}
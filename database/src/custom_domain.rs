@@ -6,11 +6,41 @@ use async_graphql::ResultExt;
 use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
 use futures::TryStreamExt;
-use sqlx::{query, query_as, Executor, QueryBuilder};
+use sqlx::{query, query_as, Acquire, Executor, QueryBuilder};
 #[cfg(feature = "graphql")]
 use std::collections::HashMap;
 use tracing::instrument;
 
+/// Where a custom domain's TLS certificate is in the provisioning process
+#[derive(Clone, Copy, Debug, Eq, PartialEq, sqlx::Type)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+#[sqlx(rename_all = "lowercase", type_name = "certificate_status")]
+pub enum CertificateStatus {
+    /// The edge proxy has not attempted to provision a certificate yet
+    Pending,
+    /// A certificate is being requested from the certificate authority
+    Issuing,
+    /// The certificate was issued and the domain is serving HTTPS
+    Issued,
+    /// The certificate could not be issued, usually because the DNS records are incorrect
+    Failed,
+    /// The certificate expired without being renewed
+    Expired,
+}
+
+impl CertificateStatus {
+    /// Get the name of the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CertificateStatus::Pending => "pending",
+            CertificateStatus::Issuing => "issuing",
+            CertificateStatus::Issued => "issued",
+            CertificateStatus::Failed => "failed",
+            CertificateStatus::Expired => "expired",
+        }
+    }
+}
+
 /// A custom domain the event is accessible at
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
@@ -21,7 +51,17 @@ pub struct CustomDomain {
     pub event: String,
     /// The domain name for the event
     pub name: String,
+    /// Whether this is the canonical domain for the event
+    pub is_primary: bool,
     // TODO: add verification fields
+    /// Where the TLS certificate for the domain is in the provisioning process
+    pub certificate_status: CertificateStatus,
+    /// Why the certificate could not be provisioned, if it failed
+    pub certificate_error: Option<String>,
+    /// When the current certificate expires, once one has been issued
+    pub certificate_expires_at: Option<DateTime<Utc>>,
+    /// When the edge proxy last reported the certificate's status
+    pub certificate_checked_at: Option<DateTime<Utc>>,
     /// When the custom domain was first created
     pub created_at: DateTime<Utc>,
     /// When the custom domain was last updated
@@ -36,14 +76,24 @@ impl CustomDomain {
         'c: 'e,
         E: 'e + Executor<'c, Database = sqlx::Postgres>,
     {
-        let domains = query_as!(CustomDomain, "SELECT * FROM custom_domains")
-            .fetch_all(db)
-            .await?;
+        let domains = query_as!(
+            CustomDomain,
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            "#
+        )
+        .fetch_all(db)
+        .await?;
 
         Ok(domains)
     }
 
-    /// Load all the custom domains by their events' slugs, for use in dataloaders
+    /// Load all the primary custom domains by their events' slugs, for use in dataloaders
     #[cfg(feature = "graphql")]
     pub(crate) async fn load<'c, 'e, E>(
         slugs: &[String],
@@ -55,7 +105,15 @@ impl CustomDomain {
     {
         let by_slug = query_as!(
             CustomDomain,
-            "SELECT * FROM custom_domains WHERE event = ANY($1)",
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            WHERE event = ANY($1) AND is_primary
+            "#,
             slugs
         )
         .fetch(db)
@@ -66,6 +124,40 @@ impl CustomDomain {
         Ok(by_slug)
     }
 
+    /// Load all the custom domains for the selected events by their slugs, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    pub(crate) async fn load_for_events<'c, 'e, E>(
+        slugs: &[String],
+        db: E,
+    ) -> Result<HashMap<String, Vec<CustomDomain>>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_event = query_as!(
+            CustomDomain,
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            WHERE event = ANY($1) ORDER BY is_primary DESC, name
+            "#,
+            slugs
+        )
+        .fetch(db)
+        .try_fold(HashMap::new(), |mut map, custom_domain| async {
+            let entry: &mut Vec<CustomDomain> = map.entry(custom_domain.event.clone()).or_default();
+            entry.push(custom_domain);
+            Ok(map)
+        })
+        .await?;
+
+        Ok(by_event)
+    }
+
     /// Test if a custom domain exists
     #[instrument(name = "CustomDomain::exists", skip(db))]
     pub async fn exists<'c, 'e, E>(name: &str, db: E) -> Result<bool>
@@ -100,7 +192,7 @@ impl CustomDomain {
         Ok(result.exists.unwrap_or_default())
     }
 
-    /// Get the custom domain for an event
+    /// Get the primary custom domain for an event
     #[instrument(name = "CustomDomain::find", skip(db))]
     pub async fn find<'c, 'e, E>(slug: &str, db: E) -> Result<Option<CustomDomain>>
     where
@@ -109,7 +201,15 @@ impl CustomDomain {
     {
         let domain = query_as!(
             CustomDomain,
-            "SELECT * FROM custom_domains WHERE event = $1",
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            WHERE event = $1 AND is_primary
+            "#,
             slug
         )
         .fetch_optional(db)
@@ -118,6 +218,32 @@ impl CustomDomain {
         Ok(domain)
     }
 
+    /// Get all the custom domains for an event
+    #[instrument(name = "CustomDomain::for_event", skip(db))]
+    pub async fn for_event<'c, 'e, E>(slug: &str, db: E) -> Result<Vec<CustomDomain>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let domains = query_as!(
+            CustomDomain,
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            WHERE event = $1 ORDER BY is_primary DESC, name
+            "#,
+            slug
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(domains)
+    }
+
     /// Get a custom domain by it's name
     #[instrument(name = "CustomDomain::find_by_name", skip(db))]
     pub async fn find_by_name<'c, 'e, E>(name: &str, db: E) -> Result<Option<CustomDomain>>
@@ -127,7 +253,15 @@ impl CustomDomain {
     {
         let domain = query_as!(
             CustomDomain,
-            "SELECT * FROM custom_domains WHERE name = $1",
+            r#"
+            SELECT
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            FROM custom_domains
+            WHERE name = $1
+            "#,
             name
         )
         .fetch_optional(db)
@@ -136,7 +270,7 @@ impl CustomDomain {
         Ok(domain)
     }
 
-    /// Create a new custom domain
+    /// Create a new custom domain, which becomes the primary if the event has none
     #[instrument(name = "CustomDomain::create", skip(db))]
     pub async fn create<'c, 'e, E>(name: &str, event: &str, db: E) -> Result<CustomDomain>
     where
@@ -145,7 +279,15 @@ impl CustomDomain {
     {
         let domain = query_as!(
             CustomDomain,
-            "INSERT INTO custom_domains (name, event) VALUES ($1, $2) RETURNING *",
+            r#"
+            INSERT INTO custom_domains (name, event, is_primary)
+            VALUES ($1, $2, NOT exists(SELECT 1 FROM custom_domains WHERE event = $2 AND is_primary))
+            RETURNING
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            "#,
             name,
             event
         )
@@ -155,12 +297,100 @@ impl CustomDomain {
         Ok(domain)
     }
 
+    /// Make the custom domain the primary domain for its event
+    #[instrument(name = "CustomDomain::make_primary", skip_all, fields(%self.event, %self.name))]
+    pub async fn make_primary<'a, A>(&mut self, db: A) -> Result<()>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        // The previous primary must be cleared first to satisfy the unique index
+        query!(
+            "UPDATE custom_domains SET is_primary = false WHERE event = $1 AND is_primary",
+            self.event
+        )
+        .execute(&mut *txn)
+        .await?;
+        query!(
+            "UPDATE custom_domains SET is_primary = true WHERE name = $1",
+            self.name
+        )
+        .execute(&mut *txn)
+        .await?;
+
+        txn.commit().await?;
+        self.is_primary = true;
+
+        Ok(())
+    }
+
+    /// Record the status of a custom domain's certificate as reported by the edge proxy, returning
+    /// the updated domain if it exists
+    #[instrument(name = "CustomDomain::report_certificate", skip(db))]
+    pub async fn report_certificate<'c, 'e, E>(
+        name: &str,
+        status: CertificateStatus,
+        error: Option<&str>,
+        expires_at: Option<DateTime<Utc>>,
+        db: E,
+    ) -> Result<Option<CustomDomain>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let domain = query_as!(
+            CustomDomain,
+            r#"
+            UPDATE custom_domains
+            SET
+                certificate_status = $2, certificate_error = $3, certificate_expires_at = $4,
+                certificate_checked_at = now()
+            WHERE name = $1
+            RETURNING
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            "#,
+            name,
+            status as _,
+            error,
+            expires_at,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(domain)
+    }
+
+    /// Mark the issued certificates that have passed their expiry as expired, in case the edge
+    /// proxy failed to renew them without reporting it. Returns how many were marked.
+    #[instrument(name = "CustomDomain::expire_certificates", skip_all)]
+    pub async fn expire_certificates<'c, 'e, E>(db: E) -> Result<u64>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            r#"
+            UPDATE custom_domains
+            SET certificate_status = 'expired'
+            WHERE certificate_status = 'issued' AND certificate_expires_at <= now()
+            "#
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
     /// Update the fields of a custom domain
     pub fn update(&mut self) -> CustomDomainUpdater<'_> {
         CustomDomainUpdater::new(self)
     }
 
-    /// Delete the custom domain for an event
+    /// Delete all the custom domains for an event
     #[instrument(name = "CustomDomain::delete", skip(db))]
     pub async fn delete<'c, 'e, E>(slug: &str, db: E) -> Result<()>
     where
@@ -173,6 +403,51 @@ impl CustomDomain {
 
         Ok(())
     }
+
+    /// Delete a custom domain by it's name, returning it if it existed. When the primary domain is
+    /// deleted, the event's oldest remaining domain becomes the primary.
+    #[instrument(name = "CustomDomain::delete_by_name", skip(db))]
+    pub async fn delete_by_name<'a, A>(name: &str, db: A) -> Result<Option<CustomDomain>>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        let domain = query_as!(
+            CustomDomain,
+            r#"
+            DELETE FROM custom_domains
+            WHERE name = $1
+            RETURNING
+                name, event, is_primary,
+                certificate_status as "certificate_status: CertificateStatus",
+                certificate_error, certificate_expires_at, certificate_checked_at,
+                created_at, updated_at
+            "#,
+            name
+        )
+        .fetch_optional(&mut *txn)
+        .await?;
+
+        if let Some(domain) = domain.as_ref().filter(|domain| domain.is_primary) {
+            query!(
+                r#"
+                UPDATE custom_domains SET is_primary = true
+                WHERE name = (
+                    SELECT name FROM custom_domains WHERE event = $1
+                    ORDER BY created_at, name LIMIT 1
+                )
+                "#,
+                domain.event
+            )
+            .execute(&mut *txn)
+            .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(domain)
+    }
 }
 
 #[cfg(feature = "graphql")]
@@ -219,7 +494,7 @@ impl<'c> CustomDomainUpdater<'c> {
     }
 
     /// Perform the update
-    #[instrument(name = "CustomDomain::update", skip_all, fields(self.event = %self.custom_domain.event))]
+    #[instrument(name = "CustomDomain::update", skip_all, fields(self.name = %self.custom_domain.name))]
     pub async fn save<'conn, 'e, E>(self, db: E) -> Result<()>
     where
         'conn: 'e,
@@ -238,8 +513,8 @@ impl<'c> CustomDomainUpdater<'c> {
             separated.push_bind_unseparated(name);
         }
 
-        builder.push(" WHERE event = ");
-        builder.push_bind(&self.custom_domain.event);
+        builder.push(" WHERE name = ");
+        builder.push_bind(&self.custom_domain.name);
         builder.build().execute(db).await?;
 
         if let Some(name) = self.name {
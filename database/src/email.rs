@@ -0,0 +1,253 @@
+use crate::{
+    oidc_client::{generate, hash},
+    Result,
+};
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Duration, Utc};
+#[cfg(feature = "graphql")]
+use futures::TryStreamExt;
+use sqlx::{query, query_as, Acquire, Executor};
+#[cfg(feature = "graphql")]
+use std::collections::HashMap;
+use tracing::instrument;
+
+/// How long a verification code can be used for
+const VERIFICATION_LIFETIME_HOURS: i64 = 24;
+
+/// The length of a verification code
+const VERIFICATION_CODE_LENGTH: usize = 8;
+
+/// An email address belonging to a user
+///
+/// An address can only belong to one user. Each user has exactly one primary address, which must
+/// be verified.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct Email {
+    /// The address
+    pub address: String,
+    /// The user the address belongs to
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub user_id: i32,
+    /// Whether this is the user's primary address
+    pub is_primary: bool,
+    /// Whether the user proved they can receive mail at the address
+    pub verified: bool,
+    /// When the address was added
+    pub created_at: DateTime<Utc>,
+    /// When the address was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Email {
+    /// Load all the addresses for the users, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    #[instrument(name = "Email::load_for_user", skip(db))]
+    pub(crate) async fn load_for_user<'c, 'e, E>(
+        user_ids: &[i32],
+        db: E,
+    ) -> Result<HashMap<i32, Vec<Email>>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_user_id = query_as!(
+            Email,
+            r#"
+            SELECT address, user_id, is_primary, verified, created_at, updated_at
+            FROM emails
+            WHERE user_id = ANY($1)
+            ORDER BY is_primary DESC, address
+            "#,
+            user_ids
+        )
+        .fetch(db)
+        .try_fold(HashMap::new(), |mut map, email| async move {
+            let entry: &mut Vec<Email> = map.entry(email.user_id).or_default();
+            entry.push(email);
+            Ok(map)
+        })
+        .await?;
+
+        Ok(by_user_id)
+    }
+
+    /// Get an address belonging to a user
+    #[instrument(name = "Email::find", skip(db))]
+    pub async fn find<'c, 'e, E>(user_id: i32, address: &str, db: E) -> Result<Option<Email>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let email = query_as!(
+            Email,
+            r#"
+            SELECT address, user_id, is_primary, verified, created_at, updated_at
+            FROM emails
+            WHERE user_id = $1 AND address = $2
+            "#,
+            user_id,
+            address,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(email)
+    }
+
+    /// Add an unverified address to a user, returning the code needed to verify it. Adding an
+    /// address that is still awaiting verification issues a new code. Returns `None` if the
+    /// address already belongs to another user or was already verified.
+    #[instrument(name = "Email::add", skip(db))]
+    pub async fn add<'c, 'e, E>(
+        user_id: i32,
+        address: &str,
+        db: E,
+    ) -> Result<Option<(Email, String)>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let code = generate(VERIFICATION_CODE_LENGTH);
+        let expires_at = Utc::now() + Duration::try_hours(VERIFICATION_LIFETIME_HOURS).unwrap();
+
+        let email = query_as!(
+            Email,
+            r#"
+            INSERT INTO emails (address, user_id, verification_code_hash, verification_expires_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (address) DO UPDATE
+                SET verification_code_hash = excluded.verification_code_hash,
+                    verification_expires_at = excluded.verification_expires_at
+                WHERE emails.user_id = excluded.user_id AND NOT emails.verified
+            RETURNING address, user_id, is_primary, verified, created_at, updated_at
+            "#,
+            address,
+            user_id,
+            hash(&code),
+            expires_at,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(email.map(|email| (email, code)))
+    }
+
+    /// Mark an address as verified if the code is correct and has not expired. A code can only be
+    /// used once.
+    #[instrument(name = "Email::verify", skip(code, db))]
+    pub async fn verify<'c, 'e, E>(
+        user_id: i32,
+        address: &str,
+        code: &str,
+        db: E,
+    ) -> Result<Option<Email>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let email = query_as!(
+            Email,
+            r#"
+            UPDATE emails
+            SET verified = true, verification_code_hash = NULL, verification_expires_at = NULL
+            WHERE user_id = $1
+                AND address = $2
+                AND verification_code_hash = $3
+                AND verification_expires_at > now()
+            RETURNING address, user_id, is_primary, verified, created_at, updated_at
+            "#,
+            user_id,
+            address,
+            hash(code),
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(email)
+    }
+
+    /// Remove the addresses whose verification code expired before they were verified, freeing
+    /// them to be added by someone else. Returns how many were removed.
+    #[instrument(name = "Email::purge_unverified", skip_all)]
+    pub async fn purge_unverified<'c, 'e, E>(db: E) -> Result<u64>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            r#"
+            DELETE FROM emails
+            WHERE NOT verified AND NOT is_primary AND verification_expires_at <= now()
+            "#
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Make a verified address the user's primary one. Returns `false` if the address does not
+    /// belong to the user or is unverified.
+    #[instrument(name = "Email::make_primary", skip(db))]
+    pub async fn make_primary<'a, A>(user_id: i32, address: &str, db: A) -> Result<bool>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        // Only one address can be primary at a time, so the previous one is cleared first
+        query!(
+            "UPDATE emails SET is_primary = false WHERE user_id = $1 AND is_primary",
+            user_id
+        )
+        .execute(&mut *txn)
+        .await?;
+
+        let result = query!(
+            r#"
+            UPDATE emails SET is_primary = true
+            WHERE user_id = $1 AND address = $2 AND verified
+            "#,
+            user_id,
+            address,
+        )
+        .execute(&mut *txn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        query!(
+            "UPDATE users SET primary_email = $2 WHERE id = $1",
+            user_id,
+            address
+        )
+        .execute(&mut *txn)
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Remove an address from a user. The primary address cannot be removed. Returns `false` if
+    /// nothing was removed.
+    #[instrument(name = "Email::remove", skip(db))]
+    pub async fn remove<'c, 'e, E>(user_id: i32, address: &str, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            "DELETE FROM emails WHERE user_id = $1 AND address = $2 AND NOT is_primary",
+            user_id,
+            address,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,17 +1,16 @@
 use crate::Result;
 #[cfg(feature = "graphql")]
 use crate::{
-    loaders::{CustomDomainLoader, OrganizationLoader},
-    CustomDomain, Organization,
+    loaders::{
+        CustomDomainLoader, CustomDomainsForEventLoader, LabelsForEventLoader, OrganizationLoader,
+    },
+    CustomDomain, EventExtensionRequest, HistoryEntry, Label, NodeId, Organization, PgPool,
 };
 #[cfg(feature = "graphql")]
 use async_graphql::ResultExt;
-use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
-use context::{
-    checks::{guard_where, has_at_least_role},
-    UserRole,
-};
+use authz::{guard, Action, Resource};
+use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
 use futures::TryStreamExt;
 use sqlx::{query, query_as, Executor, QueryBuilder};
@@ -36,9 +35,39 @@ pub struct Event {
     /// When write-access expires
     #[cfg_attr(
         feature = "graphql",
-        graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")
+        graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")
     )]
     pub expires_on: DateTime<Utc>,
+    /// When participants can start registering
+    pub registration_opens_at: Option<DateTime<Utc>>,
+    /// When participants can no longer register
+    pub registration_closes_at: Option<DateTime<Utc>>,
+    /// The maximum number of participants
+    pub capacity: Option<i32>,
+    /// The email domains participants must have, any email is allowed if empty. Subdomains of an
+    /// entry are also allowed, i.e. `edu` allows all `.edu` emails.
+    pub allowed_email_domains: Vec<String>,
+    /// When the event was archived
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")
+    )]
+    pub archived_at: Option<DateTime<Utc>>,
+    /// The logo shown in emails sent for the event, falling back to the organization's logo
+    pub email_logo_url: Option<String>,
+    /// The hex color used for buttons and links in emails sent for the event
+    pub email_accent_color: Option<String>,
+    /// Where replies to emails sent for the event are delivered
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")
+    )]
+    pub email_reply_to: Option<String>,
+    /// An additional message from the organizers included in the welcome email
+    pub email_welcome_message: Option<String>,
+    /// The number of participants registered for the event, kept up to date by the database
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub participant_count: i64,
     /// When the event was first created
     pub created_at: DateTime<Utc>,
     /// When the event was last updated
@@ -60,6 +89,52 @@ impl Event {
         Ok(events)
     }
 
+    /// Get all the events that have not been archived
+    #[instrument(name = "Event::all_unarchived", skip_all)]
+    pub async fn all_unarchived<'c, 'e, E>(db: E) -> Result<Vec<Event>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let events = query_as!(Event, "SELECT * FROM events WHERE archived_at IS NULL")
+            .fetch_all(db)
+            .await?;
+
+        Ok(events)
+    }
+
+    /// Get all the events with a label, optionally requiring it to have a specific value
+    #[instrument(name = "Event::all_with_label", skip(db))]
+    pub async fn all_with_label<'c, 'e, E>(
+        key: &str,
+        value: Option<&str>,
+        include_archived: bool,
+        db: E,
+    ) -> Result<Vec<Event>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let events = query_as!(
+            Event,
+            r#"
+            SELECT events.* FROM events
+            INNER JOIN labels ON events.slug = labels.event
+            WHERE
+                labels.key = $1
+                AND ($2::text IS NULL OR labels.value = $2)
+                AND ($3 OR events.archived_at IS NULL)
+            "#,
+            key,
+            value,
+            include_archived,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(events)
+    }
+
     /// Load all the events by their slugs, for use in dataloaders
     #[cfg(feature = "graphql")]
     pub(crate) async fn load<'c, 'e, E>(slugs: &[String], db: E) -> Result<HashMap<String, Event>>
@@ -100,6 +175,33 @@ impl Event {
         Ok(by_organization)
     }
 
+    /// Count the unarchived events for the selected organizations by their IDs, for use in
+    /// dataloaders
+    #[cfg(feature = "graphql")]
+    pub(crate) async fn count_for_organizations<'c, 'e, E>(
+        organization_ids: &[i32],
+        db: E,
+    ) -> Result<HashMap<i32, i64>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_organization = query!(
+            r#"
+            SELECT organization_id, count(*) as "count!" 
+            FROM events 
+            WHERE organization_id = ANY($1) AND archived_at IS NULL 
+            GROUP BY organization_id
+            "#,
+            organization_ids
+        )
+        .fetch(db)
+        .map_ok(|row| (row.organization_id, row.count))
+        .try_collect()
+        .await?;
+        Ok(by_organization)
+    }
+
     /// Get all the events for an organization
     #[instrument(name = "Event::for_organization", skip(db))]
     pub async fn for_organization<'c, 'e, E>(organization_id: i32, db: E) -> Result<Vec<Event>>
@@ -200,6 +302,45 @@ impl Event {
         self.expires_on >= Utc::now()
     }
 
+    /// Check if the event has been archived
+    pub fn is_archived(&self) -> bool {
+        self.archived_at.is_some()
+    }
+
+    /// Get the current state of registration for the event
+    pub fn registration_status(&self) -> RegistrationStatus {
+        let now = Utc::now();
+
+        if self.is_archived() {
+            RegistrationStatus::Closed
+        } else if matches!(self.registration_opens_at, Some(opens_at) if now < opens_at) {
+            RegistrationStatus::NotYetOpen
+        } else if matches!(self.registration_closes_at, Some(closes_at) if now >= closes_at) {
+            RegistrationStatus::Closed
+        } else {
+            RegistrationStatus::Open
+        }
+    }
+
+    /// Check if a participant with the email is allowed to join the event
+    pub fn allows_email(&self, email: &str) -> bool {
+        if self.allowed_email_domains.is_empty() {
+            return true;
+        }
+
+        let Some((_, domain)) = email.rsplit_once('@') else {
+            return false;
+        };
+        let domain = domain.to_lowercase();
+
+        self.allowed_email_domains.iter().any(|allowed| {
+            domain == *allowed
+                || domain
+                    .strip_suffix(allowed.as_str())
+                    .is_some_and(|rest| rest.ends_with('.'))
+        })
+    }
+
     /// Update the fields of an event
     pub fn update(&mut self) -> EventUpdater<'_> {
         EventUpdater::new(self)
@@ -223,11 +364,33 @@ impl Event {
 #[cfg(feature = "graphql")]
 #[async_graphql::ComplexObject]
 impl Event {
+    /// An opaque identifier that is unique across all types
+    async fn global_id(&self) -> async_graphql::ID {
+        NodeId::Event(self.slug.clone()).encode()
+    }
+
     /// Whether the event is active
     async fn active(&self) -> bool {
         self.is_active()
     }
 
+    /// Whether the event has been archived
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
+    async fn archived(&self) -> bool {
+        self.is_archived()
+    }
+
+    /// The number of participants registered for the event
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
+    async fn participant_count(&self) -> i64 {
+        self.participant_count
+    }
+
+    /// Whether participants can currently register for the event
+    async fn registration_status(&self) -> RegistrationStatus {
+        self.registration_status()
+    }
+
     /// The domain where the event is accessible
     #[instrument(name = "Event::domain", skip_all, fields(%self.slug))]
     async fn domain(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<String> {
@@ -243,8 +406,8 @@ impl Event {
         })
     }
 
-    /// The custom domain for the event
-    #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+    /// The primary custom domain for the event
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
     #[instrument(name = "Event::custom_domain", skip_all, fields(%self.slug))]
     async fn custom_domain(
         &self,
@@ -256,6 +419,67 @@ impl Event {
         Ok(custom_domain)
     }
 
+    /// All the custom domains for the event, starting with the primary domain
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
+    #[instrument(name = "Event::custom_domains", skip_all, fields(%self.slug))]
+    async fn custom_domains(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<CustomDomain>> {
+        let loader = ctx.data_unchecked::<CustomDomainsForEventLoader>();
+        let custom_domains = loader
+            .load_one(self.slug.to_owned())
+            .await
+            .extend()?
+            .unwrap_or_default();
+
+        Ok(custom_domains)
+    }
+
+    /// The labels used to group the event
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
+    #[instrument(name = "Event::labels", skip_all, fields(%self.slug))]
+    async fn labels(&self, ctx: &async_graphql::Context<'_>) -> async_graphql::Result<Vec<Label>> {
+        let loader = ctx.data_unchecked::<LabelsForEventLoader>();
+        let labels = loader
+            .load_one(self.slug.to_owned())
+            .await
+            .extend()?
+            .unwrap_or_default();
+
+        Ok(labels)
+    }
+
+    /// Requests to extend when write-access to the event expires, newest first
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Event)")]
+    #[instrument(name = "Event::extension_requests", skip_all, fields(%self.slug))]
+    async fn extension_requests(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+    ) -> async_graphql::Result<Vec<EventExtensionRequest>> {
+        let db = ctx.data_unchecked::<PgPool>();
+        let requests = EventExtensionRequest::for_event(&self.slug, db)
+            .await
+            .extend()?;
+
+        Ok(requests)
+    }
+
+    /// The previous versions of the event, newest first. If a point in time is provided, only the
+    /// version that was valid at that time is returned.
+    #[graphql(guard = "guard(Action::Read, Resource::History)")]
+    #[instrument(name = "Event::history", skip_all, fields(%self.slug))]
+    async fn history(
+        &self,
+        ctx: &async_graphql::Context<'_>,
+        at: Option<DateTime<Utc>>,
+    ) -> async_graphql::Result<Vec<HistoryEntry>> {
+        let db = ctx.data_unchecked::<PgPool>();
+        let entries = HistoryEntry::for_event(&self.slug, at, db).await.extend()?;
+
+        Ok(entries)
+    }
+
     /// The organization that owns the event
     #[instrument(name = "Event::organization", skip_all, fields(%self.slug))]
     async fn organization(
@@ -273,12 +497,33 @@ impl Event {
     }
 }
 
+/// The state of registration for an event
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
+pub enum RegistrationStatus {
+    /// Registration has not opened yet
+    NotYetOpen,
+    /// Participants can register
+    Open,
+    /// Registration has closed
+    Closed,
+}
+
 /// Handles updating individual fields of the event
 pub struct EventUpdater<'e> {
     event: &'e mut Event,
     name: Option<String>,
     organization_id: Option<i32>,
     expires_on: Option<DateTime<Utc>>,
+    registration_opens_at: Option<Option<DateTime<Utc>>>,
+    registration_closes_at: Option<Option<DateTime<Utc>>>,
+    capacity: Option<Option<i32>>,
+    allowed_email_domains: Option<Vec<String>>,
+    archived_at: Option<Option<DateTime<Utc>>>,
+    email_logo_url: Option<Option<String>>,
+    email_accent_color: Option<Option<String>>,
+    email_reply_to: Option<Option<String>>,
+    email_welcome_message: Option<Option<String>>,
 }
 
 impl<'e> EventUpdater<'e> {
@@ -288,6 +533,15 @@ impl<'e> EventUpdater<'e> {
             name: None,
             organization_id: None,
             expires_on: None,
+            registration_opens_at: None,
+            registration_closes_at: None,
+            capacity: None,
+            allowed_email_domains: None,
+            archived_at: None,
+            email_logo_url: None,
+            email_accent_color: None,
+            email_reply_to: None,
+            email_welcome_message: None,
         }
     }
 
@@ -327,6 +581,108 @@ impl<'e> EventUpdater<'e> {
         self
     }
 
+    /// Set when registration opens
+    pub fn registration_opens_at(mut self, at: Option<DateTime<Utc>>) -> Self {
+        self.registration_opens_at = Some(at);
+        self
+    }
+
+    /// Override when registration opens
+    pub fn override_registration_opens_at(mut self, at: Option<Option<DateTime<Utc>>>) -> Self {
+        self.registration_opens_at = at;
+        self
+    }
+
+    /// Set when registration closes
+    pub fn registration_closes_at(mut self, at: Option<DateTime<Utc>>) -> Self {
+        self.registration_closes_at = Some(at);
+        self
+    }
+
+    /// Override when registration closes
+    pub fn override_registration_closes_at(mut self, at: Option<Option<DateTime<Utc>>>) -> Self {
+        self.registration_closes_at = at;
+        self
+    }
+
+    /// Set the maximum number of participants
+    pub fn capacity(mut self, capacity: Option<i32>) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Override the maximum number of participants
+    pub fn override_capacity(mut self, capacity: Option<Option<i32>>) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Set the email domains participants must have
+    pub fn allowed_email_domains(mut self, domains: Vec<String>) -> Self {
+        self.allowed_email_domains = Some(domains);
+        self
+    }
+
+    /// Override the email domains participants must have
+    pub fn override_allowed_email_domains(mut self, domains: Option<Vec<String>>) -> Self {
+        self.allowed_email_domains = domains;
+        self
+    }
+
+    /// Archive the event, or restore it when `None`
+    pub fn archived_at(mut self, at: Option<DateTime<Utc>>) -> Self {
+        self.archived_at = Some(at);
+        self
+    }
+
+    /// Set the logo shown in emails
+    pub fn email_logo_url(mut self, url: Option<String>) -> Self {
+        self.email_logo_url = Some(url);
+        self
+    }
+
+    /// Override the logo shown in emails
+    pub fn override_email_logo_url(mut self, url: Option<Option<String>>) -> Self {
+        self.email_logo_url = url;
+        self
+    }
+
+    /// Set the color used in emails
+    pub fn email_accent_color(mut self, color: Option<String>) -> Self {
+        self.email_accent_color = Some(color);
+        self
+    }
+
+    /// Override the color used in emails
+    pub fn override_email_accent_color(mut self, color: Option<Option<String>>) -> Self {
+        self.email_accent_color = color;
+        self
+    }
+
+    /// Set where replies to emails are delivered
+    pub fn email_reply_to(mut self, address: Option<String>) -> Self {
+        self.email_reply_to = Some(address);
+        self
+    }
+
+    /// Override where replies to emails are delivered
+    pub fn override_email_reply_to(mut self, address: Option<Option<String>>) -> Self {
+        self.email_reply_to = address;
+        self
+    }
+
+    /// Set the message included in the welcome email
+    pub fn email_welcome_message(mut self, message: Option<String>) -> Self {
+        self.email_welcome_message = Some(message);
+        self
+    }
+
+    /// Override the message included in the welcome email
+    pub fn override_email_welcome_message(mut self, message: Option<Option<String>>) -> Self {
+        self.email_welcome_message = message;
+        self
+    }
+
     /// Perform the update
     #[instrument(name = "Event::update", skip_all, fields(self.id = %self.event.slug))]
     pub async fn save<'c, 'ex, E>(self, db: E) -> Result<()>
@@ -334,7 +690,19 @@ impl<'e> EventUpdater<'e> {
         'c: 'ex,
         E: 'ex + Executor<'c, Database = sqlx::Postgres>,
     {
-        if self.name.is_none() && self.organization_id.is_none() && self.expires_on.is_none() {
+        if self.name.is_none()
+            && self.organization_id.is_none()
+            && self.expires_on.is_none()
+            && self.registration_opens_at.is_none()
+            && self.registration_closes_at.is_none()
+            && self.capacity.is_none()
+            && self.allowed_email_domains.is_none()
+            && self.archived_at.is_none()
+            && self.email_logo_url.is_none()
+            && self.email_accent_color.is_none()
+            && self.email_reply_to.is_none()
+            && self.email_welcome_message.is_none()
+        {
             // nothing changed
             return Ok(());
         }
@@ -357,6 +725,51 @@ impl<'e> EventUpdater<'e> {
             separated.push_bind_unseparated(expires_on);
         }
 
+        if let Some(registration_opens_at) = self.registration_opens_at {
+            separated.push("registration_opens_at = ");
+            separated.push_bind_unseparated(registration_opens_at);
+        }
+
+        if let Some(registration_closes_at) = self.registration_closes_at {
+            separated.push("registration_closes_at = ");
+            separated.push_bind_unseparated(registration_closes_at);
+        }
+
+        if let Some(capacity) = self.capacity {
+            separated.push("capacity = ");
+            separated.push_bind_unseparated(capacity);
+        }
+
+        if let Some(allowed_email_domains) = &self.allowed_email_domains {
+            separated.push("allowed_email_domains = ");
+            separated.push_bind_unseparated(allowed_email_domains);
+        }
+
+        if let Some(archived_at) = self.archived_at {
+            separated.push("archived_at = ");
+            separated.push_bind_unseparated(archived_at);
+        }
+
+        if let Some(email_logo_url) = &self.email_logo_url {
+            separated.push("email_logo_url = ");
+            separated.push_bind_unseparated(email_logo_url);
+        }
+
+        if let Some(email_accent_color) = &self.email_accent_color {
+            separated.push("email_accent_color = ");
+            separated.push_bind_unseparated(email_accent_color);
+        }
+
+        if let Some(email_reply_to) = &self.email_reply_to {
+            separated.push("email_reply_to = ");
+            separated.push_bind_unseparated(email_reply_to);
+        }
+
+        if let Some(email_welcome_message) = &self.email_welcome_message {
+            separated.push("email_welcome_message = ");
+            separated.push_bind_unseparated(email_welcome_message);
+        }
+
         builder.push(" WHERE slug = ");
         builder.push_bind(&self.event.slug);
         builder.build().execute(db).await?;
@@ -373,6 +786,42 @@ impl<'e> EventUpdater<'e> {
             self.event.expires_on = expires_on;
         }
 
+        if let Some(registration_opens_at) = self.registration_opens_at {
+            self.event.registration_opens_at = registration_opens_at;
+        }
+
+        if let Some(registration_closes_at) = self.registration_closes_at {
+            self.event.registration_closes_at = registration_closes_at;
+        }
+
+        if let Some(capacity) = self.capacity {
+            self.event.capacity = capacity;
+        }
+
+        if let Some(allowed_email_domains) = self.allowed_email_domains {
+            self.event.allowed_email_domains = allowed_email_domains;
+        }
+
+        if let Some(archived_at) = self.archived_at {
+            self.event.archived_at = archived_at;
+        }
+
+        if let Some(email_logo_url) = self.email_logo_url {
+            self.event.email_logo_url = email_logo_url;
+        }
+
+        if let Some(email_accent_color) = self.email_accent_color {
+            self.event.email_accent_color = email_accent_color;
+        }
+
+        if let Some(email_reply_to) = self.email_reply_to {
+            self.event.email_reply_to = email_reply_to;
+        }
+
+        if let Some(email_welcome_message) = self.email_welcome_message {
+            self.event.email_welcome_message = email_welcome_message;
+        }
+
         Ok(())
     }
 }
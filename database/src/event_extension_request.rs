@@ -0,0 +1,300 @@
+#[cfg(feature = "graphql")]
+use crate::{
+    loaders::{EventLoader, UserLoader},
+    Event, User,
+};
+use crate::{AuditLogEntry, Result};
+#[cfg(feature = "graphql")]
+use async_graphql::{ComplexObject, Context, Enum, ResultExt, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use sqlx::{query, query_as, Acquire, Executor};
+use tracing::instrument;
+
+/// Where an extension request is in the review process
+#[derive(Clone, Copy, Debug, Eq, PartialEq, sqlx::Type)]
+#[cfg_attr(feature = "graphql", derive(Enum))]
+#[sqlx(rename_all = "lowercase", type_name = "extension_request_status")]
+pub enum ExtensionRequestStatus {
+    /// Waiting for an admin to review it
+    Pending,
+    /// The event's write-access was extended
+    Approved,
+    /// The request was rejected
+    Denied,
+}
+
+impl ExtensionRequestStatus {
+    /// Get the name of the status
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtensionRequestStatus::Pending => "pending",
+            ExtensionRequestStatus::Approved => "approved",
+            ExtensionRequestStatus::Denied => "denied",
+        }
+    }
+}
+
+/// A request from an event's organizers to extend when its write-access expires
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(complex))]
+pub struct EventExtensionRequest {
+    /// A unique ID
+    pub id: i32,
+    /// The event to extend
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub event: String,
+    /// The user who requested the extension
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub requested_by: Option<i32>,
+    /// When write-access should expire instead
+    pub expires_on: DateTime<Utc>,
+    /// Why the extension is needed
+    pub reason: String,
+    /// Where the request is in the review process
+    pub status: ExtensionRequestStatus,
+    /// The admin who reviewed the request
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub reviewed_by: Option<i32>,
+    /// An explanation of the decision
+    pub review_note: Option<String>,
+    /// When the request was reviewed
+    pub reviewed_at: Option<DateTime<Utc>>,
+    /// When the request was made
+    pub created_at: DateTime<Utc>,
+}
+
+#[cfg(feature = "graphql")]
+#[ComplexObject]
+impl EventExtensionRequest {
+    /// The event to extend
+    #[instrument(name = "EventExtensionRequest::event", skip_all, fields(%self.id))]
+    async fn event(&self, ctx: &Context<'_>) -> async_graphql::Result<Event> {
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let event = loader
+            .load_one(self.event.clone())
+            .await
+            .extend()?
+            .expect("event must exist");
+
+        Ok(event)
+    }
+
+    /// The user who requested the extension, if they still exist
+    #[instrument(name = "EventExtensionRequest::requested_by", skip_all, fields(%self.id))]
+    async fn requested_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let Some(id) = self.requested_by else {
+            return Ok(None);
+        };
+
+        let loader = ctx.data_unchecked::<UserLoader>();
+        let user = loader.load_one(id).await.extend()?;
+
+        Ok(user)
+    }
+
+    /// The admin who reviewed the request, if they still exist
+    #[instrument(name = "EventExtensionRequest::reviewed_by", skip_all, fields(%self.id))]
+    async fn reviewed_by(&self, ctx: &Context<'_>) -> async_graphql::Result<Option<User>> {
+        let Some(id) = self.reviewed_by else {
+            return Ok(None);
+        };
+
+        let loader = ctx.data_unchecked::<UserLoader>();
+        let user = loader.load_one(id).await.extend()?;
+
+        Ok(user)
+    }
+}
+
+impl EventExtensionRequest {
+    /// Get all the requests waiting for review, oldest first
+    #[instrument(name = "EventExtensionRequest::pending", skip_all)]
+    pub async fn pending<'c, 'e, E>(db: E) -> Result<Vec<EventExtensionRequest>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let requests = query_as!(
+            EventExtensionRequest,
+            r#"
+            SELECT id, event, requested_by, expires_on, reason,
+                status as "status: ExtensionRequestStatus", reviewed_by, review_note, reviewed_at,
+                created_at
+            FROM event_extension_requests
+            WHERE status = 'pending'
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(requests)
+    }
+
+    /// Get all the requests for an event, newest first
+    #[instrument(name = "EventExtensionRequest::for_event", skip(db))]
+    pub async fn for_event<'c, 'e, E>(event: &str, db: E) -> Result<Vec<EventExtensionRequest>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let requests = query_as!(
+            EventExtensionRequest,
+            r#"
+            SELECT id, event, requested_by, expires_on, reason,
+                status as "status: ExtensionRequestStatus", reviewed_by, review_note, reviewed_at,
+                created_at
+            FROM event_extension_requests
+            WHERE event = $1
+            ORDER BY created_at DESC
+            "#,
+            event
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(requests)
+    }
+
+    /// Find a request by its ID
+    #[instrument(name = "EventExtensionRequest::find", skip(db))]
+    pub async fn find<'c, 'e, E>(id: i32, db: E) -> Result<Option<EventExtensionRequest>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let request = query_as!(
+            EventExtensionRequest,
+            r#"
+            SELECT id, event, requested_by, expires_on, reason,
+                status as "status: ExtensionRequestStatus", reviewed_by, review_note, reviewed_at,
+                created_at
+            FROM event_extension_requests
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(request)
+    }
+
+    /// Request an extension for an event. The request is recorded in the audit log.
+    #[instrument(name = "EventExtensionRequest::create", skip(db))]
+    pub async fn create<'a, A>(
+        event: &str,
+        requested_by: i32,
+        expires_on: DateTime<Utc>,
+        reason: &str,
+        db: A,
+    ) -> Result<EventExtensionRequest>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        let request = query_as!(
+            EventExtensionRequest,
+            r#"
+            INSERT INTO event_extension_requests (event, requested_by, expires_on, reason)
+            VALUES ($1, $2, $3, $4)
+            RETURNING id, event, requested_by, expires_on, reason,
+                status as "status: ExtensionRequestStatus", reviewed_by, review_note, reviewed_at,
+                created_at
+            "#,
+            event,
+            requested_by,
+            expires_on,
+            reason,
+        )
+        .fetch_one(&mut *txn)
+        .await?;
+
+        AuditLogEntry::record(
+            Some(requested_by),
+            "event.extension.request",
+            &format!("event:{event}"),
+            json!({ "request": request.id, "expires_on": expires_on, "reason": reason }),
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(request)
+    }
+
+    /// Approve or deny a pending request, extending the event if approved. The decision is
+    /// recorded in the audit log. Returns `false` if the request was no longer pending.
+    #[instrument(name = "EventExtensionRequest::review", skip(self, db), fields(%self.id))]
+    pub async fn review<'a, A>(
+        &mut self,
+        approved: bool,
+        reviewer: i32,
+        note: Option<String>,
+        db: A,
+    ) -> Result<bool>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let status = if approved {
+            ExtensionRequestStatus::Approved
+        } else {
+            ExtensionRequestStatus::Denied
+        };
+
+        let mut txn = db.begin().await?;
+
+        let result = query!(
+            r#"
+            UPDATE event_extension_requests
+            SET status = $2, reviewed_by = $3, review_note = $4, reviewed_at = now()
+            WHERE id = $1 AND status = 'pending'
+            RETURNING reviewed_at as "reviewed_at!"
+            "#,
+            self.id,
+            status as ExtensionRequestStatus,
+            reviewer,
+            note.as_deref(),
+        )
+        .fetch_optional(&mut *txn)
+        .await?;
+        let Some(result) = result else {
+            return Ok(false);
+        };
+
+        if approved {
+            query!(
+                "UPDATE events SET expires_on = $2 WHERE slug = $1",
+                &self.event,
+                self.expires_on
+            )
+            .execute(&mut *txn)
+            .await?;
+        }
+
+        AuditLogEntry::record(
+            Some(reviewer),
+            &format!(
+                "event.extension.{}",
+                if approved { "approve" } else { "deny" }
+            ),
+            &format!("event:{}", self.event),
+            json!({ "request": self.id, "expires_on": self.expires_on, "note": note }),
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        self.status = status;
+        self.reviewed_by = Some(reviewer);
+        self.review_note = note;
+        self.reviewed_at = Some(result.reviewed_at);
+
+        Ok(true)
+    }
+}
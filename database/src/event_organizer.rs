@@ -0,0 +1,133 @@
+#[cfg(feature = "graphql")]
+use crate::{
+    loaders::{EventLoader, UserLoader},
+    Event, User,
+};
+use crate::{Result, Role};
+#[cfg(feature = "graphql")]
+use async_graphql::{ComplexObject, Context, ResultExt, SimpleObject};
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, Executor};
+use tracing::instrument;
+
+/// Grants a user organizer access to a single event, without making them part of the
+/// organization putting it on
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(complex))]
+pub struct EventOrganizer {
+    /// The event slug
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub event: String,
+    /// The user ID
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub user_id: i32,
+    /// The permissions the user has within the event
+    pub role: Role,
+    /// When the mapping was created
+    pub created_at: DateTime<Utc>,
+    /// When the mapping was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+#[cfg(feature = "graphql")]
+#[ComplexObject]
+impl EventOrganizer {
+    /// The event the user is organizing
+    #[instrument(name = "EventOrganizer::event", skip_all, fields(%self.event, %self.user_id))]
+    async fn event(&self, ctx: &Context<'_>) -> async_graphql::Result<Event> {
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let event = loader
+            .load_one(self.event.clone())
+            .await
+            .extend()?
+            .expect("event must exist");
+
+        Ok(event)
+    }
+
+    /// The user organizing the event
+    #[instrument(name = "EventOrganizer::user", skip_all, fields(%self.event, %self.user_id))]
+    async fn user(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
+        let loader = ctx.data_unchecked::<UserLoader>();
+        let user = loader
+            .load_one(self.user_id)
+            .await
+            .extend()?
+            .expect("user must exist");
+
+        Ok(user)
+    }
+}
+
+impl EventOrganizer {
+    /// Get all the users organizing an event
+    #[instrument(name = "EventOrganizer::for_event", skip(db))]
+    pub async fn for_event<'c, 'e, E>(event: &str, db: E) -> Result<Vec<EventOrganizer>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let organizers = query_as!(
+            EventOrganizer,
+            r#"
+            SELECT event, user_id, role as "role: Role", created_at, updated_at
+            FROM event_organizers
+            WHERE event = $1
+            "#,
+            event,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(organizers)
+    }
+
+    /// Grant a user organizer access to an event, changing their role if they already have it
+    #[instrument(name = "EventOrganizer::add", skip(db))]
+    pub async fn add<'c, 'e, E>(
+        event: &str,
+        user_id: i32,
+        role: Role,
+        db: E,
+    ) -> Result<EventOrganizer>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let organizer = query_as!(
+            EventOrganizer,
+            r#"
+            INSERT INTO event_organizers (event, user_id, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (event, user_id) DO UPDATE SET role = excluded.role
+            RETURNING event, user_id, role as "role: Role", created_at, updated_at
+            "#,
+            event,
+            user_id,
+            role as _,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(organizer)
+    }
+
+    /// Revoke a user's organizer access to an event
+    #[instrument(name = "EventOrganizer::delete", skip(db))]
+    pub async fn delete<'c, 'e, E>(event: &str, user_id: i32, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        query!(
+            "DELETE FROM event_organizers WHERE event = $1 AND user_id = $2",
+            event,
+            user_id,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
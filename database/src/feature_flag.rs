@@ -0,0 +1,96 @@
+use crate::Result;
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, Executor};
+use tracing::instrument;
+
+/// Controls whether a feature is available, allowing it to be rolled out gradually
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct FeatureFlag {
+    /// A unique key identifying the feature
+    pub key: String,
+    /// What the flag controls
+    pub description: String,
+    /// Whether the feature is enabled everywhere
+    pub enabled: bool,
+    /// The slugs of events the feature is enabled for, even when it is not enabled everywhere
+    pub enabled_events: Vec<String>,
+    /// When the flag was created
+    pub created_at: DateTime<Utc>,
+    /// When the flag was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+impl FeatureFlag {
+    /// Get all the flags
+    #[instrument(name = "FeatureFlag::all", skip_all)]
+    pub async fn all<'c, 'e, E>(db: E) -> Result<Vec<FeatureFlag>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let flags = query_as!(FeatureFlag, "SELECT * FROM feature_flags ORDER BY key")
+            .fetch_all(db)
+            .await?;
+
+        Ok(flags)
+    }
+
+    /// Create a flag or update an existing one, leaving any unspecified fields unchanged. New flags
+    /// are disabled unless otherwise specified.
+    #[instrument(name = "FeatureFlag::set", skip(db))]
+    pub async fn set<'c, 'e, E>(
+        key: &str,
+        description: Option<&str>,
+        enabled: Option<bool>,
+        enabled_events: Option<&[String]>,
+        db: E,
+    ) -> Result<FeatureFlag>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let flag = query_as!(
+            FeatureFlag,
+            r#"
+            INSERT INTO feature_flags (key, description, enabled, enabled_events)
+            VALUES ($1, coalesce($2, ''), coalesce($3, false), coalesce($4, '{}'::text[]))
+            ON CONFLICT (key) DO UPDATE SET
+                description = coalesce($2, feature_flags.description),
+                enabled = coalesce($3, feature_flags.enabled),
+                enabled_events = coalesce($4, feature_flags.enabled_events)
+            RETURNING *
+            "#,
+            key,
+            description,
+            enabled,
+            enabled_events,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(flag)
+    }
+
+    /// Check whether the feature is enabled, either everywhere or for the event
+    pub fn is_enabled_for(&self, event: Option<&str>) -> bool {
+        self.enabled
+            || event.is_some_and(|event| self.enabled_events.iter().any(|slug| slug == event))
+    }
+
+    /// Delete a flag by its key, returning whether it existed
+    #[instrument(name = "FeatureFlag::delete", skip(db))]
+    pub async fn delete<'c, 'e, E>(key: &str, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!("DELETE FROM feature_flags WHERE key = $1", key)
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -0,0 +1,251 @@
+//! Factories for inserting realistic rows in tests, without hand-writing SQL
+//!
+//! Each factory fills in unique values for anything that isn't set, and creates the rows it
+//! depends on when they aren't given.
+
+use crate::{Event, Organization, Organizer, Participant, ParticipantRole, Result, Role, User};
+use rand::distributions::{Alphanumeric, DistString};
+use sqlx::Acquire;
+
+/// Generate a value that is unique between tests, even when they share a database
+fn unique(prefix: &str) -> String {
+    let suffix = Alphanumeric.sample_string(&mut rand::thread_rng(), 10);
+    format!("{prefix}-{}", suffix.to_lowercase())
+}
+
+/// Create a [`User`]
+#[derive(Debug)]
+pub struct UserFactory {
+    given_name: String,
+    family_name: String,
+    primary_email: Option<String>,
+}
+
+impl Default for UserFactory {
+    fn default() -> Self {
+        Self {
+            given_name: String::from("Ada"),
+            family_name: String::from("Lovelace"),
+            primary_email: None,
+        }
+    }
+}
+
+impl UserFactory {
+    /// Set the given name
+    pub fn given_name(mut self, given_name: impl Into<String>) -> UserFactory {
+        self.given_name = given_name.into();
+        self
+    }
+
+    /// Set the family name
+    pub fn family_name(mut self, family_name: impl Into<String>) -> UserFactory {
+        self.family_name = family_name.into();
+        self
+    }
+
+    /// Set the primary email
+    pub fn primary_email(mut self, primary_email: impl Into<String>) -> UserFactory {
+        self.primary_email = Some(primary_email.into());
+        self
+    }
+
+    /// Insert the user
+    pub async fn create<'a, A>(self, db: A) -> Result<User>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut conn = db.acquire().await?;
+
+        let primary_email = self
+            .primary_email
+            .unwrap_or_else(|| format!("{}@example.com", unique("user")));
+        User::create(
+            &self.given_name,
+            &self.family_name,
+            &primary_email,
+            &mut *conn,
+        )
+        .await
+    }
+}
+
+/// Create an [`Organization`], along with its owner if none is set
+#[derive(Debug, Default)]
+pub struct OrganizationFactory {
+    slug: Option<String>,
+    name: Option<String>,
+    owner_id: Option<i32>,
+}
+
+impl OrganizationFactory {
+    /// Set the slug
+    pub fn slug(mut self, slug: impl Into<String>) -> OrganizationFactory {
+        self.slug = Some(slug.into());
+        self
+    }
+
+    /// Set the display name
+    pub fn name(mut self, name: impl Into<String>) -> OrganizationFactory {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the owner
+    pub fn owner(mut self, id: i32) -> OrganizationFactory {
+        self.owner_id = Some(id);
+        self
+    }
+
+    /// Insert the organization
+    pub async fn create<'a, A>(self, db: A) -> Result<Organization>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut conn = db.acquire().await?;
+
+        let owner_id = match self.owner_id {
+            Some(id) => id,
+            None => UserFactory::default().create(&mut *conn).await?.id,
+        };
+        let slug = self.slug.unwrap_or_else(|| unique("organization"));
+        let name = self.name.unwrap_or_else(|| String::from("Hack Club"));
+        Organization::create(&slug, &name, owner_id, &mut *conn).await
+    }
+}
+
+/// Create an [`Event`], along with its organization if none is set
+#[derive(Debug, Default)]
+pub struct EventFactory {
+    slug: Option<String>,
+    name: Option<String>,
+    organization_id: Option<i32>,
+}
+
+impl EventFactory {
+    /// Set the slug
+    pub fn slug(mut self, slug: impl Into<String>) -> EventFactory {
+        self.slug = Some(slug.into());
+        self
+    }
+
+    /// Set the display name
+    pub fn name(mut self, name: impl Into<String>) -> EventFactory {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Set the organization putting on the event
+    pub fn organization(mut self, id: i32) -> EventFactory {
+        self.organization_id = Some(id);
+        self
+    }
+
+    /// Insert the event
+    pub async fn create<'a, A>(self, db: A) -> Result<Event>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut conn = db.acquire().await?;
+
+        let organization_id = match self.organization_id {
+            Some(id) => id,
+            None => OrganizationFactory::default().create(&mut *conn).await?.id,
+        };
+        let slug = self.slug.unwrap_or_else(|| unique("event"));
+        let name = self.name.unwrap_or_else(|| String::from("Hack the North"));
+        Event::create(&slug, &name, organization_id, &mut *conn).await
+    }
+}
+
+/// Add a [`Participant`] to an event, creating the event and user if they are not set
+#[derive(Debug, Default)]
+pub struct ParticipantFactory {
+    event: Option<String>,
+    user_id: Option<i32>,
+    role: ParticipantRole,
+}
+
+impl ParticipantFactory {
+    /// Set the event
+    pub fn event(mut self, slug: impl Into<String>) -> ParticipantFactory {
+        self.event = Some(slug.into());
+        self
+    }
+
+    /// Set the user
+    pub fn user(mut self, id: i32) -> ParticipantFactory {
+        self.user_id = Some(id);
+        self
+    }
+
+    /// Set the role
+    pub fn role(mut self, role: ParticipantRole) -> ParticipantFactory {
+        self.role = role;
+        self
+    }
+
+    /// Insert the participant
+    pub async fn create<'a, A>(self, db: A) -> Result<Participant>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut conn = db.acquire().await?;
+
+        let event = match self.event {
+            Some(slug) => slug,
+            None => EventFactory::default().create(&mut *conn).await?.slug,
+        };
+        let user_id = match self.user_id {
+            Some(id) => id,
+            None => UserFactory::default().create(&mut *conn).await?.id,
+        };
+        Participant::add(&event, user_id, Some(self.role), &mut *conn).await
+    }
+}
+
+/// Add an [`Organizer`] to an organization, creating the organization and user if they are not set
+#[derive(Debug, Default)]
+pub struct OrganizerFactory {
+    organization_id: Option<i32>,
+    user_id: Option<i32>,
+    role: Role,
+}
+
+impl OrganizerFactory {
+    /// Set the organization
+    pub fn organization(mut self, id: i32) -> OrganizerFactory {
+        self.organization_id = Some(id);
+        self
+    }
+
+    /// Set the user
+    pub fn user(mut self, id: i32) -> OrganizerFactory {
+        self.user_id = Some(id);
+        self
+    }
+
+    /// Set the role
+    pub fn role(mut self, role: Role) -> OrganizerFactory {
+        self.role = role;
+        self
+    }
+
+    /// Insert the organizer
+    pub async fn create<'a, A>(self, db: A) -> Result<Organizer>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut conn = db.acquire().await?;
+
+        let organization_id = match self.organization_id {
+            Some(id) => id,
+            None => OrganizationFactory::default().create(&mut *conn).await?.id,
+        };
+        let user_id = match self.user_id {
+            Some(id) => id,
+            None => UserFactory::default().create(&mut *conn).await?.id,
+        };
+        Organizer::add(organization_id, user_id, self.role, &mut *conn).await
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{Json, Result};
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use serde_json::{Map, Value};
+use sqlx::{query_as, Executor};
+use tracing::instrument;
+
+/// A previous version of a user or event, recorded by the database whenever it changes
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct HistoryEntry {
+    /// The fields as they were, keyed by their column names
+    pub data: Json<Map<String, Value>>,
+    /// When the version took effect
+    pub valid_from: DateTime<Utc>,
+    /// When the version was replaced
+    pub valid_to: DateTime<Utc>,
+}
+
+impl HistoryEntry {
+    /// Get the previous versions of a user, newest first. If a point in time is provided, only
+    /// the version that was valid at that time is returned, which is nothing when the current
+    /// version was already in effect.
+    #[instrument(name = "HistoryEntry::for_user", skip(db))]
+    pub async fn for_user<'c, 'e, E>(
+        user_id: i32,
+        at: Option<DateTime<Utc>>,
+        db: E,
+    ) -> Result<Vec<HistoryEntry>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let entries = query_as!(
+            HistoryEntry,
+            r#"
+            SELECT data as "data: Json<Map<String, Value>>", valid_from, valid_to
+            FROM user_history
+            WHERE user_id = $1 AND ($2::timestamptz IS NULL OR (valid_from <= $2 AND $2 < valid_to))
+            ORDER BY valid_to DESC
+            "#,
+            user_id,
+            at,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(entries)
+    }
+
+    /// Get the previous versions of an event, newest first. If a point in time is provided, only
+    /// the version that was valid at that time is returned, which is nothing when the current
+    /// version was already in effect.
+    #[instrument(name = "HistoryEntry::for_event", skip(db))]
+    pub async fn for_event<'c, 'e, E>(
+        event: &str,
+        at: Option<DateTime<Utc>>,
+        db: E,
+    ) -> Result<Vec<HistoryEntry>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let entries = query_as!(
+            HistoryEntry,
+            r#"
+            SELECT data as "data: Json<Map<String, Value>>", valid_from, valid_to
+            FROM event_history
+            WHERE event = $1 AND ($2::timestamptz IS NULL OR (valid_from <= $2 AND $2 < valid_to))
+            ORDER BY valid_to DESC
+            "#,
+            event,
+            at,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(entries)
+    }
+}
@@ -23,6 +23,20 @@ pub struct Identity {
     pub created_at: DateTime<Utc>,
     /// When the identity was last updated
     pub updated_at: DateTime<Utc>,
+    /// When the identity was last used to sign in
+    pub last_login_at: Option<DateTime<Utc>>,
+    /// How many times the identity has been used to sign in
+    pub login_count: i32,
+}
+
+/// Aggregate statistics about how a provider is used
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct ProviderUsage {
+    /// The number of identities linked to the provider
+    pub identities: i64,
+    /// The number of identities used to sign in within the last 30 days
+    pub recent_logins: i64,
 }
 
 impl Identity {
@@ -51,6 +65,41 @@ impl Identity {
         Ok(by_user_id)
     }
 
+    /// Get the usage statistics for providers, for use in dataloaders
+    #[instrument(name = "Identity::usage_for_providers", skip(db))]
+    pub(crate) async fn usage_for_providers<'c, 'e, E>(
+        slugs: &[String],
+        db: E,
+    ) -> Result<HashMap<String, ProviderUsage>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_provider = query!(
+            r#"
+            SELECT
+                provider,
+                count(*) as "identities!",
+                count(*) FILTER (WHERE last_login_at > now() - interval '30 days') as "recent_logins!"
+            FROM identities
+            WHERE provider = ANY($1)
+            GROUP BY provider
+            "#,
+            slugs
+        )
+        .fetch(db)
+        .map_ok(|row| {
+            let usage = ProviderUsage {
+                identities: row.identities,
+                recent_logins: row.recent_logins,
+            };
+            (row.provider, usage)
+        })
+        .try_collect()
+        .await?;
+        Ok(by_provider)
+    }
+
     /// Get all the identities associated with a provider
     #[instrument(name = "Identity::for_user", skip(db))]
     pub async fn for_user<'c, 'e, E>(user_id: i32, db: E) -> Result<Vec<Identity>>
@@ -106,8 +155,8 @@ impl Identity {
         let identity = query_as!(
             Identity,
             r#"
-            INSERT INTO identities (provider, user_id, remote_id, email) 
-            VALUES ($1, $2, $3, $4) 
+            INSERT INTO identities (provider, user_id, remote_id, email, last_login_at, login_count)
+            VALUES ($1, $2, $3, $4, now(), 1)
             RETURNING *
             "#,
             provider,
@@ -141,6 +190,32 @@ impl Identity {
         Ok(())
     }
 
+    /// Record that the identity was used to sign in
+    #[instrument(name = "Identity::record_login", skip(self, db), fields(%self.provider, %self.user_id))]
+    pub async fn record_login<'c, 'e, E>(&mut self, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            r#"
+            UPDATE identities
+            SET last_login_at = now(), login_count = login_count + 1
+            WHERE provider = $1 AND user_id = $2
+            RETURNING last_login_at, login_count
+            "#,
+            &self.provider,
+            &self.user_id,
+        )
+        .fetch_one(db)
+        .await?;
+
+        self.last_login_at = result.last_login_at;
+        self.login_count = result.login_count;
+
+        Ok(())
+    }
+
     /// Unlink a user from a provider
     #[instrument(name = "Identity::unlink", skip(db))]
     pub async fn unlink<'c, 'e, E>(provider: &str, user_id: i32, db: E) -> Result<()>
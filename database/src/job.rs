@@ -0,0 +1,209 @@
+use crate::{Json, Result};
+use chrono::{DateTime, Utc};
+use serde_json::{json, Value};
+use sqlx::{query, query_as, Acquire, Executor};
+use tracing::instrument;
+
+/// The kinds of work that can be run in the background
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, sqlx::Type)]
+#[sqlx(rename_all = "snake_case", type_name = "job_kind")]
+pub enum JobKind {
+    /// Remove the OpenID Connect authorization codes that were never redeemed
+    PurgeAuthorizationCodes,
+    /// Remove the email addresses that were never verified
+    PurgeUnverifiedEmails,
+    /// Mark the custom domain certificates that were not renewed in time as expired
+    ExpireCertificates,
+    /// Deliver a webhook's queued events in order
+    DeliverWebhook,
+    /// Queue deliveries for the webhooks with events that are not being delivered
+    ScheduleWebhookDeliveries,
+    /// Log a user out of all their sessions
+    RevokeSessions,
+}
+
+impl JobKind {
+    /// Get the name of the kind
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::PurgeAuthorizationCodes => "purge_authorization_codes",
+            JobKind::PurgeUnverifiedEmails => "purge_unverified_emails",
+            JobKind::ExpireCertificates => "expire_certificates",
+            JobKind::DeliverWebhook => "deliver_webhook",
+            JobKind::ScheduleWebhookDeliveries => "schedule_webhook_deliveries",
+            JobKind::RevokeSessions => "revoke_sessions",
+        }
+    }
+}
+
+/// A unit of work queued to run in the background, no earlier than a point in time
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Job {
+    /// A unique ID
+    pub id: i64,
+    /// What work to perform
+    pub kind: JobKind,
+    /// Deduplicates the job, at most one job with the key can be queued at once
+    pub key: Option<String>,
+    /// Arguments for the work
+    pub payload: Json<Value>,
+    /// When the job can run
+    pub run_at: DateTime<Utc>,
+    /// How many times the job has been started, including the current attempt
+    pub attempts: i32,
+    /// Why the previous attempt failed, if it did
+    pub last_error: Option<String>,
+    /// When the job was queued
+    pub created_at: DateTime<Utc>,
+}
+
+impl Job {
+    /// Queue a job to run at the given time. Returns `false` if a job with the same key is already
+    /// queued.
+    #[instrument(name = "Job::schedule", skip(payload, db))]
+    pub async fn schedule<'c, 'e, E>(
+        kind: JobKind,
+        key: Option<&str>,
+        payload: &Value,
+        run_at: DateTime<Utc>,
+        db: E,
+    ) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            r#"
+            INSERT INTO jobs (kind, key, payload, run_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (key) WHERE key IS NOT NULL DO NOTHING
+            "#,
+            kind as JobKind,
+            key,
+            Json(payload) as _,
+            run_at,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Queue delivery of a webhook's events, unless it is already queued
+    #[instrument(name = "Job::deliver_webhook", skip(db))]
+    pub async fn deliver_webhook<'c, 'e, E>(webhook_id: i32, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        // Must match the key used when events are queued in the outbox
+        let key = format!("deliver-webhook:{webhook_id}");
+        let payload = json!({ "webhook": webhook_id });
+        Job::schedule(
+            JobKind::DeliverWebhook,
+            Some(&key),
+            &payload,
+            Utc::now(),
+            db,
+        )
+        .await
+    }
+
+    /// Queue logging a user out of all their sessions, unless it is already queued
+    #[instrument(name = "Job::revoke_sessions", skip(db))]
+    pub async fn revoke_sessions<'c, 'e, E>(user_id: i32, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let key = format!("revoke-sessions:{user_id}");
+        let payload = json!({ "user": user_id });
+        Job::schedule(
+            JobKind::RevokeSessions,
+            Some(&key),
+            &payload,
+            Utc::now(),
+            db,
+        )
+        .await
+    }
+
+    /// Lease the job that has been waiting to run the longest. Leases expire so jobs held by a
+    /// worker that stopped are eventually retried.
+    #[instrument(name = "Job::claim", skip(db))]
+    pub async fn claim<'c, 'e, E>(lease_seconds: i32, db: E) -> Result<Option<Job>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let job = query_as!(
+            Job,
+            r#"
+            UPDATE jobs
+            SET locked_until = now() + make_interval(secs => $1), attempts = attempts + 1
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE run_at <= now() AND (locked_until IS NULL OR locked_until <= now())
+                ORDER BY run_at
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING
+                id, kind as "kind: JobKind", key, payload as "payload: Json<Value>", run_at,
+                attempts, last_error, created_at
+            "#,
+            f64::from(lease_seconds),
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(job)
+    }
+
+    /// Remove the job once it has finished, or has been given up on. Recurring jobs are queued to
+    /// run again at the given time.
+    #[instrument(name = "Job::complete", skip_all, fields(%self.id, kind = self.kind.as_str()))]
+    pub async fn complete<'a, A>(&self, next_run: Option<DateTime<Utc>>, db: A) -> Result<()>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        query!("DELETE FROM jobs WHERE id = $1", self.id)
+            .execute(&mut *txn)
+            .await?;
+        if let Some(run_at) = next_run {
+            Job::schedule(
+                self.kind,
+                self.key.as_deref(),
+                &self.payload,
+                run_at,
+                &mut *txn,
+            )
+            .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(())
+    }
+
+    /// Release the job after a failed attempt so it runs again at the given time
+    #[instrument(name = "Job::retry", skip_all, fields(%self.id, kind = self.kind.as_str()))]
+    pub async fn retry<'c, 'e, E>(&self, error: &str, run_at: DateTime<Utc>, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        query!(
+            "UPDATE jobs SET locked_until = NULL, last_error = $2, run_at = $3 WHERE id = $1",
+            self.id,
+            error,
+            run_at,
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+}
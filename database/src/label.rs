@@ -0,0 +1,202 @@
+use crate::Result;
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "graphql")]
+use futures::TryStreamExt;
+use sqlx::{query, query_as, Executor};
+#[cfg(feature = "graphql")]
+use std::collections::HashMap;
+use tracing::instrument;
+
+/// A label used to group events and organizations, i.e. by season, region, or program
+///
+/// Labels are either free-form, with only a key, or key/value pairs. Each key can only be used
+/// once per event or organization.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct Label {
+    /// The event the label is attached to
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub event: Option<String>,
+    /// The organization the label is attached to
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub organization_id: Option<i32>,
+    /// What the label describes, i.e. `season`
+    pub key: String,
+    /// The value for the key, if any, i.e. `fall-2024`
+    pub value: Option<String>,
+    /// When the label was attached
+    pub created_at: DateTime<Utc>,
+}
+
+impl Label {
+    /// Load all the labels for the events, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    #[instrument(name = "Label::load_for_events", skip(db))]
+    pub(crate) async fn load_for_events<'c, 'e, E>(
+        slugs: &[String],
+        db: E,
+    ) -> Result<HashMap<String, Vec<Label>>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_event = query_as!(
+            Label,
+            r#"
+            SELECT event, organization_id, key, value, created_at
+            FROM labels
+            WHERE event = ANY($1)
+            ORDER BY key
+            "#,
+            slugs
+        )
+        .fetch(db)
+        .try_fold(HashMap::new(), |mut map, label| async move {
+            if let Some(event) = label.event.clone() {
+                let entry: &mut Vec<Label> = map.entry(event).or_default();
+                entry.push(label);
+            }
+            Ok(map)
+        })
+        .await?;
+
+        Ok(by_event)
+    }
+
+    /// Load all the labels for the organizations, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    #[instrument(name = "Label::load_for_organizations", skip(db))]
+    pub(crate) async fn load_for_organizations<'c, 'e, E>(
+        organization_ids: &[i32],
+        db: E,
+    ) -> Result<HashMap<i32, Vec<Label>>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_organization_id = query_as!(
+            Label,
+            r#"
+            SELECT event, organization_id, key, value, created_at
+            FROM labels
+            WHERE organization_id = ANY($1)
+            ORDER BY key
+            "#,
+            organization_ids
+        )
+        .fetch(db)
+        .try_fold(HashMap::new(), |mut map, label| async move {
+            if let Some(organization_id) = label.organization_id {
+                let entry: &mut Vec<Label> = map.entry(organization_id).or_default();
+                entry.push(label);
+            }
+            Ok(map)
+        })
+        .await?;
+
+        Ok(by_organization_id)
+    }
+
+    /// Attach a label to an event, replacing the value if the key is already used
+    #[instrument(name = "Label::set_for_event", skip(db))]
+    pub async fn set_for_event<'c, 'e, E>(
+        event: &str,
+        key: &str,
+        value: Option<&str>,
+        db: E,
+    ) -> Result<Label>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let label = query_as!(
+            Label,
+            r#"
+            INSERT INTO labels (event, key, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (event, key) WHERE event IS NOT NULL DO UPDATE SET value = excluded.value
+            RETURNING event, organization_id, key, value, created_at
+            "#,
+            event,
+            key,
+            value,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(label)
+    }
+
+    /// Attach a label to an organization, replacing the value if the key is already used
+    #[instrument(name = "Label::set_for_organization", skip(db))]
+    pub async fn set_for_organization<'c, 'e, E>(
+        organization_id: i32,
+        key: &str,
+        value: Option<&str>,
+        db: E,
+    ) -> Result<Label>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let label = query_as!(
+            Label,
+            r#"
+            INSERT INTO labels (organization_id, key, value)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (organization_id, key) WHERE organization_id IS NOT NULL
+                DO UPDATE SET value = excluded.value
+            RETURNING event, organization_id, key, value, created_at
+            "#,
+            organization_id,
+            key,
+            value,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(label)
+    }
+
+    /// Remove a label from an event, returning whether it existed
+    #[instrument(name = "Label::remove_from_event", skip(db))]
+    pub async fn remove_from_event<'c, 'e, E>(event: &str, key: &str, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            "DELETE FROM labels WHERE event = $1 AND key = $2",
+            event,
+            key
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Remove a label from an organization, returning whether it existed
+    #[instrument(name = "Label::remove_from_organization", skip(db))]
+    pub async fn remove_from_organization<'c, 'e, E>(
+        organization_id: i32,
+        key: &str,
+        db: E,
+    ) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!(
+            "DELETE FROM labels WHERE organization_id = $1 AND key = $2",
+            organization_id,
+            key
+        )
+        .execute(db)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
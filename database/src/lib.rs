@@ -1,7 +1,9 @@
-use eyre::WrapErr;
+use eyre::{ensure, WrapErr};
+use futures::future::BoxFuture;
 use sqlx::{
+    migrate::Migrator,
     postgres::{PgConnectOptions, PgPoolOptions},
-    ConnectOptions,
+    Acquire, ConnectOptions,
 };
 use std::{
     fmt::{Debug, Display, Formatter},
@@ -11,36 +13,110 @@ use std::{
 };
 use tracing::{info, instrument, log::LevelFilter};
 
+mod allowed_redirect_domain;
+mod audit_log;
+mod authorization_code;
+mod change;
 mod custom_domain;
+mod email;
 mod event;
+mod event_extension_request;
+mod event_organizer;
+mod feature_flag;
+#[cfg(feature = "fixtures")]
+pub mod fixtures;
+mod history;
 mod identity;
+mod job;
+mod label;
 #[cfg(feature = "graphql")]
 pub mod loaders;
+#[cfg(feature = "graphql")]
+mod node;
+mod oidc_client;
 mod organization;
+mod organization_email_domain;
 mod organizer;
 mod participant;
+mod pool;
 mod provider;
 mod types;
 mod user;
-
-pub use custom_domain::CustomDomain;
-pub use event::Event;
-pub use identity::Identity;
-pub use organization::Organization;
+#[cfg(feature = "graphql")]
+mod user_session;
+mod webhook;
+mod webhook_delivery;
+mod webhook_outbox;
+
+pub use allowed_redirect_domain::AllowedRedirectDomain;
+pub use audit_log::AuditLogEntry;
+pub use authorization_code::AuthorizationCode;
+pub use change::{CachedTable, Change, ChangeListener};
+pub use custom_domain::{CertificateStatus, CustomDomain};
+pub use email::Email;
+pub use event::{Event, RegistrationStatus};
+pub use event_extension_request::{EventExtensionRequest, ExtensionRequestStatus};
+pub use event_organizer::EventOrganizer;
+pub use feature_flag::FeatureFlag;
+pub use history::HistoryEntry;
+pub use identity::{Identity, ProviderUsage};
+pub use job::{Job, JobKind};
+pub use label::Label;
+#[cfg(feature = "graphql")]
+pub use node::{Node, NodeId};
+pub use oidc_client::OidcClient;
+pub use organization::{Organization, OrganizationEvents};
+pub use organization_email_domain::OrganizationEmailDomain;
 pub use organizer::{Organizer, Role};
-pub use participant::Participant;
+pub use participant::{Participant, ParticipantAttributes, ParticipantRole};
+pub use pool::{Pool, PoolOptions, Utilization};
 pub use provider::{Provider, ProviderConfiguration};
-pub use sqlx::PgPool;
+pub use sqlx::{PgConnection, PgPool};
 pub use types::Json;
 pub use user::User;
+#[cfg(feature = "graphql")]
+pub use user_session::{SessionLookup, UserSession};
+pub use webhook::{Webhook, WebhookEvent};
+pub use webhook_delivery::{DeliveryOutcome, WebhookDelivery, RESPONSE_SNIPPET_LENGTH};
+pub use webhook_outbox::WebhookOutboxEntry;
 
 pub use sqlx::Error as SqlxError;
 
 pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
 
-/// Connect to the database and ensure it works
-#[instrument(skip_all)]
-pub async fn connect(url: &str) -> eyre::Result<PgPool> {
+/// The migrations embedded when the crate was built
+pub static MIGRATOR: Migrator = sqlx::migrate!("../migrations");
+
+/// Connect to the primary database and any read replicas, and ensure they work
+#[instrument(skip_all, fields(replicas = replica_urls.len()))]
+pub async fn connect(
+    url: &str,
+    replica_urls: &[String],
+    options: PoolOptions,
+) -> eyre::Result<Pool> {
+    ensure!(
+        options.min_connections <= options.max_connections,
+        "the minimum connections cannot exceed the maximum"
+    );
+
+    let primary = connect_to(url, options)
+        .await
+        .wrap_err("failed to connect to the database")?;
+
+    let mut replicas = Vec::with_capacity(replica_urls.len());
+    for url in replica_urls {
+        let replica = connect_to(url, options)
+            .await
+            .wrap_err("failed to connect to a database replica")?;
+        replicas.push(replica);
+    }
+
+    info!("database connected");
+    Ok(Pool::new(primary, replicas))
+}
+
+/// Connect to a single database
+async fn connect_to(url: &str, pool: PoolOptions) -> eyre::Result<PgPool> {
     let options = PgConnectOptions::from_str(url)
         .wrap_err("invalid database url format")?
         .log_statements(LevelFilter::Info)
@@ -48,14 +124,34 @@ pub async fn connect(url: &str) -> eyre::Result<PgPool> {
 
     let db = PgPoolOptions::new()
         .acquire_timeout(Duration::from_secs(10))
+        .max_connections(pool.max_connections)
+        .min_connections(pool.min_connections)
+        .idle_timeout(pool.idle_timeout)
+        .max_lifetime(pool.max_lifetime)
         .connect_with(options)
-        .await
-        .wrap_err("failed to connect to the database")?;
+        .await?;
 
-    info!("database connected");
     Ok(db)
 }
 
+/// Run the operation in a transaction, committing it if the operation succeeds and rolling it back
+/// if it fails
+///
+/// The operation's future cannot borrow from its surroundings, so anything it needs must be moved
+/// into it.
+#[instrument(skip_all)]
+pub async fn with_txn<'a, A, F, T>(db: A, operation: F) -> Result<T>
+where
+    A: Acquire<'a, Database = sqlx::Postgres>,
+    F: for<'t> FnOnce(&'t mut PgConnection) -> BoxFuture<'t, Result<T>>,
+{
+    let mut txn = db.begin().await?;
+    let value = operation(&mut *txn).await?;
+    txn.commit().await?;
+
+    Ok(value)
+}
+
 /// Represents the different way the database can fail
 #[derive(Clone)]
 pub struct Error(Arc<SqlxError>);
@@ -68,6 +164,30 @@ impl Error {
             _ => false,
         }
     }
+
+    /// Returns whether the error kind is a violation of a foreign key constraint.
+    pub fn is_foreign_key_violation(&self) -> bool {
+        match self.0.as_ref() {
+            SqlxError::Database(e) => e.is_foreign_key_violation(),
+            _ => false,
+        }
+    }
+
+    /// Returns whether the error kind is a violation of a check constraint.
+    pub fn is_check_violation(&self) -> bool {
+        match self.0.as_ref() {
+            SqlxError::Database(e) => e.is_check_violation(),
+            _ => false,
+        }
+    }
+
+    /// Returns the name of the constraint that was violated, if any.
+    pub fn constraint_name(&self) -> Option<&str> {
+        match self.0.as_ref() {
+            SqlxError::Database(e) => e.constraint(),
+            _ => None,
+        }
+    }
 }
 
 impl Debug for Error {
@@ -91,6 +211,7 @@ impl std::error::Error for Error {
 #[cfg(feature = "graphql")]
 impl async_graphql::ErrorExtensions for Error {
     fn extend(&self) -> async_graphql::Error {
+        use async_graphql::ErrorExtensions as _;
         use std::error::Error as _;
 
         match self.source() {
@@ -99,6 +220,7 @@ impl async_graphql::ErrorExtensions for Error {
         }
 
         async_graphql::Error::new("internal server error")
+            .extend_with(|_, extensions| extensions.set("code", "INTERNAL_SERVER_ERROR"))
     }
 }
 
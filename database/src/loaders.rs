@@ -1,5 +1,6 @@
 use crate::{
-    CustomDomain, Event, Identity, Organization, Organizer, Participant, PgPool, Provider, User,
+    CustomDomain, Event, Identity, Label, Organization, OrganizationEmailDomain, Organizer,
+    Participant, Pool, Provider, ProviderUsage, User,
 };
 use async_graphql::{
     dataloader::{DataLoader, Loader, NoCache},
@@ -22,12 +23,12 @@ macro_rules! declare_loader {
         pub type $name = DataLoader<$impl_name, NoCache>;
 
         #[doc = concat!("The dataloader implementation for [`", stringify!($model), "`]s")]
-        pub struct $impl_name(PgPool);
+        pub struct $impl_name(Pool);
 
         impl $impl_name {
             #[doc = concat!("Create a new dataloader for [`", stringify!($model), "`]s")]
             #[inline(always)]
-            fn new(db: &PgPool) -> $name {
+            fn new(db: &Pool) -> $name {
                 DataLoader::new($impl_name(db.clone()), tokio::task::spawn)
             }
         }
@@ -40,41 +41,63 @@ macro_rules! declare_loader {
                 &self,
                 keys: &[$key_type],
             ) -> Result<HashMap<$key_type, Self::Value>, Self::Error> {
-                <$model>::$method(keys, &self.0).await
+                <$model>::$method(keys, self.0.reader()).await
             }
         }
     };
 }
 
 declare_loader!(CustomDomainLoader<CustomDomainLoaderImpl> for CustomDomain => event(String));
+declare_loader!(CustomDomainsForEventLoader<CustomDomainsForEventLoaderImpl> for CustomDomain => event(String) using load_for_events providing Vec<CustomDomain>);
+declare_loader!(EmailsForUserLoader<EmailsForUserLoaderImpl> for Email => user_id(i32) using load_for_user providing Vec<Email>);
+declare_loader!(EmailDomainsForOrganizationLoader<EmailDomainsForOrganizationLoaderImpl> for OrganizationEmailDomain => organization_id(i32) using load_for_organizations providing Vec<OrganizationEmailDomain>);
 declare_loader!(EventLoader<EventLoaderImpl> for Event => slug(String));
+declare_loader!(EventCountForOrganizationLoader<EventCountForOrganizationLoaderImpl> for Event => organization_id(i32) using count_for_organizations providing i64);
 declare_loader!(EventsForOrganizationLoader<EventsForOrganizationLoaderImpl> for Event => organization_id(i32) using load_for_organizations providing Vec<Event>);
 declare_loader!(EventsForUserLoader<EventsForUserLoaderImpl> for Participant => user_id(i32) using load_for_user providing Vec<Participant>);
 declare_loader!(IdentitiesForUserLoader<IdentitiesForUserLoaderImpl> for Identity => user_id(i32) using load_for_user providing Vec<Identity>);
+declare_loader!(LabelsForEventLoader<LabelsForEventLoaderImpl> for Label => event(String) using load_for_events providing Vec<Label>);
+declare_loader!(LabelsForOrganizationLoader<LabelsForOrganizationLoaderImpl> for Label => organization_id(i32) using load_for_organizations providing Vec<Label>);
 declare_loader!(OrganizationLoader<OrganizationLoaderImpl> for Organization => id(i32));
+declare_loader!(OrganizationByPublicIdLoader<OrganizationByPublicIdLoaderImpl> for Organization => public_id(String) using load_by_public_id);
+declare_loader!(OrganizationBySlugLoader<OrganizationBySlugLoaderImpl> for Organization => slug(String) using load_by_slug);
 declare_loader!(OrganizationsForUserLoader<OrganizationsForUserLoaderImpl> for Organizer => user_id(i32) using load_for_user providing Vec<Organizer>);
+declare_loader!(ParticipantLoader<ParticipantLoaderImpl> for Participant => key((String, i32)));
 declare_loader!(ProviderLoader<ProviderLoaderImpl> for Provider => slug(String));
+declare_loader!(ProviderUsageLoader<ProviderUsageLoaderImpl> for Identity => provider(String) using usage_for_providers providing ProviderUsage);
 declare_loader!(UserLoader<UserLoaderImpl> for User => id(i32));
+declare_loader!(UserByPublicIdLoader<UserByPublicIdLoaderImpl> for User => public_id(String) using load_by_public_id);
 declare_loader!(UserByPrimaryEmailLoader<UserByPrimaryEmailLoaderImpl> for User => primary_email(String) using load_by_primary_email);
 declare_loader!(UsersForEventLoader<UsersForEventLoaderImpl> for Participant => event(String) using load_for_event providing Vec<Participant>);
 declare_loader!(UsersForOrganizationLoader<UsersForOrganizationLoaderImpl> for Organizer => organization_id(i32) using load_for_organization providing Vec<Organizer>);
 
 /// Registers the defined dataloaders
 pub trait RegisterDataLoaders {
-    fn register_dataloaders(self, db: &PgPool) -> Self;
+    fn register_dataloaders(self, db: &Pool) -> Self;
 }
 
 impl<Q, M, S> RegisterDataLoaders for SchemaBuilder<Q, M, S> {
-    fn register_dataloaders(self, db: &PgPool) -> Self {
+    fn register_dataloaders(self, db: &Pool) -> Self {
         self.data(CustomDomainLoaderImpl::new(db))
+            .data(CustomDomainsForEventLoaderImpl::new(db))
+            .data(EmailsForUserLoaderImpl::new(db))
+            .data(EmailDomainsForOrganizationLoaderImpl::new(db))
             .data(EventLoaderImpl::new(db))
+            .data(EventCountForOrganizationLoaderImpl::new(db))
             .data(EventsForOrganizationLoaderImpl::new(db))
             .data(EventsForUserLoaderImpl::new(db))
             .data(IdentitiesForUserLoaderImpl::new(db))
+            .data(LabelsForEventLoaderImpl::new(db))
+            .data(LabelsForOrganizationLoaderImpl::new(db))
             .data(OrganizationLoaderImpl::new(db))
+            .data(OrganizationByPublicIdLoaderImpl::new(db))
+            .data(OrganizationBySlugLoaderImpl::new(db))
             .data(OrganizationsForUserLoaderImpl::new(db))
+            .data(ParticipantLoaderImpl::new(db))
             .data(ProviderLoaderImpl::new(db))
+            .data(ProviderUsageLoaderImpl::new(db))
             .data(UserLoaderImpl::new(db))
+            .data(UserByPublicIdLoaderImpl::new(db))
             .data(UserByPrimaryEmailLoaderImpl::new(db))
             .data(UsersForEventLoaderImpl::new(db))
             .data(UsersForOrganizationLoaderImpl::new(db))
@@ -0,0 +1,59 @@
+use crate::{Event, Organization, Provider, User};
+use async_graphql::{Interface, ID};
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+
+/// An object with a globally unique, opaque identifier, for clients that cache objects by a single
+/// key. This is not Relay's `Node` interface: `id` stays the type-specific identifier that other
+/// services use as the federation key, so the global identifier is exposed as `globalId` instead.
+#[derive(Interface)]
+#[graphql(field(
+    name = "global_id",
+    ty = "ID",
+    desc = "An opaque identifier that is unique across all types"
+))]
+pub enum Node {
+    Event(Event),
+    Organization(Organization),
+    Provider(Provider),
+    User(User),
+}
+
+/// The decoded form of a global ID
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NodeId {
+    Event(String),
+    Organization(String),
+    Provider(String),
+    User(String),
+}
+
+impl NodeId {
+    /// Encode the ID into its opaque form
+    pub fn encode(&self) -> ID {
+        let raw = match self {
+            NodeId::Event(slug) => format!("Event:{slug}"),
+            NodeId::Organization(id) => format!("Organization:{id}"),
+            NodeId::Provider(slug) => format!("Provider:{slug}"),
+            NodeId::User(id) => format!("User:{id}"),
+        };
+
+        ID(BASE64_URL_SAFE_NO_PAD.encode(raw))
+    }
+
+    /// Decode an opaque ID, returning `None` if it is malformed
+    pub fn decode(id: &str) -> Option<Self> {
+        let raw = BASE64_URL_SAFE_NO_PAD.decode(id).ok()?;
+        let raw = String::from_utf8(raw).ok()?;
+        let (kind, key) = raw.split_once(':')?;
+
+        let id = match kind {
+            "Event" => NodeId::Event(key.to_owned()),
+            "Organization" => NodeId::Organization(key.to_owned()),
+            "Provider" => NodeId::Provider(key.to_owned()),
+            "User" => NodeId::User(key.to_owned()),
+            _ => return None,
+        };
+
+        Some(id)
+    }
+}
@@ -0,0 +1,141 @@
+use crate::Result;
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+use rand::distributions::{Alphanumeric, DistString};
+use sha2::{Digest, Sha256};
+use sqlx::{query, query_as, Executor};
+use tracing::instrument;
+
+/// A first-party service that signs users in through the OpenID Connect provider
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct OidcClient {
+    /// The client ID used when requesting authorization
+    pub id: String,
+    /// A human-readable name for the client
+    pub name: String,
+    /// A hash of the client's secret
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub secret_hash: String,
+    /// The URIs users may be returned to after authorizing
+    pub redirect_uris: Vec<String>,
+    /// When the client was registered
+    pub created_at: DateTime<Utc>,
+    /// When the client was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+impl OidcClient {
+    /// Get all the clients
+    #[instrument(name = "OidcClient::all", skip_all)]
+    pub async fn all<'c, 'e, E>(db: E) -> Result<Vec<OidcClient>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let clients = query_as!(OidcClient, "SELECT * FROM oidc_clients ORDER BY name")
+            .fetch_all(db)
+            .await?;
+
+        Ok(clients)
+    }
+
+    /// Get a client by its ID
+    #[instrument(name = "OidcClient::find", skip(db))]
+    pub async fn find<'c, 'e, E>(id: &str, db: E) -> Result<Option<OidcClient>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let client = query_as!(OidcClient, "SELECT * FROM oidc_clients WHERE id = $1", id)
+            .fetch_optional(db)
+            .await?;
+
+        Ok(client)
+    }
+
+    /// Get a client using its credentials, returning nothing if the secret is incorrect
+    #[instrument(name = "OidcClient::authenticate", skip(secret, db))]
+    pub async fn authenticate<'c, 'e, E>(
+        id: &str,
+        secret: &str,
+        db: E,
+    ) -> Result<Option<OidcClient>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let client = query_as!(
+            OidcClient,
+            "SELECT * FROM oidc_clients WHERE id = $1 AND secret_hash = $2",
+            id,
+            hash(secret),
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(client)
+    }
+
+    /// Register a new client, returning it along with its secret. The secret cannot be retrieved
+    /// again later.
+    #[instrument(name = "OidcClient::create", skip(db))]
+    pub async fn create<'c, 'e, E>(
+        name: &str,
+        redirect_uris: &[String],
+        db: E,
+    ) -> Result<(OidcClient, String)>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let secret = generate(48);
+
+        let client = query_as!(
+            OidcClient,
+            r#"
+            INSERT INTO oidc_clients (id, name, secret_hash, redirect_uris)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+            generate(24),
+            name,
+            hash(&secret),
+            redirect_uris,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok((client, secret))
+    }
+
+    /// Check if the URI is registered for the client
+    pub fn allows_redirect_to(&self, uri: &str) -> bool {
+        self.redirect_uris.iter().any(|allowed| allowed == uri)
+    }
+
+    /// Delete a client by its ID
+    #[instrument(name = "OidcClient::delete", skip(db))]
+    pub async fn delete<'c, 'e, E>(id: &str, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        query!("DELETE FROM oidc_clients WHERE id = $1", id)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Generate a random alphanumeric string
+pub(crate) fn generate(length: usize) -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), length)
+}
+
+/// Hash a secret for storage
+pub(crate) fn hash(secret: &str) -> String {
+    format!("{:x}", Sha256::digest(secret.as_bytes()))
+}
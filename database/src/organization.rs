@@ -1,20 +1,21 @@
-use crate::Result;
 #[cfg(feature = "graphql")]
 use crate::{
-    loaders::{EventsForOrganizationLoader, UserLoader},
-    Event, User,
+    loaders::{
+        EmailDomainsForOrganizationLoader, EventCountForOrganizationLoader,
+        EventsForOrganizationLoader, LabelsForOrganizationLoader, UserLoader,
+    },
+    Event, Label, NodeId, OrganizationEmailDomain, User,
 };
+use crate::{AuditLogEntry, Result};
 #[cfg(feature = "graphql")]
 use async_graphql::{Context, ResultExt};
-use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
-use context::{
-    checks::{guard_where, has_at_least_role},
-    UserRole,
-};
+use authz::{guard, Action, Resource};
+use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
 use futures::TryStreamExt;
-use sqlx::{query, query_as, Executor, QueryBuilder};
+use serde_json::json;
+use sqlx::{query, query_as, Acquire, Executor, QueryBuilder};
 #[cfg(feature = "graphql")]
 use std::collections::HashMap;
 use tracing::instrument;
@@ -24,8 +25,14 @@ use tracing::instrument;
 #[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[cfg_attr(feature = "graphql", graphql(complex))]
 pub struct Organization {
-    /// A unique ID
+    /// A unique ID, only for internal use
+    #[cfg_attr(feature = "graphql", graphql(skip))]
     pub id: i32,
+    /// An opaque, unique ID that is safe to expose
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub public_id: String,
+    /// A unique slug, for use in URLs
+    pub slug: String,
     /// The name of the organization
     pub name: String,
     /// URL for the organization's logo
@@ -35,6 +42,9 @@ pub struct Organization {
     /// The user who owns the organization
     #[cfg_attr(feature = "graphql", graphql(skip))]
     pub owner_id: i32,
+    /// The number of organizers in the organization, kept up to date by the database
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub organizer_count: i64,
     /// When the organization was first created
     pub created_at: DateTime<Utc>,
     /// When the organization was last updated
@@ -56,6 +66,33 @@ impl Organization {
         Ok(organizations)
     }
 
+    /// Get all the organizations with a label, optionally requiring it to have a specific value
+    #[instrument(name = "Organization::all_with_label", skip(db))]
+    pub async fn all_with_label<'c, 'e, E>(
+        key: &str,
+        value: Option<&str>,
+        db: E,
+    ) -> Result<Vec<Organization>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let organizations = query_as!(
+            Organization,
+            r#"
+            SELECT organizations.* FROM organizations
+            INNER JOIN labels ON organizations.id = labels.organization_id
+            WHERE labels.key = $1 AND ($2::text IS NULL OR labels.value = $2)
+            "#,
+            key,
+            value,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(organizations)
+    }
+
     /// Load all the organizations by the IDs, for use in dataloaders
     #[cfg(feature = "graphql")]
     pub(crate) async fn load<'c, 'e, E>(ids: &[i32], db: E) -> Result<HashMap<i32, Organization>>
@@ -75,6 +112,50 @@ impl Organization {
         Ok(by_id)
     }
 
+    /// Load all the organizations by their public IDs, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    pub(crate) async fn load_by_public_id<'c, 'e, E>(
+        public_ids: &[String],
+        db: E,
+    ) -> Result<HashMap<String, Organization>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_public_id = query_as!(
+            Organization,
+            "SELECT * FROM organizations WHERE public_id = ANY($1)",
+            public_ids
+        )
+        .fetch(db)
+        .map_ok(|organization| (organization.public_id.clone(), organization))
+        .try_collect()
+        .await?;
+        Ok(by_public_id)
+    }
+
+    /// Load all the organizations by their slugs, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    pub(crate) async fn load_by_slug<'c, 'e, E>(
+        slugs: &[String],
+        db: E,
+    ) -> Result<HashMap<String, Organization>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_slug = query_as!(
+            Organization,
+            "SELECT * FROM organizations WHERE slug = ANY($1)",
+            slugs
+        )
+        .fetch(db)
+        .map_ok(|organization| (organization.slug.clone(), organization))
+        .try_collect()
+        .await?;
+        Ok(by_slug)
+    }
+
     /// Check if an organization exists
     #[instrument(name = "Organization::exists", skip(db))]
     pub async fn exists<'c, 'e, E>(id: i32, db: E) -> Result<bool>
@@ -110,16 +191,40 @@ impl Organization {
         Ok(organization)
     }
 
+    /// Get an organization by it's slug
+    #[instrument(name = "Organization::find_by_slug", skip(db))]
+    pub async fn find_by_slug<'c, 'e, E>(slug: &str, db: E) -> Result<Option<Organization>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let organization = query_as!(
+            Organization,
+            "SELECT * FROM organizations WHERE slug = $1",
+            slug
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(organization)
+    }
+
     /// Create a new organization
     #[instrument(name = "Organization::create", skip(db))]
-    pub async fn create<'c, 'e, E>(name: &str, owner_id: i32, db: E) -> Result<Organization>
+    pub async fn create<'c, 'e, E>(
+        slug: &str,
+        name: &str,
+        owner_id: i32,
+        db: E,
+    ) -> Result<Organization>
     where
         'c: 'e,
         E: 'e + Executor<'c, Database = sqlx::Postgres>,
     {
         let organization = query_as!(
             Organization,
-            "INSERT INTO organizations (name, owner_id) VALUES ($1, $2) RETURNING *",
+            "INSERT INTO organizations (slug, name, owner_id) VALUES ($1, $2, $3) RETURNING *",
+            slug,
             name,
             owner_id
         )
@@ -147,23 +252,168 @@ impl Organization {
 
         Ok(())
     }
+
+    /// Delete an organization along with its organizers, handling any events it still owns. The
+    /// deletion is recorded in the audit log. Returns `false` if the organization still has events
+    /// and they were not handled.
+    #[instrument(name = "Organization::delete_with_events", skip(db))]
+    pub async fn delete_with_events<'a, A>(
+        id: i32,
+        events: OrganizationEvents,
+        actor_id: Option<i32>,
+        db: A,
+    ) -> Result<bool>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        let slugs = query!(
+            "SELECT slug FROM events WHERE organization_id = $1 FOR UPDATE",
+            id
+        )
+        .fetch_all(&mut *txn)
+        .await?
+        .into_iter()
+        .map(|row| row.slug)
+        .collect::<Vec<_>>();
+
+        match events {
+            _ if slugs.is_empty() => {}
+            OrganizationEvents::Restrict => return Ok(false),
+            OrganizationEvents::Cascade => {
+                query!("DELETE FROM participants WHERE event = ANY($1)", &slugs)
+                    .execute(&mut *txn)
+                    .await?;
+                query!("DELETE FROM custom_domains WHERE event = ANY($1)", &slugs)
+                    .execute(&mut *txn)
+                    .await?;
+                query!("DELETE FROM events WHERE slug = ANY($1)", &slugs)
+                    .execute(&mut *txn)
+                    .await?;
+            }
+            OrganizationEvents::TransferTo(target) => {
+                query!(
+                    "UPDATE events SET organization_id = $2 WHERE slug = ANY($1)",
+                    &slugs,
+                    target
+                )
+                .execute(&mut *txn)
+                .await?;
+            }
+        }
+
+        query!("DELETE FROM organizers WHERE organization_id = $1", id)
+            .execute(&mut *txn)
+            .await?;
+        query!("DELETE FROM organizations WHERE id = $1", id)
+            .execute(&mut *txn)
+            .await?;
+
+        let details = match events {
+            OrganizationEvents::TransferTo(target) => {
+                json!({ "events": slugs, "transferred_to": target })
+            }
+            _ => json!({ "events": slugs }),
+        };
+        AuditLogEntry::record(
+            actor_id,
+            "organization.delete",
+            &format!("organization:{id}"),
+            details,
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(true)
+    }
+}
+
+/// How to handle the events owned by an organization when it is deleted
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrganizationEvents {
+    /// Refuse to delete the organization while it has events
+    Restrict,
+    /// Delete the events along with their participants and custom domains
+    Cascade,
+    /// Move the events to another organization
+    TransferTo(i32),
 }
 
 #[cfg(feature = "graphql")]
 #[async_graphql::ComplexObject]
 impl Organization {
+    /// A unique ID
+    async fn id(&self) -> async_graphql::ID {
+        async_graphql::ID(self.public_id.clone())
+    }
+
+    /// An opaque identifier that is unique across all types
+    async fn global_id(&self) -> async_graphql::ID {
+        NodeId::Organization(self.public_id.clone()).encode()
+    }
+
     /// All the events owned by the organization
-    #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
     #[instrument(name = "Organization::events", skip_all, fields(%self.id))]
-    async fn events(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Event>> {
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_archived: bool,
+    ) -> async_graphql::Result<Vec<Event>> {
         let loader = ctx.data_unchecked::<EventsForOrganizationLoader>();
-        let events = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+        let mut events = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+
+        if !include_archived {
+            events.retain(|event| !event.is_archived());
+        }
 
         Ok(events)
     }
 
+    /// The number of unarchived events owned by the organization
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
+    #[instrument(name = "Organization::event_count", skip_all, fields(%self.id))]
+    async fn event_count(&self, ctx: &Context<'_>) -> async_graphql::Result<i64> {
+        let loader = ctx.data_unchecked::<EventCountForOrganizationLoader>();
+        let count = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+
+        Ok(count)
+    }
+
+    /// The number of organizers in the organization
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
+    async fn member_count(&self) -> i64 {
+        self.organizer_count
+    }
+
+    /// The email domains whose verified users automatically join the organization
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
+    #[instrument(name = "Organization::email_domains", skip_all, fields(%self.id))]
+    async fn email_domains(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<Vec<OrganizationEmailDomain>> {
+        let loader = ctx.data_unchecked::<EmailDomainsForOrganizationLoader>();
+        let domains = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+
+        Ok(domains)
+    }
+
+    /// The labels used to group the organization
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
+    #[instrument(name = "Organization::labels", skip_all, fields(%self.id))]
+    async fn labels(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Label>> {
+        let loader = ctx.data_unchecked::<LabelsForOrganizationLoader>();
+        let labels = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+
+        Ok(labels)
+    }
+
     /// The owner of the organization
-    #[graphql(guard = "guard_where(has_at_least_role, UserRole::Organizer)")]
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Organization { owner_id: None })")]
     #[instrument(name = "Organization::owner", skip_all, fields(%self.id))]
     async fn owner(&self, ctx: &Context<'_>) -> async_graphql::Result<User> {
         let loader = ctx.data_unchecked::<UserLoader>();
@@ -0,0 +1,189 @@
+use crate::{AuditLogEntry, Result, Role};
+#[cfg(feature = "graphql")]
+use async_graphql::SimpleObject;
+use chrono::{DateTime, Utc};
+#[cfg(feature = "graphql")]
+use futures::TryStreamExt;
+use serde_json::json;
+use sqlx::{query, query_as, Acquire, Executor};
+#[cfg(feature = "graphql")]
+use std::collections::HashMap;
+use tracing::instrument;
+
+/// An email domain whose verified users automatically join an organization upon registering
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct OrganizationEmailDomain {
+    /// The organization the domain belongs to
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub organization_id: i32,
+    /// The email domain
+    pub domain: String,
+    /// The role given to users who join through the domain
+    pub role: Role,
+    /// When the domain was added
+    pub created_at: DateTime<Utc>,
+}
+
+impl OrganizationEmailDomain {
+    /// Load all the email domains for the organizations, for use in dataloaders
+    #[cfg(feature = "graphql")]
+    pub(crate) async fn load_for_organizations<'c, 'e, E>(
+        organization_ids: &[i32],
+        db: E,
+    ) -> Result<HashMap<i32, Vec<OrganizationEmailDomain>>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_organization_id = query_as!(
+            OrganizationEmailDomain,
+            r#"
+            SELECT organization_id, domain, role as "role: Role", created_at
+            FROM organization_email_domains
+            WHERE organization_id = ANY($1)
+            ORDER BY domain
+            "#,
+            organization_ids
+        )
+        .fetch(db)
+        .try_fold(HashMap::new(), |mut map, domain| async move {
+            let entry: &mut Vec<OrganizationEmailDomain> =
+                map.entry(domain.organization_id).or_default();
+            entry.push(domain);
+            Ok(map)
+        })
+        .await?;
+
+        Ok(by_organization_id)
+    }
+
+    /// Allow users with the email domain to join the organization, or change the role they join
+    /// with. The change is recorded in the audit log.
+    #[instrument(name = "OrganizationEmailDomain::add", skip(db))]
+    pub async fn add<'a, A>(
+        organization_id: i32,
+        domain: &str,
+        role: Role,
+        actor_id: i32,
+        db: A,
+    ) -> Result<OrganizationEmailDomain>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        let email_domain = query_as!(
+            OrganizationEmailDomain,
+            r#"
+            INSERT INTO organization_email_domains (organization_id, domain, role)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (organization_id, domain) DO UPDATE SET role = excluded.role
+            RETURNING organization_id, domain, role as "role: Role", created_at
+            "#,
+            organization_id,
+            domain,
+            role as _,
+        )
+        .fetch_one(&mut *txn)
+        .await?;
+
+        AuditLogEntry::record(
+            Some(actor_id),
+            "organization.email_domain.add",
+            &format!("organization:{organization_id}"),
+            json!({ "domain": domain, "role": role.as_str() }),
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(email_domain)
+    }
+
+    /// Stop users with the email domain from joining the organization. The change is recorded in
+    /// the audit log. Returns `false` if the domain was not present.
+    #[instrument(name = "OrganizationEmailDomain::remove", skip(db))]
+    pub async fn remove<'a, A>(
+        organization_id: i32,
+        domain: &str,
+        actor_id: i32,
+        db: A,
+    ) -> Result<bool>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        let result = query!(
+            "DELETE FROM organization_email_domains WHERE organization_id = $1 AND domain = $2",
+            organization_id,
+            domain,
+        )
+        .execute(&mut *txn)
+        .await?;
+        if result.rows_affected() == 0 {
+            return Ok(false);
+        }
+
+        AuditLogEntry::record(
+            Some(actor_id),
+            "organization.email_domain.remove",
+            &format!("organization:{organization_id}"),
+            json!({ "domain": domain }),
+            &mut *txn,
+        )
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(true)
+    }
+
+    /// Add a newly registered user to every organization that allows their email's domain. The
+    /// email must be verified by the provider. Each join is recorded in the audit log. Returns the
+    /// IDs of the organizations that were joined.
+    #[instrument(name = "OrganizationEmailDomain::auto_join", skip(db))]
+    pub async fn auto_join<'a, A>(user_id: i32, email: &str, db: A) -> Result<Vec<i32>>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let Some((_, domain)) = email.rsplit_once('@') else {
+            return Ok(Vec::new());
+        };
+        let domain = domain.to_lowercase();
+
+        let mut txn = db.begin().await?;
+
+        let joined = query!(
+            r#"
+            INSERT INTO organizers (organization_id, user_id, role)
+            SELECT organization_id, $1, role
+            FROM organization_email_domains
+            WHERE domain = $2
+            ON CONFLICT (organization_id, user_id) DO NOTHING
+            RETURNING organization_id, role as "role: Role"
+            "#,
+            user_id,
+            domain,
+        )
+        .fetch_all(&mut *txn)
+        .await?;
+
+        for row in &joined {
+            AuditLogEntry::record(
+                Some(user_id),
+                "organization.email_domain.join",
+                &format!("organization:{}", row.organization_id),
+                json!({ "domain": domain, "role": row.role.as_str() }),
+                &mut *txn,
+            )
+            .await?;
+        }
+
+        txn.commit().await?;
+
+        Ok(joined.into_iter().map(|row| row.organization_id).collect())
+    }
+}
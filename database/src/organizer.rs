@@ -28,6 +28,17 @@ pub enum Role {
     Organizer,
 }
 
+impl Role {
+    /// Get the name of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Director => "director",
+            Role::Manager => "manager",
+            Role::Organizer => "organizer",
+        }
+    }
+}
+
 impl From<Role> for UserRole {
     fn from(role: Role) -> Self {
         match role {
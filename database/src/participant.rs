@@ -1,17 +1,66 @@
-use crate::Result;
 #[cfg(feature = "graphql")]
 use crate::{
     loaders::{EventLoader, UserLoader},
     Event, User,
 };
+use crate::{Json, Result};
 #[cfg(feature = "graphql")]
-use async_graphql::{ComplexObject, Context, ResultExt, SimpleObject};
+use async_graphql::{ComplexObject, Context, Enum, ResultExt, SimpleObject};
+#[cfg(feature = "graphql")]
+use authz::{Action, Actor, Resource};
 use chrono::{DateTime, Utc};
 use futures::stream::TryStreamExt;
-use sqlx::{query, query_as, Executor};
+use serde_json::{Map, Value};
+use sqlx::{query, query_as, Acquire, Executor, QueryBuilder};
 use std::collections::HashMap;
 use tracing::instrument;
 
+/// The part a participant plays in an event
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, sqlx::Type)]
+#[cfg_attr(feature = "graphql", derive(Enum))]
+#[sqlx(rename_all = "lowercase", type_name = "participant_role")]
+pub enum ParticipantRole {
+    /// Someone building a project
+    #[default]
+    Hacker,
+    /// Helps hackers with their projects
+    Mentor,
+    /// Evaluates projects
+    Judge,
+    /// Helps run the event
+    Volunteer,
+    /// Represents a sponsoring company
+    Sponsor,
+}
+
+impl ParticipantRole {
+    /// Get the name of the role
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ParticipantRole::Hacker => "hacker",
+            ParticipantRole::Mentor => "mentor",
+            ParticipantRole::Judge => "judge",
+            ParticipantRole::Volunteer => "volunteer",
+            ParticipantRole::Sponsor => "sponsor",
+        }
+    }
+
+    /// Get the role from its name, as returned by [`ParticipantRole::as_str`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "hacker" => Some(ParticipantRole::Hacker),
+            "mentor" => Some(ParticipantRole::Mentor),
+            "judge" => Some(ParticipantRole::Judge),
+            "volunteer" => Some(ParticipantRole::Volunteer),
+            "sponsor" => Some(ParticipantRole::Sponsor),
+            _ => None,
+        }
+    }
+}
+
+/// Arbitrary event-specific information about a participant, keyed by name
+pub type ParticipantAttributes = Map<String, Value>;
+
 /// Maps a user to an event as a participant
 #[derive(Clone, Debug, Eq, PartialEq)]
 #[cfg_attr(feature = "graphql", derive(SimpleObject))]
@@ -23,6 +72,11 @@ pub struct Participant {
     /// The user ID
     #[cfg_attr(feature = "graphql", graphql(skip))]
     pub user_id: i32,
+    /// The part the user plays in the event
+    pub role: ParticipantRole,
+    /// Arbitrary event-specific information, i.e. shirt size or dietary restrictions
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub attributes: Json<ParticipantAttributes>,
     /// When the mapping was first created
     pub created_at: DateTime<Utc>,
     /// When the mapping was last updated
@@ -57,9 +111,57 @@ impl Participant {
 
         Ok(user)
     }
+
+    /// Arbitrary event-specific information, i.e. shirt size or dietary restrictions. Only visible
+    /// to the participant and organizers.
+    #[instrument(name = "Participant::attributes", skip_all, fields(%self.event, %self.user_id))]
+    async fn attributes(
+        &self,
+        ctx: &Context<'_>,
+    ) -> async_graphql::Result<&Json<ParticipantAttributes>> {
+        let resource = Resource::Participant {
+            user_id: self.user_id,
+        };
+        authz::require(&Actor::from_graphql(ctx), Action::Read, &resource)?;
+
+        Ok(&self.attributes)
+    }
 }
 
 impl Participant {
+    /// Load participants by their event slugs and user IDs, for use in dataloaders
+    #[instrument(name = "Participant::load", skip(db))]
+    pub(crate) async fn load<'c, 'e, E>(
+        keys: &[(String, i32)],
+        db: E,
+    ) -> Result<HashMap<(String, i32), Participant>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let (events, user_ids): (Vec<String>, Vec<i32>) = keys.iter().cloned().unzip();
+        let by_key = query_as!(
+            Participant,
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE (event, user_id) IN (SELECT * FROM unnest($1::text[], $2::int[]))
+            "#,
+            &events,
+            &user_ids,
+        )
+        .fetch(db)
+        .map_ok(|participant| {
+            let key = (participant.event.clone(), participant.user_id);
+            (key, participant)
+        })
+        .try_collect()
+        .await?;
+
+        Ok(by_key)
+    }
+
     /// Load all the event slugs for a user, for use in dataloaders
     #[instrument(name = "Participant::load_for_user", skip(db))]
     pub(crate) async fn load_for_user<'c, 'e, E>(
@@ -72,7 +174,12 @@ impl Participant {
     {
         let by_user_id = query_as!(
             Participant,
-            "SELECT * FROM participants WHERE user_id = ANY($1)",
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE user_id = ANY($1)
+            "#,
             user_ids
         )
         .fetch(db)
@@ -98,7 +205,12 @@ impl Participant {
     {
         let by_event = query_as!(
             Participant,
-            "SELECT * FROM participants WHERE event = ANY($1)",
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE event = ANY($1)
+            "#,
             slugs
         )
         .fetch(db)
@@ -121,7 +233,12 @@ impl Participant {
     {
         let participant = query_as!(
             Participant,
-            "SELECT * FROM participants WHERE event = $1 AND user_id = $2",
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE event = $1 AND user_id = $2
+            "#,
             event,
             user_id
         )
@@ -140,7 +257,12 @@ impl Participant {
     {
         let participants = query_as!(
             Participant,
-            "SELECT * FROM participants WHERE user_id = $1",
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE user_id = $1
+            "#,
             user_id,
         )
         .fetch_all(db)
@@ -158,7 +280,12 @@ impl Participant {
     {
         let participants = query_as!(
             Participant,
-            "SELECT * FROM participants WHERE event = $1",
+            r#"
+            SELECT event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            FROM participants
+            WHERE event = $1
+            "#,
             event,
         )
         .fetch_all(db)
@@ -167,24 +294,69 @@ impl Participant {
         Ok(participants)
     }
 
+    /// Add a user to an event as a hacker while respecting the event's capacity
+    ///
+    /// Returns `None` if the event is full. Users who are already participating are returned
+    /// unchanged.
+    #[instrument(name = "Participant::register", skip(db))]
+    pub async fn register<'a, A>(event: &str, user_id: i32, db: A) -> Result<Option<Participant>>
+    where
+        A: Acquire<'a, Database = sqlx::Postgres>,
+    {
+        let mut txn = db.begin().await?;
+
+        // Lock the event so concurrent registrations can't exceed the capacity
+        let result = query!(
+            "SELECT capacity, participant_count FROM events WHERE slug = $1 FOR UPDATE",
+            event
+        )
+        .fetch_one(&mut *txn)
+        .await?;
+
+        if let Some(existing) = Participant::find(user_id, event, &mut *txn).await? {
+            return Ok(Some(existing));
+        }
+
+        if let Some(capacity) = result.capacity {
+            if result.participant_count >= i64::from(capacity) {
+                return Ok(None);
+            }
+        }
+
+        let participant =
+            Participant::add(event, user_id, Some(ParticipantRole::Hacker), &mut *txn).await?;
+        txn.commit().await?;
+
+        Ok(Some(participant))
+    }
+
     /// Add a user to an event
+    ///
+    /// New participants default to being hackers. Users who are already participating only have
+    /// their role changed if one is given.
     #[instrument(name = "Participant::add", skip(db))]
-    pub async fn add<'c, 'e, E>(event: &str, user_id: i32, db: E) -> Result<Participant>
+    pub async fn add<'c, 'e, E>(
+        event: &str,
+        user_id: i32,
+        role: Option<ParticipantRole>,
+        db: E,
+    ) -> Result<Participant>
     where
         'c: 'e,
         E: 'e + Executor<'c, Database = sqlx::Postgres>,
     {
-        // The updated_at column needs to be explicitly set so rows are returned
         let participant = query_as!(
             Participant,
             r#"
-            INSERT INTO participants (event, user_id) 
-            VALUES ($1, $2) 
-            ON CONFLICT (event, user_id) DO UPDATE SET updated_at = now()
-            RETURNING *
+            INSERT INTO participants (event, user_id, role)
+            VALUES ($1, $2, COALESCE($3, 'hacker'::participant_role))
+            ON CONFLICT (event, user_id) DO UPDATE SET role = COALESCE($3, participants.role)
+            RETURNING event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
             "#,
             event,
             user_id,
+            role as _,
         )
         .fetch_one(db)
         .await?;
@@ -192,21 +364,117 @@ impl Participant {
         Ok(participant)
     }
 
-    /// Delete a user from an event
+    /// Merge attributes into a participant's existing attributes. Keys set to `null` are removed.
+    ///
+    /// Returns `None` if the user is not participating in the event.
+    #[instrument(name = "Participant::merge_attributes", skip(attributes, db))]
+    pub async fn merge_attributes<'c, 'e, E>(
+        event: &str,
+        user_id: i32,
+        attributes: ParticipantAttributes,
+        db: E,
+    ) -> Result<Option<Participant>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let participant = query_as!(
+            Participant,
+            r#"
+            UPDATE participants 
+            SET attributes = jsonb_strip_nulls(attributes || $3) 
+            WHERE event = $1 AND user_id = $2
+            RETURNING event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            "#,
+            event,
+            user_id,
+            Json(attributes) as _,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(participant)
+    }
+
+    /// Update the participant's fields
+    pub fn update(&mut self) -> ParticipantUpdater<'_> {
+        ParticipantUpdater::new(self)
+    }
+
+    /// Delete a user from an event, returning the removed participant if they were participating
     #[instrument(name = "Participant::delete", skip(db))]
-    pub async fn delete<'c, 'e, E>(event: &str, user_id: i32, db: E) -> Result<()>
+    pub async fn delete<'c, 'e, E>(event: &str, user_id: i32, db: E) -> Result<Option<Participant>>
     where
         'c: 'e,
         E: 'e + Executor<'c, Database = sqlx::Postgres>,
     {
-        query!(
-            "DELETE FROM participants WHERE event = $1 AND user_id = $2",
+        let participant = query_as!(
+            Participant,
+            r#"
+            DELETE FROM participants
+            WHERE event = $1 AND user_id = $2
+            RETURNING event, user_id, role as "role: ParticipantRole",
+                attributes as "attributes: Json<ParticipantAttributes>", created_at, updated_at
+            "#,
             event,
             user_id,
         )
-        .execute(db)
+        .fetch_optional(db)
         .await?;
 
+        Ok(participant)
+    }
+}
+
+/// Handles updating individual fields of the participant. Attributes are changed with
+/// [`Participant::merge_attributes`] instead, so concurrent changes to different keys don't
+/// overwrite each other.
+pub struct ParticipantUpdater<'p> {
+    participant: &'p mut Participant,
+    role: Option<ParticipantRole>,
+}
+
+impl<'p> ParticipantUpdater<'p> {
+    fn new(participant: &'p mut Participant) -> ParticipantUpdater<'p> {
+        Self {
+            participant,
+            role: None,
+        }
+    }
+
+    /// Set the role
+    pub fn role(mut self, role: ParticipantRole) -> ParticipantUpdater<'p> {
+        self.role = Some(role);
+        self
+    }
+
+    /// Perform the update
+    #[instrument(
+        name = "Participant::update",
+        skip_all,
+        fields(%self.participant.event, %self.participant.user_id)
+    )]
+    pub async fn save<'c, 'e, E>(self, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let Some(role) = self.role else {
+            // nothing was changed
+            return Ok(());
+        };
+
+        let mut builder = QueryBuilder::new("UPDATE participants SET role = ");
+        builder.push_bind(role);
+        builder.push(" WHERE event = ");
+        builder.push_bind(&self.participant.event);
+        builder.push(" AND user_id = ");
+        builder.push_bind(self.participant.user_id);
+        builder.build().execute(db).await?;
+
+        self.participant.role = role;
+
         Ok(())
     }
 }
@@ -0,0 +1,101 @@
+use sqlx::PgPool;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How many connections to keep open to each database, and for how long
+#[derive(Clone, Copy, Debug)]
+pub struct PoolOptions {
+    /// The most connections that can be open at once
+    pub max_connections: u32,
+    /// How many connections to keep open, even when idle
+    pub min_connections: u32,
+    /// How long a connection can be idle for before it is closed, if ever
+    pub idle_timeout: Option<Duration>,
+    /// How long a connection can be open for before it is closed, if ever
+    pub max_lifetime: Option<Duration>,
+}
+
+/// How a connection pool is being used
+#[derive(Clone, Copy, Debug)]
+pub struct Utilization {
+    /// Which replica the pool connects to, or `None` for the primary
+    pub replica: Option<usize>,
+    /// How many connections are open
+    pub size: u32,
+    /// How many open connections are not in use
+    pub idle: usize,
+    /// The most connections that can be open at once
+    pub max_connections: u32,
+    /// How long it took to acquire a connection, if one could be acquired
+    pub acquire_time: Option<Duration>,
+}
+
+/// Connections to the primary database and any read replicas
+///
+/// Writes, and reads that must observe them, go to the primary. Reads that can tolerate
+/// replication lag are spread across the replicas, falling back to the primary when there are
+/// none.
+#[derive(Clone, Debug)]
+pub struct Pool {
+    primary: PgPool,
+    replicas: Arc<[PgPool]>,
+    next: Arc<AtomicUsize>,
+}
+
+impl Pool {
+    pub(crate) fn new(primary: PgPool, replicas: Vec<PgPool>) -> Self {
+        Self {
+            primary,
+            replicas: replicas.into(),
+            next: Arc::default(),
+        }
+    }
+
+    /// The connection pool for the primary database
+    pub fn writer(&self) -> &PgPool {
+        &self.primary
+    }
+
+    /// A connection pool to read from, rotating between the replicas
+    pub fn reader(&self) -> &PgPool {
+        if self.replicas.is_empty() {
+            return &self.primary;
+        }
+
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % self.replicas.len();
+        &self.replicas[index]
+    }
+
+    /// Get how the primary and each replica's connection pools are being used
+    ///
+    /// A connection is acquired from each pool to measure how long requests currently wait for one.
+    pub async fn utilization(&self) -> Vec<Utilization> {
+        let pools = std::iter::once((None, &self.primary)).chain(
+            self.replicas
+                .iter()
+                .enumerate()
+                .map(|(i, pool)| (Some(i), pool)),
+        );
+
+        let mut utilization = Vec::with_capacity(self.replicas.len() + 1);
+        for (replica, pool) in pools {
+            let start = Instant::now();
+            let acquire_time = pool.acquire().await.ok().map(|_| start.elapsed());
+
+            utilization.push(Utilization {
+                replica,
+                size: pool.size(),
+                idle: pool.num_idle(),
+                max_connections: pool.options().get_max_connections(),
+                acquire_time,
+            });
+        }
+
+        utilization
+    }
+}
@@ -1,18 +1,20 @@
+#[cfg(feature = "graphql")]
+use crate::{loaders::ProviderUsageLoader, NodeId, ProviderUsage};
 use crate::{Json, Result};
-use chrono::{DateTime, Utc};
 #[cfg(feature = "graphql")]
-use context::{checks, guard};
+use async_graphql::{Context, ResultExt};
+#[cfg(feature = "graphql")]
+use authz::{guard, Action, Resource};
+use chrono::{DateTime, Utc};
 use futures::stream::TryStreamExt;
-use serde::{Deserialize, Serialize};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{Deserialize, Serialize, Serializer};
 use sqlx::{query, query_as, Executor, QueryBuilder};
-use std::{
-    collections::HashMap,
-    fmt::{Debug, Formatter},
-};
+use std::collections::HashMap;
 use tracing::instrument;
 
 /// Configuration for an authentication provider
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 #[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[cfg_attr(feature = "graphql", graphql(complex))]
 pub struct Provider {
@@ -23,18 +25,18 @@ pub struct Provider {
     /// The display name
     pub name: String,
     /// Provider-specific configuration, i.e. implementation kind, OIDC URLs, scopes, etc
-    #[graphql(guard = "guard(checks::admin_only)")]
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Provider)")]
     pub config: Json<ProviderConfiguration>,
     /// When the provider was created
-    #[graphql(guard = "guard(checks::admin_only)")]
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Provider)")]
     pub created_at: DateTime<Utc>,
     /// WHen the provider was last updated
-    #[graphql(guard = "guard(checks::admin_only)")]
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Provider)")]
     pub updated_at: DateTime<Utc>,
 }
 
 /// The provider-specific configuration
-#[derive(Clone, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "kind", rename_all = "lowercase")]
 pub enum ProviderConfiguration {
     /// Google OpenID Connect provider
@@ -42,21 +44,24 @@ pub enum ProviderConfiguration {
         /// The client ID
         client_id: String,
         /// The client secret
-        client_secret: String,
+        #[serde(serialize_with = "expose_secret")]
+        client_secret: SecretString,
     },
     /// GitHub OAuth2 provider
     GitHub {
         /// The client ID
         client_id: String,
         /// The client secret
-        client_secret: String,
+        #[serde(serialize_with = "expose_secret")]
+        client_secret: SecretString,
     },
     /// Discord OAuth2 provider
     Discord {
         /// The client ID
         client_id: String,
         /// The client secret
-        client_secret: String,
+        #[serde(serialize_with = "expose_secret")]
+        client_secret: SecretString,
     },
 }
 
@@ -69,30 +74,25 @@ impl ProviderConfiguration {
             Self::Discord { .. } => "discord",
         }
     }
-}
 
-impl Debug for ProviderConfiguration {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    /// Get the client secret used to authenticate with the provider
+    pub fn client_secret(&self) -> &SecretString {
         match self {
-            Self::Google { client_id, .. } => f
-                .debug_struct("Google")
-                .field("client_id", &client_id)
-                .field("client_secret", &"<REDACTED>")
-                .finish(),
-            Self::GitHub { client_id, .. } => f
-                .debug_struct("GitHub")
-                .field("client_id", &client_id)
-                .field("client_secret", &"<REDACTED>")
-                .finish(),
-            Self::Discord { client_id, .. } => f
-                .debug_struct("Discord")
-                .field("client_id", &client_id)
-                .field("client_secret", &"<REDACTED>")
-                .finish(),
+            Self::Google { client_secret, .. }
+            | Self::GitHub { client_secret, .. }
+            | Self::Discord { client_secret, .. } => client_secret,
         }
     }
 }
 
+/// Serialize a secret in plain text so the configuration can be stored
+fn expose_secret<S>(secret: &SecretString, serializer: S) -> std::result::Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(secret.expose_secret())
+}
+
 impl Provider {
     /// Get all the providers
     #[instrument(name = "Provider::all", skip_all)]
@@ -287,10 +287,29 @@ impl Provider {
 #[cfg(feature = "graphql")]
 #[async_graphql::ComplexObject]
 impl Provider {
+    /// An opaque identifier that is unique across all types
+    async fn global_id(&self) -> async_graphql::ID {
+        NodeId::Provider(self.slug.clone()).encode()
+    }
+
     /// Get the logo to use
     async fn logo(&self) -> &'static str {
         self.config.kind()
     }
+
+    /// How many users have linked the provider and recently used it to sign in
+    #[graphql(guard = "guard(Action::ReadPrivate, Resource::Provider)")]
+    #[instrument(name = "Provider::usage", skip_all, fields(%self.slug))]
+    async fn usage(&self, ctx: &Context<'_>) -> async_graphql::Result<ProviderUsage> {
+        let loader = ctx.data_unchecked::<ProviderUsageLoader>();
+        let usage = loader
+            .load_one(self.slug.clone())
+            .await
+            .extend()?
+            .unwrap_or_default();
+
+        Ok(usage)
+    }
 }
 
 /// Handles updating individual fields of the provider
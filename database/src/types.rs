@@ -80,9 +80,12 @@ where
     T: Deserialize<'r>,
 {
     fn decode(value: <Postgres as HasValueRef<'r>>::ValueRef) -> Result<Self, BoxDynError> {
-        let buf = value.as_bytes()?;
+        let mut buf = value.as_bytes()?;
 
-        // No need for check here since we don't use JSONb
+        // JSONB values are prefixed with a version byte, which can never start valid JSON
+        if buf.first() == Some(&1) {
+            buf = &buf[1..];
+        }
 
         serde_json::from_slice(buf).map(Json).map_err(Into::into)
     }
@@ -1,15 +1,23 @@
 #[cfg(feature = "graphql")]
 use crate::{
-    loaders::{EventsForUserLoader, IdentitiesForUserLoader, OrganizationsForUserLoader},
-    Identity, Organizer, Participant,
+    loaders::{
+        EmailsForUserLoader, EventsForUserLoader, IdentitiesForUserLoader,
+        OrganizationsForUserLoader,
+    },
+    Email, HistoryEntry, Identity, NodeId, Organizer, Participant, PgPool, SessionLookup,
+    UserSession,
 };
 use crate::{Result, Role};
 #[cfg(feature = "graphql")]
 use async_graphql::{ComplexObject, Context, ResultExt};
+#[cfg(feature = "graphql")]
+use authz::{guard, Action, Actor, Resource};
 use chrono::{DateTime, Utc};
 use futures::stream::TryStreamExt;
 use sqlx::{query, query_as, Executor, QueryBuilder};
 use std::collections::HashMap;
+#[cfg(feature = "graphql")]
+use std::sync::Arc;
 use tracing::instrument;
 
 /// A user of the service
@@ -17,16 +25,44 @@ use tracing::instrument;
 #[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[cfg_attr(feature = "graphql", graphql(complex))]
 pub struct User {
-    /// A unique ID
+    /// A unique ID, only for internal use
+    #[cfg_attr(feature = "graphql", graphql(skip))]
     pub id: i32,
+    /// An opaque, unique ID that is safe to expose
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    pub public_id: String,
     /// The given/first name
     pub given_name: String,
     /// The family/last name
     pub family_name: String,
-    /// The primary email as selected by the user
+    /// The primary email as selected by the user, from the addresses they have verified
     pub primary_email: String,
     /// Whether the user is an administrator
     pub is_admin: bool,
+    /// When the user was banned or suspended
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::Read, Resource::Moderation)")
+    )]
+    pub banned_at: Option<DateTime<Utc>>,
+    /// When a suspension ends, a ban is permanent if unset
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::Read, Resource::Moderation)")
+    )]
+    pub banned_until: Option<DateTime<Utc>>,
+    /// Why the user was banned or suspended
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::Read, Resource::Moderation)")
+    )]
+    pub ban_reason: Option<String>,
+    /// Notes kept by administrators about the user
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::Read, Resource::Moderation)")
+    )]
+    pub moderation_notes: Option<String>,
     /// When the user was first created
     pub created_at: DateTime<Utc>,
     /// When the user was last updated
@@ -49,6 +85,28 @@ impl User {
         Ok(by_id)
     }
 
+    /// Load all the users by their public IDs, for use in dataloaders
+    #[instrument(name = "User::load_by_public_id", skip(db))]
+    pub(crate) async fn load_by_public_id<'c, 'e, E>(
+        public_ids: &[String],
+        db: E,
+    ) -> Result<HashMap<String, User>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let by_public_id = query_as!(
+            User,
+            "SELECT * FROM users WHERE public_id = ANY($1)",
+            public_ids
+        )
+        .fetch(db)
+        .map_ok(|user| (user.public_id.clone(), user))
+        .try_collect()
+        .await?;
+        Ok(by_public_id)
+    }
+
     /// Load all the users by their primary emails, for use in dataloaders
     #[instrument(name = "User::load_by_primary_email", skip(db))]
     pub(crate) async fn load_by_primary_email<'c, 'e, E>(
@@ -98,6 +156,19 @@ impl User {
         Ok(user)
     }
 
+    /// Get a user by their public ID
+    #[instrument(name = "User::find_by_public_id", skip(db))]
+    pub async fn find_by_public_id<'c, 'e, E>(public_id: &str, db: E) -> Result<Option<User>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let user = query_as!(User, "SELECT * FROM users WHERE public_id = $1", public_id)
+            .fetch_optional(db)
+            .await?;
+        Ok(user)
+    }
+
     /// Get a user by it's primary email
     #[instrument(name = "User::find_by_primary_email", skip(db))]
     pub async fn find_by_primary_email<'c, 'e, E>(email: &str, db: E) -> Result<Option<User>>
@@ -111,6 +182,29 @@ impl User {
         Ok(user)
     }
 
+    /// Get a user by their primary email if they have never logged in, such as those created by
+    /// an import, so the first login with that verified email can claim the account
+    #[instrument(name = "User::find_unclaimed_by_email", skip(db))]
+    pub async fn find_unclaimed_by_email<'c, 'e, E>(email: &str, db: E) -> Result<Option<User>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let user = query_as!(
+            User,
+            r#"
+            SELECT * FROM users
+            WHERE primary_email = $1
+                AND NOT EXISTS (SELECT 1 FROM identities WHERE identities.user_id = users.id)
+            FOR UPDATE
+            "#,
+            email
+        )
+        .fetch_optional(db)
+        .await?;
+        Ok(user)
+    }
+
     /// Check the role a user has in the organization
     #[instrument(name = "User::is_organizer", skip(db))]
     pub async fn is_organizer<'c, 'e, E>(
@@ -133,6 +227,41 @@ impl User {
         Ok(result.map(|result| result.role))
     }
 
+    /// Check the role a user has in an event, either through the organization putting it on or
+    /// by being granted access to the event directly. The most privileged role is used when they
+    /// have both.
+    #[instrument(name = "User::organizer_role_for_event", skip(db))]
+    pub async fn organizer_role_for_event<'c, 'e, E>(
+        id: i32,
+        event: &str,
+        organization_id: i32,
+        db: E,
+    ) -> Result<Option<Role>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        // Roles are declared from most to least privileged, so the first one is the highest
+        let result = query!(
+            r#"
+            SELECT role as "role!: Role" FROM (
+                SELECT role FROM organizers WHERE organization_id = $1 AND user_id = $3
+                UNION ALL
+                SELECT role FROM event_organizers WHERE event = $2 AND user_id = $3
+            ) roles
+            ORDER BY role
+            LIMIT 1
+            "#,
+            organization_id,
+            event,
+            id,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(result.map(|result| result.role))
+    }
+
     /// Check if the user is an organizer for the events
     #[instrument(name = "User::is_organizer_for_event", skip(db))]
     pub async fn is_organizer_for_event<'c, 'e, E>(id: i32, event: &str, db: E) -> Result<bool>
@@ -149,7 +278,9 @@ impl User {
                 WHERE 
                     users.id = $1 
                     AND events.slug = $2
-            )
+            ) OR exists(
+                SELECT 1 FROM event_organizers WHERE user_id = $1 AND event = $2
+            ) as "exists"
             "#,
             id,
             event,
@@ -178,7 +309,7 @@ impl User {
         Ok(result.exists.unwrap_or_default())
     }
 
-    /// Create a new user
+    /// Create a new user, with the primary email as their first verified address
     #[instrument(name = "User::create", skip(db))]
     pub async fn create<'c, 'e, E>(
         given_name: &str,
@@ -193,8 +324,19 @@ impl User {
         let user = query_as!(
             User,
             r#"
-            INSERT INTO users (given_name, family_name, primary_email) 
-            VALUES ($1, $2, $3) RETURNING *
+            WITH new_user AS (
+                INSERT INTO users (given_name, family_name, primary_email)
+                VALUES ($1, $2, $3) RETURNING *
+            ), new_email AS (
+                INSERT INTO emails (address, user_id, is_primary, verified)
+                SELECT primary_email, id, true, true FROM new_user
+            )
+            SELECT
+                id as "id!", public_id as "public_id!", given_name as "given_name!", family_name as "family_name!",
+                primary_email as "primary_email!", is_admin as "is_admin!",
+                banned_at, banned_until, ban_reason, moderation_notes,
+                created_at as "created_at!", updated_at as "updated_at!"
+            FROM new_user
             "#,
             given_name,
             family_name,
@@ -205,6 +347,63 @@ impl User {
         Ok(user)
     }
 
+    /// Whether the user is currently banned or suspended
+    pub fn is_banned(&self) -> bool {
+        self.banned_at.is_some() && self.banned_until.is_none_or(|until| until > Utc::now())
+    }
+
+    /// Ban a user, or suspend them if an end is provided. Banning a user that is already banned
+    /// replaces the previous ban.
+    #[instrument(name = "User::ban", skip(db))]
+    pub async fn ban<'c, 'e, E>(
+        id: i32,
+        reason: &str,
+        until: Option<DateTime<Utc>>,
+        db: E,
+    ) -> Result<Option<User>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let user = query_as!(
+            User,
+            r#"
+            UPDATE users SET banned_at = now(), banned_until = $2, ban_reason = $3
+            WHERE id = $1
+            RETURNING *
+            "#,
+            id,
+            until,
+            reason,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(user)
+    }
+
+    /// Lift a user's ban or suspension
+    #[instrument(name = "User::unban", skip(db))]
+    pub async fn unban<'c, 'e, E>(id: i32, db: E) -> Result<Option<User>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let user = query_as!(
+            User,
+            r#"
+            UPDATE users SET banned_at = NULL, banned_until = NULL, ban_reason = NULL
+            WHERE id = $1
+            RETURNING *
+            "#,
+            id,
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(user)
+    }
+
     /// Update the fields of a user
     pub fn update(&mut self) -> UserUpdater<'_> {
         UserUpdater::new(self)
@@ -228,6 +427,16 @@ impl User {
 #[cfg(feature = "graphql")]
 #[ComplexObject]
 impl User {
+    /// A unique ID
+    async fn id(&self) -> async_graphql::ID {
+        async_graphql::ID(self.public_id.clone())
+    }
+
+    /// An opaque identifier that is unique across all types
+    async fn global_id(&self) -> async_graphql::ID {
+        NodeId::User(self.public_id.clone()).encode()
+    }
+
     /// The identities the user can login with
     #[instrument(name = "User::identities", skip_all, fields(%self.id))]
     async fn identities(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Identity>> {
@@ -237,6 +446,15 @@ impl User {
         Ok(identities)
     }
 
+    /// The addresses the user can receive email at, primary first
+    #[instrument(name = "User::emails", skip_all, fields(%self.id))]
+    async fn emails(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Email>> {
+        let loader = ctx.data_unchecked::<EmailsForUserLoader>();
+        let emails = loader.load_one(self.id).await.extend()?.unwrap_or_default();
+
+        Ok(emails)
+    }
+
     /// The organizations the user is part of
     #[instrument(name = "User::organizations", skip_all, fields(%self.id))]
     async fn organizations(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<Organizer>> {
@@ -254,6 +472,34 @@ impl User {
 
         Ok(events)
     }
+
+    /// The active login sessions for the user, most recently used first
+    #[instrument(name = "User::sessions", skip_all, fields(%self.id))]
+    async fn sessions(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<UserSession>> {
+        let resource = Resource::Session { user_id: self.id };
+        authz::require(&Actor::from_graphql(ctx), Action::Read, &resource)?;
+
+        let lookup = ctx.data_unchecked::<Arc<dyn SessionLookup>>();
+        let mut sessions = lookup.for_user(ctx, self.id).await?;
+        sessions.sort_by(|a, b| b.last_seen_at.cmp(&a.last_seen_at));
+
+        Ok(sessions)
+    }
+
+    /// The previous versions of the user, newest first. If a point in time is provided, only the
+    /// version that was valid at that time is returned.
+    #[graphql(guard = "guard(Action::Read, Resource::History)")]
+    #[instrument(name = "User::history", skip_all, fields(%self.id))]
+    async fn history(
+        &self,
+        ctx: &Context<'_>,
+        at: Option<DateTime<Utc>>,
+    ) -> async_graphql::Result<Vec<HistoryEntry>> {
+        let db = ctx.data_unchecked::<PgPool>();
+        let entries = HistoryEntry::for_user(self.id, at, db).await.extend()?;
+
+        Ok(entries)
+    }
 }
 
 /// Handles updating individual fields of the user
@@ -261,8 +507,8 @@ pub struct UserUpdater<'u> {
     user: &'u mut User,
     given_name: Option<String>,
     family_name: Option<String>,
-    primary_email: Option<String>,
     is_admin: Option<bool>,
+    moderation_notes: Option<Option<String>>,
 }
 
 impl<'u> UserUpdater<'u> {
@@ -271,8 +517,8 @@ impl<'u> UserUpdater<'u> {
             user,
             given_name: None,
             family_name: None,
-            primary_email: None,
             is_admin: None,
+            moderation_notes: None,
         }
     }
 
@@ -300,18 +546,6 @@ impl<'u> UserUpdater<'u> {
         self
     }
 
-    /// Update the primary email
-    pub fn primary_email(mut self, primary_email: String) -> UserUpdater<'u> {
-        self.primary_email = Some(primary_email);
-        self
-    }
-
-    /// Directly set the primary email
-    pub fn override_primary_email(mut self, primary_email: Option<String>) -> UserUpdater<'u> {
-        self.primary_email = primary_email;
-        self
-    }
-
     /// Update whether the user is an admin
     #[allow(clippy::wrong_self_convention)]
     pub fn is_admin(mut self, is_admin: bool) -> UserUpdater<'u> {
@@ -325,6 +559,21 @@ impl<'u> UserUpdater<'u> {
         self
     }
 
+    /// Update the notes kept by administrators, removing them if `None`
+    pub fn moderation_notes(mut self, moderation_notes: Option<String>) -> UserUpdater<'u> {
+        self.moderation_notes = Some(moderation_notes);
+        self
+    }
+
+    /// Directly set the notes kept by administrators
+    pub fn override_moderation_notes(
+        mut self,
+        moderation_notes: Option<Option<String>>,
+    ) -> UserUpdater<'u> {
+        self.moderation_notes = moderation_notes;
+        self
+    }
+
     /// Perform the update
     #[instrument(name = "User::update", skip_all, fields(self.id = %self.user.id))]
     pub async fn save<'c, 'e, E>(self, db: E) -> Result<()>
@@ -332,7 +581,11 @@ impl<'u> UserUpdater<'u> {
         'c: 'e,
         E: 'e + Executor<'c, Database = sqlx::Postgres>,
     {
-        if self.given_name.is_none() && self.family_name.is_none() && self.primary_email.is_none() {
+        if self.given_name.is_none()
+            && self.family_name.is_none()
+            && self.is_admin.is_none()
+            && self.moderation_notes.is_none()
+        {
             // nothing was changed
             return Ok(());
         }
@@ -350,9 +603,14 @@ impl<'u> UserUpdater<'u> {
             separated.push_bind_unseparated(family_name);
         }
 
-        if let Some(primary_email) = &self.primary_email {
-            separated.push("primary_email = ");
-            separated.push_bind_unseparated(primary_email);
+        if let Some(is_admin) = self.is_admin {
+            separated.push("is_admin = ");
+            separated.push_bind_unseparated(is_admin);
+        }
+
+        if let Some(moderation_notes) = &self.moderation_notes {
+            separated.push("moderation_notes = ");
+            separated.push_bind_unseparated(moderation_notes);
         }
 
         builder.push(" WHERE id = ");
@@ -367,8 +625,12 @@ impl<'u> UserUpdater<'u> {
             self.user.family_name = family_name;
         }
 
-        if let Some(primary_email) = self.primary_email {
-            self.user.primary_email = primary_email;
+        if let Some(is_admin) = self.is_admin {
+            self.user.is_admin = is_admin;
+        }
+
+        if let Some(moderation_notes) = self.moderation_notes {
+            self.user.moderation_notes = moderation_notes;
         }
 
         Ok(())
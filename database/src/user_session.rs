@@ -0,0 +1,32 @@
+use async_graphql::{Context, SimpleObject};
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+
+/// A login session for a user
+#[derive(Clone, Debug, Eq, PartialEq, SimpleObject)]
+#[graphql(name = "Session")]
+pub struct UserSession {
+    /// A unique ID
+    pub id: String,
+    /// When the session was first created
+    pub created_at: DateTime<Utc>,
+    /// When the session was last used
+    pub last_seen_at: DateTime<Utc>,
+    /// The IP address the session was last used from
+    pub ip: Option<String>,
+    /// The user agent the session was last used from
+    pub user_agent: Option<String>,
+    /// Whether this is the session making the request
+    pub current: bool,
+}
+
+/// Looks up the login sessions for users. Sessions are not stored in the database, so this is
+/// provided through the schema's data by whatever does store them.
+pub trait SessionLookup: Send + Sync {
+    /// Get a user's active sessions, marking the one making the request as current
+    fn for_user(
+        &self,
+        ctx: &Context<'_>,
+        user_id: i32,
+    ) -> BoxFuture<'_, async_graphql::Result<Vec<UserSession>>>;
+}
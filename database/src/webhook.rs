@@ -0,0 +1,301 @@
+use crate::Result;
+#[cfg(feature = "graphql")]
+use async_graphql::{Enum, SimpleObject};
+#[cfg(feature = "graphql")]
+use authz::{guard, Action, Resource};
+use chrono::{DateTime, Utc};
+use sqlx::{query, query_as, Executor, QueryBuilder};
+use std::fmt::{Debug, Formatter};
+use tracing::instrument;
+
+/// Something that happened which can be delivered to webhooks
+#[derive(Clone, Copy, Debug, Eq, PartialEq, sqlx::Type)]
+#[cfg_attr(feature = "graphql", derive(Enum))]
+#[sqlx(rename_all = "snake_case", type_name = "webhook_event")]
+pub enum WebhookEvent {
+    /// A participant's information changed
+    ParticipantChanged,
+    /// An event extension was requested or reviewed
+    EventExtensionChanged,
+    /// A user finished registering
+    UserCreated,
+}
+
+impl WebhookEvent {
+    /// Get the name of the event
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WebhookEvent::ParticipantChanged => "participant_changed",
+            WebhookEvent::EventExtensionChanged => "event_extension_changed",
+            WebhookEvent::UserCreated => "user_created",
+        }
+    }
+}
+
+/// An endpoint that is notified when events occur
+#[derive(Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+pub struct Webhook {
+    /// A unique ID
+    pub id: i32,
+    /// Where deliveries are sent
+    pub url: String,
+    /// The key used to sign deliveries
+    #[cfg_attr(
+        feature = "graphql",
+        graphql(guard = "guard(Action::ReadPrivate, Resource::Webhook)")
+    )]
+    pub secret: String,
+    /// The events the webhook is subscribed to
+    pub events: Vec<WebhookEvent>,
+    /// Whether deliveries will be sent
+    pub enabled: bool,
+    /// When the webhook was created
+    pub created_at: DateTime<Utc>,
+    /// When the webhook was last updated
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Debug for Webhook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Webhook")
+            .field("id", &self.id)
+            .field("url", &self.url)
+            .field("secret", &"<REDACTED>")
+            .field("events", &self.events)
+            .field("enabled", &self.enabled)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
+
+impl Webhook {
+    /// Get all the webhooks
+    #[instrument(name = "Webhook::all", skip_all)]
+    pub async fn all<'c, 'e, E>(db: E) -> Result<Vec<Webhook>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let webhooks = query_as!(
+            Webhook,
+            r#"
+            SELECT
+                id, url, secret, events as "events: Vec<WebhookEvent>", enabled,
+                created_at, updated_at
+            FROM webhooks
+            ORDER BY id
+            "#
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(webhooks)
+    }
+
+    /// Get a webhook by its ID
+    #[instrument(name = "Webhook::find", skip(db))]
+    pub async fn find<'c, 'e, E>(id: i32, db: E) -> Result<Option<Webhook>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let webhook = query_as!(
+            Webhook,
+            r#"
+            SELECT
+                id, url, secret, events as "events: Vec<WebhookEvent>", enabled,
+                created_at, updated_at
+            FROM webhooks
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Create a new webhook
+    #[instrument(name = "Webhook::create", skip(secret, db))]
+    pub async fn create<'c, 'e, E>(
+        url: &str,
+        secret: &str,
+        events: &[WebhookEvent],
+        db: E,
+    ) -> Result<Webhook>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let webhook = query_as!(
+            Webhook,
+            r#"
+            INSERT INTO webhooks (url, secret, events)
+            VALUES ($1, $2, $3)
+            RETURNING
+                id, url, secret, events as "events: Vec<WebhookEvent>", enabled,
+                created_at, updated_at
+            "#,
+            url,
+            secret,
+            events as &[WebhookEvent],
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(webhook)
+    }
+
+    /// Update the fields of a webhook
+    pub fn update(&mut self) -> WebhookUpdater<'_> {
+        WebhookUpdater::new(self)
+    }
+
+    /// Delete a webhook by its ID, returning whether it existed
+    #[instrument(name = "Webhook::delete", skip(db))]
+    pub async fn delete<'c, 'e, E>(id: i32, db: E) -> Result<bool>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let result = query!("DELETE FROM webhooks WHERE id = $1", id)
+            .execute(db)
+            .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+/// Handles updating individual fields of the webhook
+pub struct WebhookUpdater<'w> {
+    webhook: &'w mut Webhook,
+    url: Option<String>,
+    secret: Option<String>,
+    events: Option<Vec<WebhookEvent>>,
+    enabled: Option<bool>,
+}
+
+impl<'w> WebhookUpdater<'w> {
+    fn new(webhook: &'w mut Webhook) -> WebhookUpdater<'w> {
+        Self {
+            webhook,
+            url: None,
+            secret: None,
+            events: None,
+            enabled: None,
+        }
+    }
+
+    /// Update where deliveries are sent
+    pub fn url(mut self, url: String) -> WebhookUpdater<'w> {
+        self.url = Some(url);
+        self
+    }
+
+    /// Directly set where deliveries are sent
+    pub fn override_url(mut self, url: Option<String>) -> WebhookUpdater<'w> {
+        self.url = url;
+        self
+    }
+
+    /// Update the signing key
+    pub fn secret(mut self, secret: String) -> WebhookUpdater<'w> {
+        self.secret = Some(secret);
+        self
+    }
+
+    /// Directly set the signing key
+    pub fn override_secret(mut self, secret: Option<String>) -> WebhookUpdater<'w> {
+        self.secret = secret;
+        self
+    }
+
+    /// Update the subscribed events
+    pub fn events(mut self, events: Vec<WebhookEvent>) -> WebhookUpdater<'w> {
+        self.events = Some(events);
+        self
+    }
+
+    /// Directly set the subscribed events
+    pub fn override_events(mut self, events: Option<Vec<WebhookEvent>>) -> WebhookUpdater<'w> {
+        self.events = events;
+        self
+    }
+
+    /// Update the enabled status
+    pub fn enabled(mut self, enabled: bool) -> WebhookUpdater<'w> {
+        self.enabled = Some(enabled);
+        self
+    }
+
+    /// Directly set the enabled status
+    pub fn override_enabled(mut self, enabled: Option<bool>) -> WebhookUpdater<'w> {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Perform the update
+    #[instrument(name = "Webhook::update", skip_all, fields(self.id = self.webhook.id))]
+    pub async fn save<'c, 'e, E>(self, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        if self.url.is_none()
+            && self.secret.is_none()
+            && self.events.is_none()
+            && self.enabled.is_none()
+        {
+            // nothing was changed
+            return Ok(());
+        }
+
+        let mut builder = QueryBuilder::new("UPDATE webhooks SET ");
+        let mut separated = builder.separated(", ");
+
+        if let Some(url) = &self.url {
+            separated.push("url = ");
+            separated.push_bind_unseparated(url);
+        }
+
+        if let Some(secret) = &self.secret {
+            separated.push("secret = ");
+            separated.push_bind_unseparated(secret);
+        }
+
+        if let Some(events) = &self.events {
+            separated.push("events = ");
+            separated.push_bind_unseparated(events);
+        }
+
+        if let Some(enabled) = self.enabled {
+            separated.push("enabled = ");
+            separated.push_bind_unseparated(enabled);
+        }
+
+        builder.push(" WHERE id = ");
+        builder.push_bind(self.webhook.id);
+        builder.build().execute(db).await?;
+
+        if let Some(url) = self.url {
+            self.webhook.url = url;
+        }
+
+        if let Some(secret) = self.secret {
+            self.webhook.secret = secret;
+        }
+
+        if let Some(events) = self.events {
+            self.webhook.events = events;
+        }
+
+        if let Some(enabled) = self.enabled {
+            self.webhook.enabled = enabled;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,173 @@
+use crate::{Json, Result, WebhookEvent};
+#[cfg(feature = "graphql")]
+use async_graphql::{ComplexObject, SimpleObject};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{query_as, Executor};
+use tracing::instrument;
+
+/// How much of the response body is kept for each delivery
+pub const RESPONSE_SNIPPET_LENGTH: usize = 1024;
+
+/// A single attempt at sending an event to a webhook
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(SimpleObject))]
+#[cfg_attr(feature = "graphql", graphql(complex))]
+pub struct WebhookDelivery {
+    /// A unique ID
+    pub id: i32,
+    /// The webhook the event was sent to
+    pub webhook_id: i32,
+    /// The event that was sent
+    pub event: WebhookEvent,
+    /// The body of the request
+    pub payload: Json<Value>,
+    /// The HTTP status code of the response, if one was received
+    pub status_code: Option<i32>,
+    /// How long the request took, in milliseconds
+    pub latency_ms: i32,
+    /// The start of the response body
+    pub response: Option<String>,
+    /// Why the request failed, if it could not be completed
+    pub error: Option<String>,
+    /// When the delivery was attempted
+    pub created_at: DateTime<Utc>,
+}
+
+/// The result of attempting a delivery
+#[derive(Debug, Default)]
+pub struct DeliveryOutcome {
+    /// The HTTP status code of the response, if one was received
+    pub status_code: Option<u16>,
+    /// How long the request took, in milliseconds
+    pub latency_ms: u128,
+    /// The response body
+    pub response: Option<String>,
+    /// Why the request failed, if it could not be completed
+    pub error: Option<String>,
+}
+
+#[cfg(feature = "graphql")]
+#[ComplexObject]
+impl WebhookDelivery {
+    /// Whether the webhook accepted the event
+    async fn successful(&self) -> bool {
+        self.is_successful()
+    }
+}
+
+impl WebhookDelivery {
+    /// Whether the webhook accepted the event
+    pub fn is_successful(&self) -> bool {
+        self.status_code
+            .is_some_and(|status| (200..300).contains(&status))
+    }
+
+    /// Get a page of deliveries for a webhook, newest first, starting after the cursor
+    #[instrument(name = "WebhookDelivery::page", skip(db))]
+    pub async fn page<'c, 'e, E>(
+        webhook_id: i32,
+        after: Option<i32>,
+        limit: i64,
+        db: E,
+    ) -> Result<Vec<WebhookDelivery>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let deliveries = query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT
+                id, webhook_id, event as "event: WebhookEvent",
+                payload as "payload: Json<Value>", status_code, latency_ms, response, error,
+                created_at
+            FROM webhook_deliveries
+            WHERE webhook_id = $1 AND ($2::int IS NULL OR id < $2)
+            ORDER BY id DESC
+            LIMIT $3
+            "#,
+            webhook_id,
+            after,
+            limit,
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(deliveries)
+    }
+
+    /// Find a delivery by its ID
+    #[instrument(name = "WebhookDelivery::find", skip(db))]
+    pub async fn find<'c, 'e, E>(id: i32, db: E) -> Result<Option<WebhookDelivery>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let delivery = query_as!(
+            WebhookDelivery,
+            r#"
+            SELECT
+                id, webhook_id, event as "event: WebhookEvent",
+                payload as "payload: Json<Value>", status_code, latency_ms, response, error,
+                created_at
+            FROM webhook_deliveries
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(delivery)
+    }
+
+    /// Record an attempt at delivering the event, truncating the response body
+    #[instrument(name = "WebhookDelivery::record", skip(payload, outcome, db))]
+    pub async fn record<'c, 'e, E>(
+        webhook_id: i32,
+        event: WebhookEvent,
+        payload: &Value,
+        outcome: DeliveryOutcome,
+        db: E,
+    ) -> Result<WebhookDelivery>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let response = outcome.response.map(|mut response| {
+            if response.len() > RESPONSE_SNIPPET_LENGTH {
+                let mut end = RESPONSE_SNIPPET_LENGTH;
+                while !response.is_char_boundary(end) {
+                    end -= 1;
+                }
+                response.truncate(end);
+            }
+            response
+        });
+
+        let delivery = query_as!(
+            WebhookDelivery,
+            r#"
+            INSERT INTO webhook_deliveries
+                (webhook_id, event, payload, status_code, latency_ms, response, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING
+                id, webhook_id, event as "event: WebhookEvent",
+                payload as "payload: Json<Value>", status_code, latency_ms, response, error,
+                created_at
+            "#,
+            webhook_id,
+            event as WebhookEvent,
+            Json(payload) as _,
+            outcome.status_code.map(i32::from),
+            i32::try_from(outcome.latency_ms).unwrap_or(i32::MAX),
+            response,
+            outcome.error,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(delivery)
+    }
+}
@@ -0,0 +1,179 @@
+use crate::{Json, Result, WebhookEvent};
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{query, query_as, query_scalar, Executor};
+use std::collections::HashMap;
+use tracing::instrument;
+
+/// An event waiting to be delivered to a webhook
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WebhookOutboxEntry {
+    /// A unique ID
+    pub id: i32,
+    /// The webhook to deliver to
+    pub webhook_id: i32,
+    /// The event to deliver
+    pub event: WebhookEvent,
+    /// The body of the request
+    pub payload: Json<Value>,
+    /// The ID of the request that caused the event, if any
+    pub request_id: Option<String>,
+    /// The W3C trace context of the request that caused the event, if it was traced
+    pub trace_context: Json<HashMap<String, String>>,
+    /// How many times delivery has failed
+    pub attempts: i32,
+    /// When the entry was queued
+    pub created_at: DateTime<Utc>,
+}
+
+impl WebhookOutboxEntry {
+    /// Queue the event for every enabled webhook subscribed to it, returning how many deliveries
+    /// were queued. A job to deliver to each webhook is queued alongside, unless one already is.
+    #[instrument(name = "WebhookOutboxEntry::enqueue", skip(payload, trace_context, db))]
+    pub async fn enqueue<'c, 'e, E>(
+        event: WebhookEvent,
+        payload: &Value,
+        request_id: Option<&str>,
+        trace_context: &HashMap<String, String>,
+        db: E,
+    ) -> Result<u64>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let queued = query_scalar!(
+            r#"
+            WITH queued AS (
+                INSERT INTO webhook_outbox (webhook_id, event, payload, request_id, trace_context)
+                SELECT id, $1, $2, $3, $4
+                FROM webhooks
+                WHERE enabled AND $1 = ANY(events)
+                RETURNING webhook_id
+            ), scheduled AS (
+                INSERT INTO jobs (kind, key, payload)
+                SELECT
+                    'deliver_webhook', 'deliver-webhook:' || webhook_id,
+                    jsonb_build_object('webhook', webhook_id)
+                FROM queued
+                ON CONFLICT (key) WHERE key IS NOT NULL DO NOTHING
+            )
+            SELECT count(*) as "count!" FROM queued
+            "#,
+            event as WebhookEvent,
+            Json(payload) as _,
+            request_id,
+            Json(trace_context) as _,
+        )
+        .fetch_one(db)
+        .await?;
+
+        Ok(queued as u64)
+    }
+
+    /// Get the IDs of the enabled webhooks with deliveries that are not currently being worked on or
+    /// waiting to be retried
+    #[instrument(name = "WebhookOutboxEntry::pending_webhooks", skip_all)]
+    pub async fn pending_webhooks<'c, 'e, E>(db: E) -> Result<Vec<i32>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let ids = query_scalar!(
+            r#"
+            SELECT DISTINCT webhook_id
+            FROM webhook_outbox
+            INNER JOIN webhooks ON webhooks.id = webhook_outbox.webhook_id
+            WHERE webhooks.enabled
+                AND NOT EXISTS (
+                    SELECT 1 FROM webhook_outbox leased
+                    WHERE leased.webhook_id = webhook_outbox.webhook_id
+                        AND leased.locked_until > now()
+                )
+            "#
+        )
+        .fetch_all(db)
+        .await?;
+
+        Ok(ids)
+    }
+
+    /// Lease the oldest entry for the webhook, unless another entry for it is already leased.
+    /// Leases expire so entries held by a worker that stopped are eventually retried.
+    #[instrument(name = "WebhookOutboxEntry::claim", skip(db))]
+    pub async fn claim<'c, 'e, E>(
+        webhook_id: i32,
+        lease_seconds: i32,
+        db: E,
+    ) -> Result<Option<WebhookOutboxEntry>>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        let entry = query_as!(
+            WebhookOutboxEntry,
+            r#"
+            UPDATE webhook_outbox
+            SET locked_until = now() + make_interval(secs => $2)
+            WHERE id = (
+                SELECT id FROM webhook_outbox
+                WHERE webhook_id = $1
+                    AND NOT EXISTS (
+                        SELECT 1 FROM webhook_outbox leased
+                        WHERE leased.webhook_id = $1 AND leased.locked_until > now()
+                    )
+                ORDER BY id
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING
+                id, webhook_id, event as "event: WebhookEvent",
+                payload as "payload: Json<Value>", request_id,
+                trace_context as "trace_context: Json<HashMap<String, String>>", attempts,
+                created_at
+            "#,
+            webhook_id,
+            f64::from(lease_seconds),
+        )
+        .fetch_optional(db)
+        .await?;
+
+        Ok(entry)
+    }
+
+    /// Record a failed attempt and hold the entry until it should be retried. The webhook's later
+    /// entries wait behind it so they are still delivered in order.
+    #[instrument(name = "WebhookOutboxEntry::retry", skip(self, db), fields(%self.id))]
+    pub async fn retry<'c, 'e, E>(&self, delay_seconds: i32, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        query!(
+            r#"
+            UPDATE webhook_outbox
+            SET attempts = attempts + 1, locked_until = now() + make_interval(secs => $2)
+            WHERE id = $1
+            "#,
+            self.id,
+            f64::from(delay_seconds),
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Remove the entry once it has been delivered or given up on
+    #[instrument(name = "WebhookOutboxEntry::complete", skip_all, fields(%self.id))]
+    pub async fn complete<'c, 'e, E>(&self, db: E) -> Result<()>
+    where
+        'c: 'e,
+        E: 'e + Executor<'c, Database = sqlx::Postgres>,
+    {
+        query!("DELETE FROM webhook_outbox WHERE id = $1", self.id)
+            .execute(db)
+            .await?;
+
+        Ok(())
+    }
+}
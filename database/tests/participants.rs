@@ -0,0 +1,88 @@
+use database::{
+    fixtures::{EventFactory, OrganizationFactory, OrganizerFactory, ParticipantFactory},
+    Event, Organization, Organizer, Participant, ParticipantRole, Role,
+};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+
+#[sqlx::test(migrations = "../migrations")]
+async fn participant_count_follows_participants(db: PgPool) {
+    let event = EventFactory::default().create(&db).await.unwrap();
+    assert_eq!(event.participant_count, 0);
+
+    let first = ParticipantFactory::default()
+        .event(&event.slug)
+        .create(&db)
+        .await
+        .unwrap();
+    ParticipantFactory::default()
+        .event(&event.slug)
+        .role(ParticipantRole::Mentor)
+        .create(&db)
+        .await
+        .unwrap();
+
+    let event = Event::find(&event.slug, &db).await.unwrap().unwrap();
+    assert_eq!(event.participant_count, 2);
+
+    Participant::delete(&event.slug, first.user_id, &db)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let event = Event::find(&event.slug, &db).await.unwrap().unwrap();
+    assert_eq!(event.participant_count, 1);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn organizer_count_follows_organizers(db: PgPool) {
+    let organization = OrganizationFactory::default().create(&db).await.unwrap();
+    let before = organization.organizer_count;
+
+    let organizer = OrganizerFactory::default()
+        .organization(organization.id)
+        .role(Role::Manager)
+        .create(&db)
+        .await
+        .unwrap();
+
+    let organization = Organization::find(organization.id, &db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(organization.organizer_count, before + 1);
+
+    Organizer::delete(organization.id, organizer.user_id, &db)
+        .await
+        .unwrap();
+
+    let organization = Organization::find(organization.id, &db)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(organization.organizer_count, before);
+}
+
+#[sqlx::test(migrations = "../migrations")]
+async fn merge_attributes_removes_null_keys(db: PgPool) {
+    let participant = ParticipantFactory::default().create(&db).await.unwrap();
+
+    let attributes = json!({ "shirt": "m", "diet": "vegan" });
+    let attributes = serde_json::from_value(attributes).unwrap();
+    Participant::merge_attributes(&participant.event, participant.user_id, attributes, &db)
+        .await
+        .unwrap()
+        .unwrap();
+
+    let attributes = json!({ "shirt": "l", "diet": null });
+    let attributes = serde_json::from_value(attributes).unwrap();
+    let participant =
+        Participant::merge_attributes(&participant.event, participant.user_id, attributes, &db)
+            .await
+            .unwrap()
+            .unwrap();
+    assert_eq!(
+        Value::Object(participant.attributes.0),
+        json!({ "shirt": "l" })
+    );
+}
@@ -0,0 +1,161 @@
+use lettre::message::Mailbox;
+use serde::Serialize;
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time,
+};
+use tracing::{error, info, instrument, warn, Instrument, Span};
+use url::Url;
+
+mod messages;
+#[cfg(feature = "ses")]
+mod ses;
+mod smtp;
+mod templates;
+
+pub use messages::{EventExtensionReviewed, NewLogin, VerifyEmail, Welcome};
+pub use templates::Template;
+use templates::Templates;
+
+/// Any error that occurs while connecting or sending
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// How many emails can be waiting to be sent before new ones are dropped
+const QUEUE_SIZE: usize = 1024;
+/// How many times sending an email is attempted before giving up
+const MAX_ATTEMPTS: u32 = 3;
+/// How long to wait after the first failed attempt, doubling after each subsequent failure
+const RETRY_DELAY: Duration = Duration::from_secs(2);
+
+/// How an email is styled and who replies go to, allowing events to customize emails sent on
+/// their behalf
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Branding {
+    /// The name emails are sent on behalf of
+    pub name: Option<String>,
+    /// The URL of the logo shown at the top of emails
+    pub logo_url: Option<String>,
+    /// The hex color used for buttons and links
+    pub accent_color: Option<String>,
+    /// Where replies are delivered, instead of the sending address
+    pub reply_to: Option<String>,
+}
+
+/// A rendered email, ready to be sent
+struct Message {
+    to: String,
+    reply_to: Option<String>,
+    subject: String,
+    html: String,
+    text: String,
+    span: Span,
+}
+
+/// An email delivery implementation
+#[async_trait::async_trait]
+trait Backend: Send + Sync {
+    /// Send the message from the address
+    async fn send(&self, from: &Mailbox, message: &Message) -> Result<(), Error>;
+}
+
+/// Renders emails from templates and sends them in the background
+///
+/// Emails are sent one at a time in the order they were queued, and are retried a few times
+/// before being dropped.
+#[derive(Clone)]
+pub struct Mailer {
+    queue: mpsc::Sender<Message>,
+    templates: Arc<Templates>,
+}
+
+impl Mailer {
+    /// Connect to the delivery service, choosing the implementation from the URL's scheme
+    ///
+    /// - `smtp://<user>:<password>@<host>:<port>` connects to an SMTP server using STARTTLS, or
+    ///   plaintext with `?tls=none`
+    /// - `smtps://<user>:<password>@<host>:<port>` connects to an SMTP server using implicit TLS
+    /// - `ses://` sends through Amazon SES, using credentials and the region from the environment.
+    ///   Only available when the `ses` feature is enabled.
+    pub async fn connect(url: &Url, from: &str) -> Result<Self, Error> {
+        let from = from.parse::<Mailbox>()?;
+        let backend: Box<dyn Backend> = match url.scheme() {
+            "smtp" | "smtps" => Box::new(smtp::Smtp::connect(url)?),
+            #[cfg(feature = "ses")]
+            "ses" => Box::new(ses::Ses::connect().await),
+            scheme => {
+                return Err(format!(
+                    "unsupported email backend {scheme:?}, is its feature enabled?"
+                )
+                .into())
+            }
+        };
+
+        let (queue, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::task::spawn(work(backend, from, receiver));
+
+        Ok(Self {
+            queue,
+            templates: Arc::new(Templates::new()),
+        })
+    }
+
+    /// Render the email with the default branding and queue it to be sent in the background
+    pub fn send<T: Template>(&self, to: &str, email: &T) {
+        self.send_branded(to, email, &Branding::default());
+    }
+
+    /// Render the email with the branding and queue it to be sent in the background
+    #[instrument(name = "Mailer::send", skip_all, fields(template = T::NAME))]
+    pub fn send_branded<T: Template>(&self, to: &str, email: &T, branding: &Branding) {
+        let (html, text) = match self.templates.render(email, branding) {
+            Ok(rendered) => rendered,
+            Err(error) => {
+                error!(%error, "failed to render email");
+                return;
+            }
+        };
+
+        let message = Message {
+            to: to.to_owned(),
+            reply_to: branding.reply_to.clone(),
+            subject: email.subject(),
+            html,
+            text,
+            span: Span::current(),
+        };
+        match self.queue.try_send(message) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => error!("email queue is full, dropping email"),
+            Err(TrySendError::Closed(_)) => error!("email worker stopped, dropping email"),
+        }
+    }
+}
+
+/// Send queued emails until every mailer is dropped
+async fn work(backend: Box<dyn Backend>, from: Mailbox, mut queue: mpsc::Receiver<Message>) {
+    while let Some(message) = queue.recv().await {
+        let span = message.span.clone();
+        deliver(&*backend, &from, &message).instrument(span).await;
+    }
+}
+
+/// Send the message, retrying with a backoff if it fails
+async fn deliver(backend: &dyn Backend, from: &Mailbox, message: &Message) {
+    let mut delay = RETRY_DELAY;
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        match backend.send(from, message).await {
+            Ok(()) => {
+                info!(attempt, "sent email");
+                return;
+            }
+            Err(error) if attempt < MAX_ATTEMPTS => {
+                warn!(%error, attempt, "failed to send email, retrying");
+                time::sleep(delay).await;
+                delay *= 2;
+            }
+            Err(error) => error!(%error, attempt, "failed to send email, giving up"),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use crate::Template;
+use serde::Serialize;
+
+/// Sent to the user who requested an event extension once an administrator reviews it
+#[derive(Debug, Serialize)]
+pub struct EventExtensionReviewed {
+    /// The requester's given name
+    pub given_name: String,
+    /// The name of the event the extension was requested for
+    pub event_name: String,
+    /// Whether the extension was approved
+    pub approved: bool,
+    /// When write-access was requested to expire, formatted for display
+    pub expires_on: String,
+    /// The reviewer's explanation of the decision, if any
+    pub note: Option<String>,
+}
+
+impl Template for EventExtensionReviewed {
+    const NAME: &'static str = "event_extension_reviewed";
+
+    fn subject(&self) -> String {
+        if self.approved {
+            format!("Extension approved for {}", self.event_name)
+        } else {
+            format!("Extension denied for {}", self.event_name)
+        }
+    }
+}
+
+/// Sent when a user logs in from a device or location they have not recently used
+#[derive(Debug, Serialize)]
+pub struct NewLogin {
+    /// The user's given name
+    pub given_name: String,
+    /// When the login happened, formatted for display
+    pub logged_in_at: String,
+    /// The IP address the login came from
+    pub ip: Option<String>,
+    /// The user agent the login came from
+    pub user_agent: Option<String>,
+    /// Where the user can confirm revoking the new session
+    pub revoke_url: String,
+}
+
+impl Template for NewLogin {
+    const NAME: &'static str = "new_login";
+
+    fn subject(&self) -> String {
+        String::from("New login to your account")
+    }
+}
+
+/// Sent when a user adds an email address, proving they can receive mail at it
+#[derive(Debug, Serialize)]
+pub struct VerifyEmail {
+    /// The user's given name
+    pub given_name: String,
+    /// The address being verified
+    pub address: String,
+    /// The code the user must enter to verify the address
+    pub code: String,
+}
+
+impl Template for VerifyEmail {
+    const NAME: &'static str = "verify_email";
+
+    fn subject(&self) -> String {
+        String::from("Verify your email address")
+    }
+}
+
+/// Sent when a user finishes registering, confirming their account exists
+#[derive(Debug, Serialize)]
+pub struct Welcome {
+    /// The user's given name
+    pub given_name: String,
+    /// The name of the event the user registered through, if any
+    pub event_name: Option<String>,
+    /// An additional message from the event's organizers
+    pub message: Option<String>,
+    /// Where the user can continue once they've read the email
+    pub continue_url: String,
+}
+
+impl Template for Welcome {
+    const NAME: &'static str = "welcome";
+
+    fn subject(&self) -> String {
+        match &self.event_name {
+            Some(name) => format!("Welcome to {name}"),
+            None => String::from("Welcome to The Hacker App"),
+        }
+    }
+}
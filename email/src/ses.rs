@@ -0,0 +1,53 @@
+use super::{Backend, Error, Message};
+use aws_sdk_sesv2::{
+    types::{Body, Content, Destination, EmailContent, Message as SesMessage},
+    Client,
+};
+use lettre::message::Mailbox;
+
+/// Sends emails through Amazon SES
+pub(super) struct Ses {
+    client: Client,
+}
+
+impl Ses {
+    /// Create a client, loading credentials and the region from the environment
+    pub async fn connect() -> Self {
+        let config = aws_config::load_from_env().await;
+        Self {
+            client: Client::new(&config),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Ses {
+    async fn send(&self, from: &Mailbox, message: &Message) -> Result<(), Error> {
+        let content = SesMessage::builder()
+            .subject(content(&message.subject)?)
+            .body(
+                Body::builder()
+                    .html(content(&message.html)?)
+                    .text(content(&message.text)?)
+                    .build(),
+            )
+            .build()?;
+
+        self.client
+            .send_email()
+            .from_email_address(from.to_string())
+            .destination(Destination::builder().to_addresses(&message.to).build())
+            .set_reply_to_addresses(message.reply_to.clone().map(|address| vec![address]))
+            .content(EmailContent::builder().simple(content).build())
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
+
+/// Build UTF-8 encoded content
+fn content(data: &str) -> Result<Content, Error> {
+    let content = Content::builder().data(data).charset("UTF-8").build()?;
+    Ok(content)
+}
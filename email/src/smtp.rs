@@ -0,0 +1,40 @@
+use super::{Backend, Error, Message};
+use lettre::{
+    message::{Mailbox, MultiPart},
+    AsyncSmtpTransport, AsyncTransport, Tokio1Executor,
+};
+use url::Url;
+
+/// Sends emails through an SMTP server, reusing connections between emails
+pub(super) struct Smtp {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl Smtp {
+    /// Create a connection pool for the server
+    pub fn connect(url: &Url) -> Result<Self, Error> {
+        let transport = AsyncSmtpTransport::<Tokio1Executor>::from_url(url.as_str())?.build();
+        Ok(Self { transport })
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Smtp {
+    async fn send(&self, from: &Mailbox, message: &Message) -> Result<(), Error> {
+        let mut builder = lettre::Message::builder()
+            .from(from.clone())
+            .to(message.to.parse()?)
+            .subject(&message.subject);
+        if let Some(reply_to) = &message.reply_to {
+            builder = builder.reply_to(reply_to.parse()?);
+        }
+
+        let email = builder.multipart(MultiPart::alternative_plain_html(
+            message.text.clone(),
+            message.html.clone(),
+        ))?;
+
+        self.transport.send(email).await?;
+        Ok(())
+    }
+}
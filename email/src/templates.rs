@@ -0,0 +1,82 @@
+use crate::Branding;
+use minijinja::{context, Environment, Value};
+use serde::Serialize;
+
+/// The templates bundled into the binary, as pairs of their name and source
+const TEMPLATES: &[(&str, &str)] = &[
+    (
+        "event_extension_reviewed.html",
+        include_str!("../templates/event_extension_reviewed.html"),
+    ),
+    (
+        "event_extension_reviewed.txt",
+        include_str!("../templates/event_extension_reviewed.txt"),
+    ),
+    ("layout.html", include_str!("../templates/layout.html")),
+    (
+        "new_login.html",
+        include_str!("../templates/new_login.html"),
+    ),
+    ("new_login.txt", include_str!("../templates/new_login.txt")),
+    (
+        "verify_email.html",
+        include_str!("../templates/verify_email.html"),
+    ),
+    (
+        "verify_email.txt",
+        include_str!("../templates/verify_email.txt"),
+    ),
+    ("welcome.html", include_str!("../templates/welcome.html")),
+    ("welcome.txt", include_str!("../templates/welcome.txt")),
+];
+
+/// An email that can be rendered from a template
+///
+/// Each template has a `<name>.html` and `<name>.txt` file in the `templates` directory, which are
+/// rendered with the email's fields and the `branding` as their context. HTML templates should
+/// extend `layout.html`.
+pub trait Template: Serialize {
+    /// The name of the template's files
+    const NAME: &'static str;
+
+    /// The subject line of the email
+    fn subject(&self) -> String;
+}
+
+/// The compiled templates
+pub(crate) struct Templates {
+    env: Environment<'static>,
+}
+
+impl Templates {
+    /// Compile the bundled templates
+    pub fn new() -> Self {
+        let mut env = Environment::new();
+        for (name, source) in TEMPLATES {
+            env.add_template(name, source)
+                .expect("bundled templates must be valid");
+        }
+
+        Self { env }
+    }
+
+    /// Render the HTML and plain text versions of the email
+    pub fn render<T: Template>(
+        &self,
+        email: &T,
+        branding: &Branding,
+    ) -> Result<(String, String), minijinja::Error> {
+        let ctx = context! { branding, ..Value::from_serialize(email) };
+
+        let html = self
+            .env
+            .get_template(&format!("{}.html", T::NAME))?
+            .render(&ctx)?;
+        let text = self
+            .env
+            .get_template(&format!("{}.txt", T::NAME))?
+            .render(&ctx)?;
+
+        Ok((html, text))
+    }
+}
@@ -0,0 +1,81 @@
+use crate::errors::Unauthorized;
+use async_graphql::{Context, Result, ResultExt};
+use authz::{Action, Resource};
+use context::UserRole;
+use database::{loaders::UserLoader, PgPool, User};
+use tracing::instrument;
+
+/// The user performing a mutation, as currently stored in the database
+///
+/// The gateway-provided user context is only used to identify the user. Permissions are always
+/// checked against the database so a misconfigured gateway cannot grant write access.
+#[derive(Debug)]
+pub(crate) struct Actor {
+    user: User,
+}
+
+impl Actor {
+    /// Load the actor for the current request
+    #[instrument(name = "Actor::load", skip_all)]
+    pub async fn load(ctx: &Context<'_>) -> Result<Actor> {
+        let Some(id) = authz::Actor::from_graphql(ctx).id else {
+            return Err(Unauthorized.into());
+        };
+
+        let loader = ctx.data_unchecked::<UserLoader>();
+        let Some(user) = loader.load_one(id).await.extend()? else {
+            return Err(Unauthorized.into());
+        };
+
+        Ok(Actor { user })
+    }
+
+    /// The ID of the user
+    pub fn id(&self) -> i32 {
+        self.user.id
+    }
+
+    /// The user's details
+    pub fn user(&self) -> &User {
+        &self.user
+    }
+
+    /// Require permission to perform an action on a resource outside of any organization
+    pub fn require(&self, action: Action, resource: Resource) -> Result<()> {
+        authz::require(&self.policy_actor(None), action, &resource)?;
+        Ok(())
+    }
+
+    /// Require permission to perform an action on a resource owned by an organization, using the
+    /// actor's role within the organization
+    #[instrument(name = "Actor::require_in", skip(self, ctx), fields(id = self.user.id))]
+    pub async fn require_in(
+        &self,
+        ctx: &Context<'_>,
+        organization_id: i32,
+        action: Action,
+        resource: Resource,
+    ) -> Result<()> {
+        let role = if self.user.is_admin {
+            None
+        } else {
+            let db = ctx.data_unchecked::<PgPool>();
+            User::is_organizer(self.user.id, organization_id, db)
+                .await
+                .extend()?
+                .map(UserRole::from)
+        };
+
+        authz::require(&self.policy_actor(role), action, &resource)?;
+        Ok(())
+    }
+
+    /// Get the facts about the actor used by the policy
+    fn policy_actor(&self, role: Option<UserRole>) -> authz::Actor {
+        authz::Actor {
+            id: Some(self.user.id),
+            is_admin: self.user.is_admin,
+            role,
+        }
+    }
+}
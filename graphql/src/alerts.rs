@@ -0,0 +1,156 @@
+use chrono::Utc;
+use database::User;
+use redis::aio::ConnectionManager;
+use serde_json::json;
+use std::time::Duration;
+use tracing::{error, instrument, warn, Instrument, Span};
+use url::Url;
+
+/// How many failed logins through a provider within the window trigger an alert
+const OAUTH_FAILURE_THRESHOLD: u64 = 10;
+/// How long failed logins are counted for before the count resets, in seconds
+const OAUTH_FAILURE_WINDOW: i64 = 5 * 60;
+
+/// The chat service alerts are posted to, which determines the shape of the message
+#[derive(Clone, Copy, Debug)]
+enum Destination {
+    Discord,
+    Slack,
+}
+
+/// Posts high-severity security events to a Slack or Discord incoming webhook so administrators
+/// hear about them as they happen
+///
+/// Alerts are sent in the background and failures are only logged, so an unavailable chat service
+/// never affects a request. Failed logins are counted in the cache so every instance contributes to
+/// the same count.
+#[derive(Clone)]
+pub struct Alerter {
+    cache: ConnectionManager,
+    client: reqwest::Client,
+    url: Url,
+    destination: Destination,
+}
+
+impl Alerter {
+    /// Post alerts to the incoming webhook, formatting them for Discord when the URL points to
+    /// Discord and for Slack otherwise
+    pub fn new(url: Url, cache: ConnectionManager) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent("the-hacker-app/identity")
+            .timeout(Duration::from_secs(3))
+            .build()
+            .expect("client must build");
+
+        let destination = match url.host_str() {
+            Some("discord.com" | "discordapp.com") => Destination::Discord,
+            _ => Destination::Slack,
+        };
+
+        Self {
+            cache,
+            client,
+            url,
+            destination,
+        }
+    }
+
+    /// Alert that a user was made an administrator
+    pub fn on_admin_granted(&self, user: &User, actor_id: i32) {
+        self.alert(
+            "Administrator granted",
+            format!(
+                "{} {} <{}> (user {}) was made an administrator by user {actor_id}",
+                user.given_name, user.family_name, user.primary_email, user.id
+            ),
+        );
+    }
+
+    /// Alert that the client secret for an authentication provider was changed
+    pub fn on_provider_secret_changed(&self, provider: &str, actor_id: i32) {
+        self.alert(
+            "Provider secret changed",
+            format!("The client secret for the {provider} provider was changed by user {actor_id}"),
+        );
+    }
+
+    /// Alert that all of a user's sessions were revoked at once
+    pub fn on_sessions_revoked(&self, user_id: i32, count: usize, actor_id: i32) {
+        let by = if user_id == actor_id {
+            String::from("themselves")
+        } else {
+            format!("user {actor_id}")
+        };
+
+        self.alert(
+            "Sessions revoked",
+            format!("All {count} sessions for user {user_id} were revoked by {by}"),
+        );
+    }
+
+    /// Count a failed login through a provider in the background, alerting once per window when
+    /// failures reach the threshold
+    pub fn on_oauth_failure(&self, provider: &str) {
+        let alerter = self.clone();
+        let provider = provider.to_owned();
+        tokio::task::spawn(
+            async move { alerter.count_oauth_failure(&provider).await }.instrument(Span::current()),
+        );
+    }
+
+    #[instrument(name = "Alerter::count_oauth_failure", skip(self))]
+    async fn count_oauth_failure(&self, provider: &str) {
+        let now = Utc::now().timestamp();
+        let key = format!(
+            "identity:oauth-failures:{provider}:{}",
+            now / OAUTH_FAILURE_WINDOW
+        );
+
+        let mut conn = self.cache.clone();
+        let result = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, OAUTH_FAILURE_WINDOW)
+            .ignore()
+            .query_async::<_, (u64,)>(&mut conn)
+            .await;
+        let count = match result {
+            Ok((count,)) => count,
+            Err(error) => {
+                error!(%error, "failed to count oauth failure");
+                return;
+            }
+        };
+
+        if count == OAUTH_FAILURE_THRESHOLD {
+            warn!(provider, count, "repeated oauth failures");
+            self.alert(
+                "Repeated login failures",
+                format!(
+                    "{count} logins through the {provider} provider failed within {} minutes",
+                    OAUTH_FAILURE_WINDOW / 60
+                ),
+            );
+        }
+    }
+
+    /// Post the alert in the background
+    #[instrument(name = "Alerter::alert", skip(self, details))]
+    fn alert(&self, title: &'static str, details: String) {
+        let payload = match self.destination {
+            Destination::Discord => json!({ "content": format!("**{title}**\n{details}") }),
+            Destination::Slack => json!({ "text": format!("*{title}*\n{details}") }),
+        };
+        let request = self.client.post(self.url.clone()).json(&payload);
+
+        tokio::task::spawn(
+            async move {
+                let result = request.send().await.and_then(|r| r.error_for_status());
+                if let Err(error) = result {
+                    error!(%error, "failed to send security alert");
+                }
+            }
+            .instrument(Span::current()),
+        );
+    }
+}
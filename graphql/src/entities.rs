@@ -1,4 +1,4 @@
-use async_graphql::InputObject;
+use async_graphql::{InputObject, ID};
 
 /// A minimal event model, for use in entity keys
 #[derive(Debug, InputObject)]
@@ -9,11 +9,11 @@ pub(crate) struct Event {
 /// A minimal organization model, for use in entity keys
 #[derive(Debug, InputObject)]
 pub(crate) struct Organization {
-    pub id: i32,
+    pub id: ID,
 }
 
 /// A minimal user model, for use in entity keys
 #[derive(Debug, InputObject)]
 pub(crate) struct User {
-    pub id: i32,
+    pub id: ID,
 }
@@ -1,13 +1,49 @@
 use async_graphql::{Error, ErrorExtensions};
 
+/// Stable, machine-readable error codes exposed in the `code` extension of GraphQL errors. Database
+/// errors are reported as `INTERNAL_SERVER_ERROR` by the database crate.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorCode {
+    /// We do not know who the user is
+    Unauthorized,
+    /// The user has invalid permissions
+    Forbidden,
+    /// The requested resource could not be found
+    NotFound,
+    /// The request was malformed
+    BadRequest,
+    /// The user has made too many requests
+    Throttled,
+    /// The feature is disabled by its flag
+    FeatureDisabled,
+}
+
+impl ErrorCode {
+    /// Get the string representation of the code
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::Unauthorized => "UNAUTHORIZED",
+            ErrorCode::Forbidden => "FORBIDDEN",
+            ErrorCode::NotFound => "NOT_FOUND",
+            ErrorCode::BadRequest => "BAD_REQUEST",
+            ErrorCode::Throttled => "THROTTLED",
+            ErrorCode::FeatureDisabled => "FEATURE_DISABLED",
+        }
+    }
+
+    /// Create an error with the code attached
+    pub fn error(self, message: impl Into<String>) -> Error {
+        Error::new(message).extend_with(|_, extensions| extensions.set("code", self.as_str()))
+    }
+}
+
 /// An error raised when we do not know who the user is
 #[derive(Debug)]
 pub struct Unauthorized;
 
 impl From<Unauthorized> for Error {
     fn from(_: Unauthorized) -> Self {
-        Error::new("unauthorized")
-            .extend_with(|_, extensions| extensions.set("code", "UNAUTHORIZED"))
+        ErrorCode::Unauthorized.error("unauthorized")
     }
 }
 
@@ -17,6 +53,26 @@ pub struct Forbidden;
 
 impl From<Forbidden> for Error {
     fn from(_: Forbidden) -> Self {
-        Error::new("forbidden").extend_with(|_, extensions| extensions.set("code", "FORBIDDEN"))
+        ErrorCode::Forbidden.error("forbidden")
+    }
+}
+
+/// An error raised when a resource that must exist could not be found
+#[derive(Debug)]
+pub struct NotFound(pub &'static str);
+
+impl From<NotFound> for Error {
+    fn from(NotFound(kind): NotFound) -> Self {
+        ErrorCode::NotFound.error(format!("{kind} not found"))
+    }
+}
+
+/// An error raised when the request is malformed, i.e. missing a contextually required argument
+#[derive(Debug)]
+pub struct BadRequest(pub &'static str);
+
+impl From<BadRequest> for Error {
+    fn from(BadRequest(message): BadRequest) -> Self {
+        ErrorCode::BadRequest.error(message)
     }
 }
@@ -0,0 +1,136 @@
+use crate::errors::ErrorCode;
+use async_graphql::{Context, Guard};
+use context::Scope;
+use database::{FeatureFlag, PgPool};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::{error, instrument};
+
+/// How long flags are cached for before being reloaded
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A feature that can be rolled out using a flag
+#[derive(Clone, Copy, Debug)]
+pub enum Flag<'f> {
+    /// Whether the OpenID Connect provider accepts new authorizations
+    OidcProvider,
+    /// Whether users can log in with the provider
+    Provider(&'f str),
+}
+
+impl Flag<'_> {
+    /// The key of the flag controlling the feature
+    fn key(&self) -> String {
+        match self {
+            Flag::OidcProvider => String::from("oidc-provider"),
+            Flag::Provider(slug) => format!("provider:{slug}"),
+        }
+    }
+
+    /// Whether the feature is enabled when no flag exists for it
+    fn default(&self) -> bool {
+        match self {
+            Flag::OidcProvider | Flag::Provider(_) => true,
+        }
+    }
+}
+
+/// The flags as of when they were last loaded
+struct Snapshot {
+    loaded_at: Instant,
+    flags: HashMap<String, FeatureFlag>,
+}
+
+/// Evaluates feature flags, keeping them cached in memory
+///
+/// Flags are reloaded from the database periodically, so changes made by other instances take
+/// effect within the refresh interval. If they cannot be loaded, the previous flags continue to be
+/// used.
+#[derive(Clone)]
+pub struct FeatureFlags {
+    db: PgPool,
+    snapshot: Arc<RwLock<Option<Snapshot>>>,
+}
+
+impl FeatureFlags {
+    /// Create a new evaluator, loading flags from the database on first use
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            snapshot: Arc::default(),
+        }
+    }
+
+    /// Check whether the feature is enabled, either everywhere or for the event
+    #[instrument(name = "FeatureFlags::is_enabled", skip(self))]
+    pub async fn is_enabled(&self, flag: Flag<'_>, event: Option<&str>) -> bool {
+        self.refresh().await;
+
+        let snapshot = self.snapshot.read().unwrap();
+        let flag_state = snapshot
+            .as_ref()
+            .and_then(|snapshot| snapshot.flags.get(&flag.key()));
+        match flag_state {
+            Some(state) => state.is_enabled_for(event),
+            None => flag.default(),
+        }
+    }
+
+    /// Drop the cached flags so changes take effect immediately
+    pub fn invalidate(&self) {
+        *self.snapshot.write().unwrap() = None;
+    }
+
+    /// Reload the flags if they are missing or stale
+    async fn refresh(&self) {
+        let stale = match &*self.snapshot.read().unwrap() {
+            Some(snapshot) => snapshot.loaded_at.elapsed() > REFRESH_INTERVAL,
+            None => true,
+        };
+        if !stale {
+            return;
+        }
+
+        match FeatureFlag::all(&self.db).await {
+            Ok(flags) => {
+                let flags = flags
+                    .into_iter()
+                    .map(|flag| (flag.key.clone(), flag))
+                    .collect();
+                *self.snapshot.write().unwrap() = Some(Snapshot {
+                    loaded_at: Instant::now(),
+                    flags,
+                });
+            }
+            Err(error) => error!(%error, "failed to load feature flags"),
+        }
+    }
+}
+
+/// Only allow access to a field while the feature is enabled for the current event
+pub(crate) struct FeatureGuard(Flag<'static>);
+
+impl FeatureGuard {
+    pub fn new(flag: Flag<'static>) -> Self {
+        Self(flag)
+    }
+}
+
+impl Guard for FeatureGuard {
+    async fn check(&self, ctx: &Context<'_>) -> async_graphql::Result<()> {
+        let event = match ctx.data_unchecked::<Scope>() {
+            Scope::Event(scope) => Some(scope.event.as_str()),
+            Scope::Admin | Scope::User => None,
+        };
+
+        let flags = ctx.data_unchecked::<FeatureFlags>();
+        if flags.is_enabled(self.0, event).await {
+            Ok(())
+        } else {
+            Err(ErrorCode::FeatureDisabled.error("feature is not available"))
+        }
+    }
+}
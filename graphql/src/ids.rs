@@ -0,0 +1,19 @@
+use async_graphql::{Context, Result, ResultExt, ID};
+use database::loaders::{OrganizationByPublicIdLoader, UserByPublicIdLoader};
+
+/// Resolve a user's public ID to the ID used internally, returning `None` if no user has it
+pub(crate) async fn user(ctx: &Context<'_>, id: &ID) -> Result<Option<i32>> {
+    let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+    let user = loader.load_one(id.to_string()).await.extend()?;
+
+    Ok(user.map(|user| user.id))
+}
+
+/// Resolve an organization's public ID to the ID used internally, returning `None` if no
+/// organization has it
+pub(crate) async fn organization(ctx: &Context<'_>, id: &ID) -> Result<Option<i32>> {
+    let loader = ctx.data_unchecked::<OrganizationByPublicIdLoader>();
+    let organization = loader.load_one(id.to_string()).await.extend()?;
+
+    Ok(organization.map(|organization| organization.id))
+}
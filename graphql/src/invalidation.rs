@@ -0,0 +1,92 @@
+use crate::{FeatureFlags, ProviderCache, RedirectDomains, ScopeCache};
+use database::{CachedTable, Change, ChangeListener, PgPool};
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, instrument};
+
+/// How long to wait before listening again after the connection fails
+const RETRY_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Invalidates cached data as soon as it changes in the database, no matter which instance
+/// changed it
+///
+/// The periodic refreshes and expiry of each cache still apply, so data converges even while
+/// notifications cannot be received.
+#[derive(Clone)]
+pub struct Invalidator {
+    feature_flags: FeatureFlags,
+    providers: ProviderCache,
+    redirect_domains: RedirectDomains,
+    scope_cache: Option<ScopeCache>,
+}
+
+impl Invalidator {
+    /// Create an invalidator for the caches
+    pub fn new(
+        feature_flags: FeatureFlags,
+        providers: ProviderCache,
+        redirect_domains: RedirectDomains,
+        scope_cache: Option<ScopeCache>,
+    ) -> Self {
+        Self {
+            feature_flags,
+            providers,
+            redirect_domains,
+            scope_cache,
+        }
+    }
+
+    /// Start listening for changes in the background. Notifications are only sent by the primary,
+    /// so the pool must not be for a replica.
+    pub fn listen(self, db: PgPool) {
+        tokio::task::spawn(self.run(db));
+    }
+
+    async fn run(self, db: PgPool) {
+        loop {
+            match ChangeListener::connect(&db).await {
+                Ok(mut listener) => {
+                    // Changes may have been made before listening started
+                    self.invalidate_all().await;
+
+                    loop {
+                        match listener.recv().await {
+                            Ok(Some(change)) => self.invalidate(change).await,
+                            Ok(None) => self.invalidate_all().await,
+                            Err(error) => {
+                                error!(%error, "failed to receive changes");
+                                break;
+                            }
+                        }
+                    }
+                }
+                Err(error) => error!(%error, "failed to listen for changes"),
+            }
+
+            time::sleep(RETRY_INTERVAL).await;
+        }
+    }
+
+    /// Drop the cached data affected by the change
+    #[instrument(name = "Invalidator::invalidate", skip(self))]
+    async fn invalidate(&self, change: Change) {
+        match change.table {
+            CachedTable::AllowedRedirectDomains => self.redirect_domains.reload().await,
+            CachedTable::CustomDomains => {
+                if let Some(cache) = &self.scope_cache {
+                    cache.invalidate_custom_domain(&change.key).await;
+                }
+            }
+            CachedTable::FeatureFlags => self.feature_flags.invalidate(),
+            CachedTable::Providers => self.providers.invalidate(&change.key),
+        }
+    }
+
+    /// Drop everything that could have changed without being notified. Cached custom domains
+    /// cannot be listed, so they are left to expire.
+    async fn invalidate_all(&self) {
+        self.feature_flags.invalidate();
+        self.providers.invalidate_all();
+        self.redirect_domains.reload().await;
+    }
+}
@@ -1,19 +1,45 @@
 use async_graphql::{
     extensions::Analyzer, EmptySubscription, SDLExportOptions, Schema as BaseSchema, SchemaBuilder,
 };
-use database::{loaders::RegisterDataLoaders, PgPool};
+use database::{loaders::RegisterDataLoaders, Pool, SessionLookup};
+use sessions::SessionStore;
 use state::Domains;
-use url::Url;
+use std::sync::Arc;
 
+mod actor;
+mod alerts;
 mod entities;
 mod errors;
+mod feature_flags;
+mod ids;
+mod invalidation;
 mod mutation;
+mod provider_cache;
+mod publisher;
 mod query;
+mod rate_limit;
+mod redirect_domains;
+mod scalars;
+mod scope_cache;
+mod sessions;
+mod usage;
 mod webhooks;
 
 use mutation::Mutation;
 use query::Query;
 
+pub use alerts::Alerter;
+pub use feature_flags::{FeatureFlags, Flag};
+pub use invalidation::Invalidator;
+pub use provider_cache::ProviderCache;
+pub use publisher::Publisher;
+pub use rate_limit::{RateLimiter, ServiceToken};
+pub use redirect_domains::RedirectDomains;
+pub use scalars::EmailAddress;
+pub use scope_cache::{Resolution, ScopeCache};
+pub use usage::UsageReporter;
+pub use webhooks::Client as Webhooks;
+
 /// The graphql schema for the service
 pub type Schema = BaseSchema<Query, Mutation, EmptySubscription>;
 
@@ -25,15 +51,55 @@ fn builder() -> SchemaBuilder<Query, Mutation, EmptySubscription> {
         .extension(Analyzer)
 }
 
+/// Options for configuring the schema
+pub struct Options {
+    /// The domains used to determine the scope of a request
+    pub domains: Domains,
+    /// Where to report usage to, if anywhere
+    pub usage: Option<UsageReporter>,
+    /// How much work each user can request per minute, if limited
+    pub rate_limit: Option<RateLimiter>,
+    /// Queues events for webhooks
+    pub webhooks: Webhooks,
+    /// How to send emails, disabled if not set
+    pub mailer: Option<email::Mailer>,
+    /// Where to post security alerts, disabled if not set
+    pub alerter: Option<Alerter>,
+    /// Where to cache the events that slugs and domains resolve to, disabled if not set
+    pub scope_cache: Option<ScopeCache>,
+    /// Which features are enabled
+    pub feature_flags: FeatureFlags,
+    /// The domains that the OAuth flow is allowed to return to
+    pub redirect_domains: RedirectDomains,
+    /// The configured authentication providers
+    pub providers: ProviderCache,
+}
+
 /// Build the schema with the necessary extensions
-pub fn schema(db: PgPool, domains: Domains, portal_url: Url) -> Schema {
-    let client = webhooks::Client::new(portal_url);
+pub fn schema(db: Pool, sessions: session::Manager, options: Options) -> Schema {
+    let mut builder = builder();
+    if let Some(usage) = options.usage {
+        builder = builder.extension(usage);
+    }
+    if let Some(rate_limit) = options.rate_limit {
+        builder = builder.extension(rate_limit);
+    }
 
-    builder()
+    builder
         .register_dataloaders(&db)
-        .data(client)
+        .data(options.alerter)
+        .data(options.webhooks)
+        // Mutations use the primary directly, while queries may read from a replica
+        .data(db.writer().clone())
         .data(db)
-        .data(domains)
+        .data(options.domains)
+        .data(options.feature_flags)
+        .data(options.mailer)
+        .data(options.providers)
+        .data(options.redirect_domains)
+        .data(options.scope_cache)
+        .data(Arc::new(SessionStore(sessions.clone())) as Arc<dyn SessionLookup>)
+        .data(sessions)
         .finish()
 }
 
@@ -0,0 +1,165 @@
+use super::{results, validators::Validator, UserError};
+use crate::{actor::Actor, ScopeCache};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use authz::{Action, Resource};
+use database::{loaders::EventLoader, CustomDomain, PgPool};
+use state::Domains;
+use tracing::instrument;
+
+results! {
+    AddCustomDomainResult {
+        /// The added custom domain
+        custom_domain: CustomDomain,
+    }
+    RemoveCustomDomainResult {
+        /// The name of the removed custom domain
+        removed_name: String,
+    }
+    SetPrimaryCustomDomainResult {
+        /// The new primary custom domain
+        custom_domain: CustomDomain,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CustomDomainMutation;
+
+#[Object]
+impl CustomDomainMutation {
+    /// Make an event accessible at a custom domain. The first domain added to an event becomes its
+    /// primary domain.
+    #[instrument(name = "Mutation::add_custom_domain", skip(self, ctx))]
+    async fn add_custom_domain(
+        &self,
+        ctx: &Context<'_>,
+        input: AddCustomDomainInput,
+    ) -> Result<AddCustomDomainResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(input.event.clone()).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(ctx, event.organization_id, Action::Update, Resource::Event)
+            .await?;
+
+        let name = input.name.trim().to_lowercase();
+        let mut validator = Validator::default();
+        validator
+            .field(&["name"], &name)
+            .not_empty()
+            .max_length(253)
+            .domain();
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        // The service's own domains already resolve to events or the portal
+        let domains = ctx.data_unchecked::<Domains>();
+        if domains.extract_slug_for_subdomain(&name).is_some()
+            || domains.requires_admin(&name)
+            || domains.requires_user(&name)
+        {
+            return Ok(UserError::new(&["name"], "domain is reserved").into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let domain = match CustomDomain::create(&name, &event.slug, db).await {
+            Ok(domain) => domain,
+            Err(e) if e.is_unique_violation() => {
+                return Ok(UserError::new(&["name"], "domain is already in use").into())
+            }
+            Err(e) => return Err(e.extend()),
+        };
+        invalidate_custom_domain(ctx, &domain.name).await;
+
+        Ok(domain.into())
+    }
+
+    /// Stop an event from being accessible at a custom domain. If the domain was the event's
+    /// primary domain, its oldest remaining domain becomes the primary.
+    #[instrument(name = "Mutation::remove_custom_domain", skip(self, ctx))]
+    async fn remove_custom_domain(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> Result<RemoveCustomDomainResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(domain) = CustomDomain::find_by_name(&name, db).await.extend()? else {
+            return Ok(UserError::new(&["name"], "domain does not exist").into());
+        };
+        require_update_event(ctx, &actor, &domain).await?;
+
+        if CustomDomain::delete_by_name(&name, db)
+            .await
+            .extend()?
+            .is_none()
+        {
+            return Ok(UserError::new(&["name"], "domain does not exist").into());
+        }
+        invalidate_custom_domain(ctx, &name).await;
+
+        Ok(name.into())
+    }
+
+    /// Make a custom domain the canonical domain for its event
+    #[instrument(name = "Mutation::set_primary_custom_domain", skip(self, ctx))]
+    async fn set_primary_custom_domain(
+        &self,
+        ctx: &Context<'_>,
+        name: String,
+    ) -> Result<SetPrimaryCustomDomainResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(mut domain) = CustomDomain::find_by_name(&name, db).await.extend()? else {
+            return Ok(UserError::new(&["name"], "domain does not exist").into());
+        };
+        require_update_event(ctx, &actor, &domain).await?;
+
+        if !domain.is_primary {
+            domain.make_primary(db).await.extend()?;
+        }
+
+        Ok(domain.into())
+    }
+}
+
+/// Require the actor to be able to update the event the domain belongs to
+async fn require_update_event(
+    ctx: &Context<'_>,
+    actor: &Actor,
+    domain: &CustomDomain,
+) -> Result<()> {
+    let loader = ctx.data_unchecked::<EventLoader>();
+    let event = loader
+        .load_one(domain.event.clone())
+        .await
+        .extend()?
+        .expect("custom domain must have associated event");
+
+    actor
+        .require_in(ctx, event.organization_id, Action::Update, Resource::Event)
+        .await
+}
+
+/// Remove the cached event for a custom domain after it changes, rather than waiting for the
+/// change notification
+async fn invalidate_custom_domain(ctx: &Context<'_>, name: &str) {
+    if let Some(cache) = ctx.data_unchecked::<Option<ScopeCache>>() {
+        cache.invalidate_custom_domain(name).await;
+    }
+}
+
+/// Input fields for adding a custom domain
+#[derive(Debug, InputObject)]
+struct AddCustomDomainInput {
+    /// The slug of the event the domain is for
+    event: String,
+    /// The domain name, without a scheme or path
+    name: String,
+}
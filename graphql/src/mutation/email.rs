@@ -0,0 +1,143 @@
+use super::{results, UserError};
+use crate::{actor::Actor, ids, scalars::EmailAddress};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt, ID};
+use authz::{Action, Resource};
+use database::{loaders::UserByPublicIdLoader, Email, PgPool};
+use email::{Mailer, VerifyEmail};
+use tracing::{info, instrument};
+
+results! {
+    AddEmailResult {
+        /// The unverified address
+        email: Email,
+    }
+    VerifyEmailResult {
+        /// The verified address
+        email: Email,
+    }
+    RemoveEmailResult {
+        /// The address that was removed
+        removed_address: String,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct EmailMutation;
+
+#[Object]
+impl EmailMutation {
+    /// Add an email address to a user, sending them a code to verify it with. Adding an address
+    /// that is awaiting verification sends a new code.
+    #[instrument(name = "Mutation::add_email", skip(self, ctx))]
+    async fn add_email(&self, ctx: &Context<'_>, input: AddEmailInput) -> Result<AddEmailResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = loader.load_one(input.user_id.to_string()).await.extend()? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+        actor.require(Action::Update, Resource::User { id: user.id })?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some((email, code)) = Email::add(user.id, &input.address, db).await.extend()? else {
+            return Ok(UserError::new(&["address"], "email is already in use").into());
+        };
+
+        if let Some(mailer) = ctx.data_unchecked::<Option<Mailer>>() {
+            mailer.send(
+                &email.address,
+                &VerifyEmail {
+                    given_name: user.given_name,
+                    address: email.address.clone(),
+                    code,
+                },
+            );
+            info!("sent email verification code");
+        }
+
+        Ok(email.into())
+    }
+
+    /// Verify an email address using the code sent to it
+    #[instrument(name = "Mutation::verify_email", skip_all, fields(user.id = %input.user_id))]
+    async fn verify_email(
+        &self,
+        ctx: &Context<'_>,
+        input: VerifyEmailInput,
+    ) -> Result<VerifyEmailResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+        actor.require(Action::Update, Resource::User { id: user_id })?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        match Email::verify(user_id, &input.address, &input.code, db)
+            .await
+            .extend()?
+        {
+            Some(email) => Ok(email.into()),
+            None => Ok(UserError::new(&["code"], "invalid or expired verification code").into()),
+        }
+    }
+
+    /// Remove an email address from a user. The primary address cannot be removed.
+    #[instrument(name = "Mutation::remove_email", skip(self, ctx))]
+    async fn remove_email(
+        &self,
+        ctx: &Context<'_>,
+        input: RemoveEmailInput,
+    ) -> Result<RemoveEmailResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+        actor.require(Action::Update, Resource::User { id: user_id })?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(email) = Email::find(user_id, &input.address, db).await.extend()? else {
+            return Ok(UserError::new(&["address"], "email does not exist").into());
+        };
+        if email.is_primary {
+            return Ok(UserError::new(&["address"], "cannot remove the primary email").into());
+        }
+
+        if !Email::remove(user_id, &email.address, db).await.extend()? {
+            return Ok(UserError::new(&["address"], "email does not exist").into());
+        }
+
+        Ok(email.address.into())
+    }
+}
+
+/// Input for adding an email address to a user
+#[derive(Debug, InputObject)]
+struct AddEmailInput {
+    /// The ID of the user to add the address to
+    user_id: ID,
+    /// The address to add
+    address: EmailAddress,
+}
+
+/// Input for verifying a user's email address
+#[derive(Debug, InputObject)]
+struct VerifyEmailInput {
+    /// The ID of the user the address belongs to
+    user_id: ID,
+    /// The address to verify
+    address: EmailAddress,
+    /// The code that was sent to the address
+    #[graphql(secret)]
+    code: String,
+}
+
+/// Input for removing an email address from a user
+#[derive(Debug, InputObject)]
+struct RemoveEmailInput {
+    /// The ID of the user the address belongs to
+    user_id: ID,
+    /// The address to remove
+    address: EmailAddress,
+}
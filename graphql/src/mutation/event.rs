@@ -1,7 +1,18 @@
-use super::{results, validators, UserError};
-use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result, ResultExt};
-use database::{loaders::EventLoader, Event, Organization, PgPool};
-use tracing::instrument;
+use super::{results, validators::Validator, FieldPath, UserError};
+use crate::{
+    actor::Actor,
+    ids,
+    scalars::{EmailAddress, Url},
+    webhooks, ScopeCache,
+};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, MaybeUndefined, Object, Result, ResultExt, ID,
+};
+use authz::{Action, Resource};
+use chrono::{DateTime, Utc};
+use database::{loaders::EventLoader, Event, EventExtensionRequest, Organization, PgPool, User};
+use email::{EventExtensionReviewed, Mailer};
+use tracing::{info, instrument};
 
 results! {
     CreateEventResult {
@@ -12,10 +23,26 @@ results! {
         /// The event
         event: Event,
     }
+    ArchiveEventResult {
+        /// The archived event
+        event: Event,
+    }
+    RestoreEventResult {
+        /// The restored event
+        event: Event,
+    }
     DeleteEventResult {
         /// The slug of the deleted event
         deleted_slug: String,
     }
+    RequestEventExtensionResult {
+        /// The pending request
+        request: EventExtensionRequest,
+    }
+    ReviewEventExtensionResult {
+        /// The reviewed request
+        request: EventExtensionRequest,
+    }
 }
 
 #[derive(Default)]
@@ -30,36 +57,49 @@ impl EventMutation {
         ctx: &Context<'_>,
         input: CreateEventInput,
     ) -> Result<CreateEventResult> {
-        let mut user_errors = Vec::new();
+        let actor = Actor::load(ctx).await?;
 
-        if input.slug.is_empty() {
-            user_errors.push(UserError::new(&["slug"], "cannot be empty"));
-        }
-        if input.slug.len() > 63 {
-            user_errors.push(UserError::new(&["slug"], "must be less than 63 characters"));
-        }
-        if !validators::dns_segment(&input.slug) {
-            user_errors.push(UserError::new(&["slug"], "must be a valid dns segment"));
-        }
-        if input.name.is_empty() {
-            user_errors.push(UserError::new(&["name"], "cannot be empty"));
-        }
+        let Some(organization_id) = ids::organization(ctx, &input.organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+        actor
+            .require_in(ctx, organization_id, Action::Create, Resource::Event)
+            .await?;
 
-        if !user_errors.is_empty() {
+        let mut validator = Validator::default();
+        validator
+            .field(&["slug"], &input.slug)
+            .not_empty()
+            .max_length(63)
+            .dns_segment();
+        validator.field(&["name"], &input.name).not_empty();
+
+        if let Some(user_errors) = validator.errors() {
             return Ok(user_errors.into());
         }
 
         let db = ctx.data_unchecked::<PgPool>();
+        let result = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                if !Organization::exists(organization_id, &mut *txn).await? {
+                    return Ok(None);
+                }
 
-        if !Organization::exists(input.organization_id, db)
-            .await
-            .extend()?
-        {
-            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
-        }
+                let event =
+                    Event::create(&input.slug, &input.name, organization_id, &mut *txn).await?;
+                Ok(Some(event))
+            })
+        })
+        .await;
 
-        match Event::create(&input.slug, &input.name, input.organization_id, db).await {
-            Ok(organization) => Ok(organization.into()),
+        match result {
+            Ok(Some(event)) => {
+                invalidate_scope(ctx, &event.slug).await;
+                Ok(event.into())
+            }
+            Ok(None) => {
+                Ok(UserError::new(&["organization_id"], "organization does not exist").into())
+            }
             Err(e) if e.is_unique_violation() => {
                 Ok(UserError::new(&["slug"], "already in use").into())
             }
@@ -74,24 +114,157 @@ impl EventMutation {
         ctx: &Context<'_>,
         input: UpdateEventInput,
     ) -> Result<UpdateEventResult> {
-        if let Some(name) = &input.name {
-            if name.is_empty() {
-                return Ok(UserError::new(&["name"], "cannot be empty").into());
-            }
+        let actor = Actor::load(ctx).await?;
+
+        let mut validator = Validator::default();
+        validator
+            .optional(&["name"], input.name.as_ref())
+            .not_empty();
+        validator
+            .optional(&["capacity"], input.capacity.value())
+            .check(|capacity| *capacity >= 0, "cannot be negative");
+
+        let allowed_email_domains = input.allowed_email_domains.map(|domains| {
+            domains
+                .iter()
+                .map(|domain| domain.trim().trim_start_matches('.').to_lowercase())
+                .collect::<Vec<_>>()
+        });
+        validator.each(
+            &["allowed_email_domains"],
+            allowed_email_domains.as_deref(),
+            |validator, domain| {
+                validator.field(FieldPath::new(), domain).domain();
+            },
+        );
+        validator
+            .optional(
+                &["email_accent_color"],
+                input.email_accent_color.as_opt_ref(),
+            )
+            .hex_color();
+        validator
+            .optional(
+                &["email_welcome_message"],
+                input.email_welcome_message.as_opt_ref(),
+            )
+            .max_length(2000);
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
         }
 
         let loader = ctx.data_unchecked::<EventLoader>();
         let Some(mut event) = loader.load_one(input.slug).await.extend()? else {
             return Ok(UserError::new(&["slug"], "event does not exist").into());
         };
+        actor
+            .require_in(ctx, event.organization_id, Action::Update, Resource::Event)
+            .await?;
+
+        let registration_opens_at: Option<Option<DateTime<Utc>>> =
+            input.registration_opens_at.into();
+        let registration_closes_at: Option<Option<DateTime<Utc>>> =
+            input.registration_closes_at.into();
+
+        if let (Some(opens_at), Some(closes_at)) = (
+            registration_opens_at.unwrap_or(event.registration_opens_at),
+            registration_closes_at.unwrap_or(event.registration_closes_at),
+        ) {
+            if opens_at >= closes_at {
+                return Ok(UserError::new(
+                    &["registration_closes_at"],
+                    "must be after registration opens",
+                )
+                .into());
+            }
+        }
 
         let db = ctx.data_unchecked::<PgPool>();
-        event
+        let result = event
             .update()
             .override_name(input.name)
+            .override_registration_opens_at(registration_opens_at)
+            .override_registration_closes_at(registration_closes_at)
+            .override_capacity(input.capacity.into())
+            .override_allowed_email_domains(allowed_email_domains)
+            .override_email_logo_url(input.email_logo_url.map_value(Into::into).into())
+            .override_email_accent_color(
+                input
+                    .email_accent_color
+                    .map_value(|color| color.to_lowercase())
+                    .into(),
+            )
+            .override_email_reply_to(input.email_reply_to.map_value(Into::into).into())
+            .override_email_welcome_message(input.email_welcome_message.into())
+            .save(db)
+            .await;
+
+        match result {
+            Ok(()) => Ok(event.into()),
+            // The registration window was changed concurrently
+            Err(e) if e.is_check_violation() => {
+                let error = match e.constraint_name() {
+                    Some("events_registration_window_check") => UserError::new(
+                        &["registration_closes_at"],
+                        "must be after registration opens",
+                    ),
+                    _ => UserError::new(&["capacity"], "cannot be negative"),
+                };
+                Ok(error.into())
+            }
+            Err(e) => Err(e.extend()),
+        }
+    }
+
+    /// Archive an event, hiding it from listings and preventing new participants and logins
+    #[instrument(name = "Mutation::archive_event", skip(self, ctx))]
+    async fn archive_event(&self, ctx: &Context<'_>, slug: String) -> Result<ArchiveEventResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(mut event) = loader.load_one(slug).await.extend()? else {
+            return Ok(UserError::new(&["slug"], "event does not exist").into());
+        };
+        actor
+            .require_in(ctx, event.organization_id, Action::Archive, Resource::Event)
+            .await?;
+
+        if event.is_archived() {
+            return Ok(UserError::new(&["slug"], "event is already archived").into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        event
+            .update()
+            .archived_at(Some(Utc::now()))
             .save(db)
             .await
             .extend()?;
+        invalidate_scope(ctx, &event.slug).await;
+
+        Ok(event.into())
+    }
+
+    /// Restore an archived event
+    #[instrument(name = "Mutation::restore_event", skip(self, ctx))]
+    async fn restore_event(&self, ctx: &Context<'_>, slug: String) -> Result<RestoreEventResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Restore, Resource::Event)?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(mut event) = loader.load_one(slug).await.extend()? else {
+            return Ok(UserError::new(&["slug"], "event does not exist").into());
+        };
+
+        if !event.is_archived() {
+            return Ok(UserError::new(&["slug"], "event is not archived").into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        event.update().archived_at(None).save(db).await.extend()?;
+        invalidate_scope(ctx, &event.slug).await;
 
         Ok(event.into())
     }
@@ -99,11 +272,192 @@ impl EventMutation {
     /// Delete an event
     #[instrument(name = "Mutation::delete_event", skip(self, ctx))]
     async fn delete_event(&self, ctx: &Context<'_>, slug: String) -> Result<DeleteEventResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(slug.clone()).await.extend()? else {
+            return Ok(UserError::new(&["slug"], "event does not exist").into());
+        };
+        actor
+            .require_in(ctx, event.organization_id, Action::Delete, Resource::Event)
+            .await?;
+
         let db = ctx.data::<PgPool>()?;
         Event::delete(&slug, db).await.extend()?;
+        invalidate_scope(ctx, &slug).await;
 
         Ok(slug.into())
     }
+
+    /// Request that write-access to an event be extended. Only one request can be pending per
+    /// event.
+    #[instrument(name = "Mutation::request_event_extension", skip(self, ctx))]
+    async fn request_event_extension(
+        &self,
+        ctx: &Context<'_>,
+        input: RequestEventExtensionInput,
+    ) -> Result<RequestEventExtensionResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let mut validator = Validator::default();
+        validator.field(&["reason"], &input.reason).not_empty();
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(input.event.clone()).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(ctx, event.organization_id, Action::Update, Resource::Event)
+            .await?;
+
+        if input.expires_on <= event.expires_on {
+            return Ok(UserError::new(&["expires_on"], "must be after the current expiry").into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let webhooks = ctx.data_unchecked::<webhooks::Client>();
+        let (slug, requested_by) = (event.slug.clone(), actor.id());
+        let client = webhooks.clone();
+        let result = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let request = EventExtensionRequest::create(
+                    &slug,
+                    requested_by,
+                    input.expires_on,
+                    &input.reason,
+                    &mut *txn,
+                )
+                .await?;
+
+                let queued = client.on_event_extension_changed(&request, txn).await?;
+                Ok((request, queued))
+            })
+        })
+        .await;
+        let request = match result {
+            Ok((request, queued)) => {
+                webhooks.committed(queued);
+                request
+            }
+            Err(e) if e.is_unique_violation() => {
+                return Ok(
+                    UserError::new(&["event"], "an extension request is already pending").into(),
+                )
+            }
+            Err(e) => return Err(e.extend()),
+        };
+
+        Ok(request.into())
+    }
+
+    /// Approve a pending extension request, extending the event's write-access
+    #[instrument(name = "Mutation::approve_event_extension", skip(self, ctx))]
+    async fn approve_event_extension(
+        &self,
+        ctx: &Context<'_>,
+        input: ReviewEventExtensionInput,
+    ) -> Result<ReviewEventExtensionResult> {
+        review_event_extension(ctx, input, true).await
+    }
+
+    /// Deny a pending extension request
+    #[instrument(name = "Mutation::deny_event_extension", skip(self, ctx))]
+    async fn deny_event_extension(
+        &self,
+        ctx: &Context<'_>,
+        input: ReviewEventExtensionInput,
+    ) -> Result<ReviewEventExtensionResult> {
+        review_event_extension(ctx, input, false).await
+    }
+}
+
+/// Approve or deny an extension request, letting the requester know the outcome
+async fn review_event_extension(
+    ctx: &Context<'_>,
+    input: ReviewEventExtensionInput,
+    approved: bool,
+) -> Result<ReviewEventExtensionResult> {
+    let actor = Actor::load(ctx).await?;
+    actor.require(Action::Extend, Resource::Event)?;
+
+    let db = ctx.data_unchecked::<PgPool>();
+    let Some(mut request) = EventExtensionRequest::find(input.id, db).await.extend()? else {
+        return Ok(UserError::new(&["id"], "request does not exist").into());
+    };
+
+    let webhooks = ctx.data_unchecked::<webhooks::Client>();
+    let reviewer = actor.id();
+    let client = webhooks.clone();
+    let reviewed = database::with_txn(db, move |txn| {
+        Box::pin(async move {
+            if !request
+                .review(approved, reviewer, input.note, &mut *txn)
+                .await?
+            {
+                return Ok(None);
+            }
+
+            let queued = client.on_event_extension_changed(&request, txn).await?;
+            Ok(Some((request, queued)))
+        })
+    })
+    .await
+    .extend()?;
+    let Some((request, queued)) = reviewed else {
+        return Ok(UserError::new(&["id"], "request has already been reviewed").into());
+    };
+    webhooks.committed(queued);
+
+    notify_requester(ctx, &request, approved).await?;
+
+    Ok(request.into())
+}
+
+/// Email the user who requested the extension about the outcome of its review
+async fn notify_requester(
+    ctx: &Context<'_>,
+    request: &EventExtensionRequest,
+    approved: bool,
+) -> Result<()> {
+    let (Some(mailer), Some(requested_by)) =
+        (ctx.data_unchecked::<Option<Mailer>>(), request.requested_by)
+    else {
+        return Ok(());
+    };
+
+    let db = ctx.data_unchecked::<PgPool>();
+    let Some(requester) = User::find(requested_by, db).await.extend()? else {
+        return Ok(());
+    };
+    let loader = ctx.data_unchecked::<EventLoader>();
+    let Some(event) = loader.load_one(request.event.clone()).await.extend()? else {
+        return Ok(());
+    };
+
+    mailer.send(
+        &requester.primary_email,
+        &EventExtensionReviewed {
+            given_name: requester.given_name,
+            event_name: event.name,
+            approved,
+            expires_on: request.expires_on.format("%B %-d, %Y").to_string(),
+            note: request.review_note.clone(),
+        },
+    );
+    info!("sent extension review email");
+
+    Ok(())
+}
+
+/// Remove the cached scope resolution for an event after it changes
+pub(crate) async fn invalidate_scope(ctx: &Context<'_>, slug: &str) {
+    if let Some(cache) = ctx.data_unchecked::<Option<ScopeCache>>() {
+        cache.invalidate(slug).await;
+    }
 }
 
 /// Input fields for creating an event
@@ -114,7 +468,7 @@ struct CreateEventInput {
     /// The display name
     name: String,
     /// The organization putting on the event
-    organization_id: i32,
+    organization_id: ID,
 }
 
 /// Input fields for updating an event
@@ -124,4 +478,40 @@ struct UpdateEventInput {
     slug: String,
     /// The display name
     name: Option<String>,
+    /// When participants can start registering
+    registration_opens_at: MaybeUndefined<DateTime<Utc>>,
+    /// When participants can no longer register
+    registration_closes_at: MaybeUndefined<DateTime<Utc>>,
+    /// The maximum number of participants
+    capacity: MaybeUndefined<i32>,
+    /// The email domains participants must have, an empty list allows any email
+    allowed_email_domains: Option<Vec<String>>,
+    /// The logo shown in emails sent for the event
+    email_logo_url: MaybeUndefined<Url>,
+    /// The hex color used for buttons and links in emails sent for the event
+    email_accent_color: MaybeUndefined<String>,
+    /// Where replies to emails sent for the event are delivered
+    email_reply_to: MaybeUndefined<EmailAddress>,
+    /// An additional message from the organizers included in the welcome email
+    email_welcome_message: MaybeUndefined<String>,
+}
+
+/// Input fields for requesting an event extension
+#[derive(Debug, InputObject)]
+struct RequestEventExtensionInput {
+    /// The slug of the event to extend
+    event: String,
+    /// When write-access should expire instead
+    expires_on: DateTime<Utc>,
+    /// Why the extension is needed
+    reason: String,
+}
+
+/// Input fields for reviewing an event extension request
+#[derive(Debug, InputObject)]
+struct ReviewEventExtensionInput {
+    /// The ID of the request
+    id: i32,
+    /// An explanation of the decision
+    note: Option<String>,
 }
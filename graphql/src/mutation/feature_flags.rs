@@ -0,0 +1,107 @@
+use super::{results, validators::Validator, FieldPath, UserError};
+use crate::{actor::Actor, FeatureFlags};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use authz::{Action, Resource};
+use database::{Event, FeatureFlag, PgPool};
+use tracing::instrument;
+
+results! {
+    SetFeatureFlagResult {
+        /// The flag
+        flag: FeatureFlag,
+    }
+    DeleteFeatureFlagResult {
+        /// The key of the deleted flag
+        deleted_key: String,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct FeatureFlagMutation;
+
+#[Object]
+impl FeatureFlagMutation {
+    /// Create a feature flag or change an existing one. Fields that are not specified are left
+    /// unchanged, and new flags are disabled unless otherwise specified.
+    #[instrument(name = "Mutation::set_feature_flag", skip(self, ctx))]
+    async fn set_feature_flag(
+        &self,
+        ctx: &Context<'_>,
+        input: SetFeatureFlagInput,
+    ) -> Result<SetFeatureFlagResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Update, Resource::FeatureFlag)?;
+
+        let key = input.key.trim();
+        let mut validator = Validator::default();
+        validator
+            .field(&["key"], key)
+            .not_empty()
+            .max_length(64)
+            .flag_key();
+        validator
+            .optional(&["description"], input.description.as_ref())
+            .max_length(500);
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if let Some(events) = &input.enabled_events {
+            for (i, slug) in events.iter().enumerate() {
+                if !Event::exists(slug, db).await.extend()? {
+                    let field = FieldPath::new().field("enabled_events").index(i);
+                    return Ok(UserError::new(field, "event does not exist").into());
+                }
+            }
+        }
+
+        let flag = FeatureFlag::set(
+            key,
+            input.description.as_deref(),
+            input.enabled,
+            input.enabled_events.as_deref(),
+            db,
+        )
+        .await
+        .extend()?;
+        ctx.data_unchecked::<FeatureFlags>().invalidate();
+
+        Ok(flag.into())
+    }
+
+    /// Delete a feature flag, reverting the feature to its default
+    #[instrument(name = "Mutation::delete_feature_flag", skip(self, ctx))]
+    async fn delete_feature_flag(
+        &self,
+        ctx: &Context<'_>,
+        key: String,
+    ) -> Result<DeleteFeatureFlagResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::FeatureFlag)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !FeatureFlag::delete(&key, db).await.extend()? {
+            return Ok(UserError::new(&["key"], "flag does not exist").into());
+        }
+        ctx.data_unchecked::<FeatureFlags>().invalidate();
+
+        Ok(key.into())
+    }
+}
+
+/// Input fields for creating or changing a feature flag
+#[derive(Debug, InputObject)]
+struct SetFeatureFlagInput {
+    /// The key identifying the feature
+    key: String,
+    /// What the flag controls
+    description: Option<String>,
+    /// Whether the feature is enabled everywhere
+    enabled: Option<bool>,
+    /// The slugs of events the feature is enabled for, even when it is not enabled everywhere
+    enabled_events: Option<Vec<String>>,
+}
@@ -1,5 +1,7 @@
 use super::{results, UserError};
-use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use crate::{actor::Actor, ids};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt, ID};
+use authz::{Action, Resource};
 use database::{loaders::IdentitiesForUserLoader, Identity, PgPool};
 use tracing::instrument;
 
@@ -24,8 +26,15 @@ impl IdentityMutation {
         ctx: &Context<'_>,
         input: UnlinkIdentityInput,
     ) -> Result<UnlinkIdentityResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+        actor.require(Action::Update, Resource::User { id: user_id })?;
+
         let loader = ctx.data_unchecked::<IdentitiesForUserLoader>();
-        let Some(identities) = loader.load_one(input.user_id).await.extend()? else {
+        let Some(identities) = loader.load_one(user_id).await.extend()? else {
             return Ok(UserError::new(&["user_id"], "user does not exist").into());
         };
 
@@ -34,7 +43,7 @@ impl IdentityMutation {
         }
 
         let db = ctx.data_unchecked::<PgPool>();
-        Identity::unlink(&input.provider, input.user_id, db)
+        Identity::unlink(&input.provider, user_id, db)
             .await
             .extend()?;
 
@@ -46,7 +55,7 @@ impl IdentityMutation {
 #[derive(Debug, InputObject)]
 struct UnlinkIdentityInput {
     /// The ID of the user to perform the unlinking on
-    user_id: i32,
+    user_id: ID,
     /// THe provider to unlink
     provider: String,
 }
@@ -0,0 +1,178 @@
+use super::{results, validators::Validator, UserError};
+use crate::{actor::Actor, ids};
+use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result, ResultExt, ID};
+use authz::{Action, Resource};
+use database::{Event, Label, PgPool};
+use tracing::instrument;
+
+results! {
+    SetEventLabelResult {
+        /// The label
+        label: Label,
+    }
+    RemoveEventLabelResult {
+        /// The key of the removed label
+        removed_key: String,
+    }
+    SetOrganizationLabelResult {
+        /// The label
+        label: Label,
+    }
+    RemoveOrganizationLabelResult {
+        /// The key of the removed label
+        removed_key: String,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct LabelMutation;
+
+#[Object]
+impl LabelMutation {
+    /// Attach a label to an event, replacing the value if the key is already used
+    #[instrument(name = "Mutation::set_event_label", skip(self, ctx))]
+    async fn set_event_label(
+        &self,
+        ctx: &Context<'_>,
+        input: SetEventLabelInput,
+    ) -> Result<SetEventLabelResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::Label)?;
+
+        if let Some(user_errors) = validate(&input.key, input.value.as_deref()) {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !Event::exists(&input.event, db).await.extend()? {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        }
+
+        match Label::set_for_event(&input.event, &input.key, input.value.as_deref(), db).await {
+            Ok(label) => Ok(label.into()),
+            // The event was deleted after it was checked
+            Err(e) if e.is_foreign_key_violation() => {
+                Ok(UserError::new(&["event"], "event does not exist").into())
+            }
+            Err(e) => Err(e.extend()),
+        }
+    }
+
+    /// Remove a label from an event
+    #[instrument(name = "Mutation::remove_event_label", skip(self, ctx))]
+    async fn remove_event_label(
+        &self,
+        ctx: &Context<'_>,
+        event: String,
+        key: String,
+    ) -> Result<RemoveEventLabelResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::Label)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !Label::remove_from_event(&event, &key, db).await.extend()? {
+            return Ok(UserError::new(&["key"], "label does not exist").into());
+        }
+
+        Ok(key.into())
+    }
+
+    /// Attach a label to an organization, replacing the value if the key is already used
+    #[instrument(name = "Mutation::set_organization_label", skip(self, ctx))]
+    async fn set_organization_label(
+        &self,
+        ctx: &Context<'_>,
+        input: SetOrganizationLabelInput,
+    ) -> Result<SetOrganizationLabelResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::Label)?;
+
+        if let Some(user_errors) = validate(&input.key, input.value.as_deref()) {
+            return Ok(user_errors.into());
+        }
+
+        let Some(organization_id) = ids::organization(ctx, &input.organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        match Label::set_for_organization(organization_id, &input.key, input.value.as_deref(), db)
+            .await
+        {
+            Ok(label) => Ok(label.into()),
+            // The organization was deleted after it was loaded
+            Err(e) if e.is_foreign_key_violation() => {
+                Ok(UserError::new(&["organization_id"], "organization does not exist").into())
+            }
+            Err(e) => Err(e.extend()),
+        }
+    }
+
+    /// Remove a label from an organization
+    #[instrument(name = "Mutation::remove_organization_label", skip(self, ctx))]
+    async fn remove_organization_label(
+        &self,
+        ctx: &Context<'_>,
+        organization_id: ID,
+        key: String,
+    ) -> Result<RemoveOrganizationLabelResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::Label)?;
+
+        let Some(organization_id) = ids::organization(ctx, &organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !Label::remove_from_organization(organization_id, &key, db)
+            .await
+            .extend()?
+        {
+            return Ok(UserError::new(&["key"], "label does not exist").into());
+        }
+
+        Ok(key.into())
+    }
+}
+
+/// Validate the key and value of a label
+fn validate(key: &str, value: Option<&str>) -> Option<Vec<UserError>> {
+    let mut validator = Validator::default();
+    validator
+        .field(&["key"], key)
+        .not_empty()
+        .max_length(64)
+        .label_key();
+    validator
+        .optional(&["value"], value)
+        .not_empty()
+        .max_length(128);
+
+    validator.errors()
+}
+
+/// Input for attaching a label to an event
+#[derive(Debug, InputObject)]
+struct SetEventLabelInput {
+    /// The slug of the event
+    event: String,
+    /// What the label describes, i.e. `season`
+    key: String,
+    /// The value for the key, if any, i.e. `fall-2024`
+    value: Option<String>,
+}
+
+/// Input for attaching a label to an organization
+#[derive(Debug, InputObject)]
+struct SetOrganizationLabelInput {
+    /// The ID of the organization
+    organization_id: ID,
+    /// What the label describes, i.e. `season`
+    key: String,
+    /// The value for the key, if any, i.e. `fall-2024`
+    value: Option<String>,
+}
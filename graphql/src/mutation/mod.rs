@@ -1,21 +1,38 @@
 use async_graphql::{MergedObject, Object};
+use std::fmt::{Display, Formatter};
 
+mod custom_domains;
+mod email;
 mod event;
+mod feature_flags;
 mod identity;
+mod labels;
+mod oidc;
 mod organization;
 mod organizer;
 mod participant;
 mod providers;
+mod redirect_domains;
+mod sessions;
 mod user;
 mod validators;
+mod webhooks;
 
+use custom_domains::CustomDomainMutation;
+use email::EmailMutation;
 use event::EventMutation;
+use feature_flags::FeatureFlagMutation;
 use identity::IdentityMutation;
+use labels::LabelMutation;
+use oidc::OidcClientMutation;
 use organization::OrganizationMutation;
 use organizer::OrganizerMutation;
 use participant::ParticipantMutation;
 use providers::ProviderMutation;
+use redirect_domains::RedirectDomainMutation;
+use sessions::SessionMutation;
 use user::UserMutation;
+use webhooks::WebhookMutation;
 
 /// The various GraphQL mutations
 ///
@@ -23,33 +40,39 @@ use user::UserMutation;
 /// attached to this one struct.
 #[derive(Default, MergedObject)]
 pub struct Mutation(
+    CustomDomainMutation,
+    EmailMutation,
     EventMutation,
+    FeatureFlagMutation,
     IdentityMutation,
+    LabelMutation,
+    OidcClientMutation,
     OrganizationMutation,
     OrganizerMutation,
     ParticipantMutation,
     ProviderMutation,
+    RedirectDomainMutation,
+    SessionMutation,
     UserMutation,
+    WebhookMutation,
 );
 
 /// Represents and error in the input of a mutation
 #[derive(Debug)]
 pub struct UserError {
     /// The path to the input field that caused the error
-    field: &'static [&'static str],
+    field: FieldPath,
     /// The error message
     message: String,
 }
 
-// Cannot derive SimpleObject due to bug causing lifetime issues with unnecessary referencing
-// for `UserError.field`
 #[Object(shareable)]
 /// Represents and error in the input of a mutation
 impl UserError {
     /// The path to the input field that caused the error
     #[inline(always)]
-    async fn field(&self) -> &'static [&'static str] {
-        self.field
+    async fn field(&self) -> &[String] {
+        &self.field.0
     }
 
     /// The error message
@@ -61,10 +84,60 @@ impl UserError {
 
 impl UserError {
     /// Create a new user error
-    pub fn new(field: &'static [&'static str], message: impl ToString) -> Self {
+    pub fn new(field: impl Into<FieldPath>, message: impl ToString) -> Self {
+        let field = field.into();
         let message = message.to_string();
         Self { field, message }
     }
+
+    /// Nest the error under another field, i.e. an item in a list input
+    pub fn prefixed(mut self, prefix: impl Into<FieldPath>) -> Self {
+        let mut field = prefix.into();
+        field.0.append(&mut self.field.0);
+        self.field = field;
+        self
+    }
+}
+
+/// The path to an input field, with list items represented by their index
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct FieldPath(Vec<String>);
+
+impl FieldPath {
+    /// Create an empty path
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Descend into a named field
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.0.push(name.into());
+        self
+    }
+
+    /// Descend into an item of a list
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push(index.to_string());
+        self
+    }
+}
+
+impl Display for FieldPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.join("."))
+    }
+}
+
+impl From<&[&str]> for FieldPath {
+    fn from(path: &[&str]) -> Self {
+        Self(path.iter().map(|segment| segment.to_string()).collect())
+    }
+}
+
+impl<const N: usize> From<&[&str; N]> for FieldPath {
+    fn from(path: &[&str; N]) -> Self {
+        Self::from(path.as_slice())
+    }
 }
 
 /// Create mutation results with user errors
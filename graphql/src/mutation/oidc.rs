@@ -0,0 +1,121 @@
+use super::{results, validators::Validator, UserError};
+use crate::{
+    actor::Actor,
+    feature_flags::{FeatureGuard, Flag},
+    scalars::Url,
+};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt, SimpleObject};
+use authz::{Action, Resource};
+use database::{OidcClient, PgPool};
+use tracing::instrument;
+
+results! {
+    DeleteOidcClientResult {
+        /// The ID of the deleted client
+        deleted_id: String,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct OidcClientMutation;
+
+#[Object]
+impl OidcClientMutation {
+    /// Register a first-party service with the OpenID Connect provider. The client's secret is
+    /// only returned once.
+    #[instrument(name = "Mutation::create_oidc_client", skip(self, ctx))]
+    #[graphql(guard = "FeatureGuard::new(Flag::OidcProvider)")]
+    async fn create_oidc_client(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateOidcClientInput,
+    ) -> Result<CreateOidcClientResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::OidcClient)?;
+
+        let name = input.name.trim();
+        let mut validator = Validator::default();
+        validator.field(&["name"], name).not_empty().max_length(64);
+        validator
+            .field(&["redirect_uris"], &input.redirect_uris)
+            .check(
+                |uris| !uris.is_empty(),
+                "must have at least one redirect uri",
+            );
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let redirect_uris = input
+            .redirect_uris
+            .into_iter()
+            .map(Into::into)
+            .collect::<Vec<String>>();
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let registration = OidcClient::create(name, &redirect_uris, db)
+            .await
+            .extend()?;
+
+        Ok(registration.into())
+    }
+
+    /// Remove a client from the OpenID Connect provider. Tokens that were already issued remain
+    /// valid until they expire.
+    #[instrument(name = "Mutation::delete_oidc_client", skip(self, ctx))]
+    async fn delete_oidc_client(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> Result<DeleteOidcClientResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::OidcClient)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        OidcClient::delete(&id, db).await.extend()?;
+
+        Ok(id.into())
+    }
+}
+
+/// Input fields for registering a client
+#[derive(Debug, InputObject)]
+struct CreateOidcClientInput {
+    /// A human-readable name for the client
+    name: String,
+    /// The URIs users may be returned to after authorizing
+    redirect_uris: Vec<Url>,
+}
+
+#[derive(Debug, SimpleObject)]
+struct CreateOidcClientResult {
+    /// The registered client
+    client: Option<OidcClient>,
+    /// The secret the client authenticates with
+    secret: Option<String>,
+    /// Errors that may have occurred while processing the action
+    user_errors: Vec<UserError>,
+}
+
+impl From<(OidcClient, String)> for CreateOidcClientResult {
+    fn from((client, secret): (OidcClient, String)) -> Self {
+        Self {
+            client: Some(client),
+            secret: Some(secret),
+            user_errors: Vec::with_capacity(0),
+        }
+    }
+}
+
+impl From<Vec<UserError>> for CreateOidcClientResult {
+    fn from(user_errors: Vec<UserError>) -> Self {
+        Self {
+            client: None,
+            secret: None,
+            user_errors,
+        }
+    }
+}
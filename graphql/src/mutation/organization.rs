@@ -1,6 +1,13 @@
-use super::{results, validators, UserError};
-use async_graphql::{Context, InputObject, MaybeUndefined, Object, Result, ResultExt};
-use database::{loaders::OrganizationLoader, Organization, PgPool, User};
+use super::{event::invalidate_scope, results, validators::Validator, UserError};
+use crate::{actor::Actor, ids, scalars::Url};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, MaybeUndefined, Object, Result, ResultExt, ID,
+};
+use authz::{Action, Resource};
+use database::{
+    loaders::OrganizationByPublicIdLoader, Event, Organization, OrganizationEmailDomain,
+    OrganizationEvents, PgPool, Role, User,
+};
 use tracing::instrument;
 
 results! {
@@ -18,7 +25,15 @@ results! {
     }
     DeleteOrganizationResult {
         /// The ID of the deleted organization
-        deleted_id: i32,
+        deleted_id: ID,
+    }
+    AddOrganizationEmailDomainResult {
+        /// The added email domain
+        email_domain: OrganizationEmailDomain,
+    }
+    RemoveOrganizationEmailDomainResult {
+        /// The removed email domain
+        removed_domain: String,
     }
 }
 
@@ -34,21 +49,48 @@ impl OrganizationMutation {
         ctx: &Context<'_>,
         input: CreateOrganizationInput,
     ) -> Result<CreateOrganizationResult> {
-        if input.name.is_empty() {
-            return Ok(UserError::new(&["name"], "cannot be empty").into());
-        }
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::Organization { owner_id: None })?;
 
-        let db = ctx.data_unchecked::<PgPool>();
+        let mut validator = Validator::default();
+        validator
+            .field(&["slug"], &input.slug)
+            .not_empty()
+            .max_length(63)
+            .dns_segment();
+        validator.field(&["name"], &input.name).not_empty();
 
-        if !User::exists(input.owner_id, db).await.extend()? {
-            return Ok(UserError::new(&["owner_id"], "owner does not exist").into());
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
         }
 
-        let organization = Organization::create(&input.name, input.owner_id, db)
-            .await
-            .extend()?;
+        let Some(owner_id) = ids::user(ctx, &input.owner_id).await? else {
+            return Ok(UserError::new(&["owner_id"], "owner does not exist").into());
+        };
 
-        Ok(organization.into())
+        let db = ctx.data_unchecked::<PgPool>();
+        let result = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                if !User::exists(owner_id, &mut *txn).await? {
+                    return Ok(None);
+                }
+
+                let organization =
+                    Organization::create(&input.slug, &input.name, owner_id, &mut *txn).await?;
+                Ok(Some(organization))
+            })
+        })
+        .await;
+
+        match result {
+            Ok(Some(organization)) => Ok(organization.into()),
+            Ok(None) => Ok(UserError::new(&["owner_id"], "owner does not exist").into()),
+            Err(e) if e.is_unique_violation() => {
+                Ok(UserError::new(&["slug"], "already in use").into())
+            }
+            Err(e) => Err(e.extend()),
+        }
     }
 
     /// Update the details of an organization
@@ -58,47 +100,36 @@ impl OrganizationMutation {
         ctx: &Context<'_>,
         input: UpdateOrganizationInput,
     ) -> Result<UpdateOrganizationResult> {
-        let mut user_errors = Vec::new();
+        let actor = Actor::load(ctx).await?;
 
-        if let Some(name) = &input.name {
-            if name.is_empty() {
-                user_errors.push(UserError::new(&["name"], "cannot be empty"));
-            }
-        }
+        let loader = ctx.data_unchecked::<OrganizationByPublicIdLoader>();
+        let Some(mut organization) = loader.load_one(input.id.to_string()).await.extend()? else {
+            return Ok(UserError::new(&["id"], "organization does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                organization.id,
+                Action::Update,
+                Resource::Organization { owner_id: None },
+            )
+            .await?;
 
-        if let MaybeUndefined::Value(logo) = &input.logo {
-            if logo.is_empty() {
-                user_errors.push(UserError::new(&["logo"], "cannot be empty"));
-            }
-            if !validators::url(logo) {
-                user_errors.push(UserError::new(&["logo"], "must be a URL"));
-            }
-        }
+        let mut validator = Validator::default();
+        validator
+            .optional(&["name"], input.name.as_ref())
+            .not_empty();
 
-        if let MaybeUndefined::Value(website) = &input.website {
-            if website.is_empty() {
-                user_errors.push(UserError::new(&["website"], "cannot be empty"));
-            }
-            if !validators::url(website) {
-                user_errors.push(UserError::new(&["website"], "must be a URL"));
-            }
-        }
-
-        if !user_errors.is_empty() {
+        if let Some(user_errors) = validator.errors() {
             return Ok(user_errors.into());
         }
 
-        let loader = ctx.data_unchecked::<OrganizationLoader>();
-        let Some(mut organization) = loader.load_one(input.id).await.extend()? else {
-            return Ok(UserError::new(&["id"], "organization does not exist").into());
-        };
-
         let db = ctx.data_unchecked::<PgPool>();
         organization
             .update()
             .override_name(input.name)
-            .override_logo(input.logo.into())
-            .override_website(input.website.into())
+            .override_logo(input.logo.map_value(Into::into).into())
+            .override_website(input.website.map_value(Into::into).into())
             .save(db)
             .await
             .extend()?;
@@ -113,68 +144,243 @@ impl OrganizationMutation {
         ctx: &Context<'_>,
         input: TransferOrganizationOwnershipInput,
     ) -> Result<TransferOrganizationOwnershipResult> {
-        let db = ctx.data_unchecked::<PgPool>();
-        if !User::exists(input.new_owner_id, db).await.extend()? {
-            return Ok(UserError::new(&["new_owner_id"], "new owner does not exist").into());
-        }
+        let actor = Actor::load(ctx).await?;
 
-        let organization_loader = ctx.data_unchecked::<OrganizationLoader>();
-        let Some(mut organization) = organization_loader.load_one(input.id).await.extend()? else {
+        let organization_loader = ctx.data_unchecked::<OrganizationByPublicIdLoader>();
+        let Some(mut organization) = organization_loader
+            .load_one(input.id.to_string())
+            .await
+            .extend()?
+        else {
             return Ok(UserError::new(&["id"], "organization does not exist").into());
         };
+        actor.require(
+            Action::TransferOwnership,
+            Resource::Organization {
+                owner_id: Some(organization.owner_id),
+            },
+        )?;
+
+        let Some(new_owner_id) = ids::user(ctx, &input.new_owner_id).await? else {
+            return Ok(UserError::new(&["new_owner_id"], "new owner does not exist").into());
+        };
 
         let db = ctx.data_unchecked::<PgPool>();
-        organization
-            .update()
-            .owner(input.new_owner_id)
-            .save(db)
-            .await
-            .extend()?;
+        let organization = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                if !User::exists(new_owner_id, &mut *txn).await? {
+                    return Ok(None);
+                }
 
-        Ok(organization.into())
+                organization
+                    .update()
+                    .owner(new_owner_id)
+                    .save(&mut *txn)
+                    .await?;
+                Ok(Some(organization))
+            })
+        })
+        .await
+        .extend()?;
+
+        match organization {
+            Some(organization) => Ok(organization.into()),
+            None => Ok(UserError::new(&["new_owner_id"], "new owner does not exist").into()),
+        }
     }
 
-    /// Delete an organization
+    /// Delete an organization. Deletion is refused while the organization still has events,
+    /// unless they are explicitly deleted with it or transferred to another organization.
     #[instrument(name = "Mutation::delete_organization", skip(self, ctx))]
     async fn delete_organization(
         &self,
         ctx: &Context<'_>,
-        id: i32,
+        id: ID,
+        #[graphql(default)] cascade: bool,
+        transfer_events_to: Option<ID>,
     ) -> Result<DeleteOrganizationResult> {
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Delete, Resource::Organization { owner_id: None })?;
+
         let db = ctx.data::<PgPool>()?;
-        Organization::delete(id, db).await.extend()?;
+
+        let events = match (cascade, transfer_events_to) {
+            (false, None) => OrganizationEvents::Restrict,
+            (true, None) => OrganizationEvents::Cascade,
+            (false, Some(target)) if target == id => {
+                return Ok(UserError::new(
+                    &["transfer_events_to"],
+                    "cannot transfer events to the same organization",
+                )
+                .into());
+            }
+            (false, Some(target)) => match ids::organization(ctx, &target).await? {
+                Some(target) => OrganizationEvents::TransferTo(target),
+                None => {
+                    return Ok(UserError::new(
+                        &["transfer_events_to"],
+                        "organization does not exist",
+                    )
+                    .into());
+                }
+            },
+            (true, Some(_)) => {
+                return Ok(
+                    UserError::new(&["transfer_events_to"], "cannot be used with cascade").into(),
+                );
+            }
+        };
+
+        let Some(organization_id) = ids::organization(ctx, &id).await? else {
+            return Ok(UserError::new(&["id"], "organization does not exist").into());
+        };
+
+        // Deleted and transferred events no longer resolve to this organization
+        let affected = match events {
+            OrganizationEvents::Restrict => Vec::new(),
+            _ => Event::for_organization(organization_id, db)
+                .await
+                .extend()?,
+        };
+
+        if !Organization::delete_with_events(organization_id, events, Some(actor.id()), db)
+            .await
+            .extend()?
+        {
+            return Ok(UserError::new(&["id"], "organization still has events").into());
+        }
+
+        for event in affected {
+            invalidate_scope(ctx, &event.slug).await;
+        }
 
         Ok(id.into())
     }
+
+    /// Automatically add newly registered users with a verified email at the domain to the
+    /// organization. Only administrators can add domains, after checking the organization owns
+    /// them, and users joining through a domain can be at most organizers.
+    #[instrument(name = "Mutation::add_organization_email_domain", skip(self, ctx))]
+    async fn add_organization_email_domain(
+        &self,
+        ctx: &Context<'_>,
+        input: AddOrganizationEmailDomainInput,
+    ) -> Result<AddOrganizationEmailDomainResult> {
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Create, Resource::EmailDomain)?;
+
+        let Some(organization_id) = ids::organization(ctx, &input.organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+
+        let domain = input.domain.trim().to_lowercase();
+
+        let mut validator = Validator::default();
+        validator
+            .field(&["domain"], &domain)
+            .not_empty()
+            .max_length(253)
+            .domain();
+        validator.field(&["role"], &input.role).check(
+            |role| *role == Role::Organizer,
+            "cannot be higher than organizer",
+        );
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !Organization::exists(organization_id, db).await.extend()? {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        }
+
+        let email_domain =
+            OrganizationEmailDomain::add(organization_id, &domain, input.role, actor.id(), db)
+                .await
+                .extend()?;
+
+        Ok(email_domain.into())
+    }
+
+    /// Stop automatically adding users with a verified email at the domain to the organization
+    #[instrument(name = "Mutation::remove_organization_email_domain", skip(self, ctx))]
+    async fn remove_organization_email_domain(
+        &self,
+        ctx: &Context<'_>,
+        input: RemoveOrganizationEmailDomainInput,
+    ) -> Result<RemoveOrganizationEmailDomainResult> {
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Delete, Resource::EmailDomain)?;
+
+        let Some(organization_id) = ids::organization(ctx, &input.organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+
+        let domain = input.domain.trim().to_lowercase();
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !OrganizationEmailDomain::remove(organization_id, &domain, actor.id(), db)
+            .await
+            .extend()?
+        {
+            return Ok(UserError::new(&["domain"], "domain does not exist").into());
+        }
+
+        Ok(domain.into())
+    }
 }
 
 /// Input fields for creating an organization
 #[derive(Debug, InputObject)]
 struct CreateOrganizationInput {
+    /// A unique slug
+    slug: String,
     /// The display name
     name: String,
     /// Who owns the organization
-    owner_id: i32,
+    owner_id: ID,
 }
 
 /// Input fields for updating an organization
 #[derive(Debug, InputObject)]
 struct UpdateOrganizationInput {
     /// The ID of the organization to update
-    id: i32,
+    id: ID,
     /// The display name
     name: Option<String>,
     /// The URL of the organization's logo
-    logo: MaybeUndefined<String>,
+    logo: MaybeUndefined<Url>,
     /// The URL of the organization's website
-    website: MaybeUndefined<String>,
+    website: MaybeUndefined<Url>,
 }
 
 /// Input fields for transferring the ownership of an organization
 #[derive(Debug, InputObject)]
 struct TransferOrganizationOwnershipInput {
     /// The ID of the organization to transfer ownership of
-    id: i32,
+    id: ID,
     /// The ID of the new organization owner
-    new_owner_id: i32,
+    new_owner_id: ID,
+}
+
+/// Input fields for adding an email domain to an organization
+#[derive(Debug, InputObject)]
+struct AddOrganizationEmailDomainInput {
+    /// The ID of the organization
+    organization_id: ID,
+    /// The email domain, i.e. `example.com`
+    domain: String,
+    /// The role users joining through the domain should have, at most organizer
+    #[graphql(default)]
+    role: Role,
+}
+
+/// Input fields for removing an email domain from an organization
+#[derive(Debug, InputObject)]
+struct RemoveOrganizationEmailDomainInput {
+    /// The ID of the organization
+    organization_id: ID,
+    /// The email domain to remove
+    domain: String,
 }
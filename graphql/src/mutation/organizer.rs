@@ -1,11 +1,26 @@
-use super::UserError;
-use async_graphql::{Context, InputObject, Object, Result, ResultExt, SimpleObject};
+use super::{results, UserError};
+use crate::{actor::Actor, ids};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, Object, Result, ResultExt, SimpleObject, ID,
+};
+use authz::{Action, Resource};
 use database::{
-    loaders::{OrganizationLoader, UserLoader},
-    Organization, Organizer, PgPool, Role, User,
+    loaders::{EventLoader, OrganizationByPublicIdLoader, UserByPublicIdLoader},
+    EventOrganizer, Organization, Organizer, PgPool, Role, User,
 };
 use tracing::instrument;
 
+results! {
+    AddEventOrganizerResult {
+        /// The user's organizer access to the event
+        organizer: EventOrganizer,
+    }
+    RemoveEventOrganizerResult {
+        /// The ID of the user whose access was revoked
+        removed_user_id: ID,
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct OrganizerMutation;
 
@@ -18,26 +33,49 @@ impl OrganizerMutation {
         ctx: &Context<'_>,
         input: AddUserToOrganizationInput,
     ) -> Result<AddUserToOrganizationResult> {
-        let organization_loader = ctx.data_unchecked::<OrganizationLoader>();
+        let actor = Actor::load(ctx).await?;
+
+        let organization_loader = ctx.data_unchecked::<OrganizationByPublicIdLoader>();
         let Some(organization) = organization_loader
-            .load_one(input.organization_id)
+            .load_one(input.organization_id.to_string())
             .await
             .extend()?
         else {
             return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
         };
+        actor
+            .require_in(
+                ctx,
+                organization.id,
+                Action::ManageMembers,
+                Resource::Organization { owner_id: None },
+            )
+            .await?;
 
-        let user_loader = ctx.data_unchecked::<UserLoader>();
-        let Some(user) = user_loader.load_one(input.user_id).await.extend()? else {
+        let user_loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = user_loader
+            .load_one(input.user_id.to_string())
+            .await
+            .extend()?
+        else {
             return Ok(UserError::new(&["user_id"], "user does not exist").into());
         };
 
         let db = ctx.data_unchecked::<PgPool>();
-        Organizer::add(organization.id, user.id, input.role, db)
-            .await
-            .extend()?;
-
-        Ok((user, organization).into())
+        match Organizer::add(organization.id, user.id, input.role, db).await {
+            Ok(_) => Ok((user, organization).into()),
+            // The user or organization was deleted after it was loaded
+            Err(e) if e.is_foreign_key_violation() => {
+                let error = match e.constraint_name() {
+                    Some("organizers_user_id_fkey") => {
+                        UserError::new(&["user_id"], "user does not exist")
+                    }
+                    _ => UserError::new(&["organization_id"], "organization does not exist"),
+                };
+                Ok(error.into())
+            }
+            Err(e) => Err(e.extend()),
+        }
     }
 
     /// Remove a user from an organization
@@ -47,22 +85,119 @@ impl OrganizerMutation {
         ctx: &Context<'_>,
         input: RemoveUserFromOrganizationInput,
     ) -> Result<RemoveUserFromOrganizationResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let Some(organization_id) = ids::organization(ctx, &input.organization_id).await? else {
+            return Ok(UserError::new(&["organization_id"], "organization does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                organization_id,
+                Action::ManageMembers,
+                Resource::Organization { owner_id: None },
+            )
+            .await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+
         let db = ctx.data_unchecked::<PgPool>();
-        Organizer::delete(input.organization_id, input.user_id, db)
+        Organizer::delete(organization_id, user_id, db)
             .await
             .extend()?;
 
         Ok((input.user_id, input.organization_id).into())
     }
+
+    /// Grant a user organizer access to a single event, without adding them to the organization.
+    /// Granting access to a user that already has it changes their role.
+    #[instrument(name = "Mutation::add_event_organizer", skip(self, ctx))]
+    async fn add_event_organizer(
+        &self,
+        ctx: &Context<'_>,
+        input: AddEventOrganizerInput,
+    ) -> Result<AddEventOrganizerResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let event_loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = event_loader.load_one(input.event).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                event.organization_id,
+                Action::ManageMembers,
+                Resource::Organization { owner_id: None },
+            )
+            .await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        match EventOrganizer::add(&event.slug, user_id, input.role, db).await {
+            Ok(organizer) => Ok(organizer.into()),
+            // The user or event was deleted after it was loaded
+            Err(e) if e.is_foreign_key_violation() => {
+                let error = match e.constraint_name() {
+                    Some("event_organizers_user_id_fkey") => {
+                        UserError::new(&["user_id"], "user does not exist")
+                    }
+                    _ => UserError::new(&["event"], "event does not exist"),
+                };
+                Ok(error.into())
+            }
+            Err(e) => Err(e.extend()),
+        }
+    }
+
+    /// Revoke a user's organizer access to a single event. Access granted through the
+    /// organization is unaffected.
+    #[instrument(name = "Mutation::remove_event_organizer", skip(self, ctx))]
+    async fn remove_event_organizer(
+        &self,
+        ctx: &Context<'_>,
+        input: RemoveEventOrganizerInput,
+    ) -> Result<RemoveEventOrganizerResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let event_loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = event_loader.load_one(input.event).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                event.organization_id,
+                Action::ManageMembers,
+                Resource::Organization { owner_id: None },
+            )
+            .await?;
+
+        let Some(user_id) = ids::user(ctx, &input.user_id).await? else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        EventOrganizer::delete(&event.slug, user_id, db)
+            .await
+            .extend()?;
+
+        Ok(input.user_id.into())
+    }
 }
 
 /// Input for adding a user to an organization
 #[derive(Debug, InputObject)]
 struct AddUserToOrganizationInput {
     /// The ID of the organization to add the user to
-    organization_id: i32,
+    organization_id: ID,
     /// The ID of the user to add
-    user_id: i32,
+    user_id: ID,
     /// The role the user should have
     #[graphql(default)]
     role: Role,
@@ -102,23 +237,23 @@ impl From<UserError> for AddUserToOrganizationResult {
 #[derive(Debug, InputObject)]
 struct RemoveUserFromOrganizationInput {
     /// The ID of the organization to remove the user from
-    organization_id: i32,
+    organization_id: ID,
     /// The ID of the user to remove
-    user_id: i32,
+    user_id: ID,
 }
 
 #[derive(Debug, SimpleObject)]
 struct RemoveUserFromOrganizationResult {
     /// The ID of the user that was removed from the organization
-    removed_user_id: Option<i32>,
+    removed_user_id: Option<ID>,
     /// The organization the user was removed from
-    organization: Option<i32>,
+    organization: Option<ID>,
     /// Errors that may have occurred while processing the action
     user_errors: Vec<UserError>,
 }
 
-impl From<(i32, i32)> for RemoveUserFromOrganizationResult {
-    fn from((user_id, organization): (i32, i32)) -> Self {
+impl From<(ID, ID)> for RemoveUserFromOrganizationResult {
+    fn from((user_id, organization): (ID, ID)) -> Self {
         Self {
             removed_user_id: Some(user_id),
             organization: Some(organization),
@@ -126,3 +261,34 @@ impl From<(i32, i32)> for RemoveUserFromOrganizationResult {
         }
     }
 }
+
+impl From<UserError> for RemoveUserFromOrganizationResult {
+    fn from(user_error: UserError) -> Self {
+        Self {
+            removed_user_id: None,
+            organization: None,
+            user_errors: vec![user_error],
+        }
+    }
+}
+
+/// Input for granting a user organizer access to an event
+#[derive(Debug, InputObject)]
+struct AddEventOrganizerInput {
+    /// The slug of the event to grant access to
+    event: String,
+    /// The ID of the user to grant access
+    user_id: ID,
+    /// The role the user should have within the event
+    #[graphql(default)]
+    role: Role,
+}
+
+/// Input for revoking a user's organizer access to an event
+#[derive(Debug, InputObject)]
+struct RemoveEventOrganizerInput {
+    /// The slug of the event to revoke access to
+    event: String,
+    /// The ID of the user to revoke access from
+    user_id: ID,
+}
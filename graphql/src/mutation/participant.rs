@@ -1,12 +1,41 @@
-use super::UserError;
-use crate::webhooks;
-use async_graphql::{Context, InputObject, Object, Result, ResultExt, SimpleObject};
+use super::{results, validators::Validator, UserError};
+use crate::{
+    actor::Actor,
+    errors::{BadRequest, Forbidden},
+    webhooks::{self, ParticipantChange},
+};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, Object, Result, ResultExt, SimpleObject, ID,
+};
+use authz::{Action, Resource};
+use context::Scope;
 use database::{
-    loaders::{EventLoader, UserLoader},
-    Event, Participant, PgPool, User,
+    loaders::{EventLoader, UserByPublicIdLoader},
+    Event, Json, Participant, ParticipantRole, PgPool, RegistrationStatus, User,
 };
+use serde_json::{Map, Value};
 use tracing::instrument;
 
+/// How many attributes can be set in a single update
+const MAX_ATTRIBUTE_KEYS: usize = 64;
+/// The largest an update to the attributes can be, in bytes of JSON
+const MAX_ATTRIBUTES_SIZE: usize = 16 * 1024;
+
+results! {
+    JoinEventResult {
+        /// The created participant
+        participant: Participant,
+    }
+    UpdateParticipantAttributesResult {
+        /// The updated participant
+        participant: Participant,
+    }
+    UpdateParticipantRoleResult {
+        /// The updated participant
+        participant: Participant,
+    }
+}
+
 #[derive(Default)]
 pub(crate) struct ParticipantMutation;
 
@@ -19,21 +48,81 @@ impl ParticipantMutation {
         ctx: &Context<'_>,
         input: AddUserToEventInput,
     ) -> Result<AddUserToEventResult> {
+        let actor = Actor::load(ctx).await?;
+
         let event_loader = ctx.data_unchecked::<EventLoader>();
         let Some(event) = event_loader.load_one(input.event).await.extend()? else {
             return Ok(UserError::new(&["event"], "event does not exist").into());
         };
+        actor
+            .require_in(
+                ctx,
+                event.organization_id,
+                Action::ManageParticipants,
+                Resource::Event,
+            )
+            .await?;
+
+        if event.is_archived() {
+            return Ok(UserError::new(&["event"], "event is archived").into());
+        }
 
-        let user_loader = ctx.data_unchecked::<UserLoader>();
-        let Some(user) = user_loader.load_one(input.user_id).await.extend()? else {
+        let user_loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = user_loader
+            .load_one(input.user_id.to_string())
+            .await
+            .extend()?
+        else {
             return Ok(UserError::new(&["user_id"], "user does not exist").into());
         };
 
-        let db = ctx.data_unchecked::<PgPool>();
-        Participant::add(&event.slug, user.id, db).await.extend()?;
+        if !event.allows_email(&user.primary_email) {
+            return Ok(UserError::new(
+                &["user_id"],
+                "user's email domain is not allowed for the event",
+            )
+            .into());
+        }
 
+        let db = ctx.data_unchecked::<PgPool>();
         let webhooks = ctx.data_unchecked::<webhooks::Client>();
-        webhooks.on_participant_changed(user.id, &user.primary_email);
+        let (slug, role, added, actor_user) = (
+            event.slug.clone(),
+            input.role,
+            user.clone(),
+            actor.user().clone(),
+        );
+        let client = webhooks.clone();
+        let result = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let participant = Participant::add(&slug, added.id, role, &mut *txn).await?;
+                client
+                    .on_participant_changed(
+                        ParticipantChange::Added,
+                        &added,
+                        Some(&participant),
+                        &actor_user,
+                        txn,
+                    )
+                    .await
+            })
+        })
+        .await;
+        let queued = match result {
+            Ok(queued) => queued,
+            // The user or event was deleted after it was loaded
+            Err(e) if e.is_foreign_key_violation() => {
+                let error = match e.constraint_name() {
+                    Some("participants_user_id_fkey") => {
+                        UserError::new(&["user_id"], "user does not exist")
+                    }
+                    _ => UserError::new(&["event"], "event does not exist"),
+                };
+                return Ok(error.into());
+            }
+            Err(e) => return Err(e.extend()),
+        };
+        webhooks.committed(queued);
 
         Ok((user, event).into())
     }
@@ -45,13 +134,295 @@ impl ParticipantMutation {
         ctx: &Context<'_>,
         input: RemoveUserFromEventInput,
     ) -> Result<RemoveUserFromEventResult> {
-        let db = ctx.data_unchecked::<PgPool>();
-        Participant::delete(&input.event, input.user_id, db)
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(input.event.clone()).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                event.organization_id,
+                Action::ManageParticipants,
+                Resource::Event,
+            )
+            .await?;
+
+        let user_loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = user_loader
+            .load_one(input.user_id.to_string())
             .await
-            .extend()?;
+            .extend()?
+        else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let webhooks = ctx.data_unchecked::<webhooks::Client>();
+        let (slug, actor_user) = (input.event.clone(), actor.user().clone());
+        let client = webhooks.clone();
+        let queued = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let Some(participant) = Participant::delete(&slug, user.id, &mut *txn).await?
+                else {
+                    return Ok(None);
+                };
+
+                let queued = client
+                    .on_participant_changed(
+                        ParticipantChange::Removed,
+                        &user,
+                        Some(&participant),
+                        &actor_user,
+                        txn,
+                    )
+                    .await?;
+                Ok(Some(queued))
+            })
+        })
+        .await
+        .extend()?;
+        if let Some(queued) = queued {
+            webhooks.committed(queued);
+        }
 
         Ok((input.user_id, input.event).into())
     }
+
+    /// Join an event as the current user, if registration is open and the event is not full
+    #[instrument(name = "Mutation::join_event", skip(self, ctx))]
+    async fn join_event(
+        &self,
+        ctx: &Context<'_>,
+        input: JoinEventInput,
+    ) -> Result<JoinEventResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let scope = ctx.data_unchecked::<Scope>();
+        let slug = match (scope, input.event) {
+            (Scope::Event(e), Some(slug)) if e.event == slug => slug,
+            (Scope::Event(e), None) => e.event.to_owned(),
+            (Scope::Event(_), Some(_)) => return Err(Forbidden.into()),
+            (_, Some(slug)) => slug,
+            (_, None) => {
+                return Err(BadRequest(
+                    r#"argument "event" is required as the event could not be inferred"#,
+                )
+                .into());
+            }
+        };
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(slug).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+
+        if event.is_archived() {
+            return Ok(UserError::new(&["event"], "event is archived").into());
+        }
+
+        match event.registration_status() {
+            RegistrationStatus::Open => {}
+            RegistrationStatus::NotYetOpen => {
+                return Ok(UserError::new(&["event"], "registration has not opened yet").into())
+            }
+            RegistrationStatus::Closed => {
+                return Ok(UserError::new(&["event"], "registration has closed").into())
+            }
+        }
+
+        if !event.allows_email(&actor.user().primary_email) {
+            return Ok(UserError::new(
+                &["event"],
+                "your email domain is not allowed for the event",
+            )
+            .into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let webhooks = ctx.data_unchecked::<webhooks::Client>();
+        let (slug, user) = (event.slug.clone(), actor.user().clone());
+        let client = webhooks.clone();
+        let registered = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let Some(participant) = Participant::register(&slug, user.id, &mut *txn).await?
+                else {
+                    return Ok(None);
+                };
+
+                let queued = client
+                    .on_participant_changed(
+                        ParticipantChange::Added,
+                        &user,
+                        Some(&participant),
+                        &user,
+                        txn,
+                    )
+                    .await?;
+                Ok(Some((participant, queued)))
+            })
+        })
+        .await
+        .extend()?;
+        let Some((participant, queued)) = registered else {
+            return Ok(UserError::new(&["event"], "event is full").into());
+        };
+        webhooks.committed(queued);
+
+        Ok(participant.into())
+    }
+
+    /// Update the event-specific attributes of a participant. Only the participant themselves and
+    /// organizers of the event can make changes.
+    #[instrument(name = "Mutation::update_participant_attributes", skip(self, ctx))]
+    async fn update_participant_attributes(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateParticipantAttributesInput,
+    ) -> Result<UpdateParticipantAttributesResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let user_loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = user_loader
+            .load_one(input.user_id.to_string())
+            .await
+            .extend()?
+        else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+        let resource = Resource::Participant { user_id: user.id };
+
+        let scope = ctx.data_unchecked::<Scope>();
+        match scope {
+            Scope::Event(e) if e.event == input.event => {
+                let loader = ctx.data_unchecked::<EventLoader>();
+                let Some(event) = loader.load_one(input.event.clone()).await.extend()? else {
+                    return Ok(UserError::new(&["event"], "event does not exist").into());
+                };
+                actor
+                    .require_in(ctx, event.organization_id, Action::Update, resource)
+                    .await?;
+            }
+            Scope::Admin => actor.require(Action::Update, resource)?,
+            _ => return Err(Forbidden.into()),
+        }
+
+        let mut validator = Validator::default();
+        validator
+            .field(&["attributes"], &input.attributes.0)
+            .max_keys(MAX_ATTRIBUTE_KEYS)
+            .max_serialized_size(MAX_ATTRIBUTES_SIZE);
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let webhooks = ctx.data_unchecked::<webhooks::Client>();
+        let (slug, attributes, actor_user) =
+            (input.event, input.attributes.0, actor.user().clone());
+        let client = webhooks.clone();
+        let updated = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let Some(participant) =
+                    Participant::merge_attributes(&slug, user.id, attributes, &mut *txn).await?
+                else {
+                    return Ok(None);
+                };
+
+                let queued = client
+                    .on_participant_changed(
+                        ParticipantChange::Updated,
+                        &user,
+                        Some(&participant),
+                        &actor_user,
+                        txn,
+                    )
+                    .await?;
+                Ok(Some((participant, queued)))
+            })
+        })
+        .await
+        .extend()?;
+        let Some((participant, queued)) = updated else {
+            return Ok(
+                UserError::new(&["user_id"], "user is not participating in the event").into(),
+            );
+        };
+        webhooks.committed(queued);
+
+        Ok(participant.into())
+    }
+
+    /// Change the part a participant plays in an event
+    #[instrument(name = "Mutation::update_participant_role", skip(self, ctx))]
+    async fn update_participant_role(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateParticipantRoleInput,
+    ) -> Result<UpdateParticipantRoleResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let loader = ctx.data_unchecked::<EventLoader>();
+        let Some(event) = loader.load_one(input.event.clone()).await.extend()? else {
+            return Ok(UserError::new(&["event"], "event does not exist").into());
+        };
+        actor
+            .require_in(
+                ctx,
+                event.organization_id,
+                Action::ManageParticipants,
+                Resource::Event,
+            )
+            .await?;
+
+        if event.is_archived() {
+            return Ok(UserError::new(&["event"], "event is archived").into());
+        }
+
+        let user_loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(user) = user_loader
+            .load_one(input.user_id.to_string())
+            .await
+            .extend()?
+        else {
+            return Ok(UserError::new(&["user_id"], "user does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(mut participant) = Participant::find(user.id, &event.slug, db).await.extend()?
+        else {
+            return Ok(
+                UserError::new(&["user_id"], "user is not participating in the event").into(),
+            );
+        };
+
+        let webhooks = ctx.data_unchecked::<webhooks::Client>();
+        let (role, actor_user) = (input.role, actor.user().clone());
+        let client = webhooks.clone();
+        let (participant, queued) = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                participant.update().role(role).save(&mut *txn).await?;
+
+                let queued = client
+                    .on_participant_changed(
+                        ParticipantChange::Updated,
+                        &user,
+                        Some(&participant),
+                        &actor_user,
+                        txn,
+                    )
+                    .await?;
+                Ok((participant, queued))
+            })
+        })
+        .await
+        .extend()?;
+        webhooks.committed(queued);
+
+        Ok(participant.into())
+    }
 }
 
 /// Input for adding a user to an event
@@ -60,7 +431,10 @@ struct AddUserToEventInput {
     /// The slug of the event to add the user to
     event: String,
     /// The ID of the user to add
-    user_id: i32,
+    user_id: ID,
+    /// The role the user should have. New participants default to hackers, while existing
+    /// participants keep their role when it is omitted.
+    role: Option<ParticipantRole>,
 }
 
 #[derive(Debug, SimpleObject)]
@@ -93,27 +467,57 @@ impl From<UserError> for AddUserToEventResult {
     }
 }
 
+/// Input for joining an event as the current user
+#[derive(Debug, InputObject)]
+struct JoinEventInput {
+    /// The slug of the event to join, inferred from the request domain if omitted
+    event: Option<String>,
+}
+
+/// Input for updating a participant's attributes
+#[derive(Debug, InputObject)]
+struct UpdateParticipantAttributesInput {
+    /// The slug of the event the user is participating in
+    event: String,
+    /// The ID of the participant's user
+    user_id: ID,
+    /// The attributes to merge into the existing ones, keys set to `null` are removed. At most 64
+    /// keys and 16KiB of JSON can be set at once.
+    attributes: Json<Map<String, Value>>,
+}
+
+/// Input for changing a participant's role
+#[derive(Debug, InputObject)]
+struct UpdateParticipantRoleInput {
+    /// The slug of the event the user is participating in
+    event: String,
+    /// The ID of the participant's user
+    user_id: ID,
+    /// The part the user should play in the event
+    role: ParticipantRole,
+}
+
 /// Input for removing a user from an event
 #[derive(Debug, InputObject)]
 struct RemoveUserFromEventInput {
     /// The slug of the event to remove the user from
     event: String,
     /// The ID of the user to remove
-    user_id: i32,
+    user_id: ID,
 }
 
 #[derive(Debug, SimpleObject)]
 struct RemoveUserFromEventResult {
     /// The ID of the user that was removed from the event
-    removed_user_id: Option<i32>,
+    removed_user_id: Option<ID>,
     /// The event the user was removed from
     event: Option<String>,
     /// Errors that may have occurred while processing the action
     user_errors: Vec<UserError>,
 }
 
-impl From<(i32, String)> for RemoveUserFromEventResult {
-    fn from((user_id, event): (i32, String)) -> Self {
+impl From<(ID, String)> for RemoveUserFromEventResult {
+    fn from((user_id, event): (ID, String)) -> Self {
         Self {
             removed_user_id: Some(user_id),
             event: Some(event),
@@ -121,3 +525,13 @@ impl From<(i32, String)> for RemoveUserFromEventResult {
         }
     }
 }
+
+impl From<UserError> for RemoveUserFromEventResult {
+    fn from(user_error: UserError) -> Self {
+        Self {
+            removed_user_id: None,
+            event: None,
+            user_errors: vec![user_error],
+        }
+    }
+}
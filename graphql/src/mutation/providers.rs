@@ -1,6 +1,12 @@
-use super::{results, validators, UserError};
-use async_graphql::{Context, ErrorExtensions, InputObject, Object, Result, ResultExt};
+use super::{results, validators::Validator, UserError};
+use crate::{actor::Actor, alerts::Alerter, ProviderCache};
+use async_graphql::{
+    Context, ErrorExtensions, InputObject, Object, OneofObject, Result, ResultExt,
+};
+use authz::{Action, Resource};
 use database::{loaders::ProviderLoader, Json, PgPool, Provider, ProviderConfiguration};
+use secrecy::{ExposeSecret, SecretString};
+use std::fmt::{Debug, Formatter};
 use tracing::instrument;
 
 results! {
@@ -30,25 +36,29 @@ impl ProviderMutation {
         ctx: &Context<'_>,
         input: CreateProviderInput,
     ) -> Result<CreateProviderResult> {
-        let mut user_errors = Vec::new();
-
-        if input.slug.is_empty() {
-            user_errors.push(UserError::new(&["slug"], "cannot be empty"));
-        }
-        if !validators::identifier(&input.slug) {
-            user_errors.push(UserError::new(&["slug"], "must be a valid identifier"))
-        }
-        if input.name.is_empty() {
-            user_errors.push(UserError::new(&["name"], "cannot be empty"));
-        }
-
-        if !user_errors.is_empty() {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::Provider)?;
+
+        let mut validator = Validator::default();
+        validator
+            .field(&["slug"], &input.slug)
+            .not_empty()
+            .identifier();
+        validator.field(&["name"], &input.name).not_empty();
+
+        if let Some(user_errors) = validator.errors() {
             return Ok(user_errors.into());
         }
 
         let db = ctx.data_unchecked::<PgPool>();
-        match Provider::create(&input.slug, &input.name, input.config.0, db).await {
-            Ok(provider) => Ok(provider.into()),
+        match Provider::create(&input.slug, &input.name, input.config.into(), db).await {
+            Ok(provider) => {
+                // The provider may have been cached as missing
+                ctx.data_unchecked::<ProviderCache>()
+                    .invalidate(&provider.slug);
+                Ok(provider.into())
+            }
             Err(e) if e.is_unique_violation() => {
                 Ok(UserError::new(&["slug"], "already in use").into())
             }
@@ -63,15 +73,15 @@ impl ProviderMutation {
         ctx: &Context<'_>,
         input: UpdateProviderInput,
     ) -> Result<UpdateProviderResult> {
-        let mut user_errors = Vec::new();
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Update, Resource::Provider)?;
 
-        if let Some(name) = &input.name {
-            if name.is_empty() {
-                user_errors.push(UserError::new(&["name"], "cannot be empty"));
-            }
-        }
+        let mut validator = Validator::default();
+        validator
+            .optional(&["name"], input.name.as_ref())
+            .not_empty();
 
-        if !user_errors.is_empty() {
+        if let Some(user_errors) = validator.errors() {
             return Ok(user_errors.into());
         }
 
@@ -80,15 +90,25 @@ impl ProviderMutation {
             return Ok(UserError::new(&["slug"], "provider does not exist").into());
         };
 
+        let previous_secret = provider.config.client_secret().clone();
+
         let db = ctx.data_unchecked::<PgPool>();
         provider
             .update()
             .override_enabled(input.enabled)
             .override_name(input.name)
-            .override_config(input.config)
+            .override_config(input.config.map(|config| Json(config.into())))
             .save(db)
             .await
             .extend()?;
+        ctx.data_unchecked::<ProviderCache>()
+            .invalidate(&provider.slug);
+
+        if provider.config.client_secret().expose_secret() != previous_secret.expose_secret() {
+            if let Some(alerter) = ctx.data_unchecked::<Option<Alerter>>() {
+                alerter.on_provider_secret_changed(&provider.slug, actor.id());
+            }
+        }
 
         Ok(provider.into())
     }
@@ -100,8 +120,13 @@ impl ProviderMutation {
         ctx: &Context<'_>,
         slug: String,
     ) -> Result<DeleteProviderResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::Provider)?;
+
         let db = ctx.data_unchecked::<PgPool>();
         Provider::delete(&slug, db).await.extend()?;
+        ctx.data_unchecked::<ProviderCache>().invalidate(&slug);
 
         Ok(slug.into())
     }
@@ -115,8 +140,7 @@ struct CreateProviderInput {
     /// The public-facing display name
     name: String,
     /// The provider-specific configuration
-    // TODO: create specialized input-type for configuration
-    config: Json<ProviderConfiguration>,
+    config: ProviderConfigurationInput,
 }
 
 /// Input fields for updating a provider
@@ -129,5 +153,54 @@ struct UpdateProviderInput {
     /// The public-facing display name
     name: Option<String>,
     /// The provider-specific configuration
-    config: Option<Json<ProviderConfiguration>>,
+    config: Option<ProviderConfigurationInput>,
+}
+
+/// The provider-specific configuration, exactly one kind must be specified
+#[derive(Debug, OneofObject)]
+enum ProviderConfigurationInput {
+    /// Google OpenID Connect provider
+    Google(ClientCredentialsInput),
+    /// GitHub OAuth2 provider
+    #[graphql(name = "github")]
+    GitHub(ClientCredentialsInput),
+    /// Discord OAuth2 provider
+    Discord(ClientCredentialsInput),
+}
+
+impl From<ProviderConfigurationInput> for ProviderConfiguration {
+    fn from(input: ProviderConfigurationInput) -> Self {
+        match input {
+            ProviderConfigurationInput::Google(c) => ProviderConfiguration::Google {
+                client_id: c.client_id,
+                client_secret: SecretString::new(c.client_secret),
+            },
+            ProviderConfigurationInput::GitHub(c) => ProviderConfiguration::GitHub {
+                client_id: c.client_id,
+                client_secret: SecretString::new(c.client_secret),
+            },
+            ProviderConfigurationInput::Discord(c) => ProviderConfiguration::Discord {
+                client_id: c.client_id,
+                client_secret: SecretString::new(c.client_secret),
+            },
+        }
+    }
+}
+
+/// OAuth2 client credentials for a provider
+#[derive(InputObject)]
+struct ClientCredentialsInput {
+    /// The client ID
+    client_id: String,
+    /// The client secret
+    client_secret: String,
+}
+
+impl Debug for ClientCredentialsInput {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientCredentialsInput")
+            .field("client_id", &self.client_id)
+            .field("client_secret", &"<REDACTED>")
+            .finish()
+    }
 }
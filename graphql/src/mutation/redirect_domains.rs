@@ -0,0 +1,94 @@
+use super::{results, validators::Validator, UserError};
+use crate::{actor::Actor, RedirectDomains};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use authz::{Action, Resource};
+use database::{AllowedRedirectDomain, PgPool};
+use tracing::instrument;
+
+results! {
+    AllowRedirectDomainResult {
+        /// The allowed domain
+        domain: AllowedRedirectDomain,
+    }
+    DisallowRedirectDomainResult {
+        /// The pattern of the domain that is no longer allowed
+        removed_pattern: String,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct RedirectDomainMutation;
+
+#[Object]
+impl RedirectDomainMutation {
+    /// Allow the OAuth flow to return to a domain, in addition to those from the configuration.
+    /// Takes effect on all instances within 30 seconds.
+    #[instrument(name = "Mutation::allow_redirect_domain", skip(self, ctx))]
+    async fn allow_redirect_domain(
+        &self,
+        ctx: &Context<'_>,
+        input: AllowRedirectDomainInput,
+    ) -> Result<AllowRedirectDomainResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::AllowedRedirectDomain)?;
+
+        let pattern = input.pattern.trim().to_lowercase();
+        let description = input.description.as_deref().unwrap_or_default();
+        let mut validator = Validator::default();
+        validator
+            .field(&["pattern"], &pattern)
+            .not_empty()
+            .max_length(253)
+            .domain_pattern();
+        validator
+            .field(&["description"], description)
+            .max_length(500);
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let domain = match AllowedRedirectDomain::create(&pattern, description, db).await {
+            Ok(domain) => domain,
+            Err(e) if e.is_unique_violation() => {
+                return Ok(UserError::new(&["pattern"], "already allowed").into())
+            }
+            Err(e) => return Err(e.extend()),
+        };
+        ctx.data_unchecked::<RedirectDomains>().reload().await;
+
+        Ok(domain.into())
+    }
+
+    /// Stop allowing the OAuth flow to return to a domain that was added at runtime. Domains from
+    /// the configuration cannot be removed.
+    #[instrument(name = "Mutation::disallow_redirect_domain", skip(self, ctx))]
+    async fn disallow_redirect_domain(
+        &self,
+        ctx: &Context<'_>,
+        pattern: String,
+    ) -> Result<DisallowRedirectDomainResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::AllowedRedirectDomain)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !AllowedRedirectDomain::delete(&pattern, db).await.extend()? {
+            return Ok(UserError::new(&["pattern"], "domain is not allowed").into());
+        }
+        ctx.data_unchecked::<RedirectDomains>().reload().await;
+
+        Ok(pattern.into())
+    }
+}
+
+/// Input fields for allowing a redirect domain
+#[derive(Debug, InputObject)]
+struct AllowRedirectDomainInput {
+    /// The domain to allow, `*` and `?` can be used as wildcards
+    pattern: String,
+    /// Why the domain is allowed
+    description: Option<String>,
+}
@@ -0,0 +1,65 @@
+use super::{results, UserError};
+use crate::{actor::Actor, alerts::Alerter, ids};
+use async_graphql::{Context, Object, Result, ResultExt, ID};
+use authz::{Action, Resource};
+use tracing::instrument;
+
+results! {
+    RevokeSessionResult {
+        /// The ID of the revoked session
+        revoked_id: String,
+    }
+    RevokeAllSessionsResult {
+        /// How many sessions were revoked
+        revoked_count: i32,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct SessionMutation;
+
+#[Object]
+impl SessionMutation {
+    /// Revoke one of the current user's login sessions
+    #[instrument(name = "Mutation::revoke_session", skip(self, ctx))]
+    async fn revoke_session(&self, ctx: &Context<'_>, id: String) -> Result<RevokeSessionResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let manager = ctx.data_unchecked::<session::Manager>();
+        if !manager.revoke(actor.id(), &id).await.extend()? {
+            return Ok(UserError::new(&["id"], "session does not exist").into());
+        }
+
+        Ok(id.into())
+    }
+
+    /// Revoke all of a user's login sessions, logging them out everywhere. Defaults to the current
+    /// user.
+    #[instrument(name = "Mutation::revoke_all_sessions", skip(self, ctx))]
+    async fn revoke_all_sessions(
+        &self,
+        ctx: &Context<'_>,
+        user_id: Option<ID>,
+    ) -> Result<RevokeAllSessionsResult> {
+        let actor = Actor::load(ctx).await?;
+        let user_id = match user_id {
+            Some(id) => match ids::user(ctx, &id).await? {
+                Some(id) => id,
+                None => return Ok(UserError::new(&["user_id"], "user does not exist").into()),
+            },
+            None => actor.id(),
+        };
+        actor.require(Action::Update, Resource::User { id: user_id })?;
+
+        let manager = ctx.data_unchecked::<session::Manager>();
+        let count = manager.revoke_all(user_id).await.extend()?;
+
+        if count > 0 {
+            if let Some(alerter) = ctx.data_unchecked::<Option<Alerter>>() {
+                alerter.on_sessions_revoked(user_id, count, actor.id());
+            }
+        }
+
+        Ok((count as i32).into())
+    }
+}
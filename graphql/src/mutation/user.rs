@@ -1,9 +1,17 @@
-use super::{results, UserError};
-use crate::webhooks;
-use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use super::{results, validators::Validator, UserError};
+use crate::{
+    actor::Actor,
+    alerts::Alerter,
+    ids,
+    scalars::EmailAddress,
+    webhooks::{self, ParticipantChange},
+};
+use async_graphql::{Context, InputObject, MaybeUndefined, Object, Result, ResultExt, ID};
+use authz::{Action, Resource};
+use chrono::{DateTime, Utc};
 use database::{
-    loaders::{IdentitiesForUserLoader, UserLoader},
-    PgPool, User,
+    loaders::{EmailsForUserLoader, UserByPublicIdLoader},
+    Email, Job, PgPool, User,
 };
 use tracing::instrument;
 
@@ -14,7 +22,15 @@ results! {
     }
     DeleteUserResult {
         /// The ID of the deleted user
-        deleted_id: i32,
+        deleted_id: ID,
+    }
+    BanUserResult {
+        /// The banned user
+        user: User,
+    }
+    UnbanUserResult {
+        /// The user whose ban was lifted
+        user: User,
     }
 }
 
@@ -30,81 +46,216 @@ impl UserMutation {
         ctx: &Context<'_>,
         input: UpdateUserInput,
     ) -> Result<UpdateUserResult> {
-        let mut user_errors = Vec::new();
+        let actor = Actor::load(ctx).await?;
 
-        if let Some(given_name) = &input.given_name {
-            if given_name.is_empty() {
-                user_errors.push(UserError::new(&["given_name"], "cannot be empty"));
-            }
-        }
+        let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let Some(mut user) = loader.load_one(input.id.to_string()).await.extend()? else {
+            return Ok(UserError::new(&["id"], "user does not exist").into());
+        };
 
-        if let Some(family_name) = &input.family_name {
-            if family_name.is_empty() {
-                user_errors.push(UserError::new(&["family_name"], "cannot be empty"));
-            }
+        let resource = Resource::User { id: user.id };
+        actor.require(Action::Update, resource)?;
+        if input.is_admin.is_some() {
+            actor.require(Action::GrantAdmin, resource)?;
+        }
+        if !input.moderation_notes.is_undefined() {
+            actor.require(Action::Update, Resource::Moderation)?;
         }
 
-        if !user_errors.is_empty() {
+        let mut validator = Validator::default();
+        validator
+            .optional(&["given_name"], input.given_name.as_ref())
+            .not_empty();
+        validator
+            .optional(&["family_name"], input.family_name.as_ref())
+            .not_empty();
+
+        if let Some(user_errors) = validator.errors() {
             return Ok(user_errors.into());
         }
 
-        let loader = ctx.data_unchecked::<UserLoader>();
-        let Some(mut user) = loader.load_one(input.id).await.extend()? else {
-            return Ok(UserError::new(&["id"], "user does not exist").into());
-        };
-
         if let Some(primary_email) = &input.primary_email {
-            let loader = ctx.data_unchecked::<IdentitiesForUserLoader>();
-            let Some(identities) = loader.load_one(user.id).await.extend()? else {
-                return Ok(UserError::new(&["id"], "user registration incomplete").into());
-            };
+            let loader = ctx.data_unchecked::<EmailsForUserLoader>();
+            let emails = loader.load_one(user.id).await.extend()?.unwrap_or_default();
 
-            if !identities.iter().any(|i| &i.email == primary_email) {
+            if !emails
+                .iter()
+                .any(|e| e.verified && e.address == **primary_email)
+            {
                 return Ok(UserError::new(
                     &["primary_email"],
-                    "primary email must be linked to an identity",
+                    "primary email must be a verified address",
                 )
                 .into());
             }
         }
 
-        let db = ctx.data_unchecked::<PgPool>();
-        user.update()
-            .override_given_name(input.given_name)
-            .override_family_name(input.family_name)
-            .override_primary_email(input.primary_email)
-            .override_is_admin(input.is_admin)
-            .save(db)
-            .await
-            .extend()?;
+        let was_admin = user.is_admin;
 
+        let db = ctx.data_unchecked::<PgPool>();
         let webhooks = ctx.data_unchecked::<webhooks::Client>();
-        webhooks.on_participant_changed(user.id, &user.primary_email);
+        let actor_user = actor.user().clone();
+        let client = webhooks.clone();
+        let updated = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                if let Some(primary_email) = input.primary_email {
+                    if !Email::make_primary(user.id, &primary_email, &mut *txn).await? {
+                        return Ok(None);
+                    }
+                    user.primary_email = primary_email.into();
+                }
+
+                user.update()
+                    .override_given_name(input.given_name)
+                    .override_family_name(input.family_name)
+                    .override_is_admin(input.is_admin)
+                    .override_moderation_notes(input.moderation_notes.into())
+                    .save(&mut *txn)
+                    .await?;
+
+                let queued = client
+                    .on_participant_changed(
+                        ParticipantChange::Updated,
+                        &user,
+                        None,
+                        &actor_user,
+                        txn,
+                    )
+                    .await?;
+                Ok(Some((user, queued)))
+            })
+        })
+        .await
+        .extend()?;
+        let Some((user, queued)) = updated else {
+            return Ok(UserError::new(
+                &["primary_email"],
+                "primary email must be a verified address",
+            )
+            .into());
+        };
+        webhooks.committed(queued);
+
+        if user.is_admin && !was_admin {
+            if let Some(alerter) = ctx.data_unchecked::<Option<Alerter>>() {
+                alerter.on_admin_granted(&user, actor.id());
+            }
+        }
 
         Ok(user.into())
     }
 
-    /// Delete a user
+    /// Delete a user, logging them out everywhere
     #[instrument(name = "Mutation::delete_user", skip(self, ctx))]
-    async fn delete_user(&self, ctx: &Context<'_>, id: i32) -> Result<DeleteUserResult> {
+    async fn delete_user(&self, ctx: &Context<'_>, id: ID) -> Result<DeleteUserResult> {
+        let actor = Actor::load(ctx).await?;
+
+        let Some(user_id) = ids::user(ctx, &id).await? else {
+            return Ok(UserError::new(&["id"], "user does not exist").into());
+        };
+        actor.require(Action::Delete, Resource::User { id: user_id })?;
+
         let db = ctx.data_unchecked::<PgPool>();
-        User::delete(id, db).await.extend()?;
+        database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                User::delete(user_id, &mut *txn).await?;
+                Job::revoke_sessions(user_id, txn).await
+            })
+        })
+        .await
+        .extend()?;
 
         Ok(id.into())
     }
+
+    /// Ban a user, or suspend them until a point in time. They are logged out everywhere and
+    /// cannot log back in while the ban is in effect.
+    #[instrument(name = "Mutation::ban_user", skip(self, ctx))]
+    async fn ban_user(&self, ctx: &Context<'_>, input: BanUserInput) -> Result<BanUserResult> {
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Update, Resource::Moderation)?;
+
+        let Some(user_id) = ids::user(ctx, &input.id).await? else {
+            return Ok(UserError::new(&["id"], "user does not exist").into());
+        };
+        if user_id == actor.id() {
+            return Ok(UserError::new(&["id"], "cannot ban yourself").into());
+        }
+
+        let mut validator = Validator::default();
+        validator.field(&["reason"], &input.reason).not_empty();
+        validator
+            .optional(&["until"], input.until.as_ref())
+            .check(|until| *until > Utc::now(), "must be in the future");
+
+        if let Some(user_errors) = validator.errors() {
+            return Ok(user_errors.into());
+        }
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let banned = database::with_txn(db, move |txn| {
+            Box::pin(async move {
+                let Some(user) = User::ban(user_id, &input.reason, input.until, &mut *txn).await?
+                else {
+                    return Ok(None);
+                };
+
+                // The ban is also enforced when the user's sessions are next used
+                Job::revoke_sessions(user.id, txn).await?;
+                Ok(Some(user))
+            })
+        })
+        .await
+        .extend()?;
+
+        match banned {
+            Some(user) => Ok(user.into()),
+            None => Ok(UserError::new(&["id"], "user does not exist").into()),
+        }
+    }
+
+    /// Lift a user's ban or suspension
+    #[instrument(name = "Mutation::unban_user", skip(self, ctx))]
+    async fn unban_user(&self, ctx: &Context<'_>, id: ID) -> Result<UnbanUserResult> {
+        let actor = Actor::load(ctx).await?;
+        actor.require(Action::Update, Resource::Moderation)?;
+
+        let Some(user_id) = ids::user(ctx, &id).await? else {
+            return Ok(UserError::new(&["id"], "user does not exist").into());
+        };
+
+        let db = ctx.data_unchecked::<PgPool>();
+        match User::unban(user_id, db).await.extend()? {
+            Some(user) => Ok(user.into()),
+            None => Ok(UserError::new(&["id"], "user does not exist").into()),
+        }
+    }
 }
 
 /// Input fields for updating a user
 #[derive(Debug, InputObject)]
 struct UpdateUserInput {
     /// The ID of the user to update
-    pub id: i32,
+    pub id: ID,
     /// The given/first name
     pub given_name: Option<String>,
     /// The family/last name
     pub family_name: Option<String>,
-    /// The primary email as selected by the user
-    pub primary_email: Option<String>,
+    /// The primary email as selected by the user, which must be one of their verified addresses
+    pub primary_email: Option<EmailAddress>,
     /// Whether the user is an administrator
     pub is_admin: Option<bool>,
+    /// Notes kept by administrators about the user
+    pub moderation_notes: MaybeUndefined<String>,
+}
+
+/// Input fields for banning a user
+#[derive(Debug, InputObject)]
+struct BanUserInput {
+    /// The ID of the user to ban
+    pub id: ID,
+    /// Why the user is being banned, only visible to administrators
+    pub reason: String,
+    /// When the ban ends, making it a suspension. The ban is permanent if unset.
+    pub until: Option<DateTime<Utc>>,
 }
@@ -1,22 +1,246 @@
-use url::Url;
+use super::{FieldPath, UserError};
+use serde_json::{Map, Value};
 
 /// Check if the argument is a valid DNS segment
 pub fn dns_segment(raw: &str) -> bool {
     raw.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
 }
 
+/// Check if the argument is a valid domain name with at least two segments
+pub fn domain(raw: &str) -> bool {
+    let mut segments = raw.split('.');
+    segments.clone().count() >= 2 && segments.all(|s| !s.is_empty() && dns_segment(s))
+}
+
+/// Check if the argument is a valid domain name with at least two segments, where segments may
+/// contain `*` and `?` globs
+pub fn domain_pattern(raw: &str) -> bool {
+    let mut segments = raw.split('.');
+    segments.clone().count() >= 2
+        && segments.all(|s| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '*' | '?'))
+        })
+}
+
 /// Check if the argument is a valid identifier
 pub fn identifier(raw: &str) -> bool {
     raw.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
 }
 
-/// Check if the argument is a valid URL
-pub fn url(raw: &str) -> bool {
-    match Url::parse(raw) {
-        Ok(url) => {
-            let scheme = url.scheme();
-            (scheme == "http" || scheme == "https") && url.has_authority()
+/// Check if the argument is a valid feature flag key, i.e. `provider:github`
+pub fn flag_key(raw: &str) -> bool {
+    raw.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_' | ':'))
+}
+
+/// Check if the argument is a valid label key, i.e. `season`
+pub fn label_key(raw: &str) -> bool {
+    raw.chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '-' | '_'))
+}
+
+/// Check if the argument is a hex color in the form `#rrggbb`
+pub fn hex_color(raw: &str) -> bool {
+    raw.len() == 7 && raw.starts_with('#') && raw[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Accumulates [`UserError`]s while validating the fields of a mutation's input
+///
+/// ```ignore
+/// let mut validator = Validator::default();
+/// validator.field(&["slug"], &input.slug).not_empty().dns_segment();
+/// validator.optional(&["name"], input.name.as_ref()).not_empty();
+/// validator.each(&["tags"], input.tags.as_deref(), |validator, tag| {
+///     validator.field(FieldPath::new(), tag).not_empty();
+/// });
+///
+/// if let Some(user_errors) = validator.errors() {
+///     return Ok(user_errors.into());
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct Validator {
+    errors: Vec<UserError>,
+}
+
+impl Validator {
+    /// Validate a required field
+    pub fn field<'v, T: ?Sized>(
+        &'v mut self,
+        field: impl Into<FieldPath>,
+        value: &'v T,
+    ) -> FieldValidator<'v, T> {
+        self.optional(field, Some(value))
+    }
+
+    /// Validate an optional field, the checks are skipped if it is not present
+    pub fn optional<'v, T: ?Sized>(
+        &'v mut self,
+        field: impl Into<FieldPath>,
+        value: Option<&'v T>,
+    ) -> FieldValidator<'v, T> {
+        FieldValidator {
+            errors: &mut self.errors,
+            field: field.into(),
+            value,
+        }
+    }
+
+    /// Validate every item of an optional list field, nesting each item's errors under its index
+    pub fn each<T, F>(&mut self, field: impl Into<FieldPath>, items: Option<&[T]>, mut validate: F)
+    where
+        F: FnMut(&mut Validator, &T),
+    {
+        let Some(items) = items else {
+            return;
+        };
+
+        let field = field.into();
+        for (i, item) in items.iter().enumerate() {
+            let mut validator = Validator::default();
+            validate(&mut validator, item);
+
+            let prefix = field.clone().index(i);
+            self.errors.extend(
+                validator
+                    .errors
+                    .into_iter()
+                    .map(|error| error.prefixed(prefix.clone())),
+            );
+        }
+    }
+
+    /// Get the errors that occurred, if any
+    pub fn errors(self) -> Option<Vec<UserError>> {
+        if self.errors.is_empty() {
+            None
+        } else {
+            Some(self.errors)
+        }
+    }
+}
+
+/// Runs checks against a single field
+pub struct FieldValidator<'v, T: ?Sized> {
+    errors: &'v mut Vec<UserError>,
+    field: FieldPath,
+    value: Option<&'v T>,
+}
+
+impl<'v, T: ?Sized> FieldValidator<'v, T> {
+    /// Add an error with the message if the predicate does not hold
+    pub fn check<F>(self, predicate: F, message: &'static str) -> Self
+    where
+        F: FnOnce(&T) -> bool,
+    {
+        if let Some(value) = self.value {
+            if !predicate(value) {
+                self.errors
+                    .push(UserError::new(self.field.clone(), message));
+            }
         }
-        Err(_) => false,
+
+        self
+    }
+}
+
+impl<'v, T> FieldValidator<'v, T>
+where
+    T: AsRef<str> + ?Sized,
+{
+    /// Ensure the string is not empty
+    pub fn not_empty(self) -> Self {
+        self.check(|v| !v.as_ref().is_empty(), "cannot be empty")
+    }
+
+    /// Ensure the string is at most `max` bytes long
+    pub fn max_length(self, max: usize) -> Self {
+        if let Some(value) = self.value {
+            if value.as_ref().len() > max {
+                let message = format!("must be less than {max} characters");
+                self.errors
+                    .push(UserError::new(self.field.clone(), message));
+            }
+        }
+
+        self
+    }
+
+    /// Ensure the string is a valid DNS segment
+    pub fn dns_segment(self) -> Self {
+        self.check(|v| dns_segment(v.as_ref()), "must be a valid dns segment")
+    }
+
+    /// Ensure the string is a valid domain name
+    pub fn domain(self) -> Self {
+        self.check(|v| domain(v.as_ref()), "must be a valid domain")
+    }
+
+    /// Ensure the string is a valid identifier
+    pub fn identifier(self) -> Self {
+        self.check(|v| identifier(v.as_ref()), "must be a valid identifier")
+    }
+
+    /// Ensure the string is a valid domain name that may contain globs
+    pub fn domain_pattern(self) -> Self {
+        self.check(
+            |v| domain_pattern(v.as_ref()),
+            "must be a valid domain, optionally using * and ? as wildcards",
+        )
+    }
+
+    /// Ensure the string is a valid feature flag key
+    pub fn flag_key(self) -> Self {
+        self.check(
+            |v| flag_key(v.as_ref()),
+            "can only contain lowercase letters, digits, dashes, underscores, and colons",
+        )
+    }
+
+    /// Ensure the string is a valid label key
+    pub fn label_key(self) -> Self {
+        self.check(
+            |v| label_key(v.as_ref()),
+            "can only contain lowercase letters, digits, dashes, and underscores",
+        )
+    }
+
+    /// Ensure the string is a hex color
+    pub fn hex_color(self) -> Self {
+        self.check(
+            |v| hex_color(v.as_ref()),
+            "must be a hex color like #1a2b3c",
+        )
+    }
+}
+
+impl<'v> FieldValidator<'v, Map<String, Value>> {
+    /// Ensure the object has at most `max` keys
+    pub fn max_keys(self, max: usize) -> Self {
+        if let Some(value) = self.value {
+            if value.len() > max {
+                let message = format!("must have at most {max} keys");
+                self.errors
+                    .push(UserError::new(self.field.clone(), message));
+            }
+        }
+
+        self
+    }
+
+    /// Ensure the object is at most `max` bytes once serialized as JSON
+    pub fn max_serialized_size(self, max: usize) -> Self {
+        if let Some(value) = self.value {
+            let size = serde_json::to_vec(value).map_or(usize::MAX, |bytes| bytes.len());
+            if size > max {
+                let message = format!("must be at most {max} bytes when serialized");
+                self.errors
+                    .push(UserError::new(self.field.clone(), message));
+            }
+        }
+
+        self
     }
 }
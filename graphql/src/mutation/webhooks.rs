@@ -0,0 +1,151 @@
+use super::{results, UserError};
+use crate::{actor::Actor, scalars::Url, webhooks};
+use async_graphql::{Context, InputObject, Object, Result, ResultExt};
+use authz::{Action, Resource};
+use database::{PgPool, Webhook, WebhookDelivery, WebhookEvent};
+use rand::distributions::{Alphanumeric, DistString};
+use tracing::instrument;
+
+results! {
+    CreateWebhookResult {
+        /// The created webhook
+        webhook: Webhook,
+    }
+    UpdateWebhookResult {
+        /// The webhook
+        webhook: Webhook,
+    }
+    DeleteWebhookResult {
+        /// The ID of the deleted webhook
+        deleted_id: i32,
+    }
+    RedeliverWebhookResult {
+        /// The new delivery attempt
+        delivery: WebhookDelivery,
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct WebhookMutation;
+
+#[Object]
+impl WebhookMutation {
+    /// Add a new webhook. A secret for signing deliveries is generated automatically.
+    #[instrument(name = "Mutation::create_webhook", skip(self, ctx))]
+    async fn create_webhook(
+        &self,
+        ctx: &Context<'_>,
+        input: CreateWebhookInput,
+    ) -> Result<CreateWebhookResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Create, Resource::Webhook)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let webhook = Webhook::create(input.url.as_str(), &secret(), &input.events, db)
+            .await
+            .extend()?;
+
+        Ok(webhook.into())
+    }
+
+    /// Update the details of a webhook
+    #[instrument(name = "Mutation::update_webhook", skip(self, ctx))]
+    async fn update_webhook(
+        &self,
+        ctx: &Context<'_>,
+        input: UpdateWebhookInput,
+    ) -> Result<UpdateWebhookResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Update, Resource::Webhook)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(mut webhook) = Webhook::find(input.id, db).await.extend()? else {
+            return Ok(UserError::new(&["id"], "webhook does not exist").into());
+        };
+
+        webhook
+            .update()
+            .override_url(input.url.map(Into::into))
+            .override_events(input.events)
+            .override_enabled(input.enabled)
+            .override_secret(input.rotate_secret.then(secret))
+            .save(db)
+            .await
+            .extend()?;
+
+        Ok(webhook.into())
+    }
+
+    /// Delete a webhook
+    #[instrument(name = "Mutation::delete_webhook", skip(self, ctx))]
+    async fn delete_webhook(&self, ctx: &Context<'_>, id: i32) -> Result<DeleteWebhookResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Delete, Resource::Webhook)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        if !Webhook::delete(id, db).await.extend()? {
+            return Ok(UserError::new(&["id"], "webhook does not exist").into());
+        }
+
+        Ok(id.into())
+    }
+
+    /// Send the payload of a previous delivery to its webhook again, even if the webhook is
+    /// disabled
+    #[instrument(name = "Mutation::redeliver_webhook", skip(self, ctx))]
+    async fn redeliver_webhook(
+        &self,
+        ctx: &Context<'_>,
+        delivery_id: i32,
+    ) -> Result<RedeliverWebhookResult> {
+        Actor::load(ctx)
+            .await?
+            .require(Action::Update, Resource::Webhook)?;
+
+        let db = ctx.data_unchecked::<PgPool>();
+        let Some(delivery) = WebhookDelivery::find(delivery_id, db).await.extend()? else {
+            return Ok(UserError::new(&["delivery_id"], "delivery does not exist").into());
+        };
+        let Some(webhook) = Webhook::find(delivery.webhook_id, db).await.extend()? else {
+            return Ok(UserError::new(&["delivery_id"], "webhook does not exist").into());
+        };
+
+        let client = ctx.data_unchecked::<webhooks::Client>();
+        let delivery = client.redeliver(&webhook, &delivery).await.extend()?;
+
+        Ok(delivery.into())
+    }
+}
+
+/// Generate a new secret for signing deliveries
+fn secret() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 32)
+}
+
+/// Input fields for creating a webhook
+#[derive(Debug, InputObject)]
+struct CreateWebhookInput {
+    /// Where deliveries are sent
+    url: Url,
+    /// The events to subscribe to
+    events: Vec<WebhookEvent>,
+}
+
+/// Input fields for updating a webhook
+#[derive(Debug, InputObject)]
+struct UpdateWebhookInput {
+    /// The ID of the webhook to update
+    id: i32,
+    /// Where deliveries are sent
+    url: Option<Url>,
+    /// The events to subscribe to
+    events: Option<Vec<WebhookEvent>>,
+    /// Whether deliveries will be sent
+    enabled: Option<bool>,
+    /// Replace the secret used to sign deliveries
+    #[graphql(default)]
+    rotate_secret: bool,
+}
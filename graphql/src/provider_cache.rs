@@ -0,0 +1,73 @@
+use database::{PgPool, Provider};
+use std::{
+    collections::HashMap,
+    sync::{Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tracing::instrument;
+
+/// How long providers are cached for before being reloaded
+const TTL: Duration = Duration::from_secs(30);
+
+/// A provider, or its absence, as of when it was loaded
+struct Entry {
+    loaded_at: Instant,
+    provider: Option<Provider>,
+}
+
+/// Caches providers in memory, so the OAuth flow does not need to load them on every request
+///
+/// Providers are invalidated whenever they are changed, and expire after the TTL in case a change
+/// made by another instance is missed.
+#[derive(Clone)]
+pub struct ProviderCache {
+    db: PgPool,
+    entries: Arc<RwLock<HashMap<String, Entry>>>,
+}
+
+impl ProviderCache {
+    /// Create a new empty cache, loading providers from the database on first use
+    pub fn new(db: PgPool) -> Self {
+        Self {
+            db,
+            entries: Arc::default(),
+        }
+    }
+
+    /// Get a provider by its slug, whether or not it is enabled
+    #[instrument(name = "ProviderCache::find", skip(self))]
+    pub async fn find(&self, slug: &str) -> Result<Option<Provider>, database::Error> {
+        if let Some(entry) = self.entries.read().unwrap().get(slug) {
+            if entry.loaded_at.elapsed() < TTL {
+                return Ok(entry.provider.clone());
+            }
+        }
+
+        let provider = Provider::find(slug, &self.db).await?;
+        self.entries.write().unwrap().insert(
+            slug.to_owned(),
+            Entry {
+                loaded_at: Instant::now(),
+                provider: provider.clone(),
+            },
+        );
+
+        Ok(provider)
+    }
+
+    /// Get an enabled provider by its slug
+    pub async fn find_enabled(&self, slug: &str) -> Result<Option<Provider>, database::Error> {
+        let provider = self.find(slug).await?;
+        Ok(provider.filter(|provider| provider.enabled))
+    }
+
+    /// Drop the cached provider so changes take effect immediately
+    pub fn invalidate(&self, slug: &str) {
+        self.entries.write().unwrap().remove(slug);
+    }
+
+    /// Drop all the cached providers
+    pub fn invalidate_all(&self) {
+        self.entries.write().unwrap().clear();
+    }
+}
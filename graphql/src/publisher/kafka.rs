@@ -0,0 +1,42 @@
+use super::{Backend, Error};
+use async_graphql::async_trait;
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+use std::time::Duration;
+use url::Url;
+
+/// The port brokers listen on when the URL does not specify one
+const DEFAULT_PORT: u16 = 9092;
+
+/// Publishes events as Kafka records
+pub(super) struct Kafka(FutureProducer);
+
+impl Kafka {
+    /// Create a producer, discovering the rest of the cluster through the broker
+    pub fn connect(url: &Url) -> Result<Self, Error> {
+        let host = url.host_str().ok_or("kafka url must include a broker")?;
+        let broker = format!("{host}:{}", url.port().unwrap_or(DEFAULT_PORT));
+
+        let producer = ClientConfig::new()
+            .set("bootstrap.servers", broker)
+            .set("message.timeout.ms", "5000")
+            .create()?;
+
+        Ok(Self(producer))
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Kafka {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), Error> {
+        let record = FutureRecord::<(), _>::to(topic).payload(&payload);
+        self.0
+            .send(record, Duration::ZERO)
+            .await
+            .map_err(|(error, _)| error)?;
+
+        Ok(())
+    }
+}
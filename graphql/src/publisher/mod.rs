@@ -0,0 +1,77 @@
+use async_graphql::async_trait;
+use database::WebhookEvent;
+use serde_json::Value;
+use std::sync::Arc;
+use tracing::{error, instrument, Instrument, Span};
+use url::Url;
+
+#[cfg(feature = "kafka")]
+mod kafka;
+#[cfg(feature = "nats")]
+mod nats;
+#[cfg(feature = "sqs")]
+mod sqs;
+
+/// Any error that occurs while connecting or publishing
+pub type Error = Box<dyn std::error::Error + Send + Sync>;
+
+/// A message bus implementation
+#[async_trait::async_trait]
+trait Backend: Send + Sync {
+    /// Send the payload to the topic
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), Error>;
+}
+
+/// Publishes events to a message bus alongside webhooks, allowing consumers to read them from a
+/// stream instead of receiving HTTP callbacks
+///
+/// Each event is sent to the `identity.<event>` topic, with the same payload as its webhook.
+#[derive(Clone)]
+pub struct Publisher {
+    backend: Arc<dyn Backend>,
+}
+
+impl Publisher {
+    /// Connect to the message bus, choosing the implementation from the URL's scheme
+    ///
+    /// - `nats://<host>:<port>` connects to a NATS server
+    /// - `kafka://<broker>:<port>` connects to a Kafka cluster through the bootstrap broker
+    /// - `sqs://<queue host>/<account>/<queue>` sends to an SQS queue, using credentials from the
+    ///   environment
+    ///
+    /// Each implementation is only available when its feature is enabled.
+    pub async fn connect(url: &Url) -> Result<Self, Error> {
+        let backend: Arc<dyn Backend> = match url.scheme() {
+            #[cfg(feature = "kafka")]
+            "kafka" => Arc::new(kafka::Kafka::connect(url)?),
+            #[cfg(feature = "nats")]
+            "nats" => Arc::new(nats::Nats::connect(url).await?),
+            #[cfg(feature = "sqs")]
+            "sqs" => Arc::new(sqs::Sqs::connect(url).await?),
+            scheme => {
+                return Err(
+                    format!("unsupported message bus {scheme:?}, is its feature enabled?").into(),
+                )
+            }
+        };
+
+        Ok(Self { backend })
+    }
+
+    /// Publish the event in the background
+    #[instrument(name = "Publisher::publish", skip(self, payload))]
+    pub(crate) fn publish(&self, event: WebhookEvent, payload: &Value) {
+        let topic = format!("identity.{}", event.as_str());
+        let payload = serde_json::to_vec(payload).expect("payload must serialize");
+        let backend = self.backend.clone();
+
+        tokio::task::spawn(
+            async move {
+                if let Err(error) = backend.publish(&topic, payload).await {
+                    error!(%error, topic, "failed to publish event");
+                }
+            }
+            .instrument(Span::current()),
+        );
+    }
+}
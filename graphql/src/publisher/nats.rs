@@ -0,0 +1,22 @@
+use super::{Backend, Error};
+use async_graphql::async_trait;
+use url::Url;
+
+/// Publishes events as NATS messages
+pub(super) struct Nats(async_nats::Client);
+
+impl Nats {
+    /// Connect to the NATS server
+    pub async fn connect(url: &Url) -> Result<Self, Error> {
+        let client = async_nats::connect(url.as_str()).await?;
+        Ok(Self(client))
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Nats {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), Error> {
+        self.0.publish(topic.to_owned(), payload.into()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,49 @@
+use super::{Backend, Error};
+use async_graphql::async_trait;
+use aws_sdk_sqs::{types::MessageAttributeValue, Client};
+use url::Url;
+
+/// Publishes events as SQS messages, with the topic in the `topic` attribute
+pub(super) struct Sqs {
+    client: Client,
+    queue_url: String,
+}
+
+impl Sqs {
+    /// Create a client for the queue, loading credentials and the region from the environment
+    pub async fn connect(url: &Url) -> Result<Self, Error> {
+        let host = url
+            .host_str()
+            .ok_or("sqs url must include the queue's host")?;
+        let queue_url = match url.port() {
+            Some(port) => format!("https://{host}:{port}{}", url.path()),
+            None => format!("https://{host}{}", url.path()),
+        };
+
+        let config = aws_config::load_from_env().await;
+        Ok(Self {
+            client: Client::new(&config),
+            queue_url,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl Backend for Sqs {
+    async fn publish(&self, topic: &str, payload: Vec<u8>) -> Result<(), Error> {
+        let topic = MessageAttributeValue::builder()
+            .data_type("String")
+            .string_value(topic)
+            .build()?;
+
+        self.client
+            .send_message()
+            .queue_url(&self.queue_url)
+            .message_body(String::from_utf8(payload)?)
+            .message_attributes("topic", topic)
+            .send()
+            .await?;
+
+        Ok(())
+    }
+}
@@ -1,17 +1,31 @@
 use crate::{
     entities,
-    errors::{Forbidden, Unauthorized},
+    errors::{BadRequest, Forbidden, NotFound, Unauthorized},
+    ids,
+    scalars::EmailAddress,
 };
-use async_graphql::{Context, Error, Object, OneofObject, Result, ResultExt};
-use context::{checks, guard, Scope, User as UserContext};
+use async_graphql::{
+    connection::{self, Connection, Edge},
+    Context, InputObject, Object, OneofObject, Result, ResultExt, ID,
+};
+use authz::{Action, Actor, Resource};
+use context::{Scope, User as UserContext, UserRole};
 use database::{
     loaders::{
-        EventLoader, OrganizationLoader, ProviderLoader, UserByPrimaryEmailLoader, UserLoader,
+        EventLoader, OrganizationByPublicIdLoader, OrganizationBySlugLoader, OrganizationLoader,
+        ParticipantLoader, ProviderLoader, UserByPrimaryEmailLoader, UserByPublicIdLoader,
+        UserLoader,
     },
-    Event, Organization, Organizer, Participant, PgPool, Provider, User,
+    AllowedRedirectDomain, Event, EventExtensionRequest, FeatureFlag, Node, NodeId, OidcClient,
+    Organization, Organizer, Participant, Pool, Provider, User, Webhook, WebhookDelivery,
 };
 use tracing::instrument;
 
+/// How many deliveries are returned per page by default
+const DEFAULT_DELIVERIES_PAGE_SIZE: usize = 25;
+/// The most deliveries that can be returned per page
+const MAX_DELIVERIES_PAGE_SIZE: usize = 100;
+
 pub struct Query;
 
 #[Object]
@@ -22,7 +36,8 @@ impl Query {
         match ctx.data_unchecked::<UserContext>() {
             UserContext::Authenticated(user) => {
                 let loader = ctx.data_unchecked::<UserLoader>();
-                loader.load_one(user.id).await.extend().transpose().unwrap()
+                let user = loader.load_one(user.id).await.extend()?;
+                user.ok_or_else(|| NotFound("user").into())
             }
             UserContext::OAuth | UserContext::RegistrationNeeded(_) => Err(Forbidden.into()),
             UserContext::Unauthenticated => Err(Unauthorized.into()),
@@ -32,10 +47,12 @@ impl Query {
     /// Get all the authentication providers
     #[instrument(name = "Query::providers", skip_all)]
     async fn providers(&self, ctx: &Context<'_>) -> Result<Vec<Provider>> {
-        let db = ctx.data_unchecked::<PgPool>();
-        let providers = match checks::admin_only(ctx) {
-            Ok(()) => Provider::all(db).await,
-            Err(_) => Provider::all_enabled(db).await,
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let actor = Actor::from_graphql(ctx);
+        let providers = if authz::can(&actor, Action::ReadPrivate, &Resource::Provider) {
+            Provider::all(db).await
+        } else {
+            Provider::all_enabled(db).await
         }
         .extend()?;
 
@@ -44,7 +61,7 @@ impl Query {
 
     /// Get an authentication provider by its slug
     #[instrument(name = "Query::provider", skip(self, ctx))]
-    #[graphql(guard = "guard(checks::admin_only)")]
+    #[graphql(guard = "authz::guard(Action::ReadPrivate, Resource::Provider)")]
     async fn provider(&self, ctx: &Context<'_>, slug: String) -> Result<Option<Provider>> {
         let loader = ctx.data_unchecked::<ProviderLoader>();
         let provider = loader.load_one(slug).await.extend()?;
@@ -54,16 +71,16 @@ impl Query {
 
     /// Get a user by their ID
     #[instrument(name = "Query::user", skip(self, ctx))]
-    #[graphql(guard = "guard(checks::admin_only)")]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Directory)")]
     async fn user(&self, ctx: &Context<'_>, by: UserBy) -> Result<Option<User>> {
         let user = match by {
             UserBy::Id(id) => {
-                let loader = ctx.data_unchecked::<UserLoader>();
-                loader.load_one(id).await
+                let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+                loader.load_one(id.to_string()).await
             }
             UserBy::PrimaryEmail(email) => {
                 let loader = ctx.data_unchecked::<UserByPrimaryEmailLoader>();
-                loader.load_one(email).await
+                loader.load_one(email.into()).await
             }
         }
         .extend()?;
@@ -71,12 +88,21 @@ impl Query {
         Ok(user)
     }
 
-    /// Get all the registered organizations
+    /// Get all the registered organizations, optionally only those with a label
     #[instrument(name = "Query::organizations", skip_all)]
-    #[graphql(guard = "guard(checks::admin_only)")]
-    async fn organizations(&self, ctx: &Context<'_>) -> Result<Vec<Organization>> {
-        let db = ctx.data_unchecked::<PgPool>();
-        let organizations = Organization::all(db).await.extend()?;
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Directory)")]
+    async fn organizations(
+        &self,
+        ctx: &Context<'_>,
+        label: Option<LabelFilter>,
+    ) -> Result<Vec<Organization>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let organizations = match label {
+            Some(label) => Organization::all_with_label(&label.key, label.value.as_deref(), db)
+                .await
+                .extend()?,
+            None => Organization::all(db).await.extend()?,
+        };
 
         Ok(organizations)
     }
@@ -86,85 +112,270 @@ impl Query {
     async fn organization(
         &self,
         ctx: &Context<'_>,
-        id: Option<i32>,
+        id: Option<ID>,
     ) -> Result<Option<Organization>> {
+        let id = match id {
+            Some(id) => match ids::organization(ctx, &id).await? {
+                Some(id) => Some(id),
+                None => return Ok(None),
+            },
+            None => None,
+        };
+
         let scope = ctx.data_unchecked::<Scope>();
         let id = match (scope, id) {
             (Scope::Admin, Some(id)) => {
-                checks::is_admin(ctx)?;
+                let actor = Actor::from_graphql(ctx);
+                authz::require(&actor, Action::Read, &Resource::Directory)?;
                 id
             }
             (Scope::User, Some(id)) => {
-                let db = ctx.data_unchecked::<PgPool>();
-                let user = checks::is_authenticated(ctx)?;
-                if User::is_organizer(user.id, id, db).await?.is_some() {
-                    id
-                } else {
-                    return Err(Forbidden.into());
-                }
+                require_in_organization(ctx, id).await?;
+                id
             }
             (Scope::Event(e), Some(id)) if e.organization_id == id => id,
             (Scope::Event(e), None) => e.organization_id,
             (Scope::Event(_), Some(_)) => return Err(Forbidden.into()),
             (_, None) => {
-                return Err(Error::new(
+                return Err(BadRequest(
                     r#"argument "id" is required as the event could not be inferred"#,
-                ));
+                )
+                .into());
             }
         };
 
         let loader = ctx.data_unchecked::<OrganizationLoader>();
-        let organization = loader.load_one(id).await?;
+        let organization = loader.load_one(id).await.extend()?;
 
         Ok(organization)
     }
 
-    /// Get all the events being put on
+    /// Get an organization by its slug
+    #[instrument(name = "Query::organization_by_slug", skip(self, ctx))]
+    async fn organization_by_slug(
+        &self,
+        ctx: &Context<'_>,
+        slug: String,
+    ) -> Result<Option<Organization>> {
+        let loader = ctx.data_unchecked::<OrganizationBySlugLoader>();
+        let Some(organization) = loader.load_one(slug).await.extend()? else {
+            return Ok(None);
+        };
+
+        let scope = ctx.data_unchecked::<Scope>();
+        match scope {
+            Scope::Admin => {
+                let actor = Actor::from_graphql(ctx);
+                authz::require(&actor, Action::Read, &Resource::Directory)?;
+            }
+            Scope::User => require_in_organization(ctx, organization.id).await?,
+            Scope::Event(e) if e.organization_id == organization.id => {}
+            Scope::Event(_) => return Err(Forbidden.into()),
+        }
+
+        Ok(Some(organization))
+    }
+
+    /// Get all the events being put on, optionally only those with a label
     #[instrument(name = "Query::events", skip_all)]
-    #[graphql(guard = "guard(checks::is_admin)")]
-    async fn events(&self, ctx: &Context<'_>) -> Result<Vec<Event>> {
-        let db = ctx.data_unchecked::<PgPool>();
-        let events = Event::all(db).await?;
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Directory)")]
+    async fn events(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_archived: bool,
+        label: Option<LabelFilter>,
+    ) -> Result<Vec<Event>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let events = match label {
+            Some(label) => {
+                Event::all_with_label(&label.key, label.value.as_deref(), include_archived, db)
+                    .await
+                    .extend()?
+            }
+            None if include_archived => Event::all(db).await.extend()?,
+            None => Event::all_unarchived(db).await.extend()?,
+        };
 
         Ok(events)
     }
 
+    /// Get all the event extension requests waiting for review, oldest first
+    #[instrument(name = "Query::pending_event_extension_requests", skip_all)]
+    #[graphql(guard = "authz::guard(Action::Extend, Resource::Event)")]
+    async fn pending_event_extension_requests(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<EventExtensionRequest>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let requests = EventExtensionRequest::pending(db).await.extend()?;
+
+        Ok(requests)
+    }
+
+    /// Get all the domains the OAuth flow is allowed to return to that were added at runtime
+    #[instrument(name = "Query::allowed_redirect_domains", skip_all)]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::AllowedRedirectDomain)")]
+    async fn allowed_redirect_domains(
+        &self,
+        ctx: &Context<'_>,
+    ) -> Result<Vec<AllowedRedirectDomain>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let domains = AllowedRedirectDomain::all(db).await.extend()?;
+
+        Ok(domains)
+    }
+
+    /// Get all the feature flags
+    #[instrument(name = "Query::feature_flags", skip_all)]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::FeatureFlag)")]
+    async fn feature_flags(&self, ctx: &Context<'_>) -> Result<Vec<FeatureFlag>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let flags = FeatureFlag::all(db).await.extend()?;
+
+        Ok(flags)
+    }
+
+    /// Get all the clients registered with the OpenID Connect provider
+    #[instrument(name = "Query::oidc_clients", skip_all)]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::OidcClient)")]
+    async fn oidc_clients(&self, ctx: &Context<'_>) -> Result<Vec<OidcClient>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let clients = OidcClient::all(db).await.extend()?;
+
+        Ok(clients)
+    }
+
+    /// Get all the webhooks
+    #[instrument(name = "Query::webhooks", skip_all)]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Webhook)")]
+    async fn webhooks(&self, ctx: &Context<'_>) -> Result<Vec<Webhook>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let webhooks = Webhook::all(db).await.extend()?;
+
+        Ok(webhooks)
+    }
+
+    /// Get a webhook by its ID
+    #[instrument(name = "Query::webhook", skip(self, ctx))]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Webhook)")]
+    async fn webhook(&self, ctx: &Context<'_>, id: i32) -> Result<Option<Webhook>> {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let webhook = Webhook::find(id, db).await.extend()?;
+
+        Ok(webhook)
+    }
+
+    /// Get the delivery attempts for a webhook, newest first
+    #[instrument(name = "Query::webhook_deliveries", skip(self, ctx))]
+    #[graphql(guard = "authz::guard(Action::Read, Resource::Webhook)")]
+    async fn webhook_deliveries(
+        &self,
+        ctx: &Context<'_>,
+        webhook_id: i32,
+        after: Option<String>,
+        first: Option<i32>,
+    ) -> Result<Connection<i32, WebhookDelivery>> {
+        connection::query(
+            after,
+            None,
+            first,
+            None,
+            |after: Option<i32>, _: Option<i32>, first, _| async move {
+                let limit = first
+                    .unwrap_or(DEFAULT_DELIVERIES_PAGE_SIZE)
+                    .min(MAX_DELIVERIES_PAGE_SIZE);
+
+                // Fetch an extra delivery to determine if there is another page
+                let db = ctx.data_unchecked::<Pool>().reader();
+                let mut deliveries = WebhookDelivery::page(webhook_id, after, limit as i64 + 1, db)
+                    .await
+                    .extend()?;
+                let has_next_page = deliveries.len() > limit;
+                deliveries.truncate(limit);
+
+                let mut connection = Connection::new(after.is_some(), has_next_page);
+                connection.edges.extend(
+                    deliveries
+                        .into_iter()
+                        .map(|delivery| Edge::new(delivery.id, delivery)),
+                );
+
+                Ok::<_, async_graphql::Error>(connection)
+            },
+        )
+        .await
+    }
+
     /// Get an event by its slug
     #[instrument(name = "Query::event", skip(self, ctx))]
     async fn event(&self, ctx: &Context<'_>, slug: Option<String>) -> Result<Option<Event>> {
         let scope = ctx.data_unchecked::<Scope>();
         let slug = match (scope, slug) {
             (Scope::Admin, Some(slug)) => {
-                checks::is_admin(ctx)?;
+                let actor = Actor::from_graphql(ctx);
+                authz::require(&actor, Action::Read, &Resource::Directory)?;
                 slug
             }
             (Scope::User, Some(slug)) => {
-                let db = ctx.data_unchecked::<PgPool>();
-                let user = checks::is_authenticated(ctx)?;
-                if User::is_organizer_for_event(user.id, &slug, db).await?
-                    || User::is_participant(user.id, &slug, db).await?
-                {
-                    slug
-                } else {
-                    return Err(Forbidden.into());
-                }
+                require_in_event(ctx, &slug).await?;
+                slug
             }
             (Scope::Event(e), Some(slug)) if e.event == slug => slug,
             (Scope::Event(e), None) => e.event.to_owned(),
             (Scope::Event(_), Some(_)) => return Err(Forbidden.into()),
             (_, None) => {
-                return Err(Error::new(
+                return Err(BadRequest(
                     r#"argument "slug" is required as the event could not be inferred"#,
-                ));
+                )
+                .into());
             }
         };
 
         let loader = ctx.data_unchecked::<EventLoader>();
-        let event = loader.load_one(slug).await?;
+        let event = loader.load_one(slug).await.extend()?;
 
         Ok(event)
     }
 
+    /// Get any object by its global ID, as returned in its `globalId` field
+    #[instrument(name = "Query::node", skip(self, ctx))]
+    async fn node(&self, ctx: &Context<'_>, id: ID) -> Result<Option<Node>> {
+        let Some(id) = NodeId::decode(&id) else {
+            return Err(BadRequest("invalid global id").into());
+        };
+
+        let node = match id {
+            NodeId::Event(slug) => self.event(ctx, Some(slug)).await?.map(Node::Event),
+            NodeId::Organization(id) => self
+                .organization(ctx, Some(ID(id)))
+                .await?
+                .map(Node::Organization),
+            NodeId::Provider(slug) => {
+                let loader = ctx.data_unchecked::<ProviderLoader>();
+                let provider = loader.load_one(slug).await.extend()?;
+                if provider.as_ref().is_some_and(|provider| !provider.enabled) {
+                    let actor = Actor::from_graphql(ctx);
+                    authz::require(&actor, Action::ReadPrivate, &Resource::Provider)?;
+                }
+
+                provider.map(Node::Provider)
+            }
+            NodeId::User(id) => {
+                let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+                let Some(user) = loader.load_one(id).await.extend()? else {
+                    return Ok(None);
+                };
+
+                let actor = Actor::from_graphql(ctx);
+                authz::require(&actor, Action::Read, &Resource::User { id: user.id })?;
+
+                Some(Node::User(user))
+            }
+        };
+
+        Ok(node)
+    }
+
     #[graphql(entity)]
     #[instrument(name = "Query::entity::event", skip(self, ctx))]
     async fn event_entity_by_slug(
@@ -182,10 +393,22 @@ impl Query {
     async fn organization_entity_by_id(
         &self,
         ctx: &Context<'_>,
-        #[graphql(key)] id: i32,
+        #[graphql(key)] id: ID,
     ) -> Result<Option<Organization>> {
-        let loader = ctx.data_unchecked::<OrganizationLoader>();
-        let organization = loader.load_one(id).await.extend()?;
+        let loader = ctx.data_unchecked::<OrganizationByPublicIdLoader>();
+        let organization = loader.load_one(id.to_string()).await.extend()?;
+        Ok(organization)
+    }
+
+    #[graphql(entity)]
+    #[instrument(name = "Query::entity::organization_by_slug", skip(self, ctx))]
+    async fn organization_entity_by_slug(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(key)] slug: String,
+    ) -> Result<Option<Organization>> {
+        let loader = ctx.data_unchecked::<OrganizationBySlugLoader>();
+        let organization = loader.load_one(slug).await.extend()?;
         Ok(organization)
     }
 
@@ -194,10 +417,10 @@ impl Query {
     async fn user_entity_by_id(
         &self,
         ctx: &Context<'_>,
-        #[graphql(key)] id: i32,
+        #[graphql(key)] id: ID,
     ) -> Result<Option<User>> {
-        let loader = ctx.data_unchecked::<UserLoader>();
-        let user = loader.load_one(id).await.extend()?;
+        let loader = ctx.data_unchecked::<UserByPublicIdLoader>();
+        let user = loader.load_one(id.to_string()).await.extend()?;
         Ok(user)
     }
 
@@ -209,8 +432,12 @@ impl Query {
         #[graphql(key)] event: entities::Event,
         #[graphql(key)] user: entities::User,
     ) -> Result<Option<Participant>> {
-        let db = ctx.data_unchecked::<PgPool>();
-        let participant = Participant::find(user.id, &event.slug, db).await.extend()?;
+        let Some(user_id) = ids::user(ctx, &user.id).await? else {
+            return Ok(None);
+        };
+
+        let loader = ctx.data_unchecked::<ParticipantLoader>();
+        let participant = loader.load_one((event.slug, user_id)).await.extend()?;
         Ok(participant)
     }
 
@@ -222,19 +449,74 @@ impl Query {
         #[graphql(key)] organization: entities::Organization,
         #[graphql(key)] user: entities::User,
     ) -> Result<Option<Organizer>> {
-        let db = ctx.data_unchecked::<PgPool>();
-        let organizer = Organizer::find(user.id, organization.id, db)
+        let (Some(user_id), Some(organization_id)) = (
+            ids::user(ctx, &user.id).await?,
+            ids::organization(ctx, &organization.id).await?,
+        ) else {
+            return Ok(None);
+        };
+
+        let db = ctx.data_unchecked::<Pool>().reader();
+        let organizer = Organizer::find(user_id, organization_id, db)
             .await
             .extend()?;
         Ok(organizer)
     }
 }
 
+/// Require the user to be part of the organization to view it, when the request was made outside
+/// of an event
+async fn require_in_organization(ctx: &Context<'_>, organization_id: i32) -> Result<()> {
+    let mut actor = Actor::from_graphql(ctx);
+    if let Some(id) = actor.id {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        actor.role = User::is_organizer(id, organization_id, db)
+            .await
+            .extend()?
+            .map(UserRole::from);
+    }
+
+    authz::require(
+        &actor,
+        Action::Read,
+        &Resource::Organization { owner_id: None },
+    )?;
+    Ok(())
+}
+
+/// Require the user to organize or participate in the event to view it, when the request was made
+/// outside of an event
+async fn require_in_event(ctx: &Context<'_>, slug: &str) -> Result<()> {
+    let mut actor = Actor::from_graphql(ctx);
+    if let Some(id) = actor.id {
+        let db = ctx.data_unchecked::<Pool>().reader();
+        actor.role = if User::is_organizer_for_event(id, slug, db).await.extend()? {
+            Some(UserRole::Organizer)
+        } else if User::is_participant(id, slug, db).await.extend()? {
+            Some(UserRole::Participant)
+        } else {
+            None
+        };
+    }
+
+    authz::require(&actor, Action::Read, &Resource::Event)?;
+    Ok(())
+}
+
+/// Only include results with a label
+#[derive(Debug, InputObject)]
+struct LabelFilter {
+    /// The key the label must have
+    key: String,
+    /// The value the label must have, any value is allowed if unset
+    value: Option<String>,
+}
+
 /// How to look up a user
 #[derive(Debug, OneofObject)]
 enum UserBy {
     /// By ID
-    Id(i32),
+    Id(ID),
     /// By primary email
-    PrimaryEmail(String),
+    PrimaryEmail(EmailAddress),
 }
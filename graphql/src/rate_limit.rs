@@ -0,0 +1,196 @@
+use crate::errors::ErrorCode;
+use async_graphql::{
+    async_trait,
+    extensions::{Extension, ExtensionContext, ExtensionFactory, NextRequest, NextValidation},
+    ErrorExtensions, Pos, Response, ServerError, ValidationResult,
+};
+use chrono::{DateTime, Utc};
+use context::User as UserContext;
+use http::{header::RETRY_AFTER, HeaderName, HeaderValue};
+use redis::{aio::ConnectionManager, RedisResult};
+use sha2::{Digest, Sha256};
+use std::{
+    fmt::{Display, Formatter},
+    sync::{Arc, Mutex},
+};
+use tracing::{error, instrument, warn};
+
+/// The length of a rate limiting window in seconds
+const WINDOW: i64 = 60;
+
+/// The budget for the current window
+static RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+/// How much of the budget is left in the current window
+static RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+/// How many seconds until the current window resets
+static RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// The bearer token a request was made with, allowing automation that isn't acting as a user to
+/// be limited by its token
+#[derive(Clone)]
+pub struct ServiceToken(pub String);
+
+/// Who an operation's cost is counted against
+#[derive(Debug)]
+enum Consumer {
+    User(i32),
+    /// Part of the SHA-256 digest of a service token, so the token itself is never stored in the
+    /// cache
+    Token(String),
+}
+
+impl Consumer {
+    /// Determine who the request is made by, preferring the user so all of their tokens share a
+    /// budget
+    fn from_context(ctx: &ExtensionContext<'_>) -> Option<Consumer> {
+        if let Some(UserContext::Authenticated(user)) = ctx.data_opt::<UserContext>() {
+            return Some(Consumer::User(user.id));
+        }
+
+        let token = ctx.data_opt::<ServiceToken>()?;
+        let digest = Sha256::digest(token.0.as_bytes());
+        Some(Consumer::Token(hex::encode(&digest[..16])))
+    }
+}
+
+impl Display for Consumer {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::User(id) => write!(f, "user:{id}"),
+            Self::Token(digest) => write!(f, "token:{digest}"),
+        }
+    }
+}
+
+/// Limits how much work each user or service token can request within a minute
+///
+/// Every operation consumes its query complexity from the budget of the user or token for the
+/// current window. Once the budget is exhausted, operations are rejected until the next window
+/// starts. Anonymous requests without a token are not limited as they can only access public data.
+#[derive(Clone)]
+pub struct RateLimiter {
+    cache: ConnectionManager,
+    budget: u64,
+}
+
+impl RateLimiter {
+    /// Create a new rate limiter allowing the cost budget per minute
+    pub fn new(cache: ConnectionManager, budget: u64) -> Self {
+        Self { cache, budget }
+    }
+
+    /// Consume the cost from the consumer's budget
+    #[instrument(name = "RateLimiter::consume", skip(self))]
+    async fn consume(&self, consumer: &Consumer, cost: usize) -> RedisResult<Usage> {
+        let now = Utc::now().timestamp();
+        let key = format!("identity:rate-limit:{consumer}:{}", now / WINDOW);
+
+        let mut conn = self.cache.clone();
+        let (used,) = redis::pipe()
+            .atomic()
+            .incr(&key, cost)
+            .expire(&key, WINDOW)
+            .ignore()
+            .query_async::<_, (u64,)>(&mut conn)
+            .await?;
+
+        Ok(Usage {
+            limit: self.budget,
+            remaining: self.budget.saturating_sub(used),
+            reset: WINDOW - now % WINDOW,
+            exceeded: used > self.budget,
+        })
+    }
+}
+
+impl ExtensionFactory for RateLimiter {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(RateLimitExtension {
+            limiter: self.clone(),
+            usage: Mutex::default(),
+        })
+    }
+}
+
+/// How much of a user's budget has been used in the current window
+#[derive(Clone, Copy, Debug)]
+struct Usage {
+    /// The budget for the window
+    limit: u64,
+    /// How much of the budget is left in the window
+    remaining: u64,
+    /// How many seconds until the window resets
+    reset: i64,
+    /// Whether the operation went over the budget
+    exceeded: bool,
+}
+
+/// Applies the rate limit to a single request, remembering the user's usage so it can be
+/// reported in the response headers
+struct RateLimitExtension {
+    limiter: RateLimiter,
+    usage: Mutex<Option<Usage>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for RateLimitExtension {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        let mut response = next.run(ctx).await;
+
+        if let Some(usage) = *self.usage.lock().unwrap() {
+            let headers = &mut response.http_headers;
+            headers.insert(&RATE_LIMIT_LIMIT, HeaderValue::from(usage.limit));
+            headers.insert(&RATE_LIMIT_REMAINING, HeaderValue::from(usage.remaining));
+            headers.insert(&RATE_LIMIT_RESET, HeaderValue::from(usage.reset));
+            if usage.exceeded {
+                headers.insert(RETRY_AFTER, HeaderValue::from(usage.reset));
+            }
+        }
+
+        response
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        let result = next.run(ctx).await?;
+
+        let Some(consumer) = Consumer::from_context(ctx) else {
+            return Ok(result);
+        };
+
+        // Fail open so an unavailable cache does not take down the API
+        match self.limiter.consume(&consumer, result.complexity).await {
+            Ok(usage) => {
+                *self.usage.lock().unwrap() = Some(usage);
+                if usage.exceeded {
+                    warn!(%consumer, retry_after = usage.reset, "rate limit exceeded");
+                    Err(vec![throttled(&usage)])
+                } else {
+                    Ok(result)
+                }
+            }
+            Err(error) => {
+                error!(%error, "failed to check rate limit");
+                Ok(result)
+            }
+        }
+    }
+}
+
+/// Build the error returned when a budget is exceeded, including when it resets
+fn throttled(usage: &Usage) -> ServerError {
+    let reset_at = DateTime::from_timestamp(Utc::now().timestamp() + usage.reset, 0)
+        .expect("reset must be in range");
+
+    ErrorCode::Throttled
+        .error("rate limit exceeded, try again later")
+        .extend_with(|_, extensions| {
+            extensions.set("limit", usage.limit);
+            extensions.set("retryAfter", usage.reset);
+            extensions.set("resetAt", reset_at.to_rfc3339());
+        })
+        .into_server_error(Pos::default())
+}
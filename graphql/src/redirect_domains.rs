@@ -0,0 +1,50 @@
+use database::{AllowedRedirectDomain, PgPool};
+use state::AllowedRedirectDomains;
+use std::time::Duration;
+use tokio::time;
+use tracing::{error, instrument};
+
+/// How often to reload the domains, picking up changes made by other instances
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Keeps the allowed redirect domains in sync with those stored in the database
+#[derive(Clone)]
+pub struct RedirectDomains {
+    allowed: AllowedRedirectDomains,
+    db: PgPool,
+}
+
+impl RedirectDomains {
+    /// Start reloading the domains in the background
+    pub fn new(allowed: AllowedRedirectDomains, db: PgPool) -> Self {
+        let domains = Self { allowed, db };
+        tokio::task::spawn(domains.clone().refresh_periodically());
+
+        domains
+    }
+
+    /// Reload the domains so changes take effect immediately. If they cannot be loaded, the
+    /// previous domains continue to be used.
+    #[instrument(name = "RedirectDomains::reload", skip_all)]
+    pub async fn reload(&self) {
+        let domains = match AllowedRedirectDomain::all(&self.db).await {
+            Ok(domains) => domains,
+            Err(error) => {
+                error!(%error, "failed to load allowed redirect domains");
+                return;
+            }
+        };
+
+        let patterns = domains.iter().map(|domain| domain.pattern.as_str());
+        if let Err(error) = self.allowed.replace_dynamic(patterns) {
+            error!(%error, "invalid allowed redirect domain");
+        }
+    }
+
+    async fn refresh_periodically(self) {
+        loop {
+            self.reload().await;
+            time::sleep(REFRESH_INTERVAL).await;
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+use std::ops::Deref;
+
+/// An email address
+///
+/// Only checks that the address is well-formed, not that it can receive mail.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct EmailAddress(String);
+
+impl EmailAddress {
+    /// Check if the raw value looks like an email address
+    pub fn is_well_formed(raw: &str) -> bool {
+        let Some((local, domain)) = raw.rsplit_once('@') else {
+            return false;
+        };
+
+        !local.is_empty()
+            && !domain.is_empty()
+            && domain.contains('.')
+            && !domain.starts_with('.')
+            && !domain.ends_with('.')
+            && !raw.chars().any(|c| c.is_whitespace() || c.is_control())
+    }
+}
+
+/// An email address
+///
+/// Only checks that the address is well-formed, not that it can receive mail.
+#[Scalar]
+impl ScalarType for EmailAddress {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let Value::String(raw) = &value else {
+            return Err(InputValueError::expected_type(value));
+        };
+
+        if Self::is_well_formed(raw) {
+            Ok(Self(raw.to_owned()))
+        } else {
+            Err(InputValueError::custom("invalid email address"))
+        }
+    }
+
+    fn is_valid(value: &Value) -> bool {
+        matches!(value, Value::String(_))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.clone())
+    }
+}
+
+impl Deref for EmailAddress {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<EmailAddress> for String {
+    fn from(email: EmailAddress) -> Self {
+        email.0
+    }
+}
+
+/// An absolute HTTP(S) URL
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Url(url::Url);
+
+/// An absolute HTTP(S) URL
+#[Scalar]
+impl ScalarType for Url {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        let Value::String(raw) = &value else {
+            return Err(InputValueError::expected_type(value));
+        };
+
+        let url = url::Url::parse(raw).map_err(|_| InputValueError::custom("invalid URL"))?;
+        let scheme = url.scheme();
+        if (scheme == "http" || scheme == "https") && url.has_authority() {
+            Ok(Self(url))
+        } else {
+            Err(InputValueError::custom("URL must use HTTP or HTTPS"))
+        }
+    }
+
+    fn is_valid(value: &Value) -> bool {
+        matches!(value, Value::String(_))
+    }
+
+    fn to_value(&self) -> Value {
+        Value::String(self.0.to_string())
+    }
+}
+
+impl Deref for Url {
+    type Target = url::Url;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl From<Url> for String {
+    fn from(url: Url) -> Self {
+        url.0.into()
+    }
+}
@@ -0,0 +1,153 @@
+use database::Event;
+use redis::{aio::ConnectionManager, AsyncCommands, RedisResult};
+use tracing::{error, instrument};
+
+/// What a slug or domain resolved to
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Resolution {
+    /// The event exists and is active
+    Active {
+        /// The organization putting on the event
+        organization_id: i32,
+    },
+    /// The event has been archived
+    Archived,
+    /// No event exists
+    Missing,
+}
+
+impl Resolution {
+    /// Encode the resolution to be stored in the cache
+    fn encode(&self) -> String {
+        match self {
+            Resolution::Active { organization_id } => format!("active:{organization_id}"),
+            Resolution::Archived => String::from("archived"),
+            Resolution::Missing => String::from("missing"),
+        }
+    }
+
+    /// Decode a resolution stored in the cache
+    fn decode(raw: &str) -> Option<Self> {
+        match raw {
+            "archived" => Some(Resolution::Archived),
+            "missing" => Some(Resolution::Missing),
+            raw => {
+                let organization_id = raw.strip_prefix("active:")?.parse().ok()?;
+                Some(Resolution::Active { organization_id })
+            }
+        }
+    }
+}
+
+impl From<Option<&Event>> for Resolution {
+    fn from(event: Option<&Event>) -> Self {
+        match event {
+            Some(event) if event.is_archived() => Resolution::Archived,
+            Some(event) => Resolution::Active {
+                organization_id: event.organization_id,
+            },
+            None => Resolution::Missing,
+        }
+    }
+}
+
+/// Caches which event slugs and custom domains resolve to, so resolving the scope of a request
+/// does not need to hit the database
+///
+/// Events are invalidated whenever they are created, archived, restored, moved, or deleted.
+/// Custom domains are invalidated when they are added or removed through the API, and when
+/// notified that they changed otherwise, so a domain changed elsewhere while notifications are
+/// missed may resolve to its old event for up to the TTL.
+/// Cache errors are logged and treated as a miss.
+#[derive(Clone)]
+pub struct ScopeCache {
+    cache: ConnectionManager,
+    ttl: u64,
+}
+
+impl ScopeCache {
+    /// Create a new cache, keeping resolutions for the TTL in seconds
+    pub fn new(cache: ConnectionManager, ttl: u64) -> Self {
+        Self { cache, ttl }
+    }
+
+    /// Get the cached resolution for an event
+    #[instrument(name = "ScopeCache::event", skip(self))]
+    pub async fn event(&self, slug: &str) -> Option<Resolution> {
+        let raw = self.get(&event_key(slug)).await?;
+        Resolution::decode(&raw)
+    }
+
+    /// Cache the resolution for an event
+    #[instrument(name = "ScopeCache::store_event", skip(self))]
+    pub async fn store_event(&self, slug: &str, resolution: Resolution) {
+        self.set(&event_key(slug), resolution.encode()).await;
+    }
+
+    /// Get the cached slug of the event a custom domain belongs to. The inner value is `None` if
+    /// the domain is known to not belong to any event.
+    #[instrument(name = "ScopeCache::custom_domain", skip(self))]
+    pub async fn custom_domain(&self, domain: &str) -> Option<Option<String>> {
+        let raw = self.get(&custom_domain_key(domain)).await?;
+        Some((!raw.is_empty()).then_some(raw))
+    }
+
+    /// Cache the slug of the event a custom domain belongs to, if any
+    #[instrument(name = "ScopeCache::store_custom_domain", skip(self))]
+    pub async fn store_custom_domain(&self, domain: &str, slug: Option<&str>) {
+        self.set(
+            &custom_domain_key(domain),
+            slug.unwrap_or_default().to_owned(),
+        )
+        .await;
+    }
+
+    /// Remove the cached resolution for an event after it changes
+    #[instrument(name = "ScopeCache::invalidate", skip(self))]
+    pub async fn invalidate(&self, slug: &str) {
+        let mut conn = self.cache.clone();
+        let result: RedisResult<()> = conn.del(event_key(slug)).await;
+        if let Err(error) = result {
+            error!(%error, "failed to invalidate cached scope");
+        }
+    }
+
+    /// Remove the cached event for a custom domain after it changes
+    #[instrument(name = "ScopeCache::invalidate_custom_domain", skip(self))]
+    pub async fn invalidate_custom_domain(&self, domain: &str) {
+        let mut conn = self.cache.clone();
+        let result: RedisResult<()> = conn.del(custom_domain_key(domain)).await;
+        if let Err(error) = result {
+            error!(%error, "failed to invalidate cached custom domain");
+        }
+    }
+
+    async fn get(&self, key: &str) -> Option<String> {
+        let mut conn = self.cache.clone();
+        match conn.get::<_, Option<String>>(key).await {
+            Ok(raw) => raw,
+            Err(error) => {
+                error!(%error, "failed to load cached scope");
+                None
+            }
+        }
+    }
+
+    async fn set(&self, key: &str, value: String) {
+        let mut conn = self.cache.clone();
+        let result: RedisResult<()> = conn.set_ex(key, value, self.ttl).await;
+        if let Err(error) = result {
+            error!(%error, "failed to cache scope");
+        }
+    }
+}
+
+/// The key an event's resolution is stored under
+fn event_key(slug: &str) -> String {
+    format!("identity:scope:event:{slug}")
+}
+
+/// The key a custom domain's event is stored under
+fn custom_domain_key(domain: &str) -> String {
+    format!("identity:scope:domain:{domain}")
+}
@@ -0,0 +1,34 @@
+use async_graphql::{Context, Result, ResultExt};
+use database::{SessionLookup, UserSession};
+use session::{CurrentSessionId, Manager};
+use std::{future::Future, pin::Pin};
+
+/// Looks up users' login sessions from the session store
+pub(crate) struct SessionStore(pub Manager);
+
+impl SessionLookup for SessionStore {
+    fn for_user(
+        &self,
+        ctx: &Context<'_>,
+        user_id: i32,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<UserSession>>> + Send + '_>> {
+        let current = ctx.data_opt::<CurrentSessionId>().map(|id| id.0.clone());
+
+        Box::pin(async move {
+            let sessions = self.0.for_user(user_id).await.extend()?;
+            let sessions = sessions
+                .iter()
+                .map(|session| UserSession {
+                    id: session.id().to_owned(),
+                    created_at: session.created_at(),
+                    last_seen_at: session.last_seen_at(),
+                    ip: session.ip().map(ToOwned::to_owned),
+                    user_agent: session.user_agent().map(ToOwned::to_owned),
+                    current: current.as_deref() == Some(session.id()),
+                })
+                .collect();
+
+            Ok(sessions)
+        })
+    }
+}
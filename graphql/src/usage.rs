@@ -0,0 +1,283 @@
+use async_graphql::{
+    async_trait,
+    extensions::{
+        Extension, ExtensionContext, ExtensionFactory, NextExecute, NextParseQuery, NextResolve,
+        ResolveInfo,
+    },
+    parser::types::ExecutableDocument,
+    Response, ServerResult, Value, Variables,
+};
+use serde::Serialize;
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::{DefaultHasher, Hash, Hasher},
+    mem,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::{
+    sync::mpsc::{self, error::TrySendError},
+    time,
+};
+use tracing::{error, info, instrument, warn};
+use url::Url;
+
+/// How many operations can be waiting to be reported before new ones are dropped
+const QUEUE_SIZE: usize = 4096;
+/// How many operations to buffer before sending a report
+const BATCH_SIZE: usize = 256;
+/// How often to send a report, regardless of how many operations are buffered
+const FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Reports operation statistics to a GraphQL Hive-compatible usage endpoint
+///
+/// Operations are collected in the background and sent in batches, so reporting never delays a
+/// response. If the endpoint cannot keep up, operations are dropped rather than buffered
+/// indefinitely.
+#[derive(Clone)]
+pub struct UsageReporter {
+    sender: mpsc::Sender<Operation>,
+}
+
+impl UsageReporter {
+    /// Start reporting usage to the endpoint, authenticating with the token
+    pub fn new(endpoint: Url, token: &str) -> Self {
+        let client = reqwest::Client::builder()
+            .user_agent("the-hacker-app/identity")
+            .timeout(Duration::from_secs(10))
+            .build()
+            .expect("client must build");
+
+        let (sender, receiver) = mpsc::channel(QUEUE_SIZE);
+        tokio::task::spawn(report(
+            client,
+            endpoint,
+            format!("Bearer {token}"),
+            receiver,
+        ));
+
+        Self { sender }
+    }
+}
+
+impl ExtensionFactory for UsageReporter {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(UsageExtension {
+            sender: self.sender.clone(),
+            query: Mutex::default(),
+            fields: Mutex::default(),
+        })
+    }
+}
+
+/// Collects the statistics for a single request
+struct UsageExtension {
+    sender: mpsc::Sender<Operation>,
+    query: Mutex<Option<String>>,
+    fields: Mutex<BTreeSet<String>>,
+}
+
+#[async_trait::async_trait]
+impl Extension for UsageExtension {
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        *self.query.lock().unwrap() = Some(query.to_owned());
+        next.run(ctx, query, variables).await
+    }
+
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        let start = Instant::now();
+        let response = next.run(ctx, operation_name).await;
+        let duration = start.elapsed();
+
+        let Some(query) = self.query.lock().unwrap().take() else {
+            return response;
+        };
+
+        let operation = Operation {
+            query,
+            name: operation_name.map(ToOwned::to_owned),
+            fields: mem::take(&mut *self.fields.lock().unwrap()),
+            timestamp: SystemTime::now(),
+            duration,
+            errors: response.errors.len(),
+        };
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(operation) {
+            warn!("usage reporting queue is full, dropping operation");
+        }
+
+        response
+    }
+
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let coordinate = format!("{}.{}", info.parent_type, info.name);
+        self.fields.lock().unwrap().insert(coordinate);
+
+        next.run(ctx, info).await
+    }
+}
+
+/// The statistics for an executed operation
+struct Operation {
+    query: String,
+    name: Option<String>,
+    fields: BTreeSet<String>,
+    timestamp: SystemTime,
+    duration: Duration,
+    errors: usize,
+}
+
+impl Operation {
+    /// A key identifying the operation document
+    fn key(&self) -> String {
+        let mut hasher = DefaultHasher::new();
+        self.name.hash(&mut hasher);
+        self.query.hash(&mut hasher);
+        format!("{:016x}", hasher.finish())
+    }
+}
+
+/// Collect operations and periodically send them to the endpoint
+async fn report(
+    client: reqwest::Client,
+    endpoint: Url,
+    authorization: String,
+    mut receiver: mpsc::Receiver<Operation>,
+) {
+    let mut interval = time::interval(FLUSH_INTERVAL);
+    let mut buffer = Vec::with_capacity(BATCH_SIZE);
+
+    loop {
+        tokio::select! {
+            operation = receiver.recv() => match operation {
+                Some(operation) => {
+                    buffer.push(operation);
+                    if buffer.len() < BATCH_SIZE {
+                        continue;
+                    }
+                }
+                None => {
+                    flush(&client, &endpoint, &authorization, mem::take(&mut buffer)).await;
+                    info!("usage reporting stopped");
+                    return;
+                }
+            },
+            _ = interval.tick() => {},
+        }
+
+        if !buffer.is_empty() {
+            let operations = mem::replace(&mut buffer, Vec::with_capacity(BATCH_SIZE));
+            flush(&client, &endpoint, &authorization, operations).await;
+        }
+    }
+}
+
+/// Send a report containing the operations
+#[instrument(skip_all, fields(size = operations.len()))]
+async fn flush(
+    client: &reqwest::Client,
+    endpoint: &Url,
+    authorization: &str,
+    operations: Vec<Operation>,
+) {
+    if operations.is_empty() {
+        return;
+    }
+
+    let result = client
+        .post(endpoint.clone())
+        .header("authorization", authorization)
+        .header("x-usage-api-version", "2")
+        .json(&Report::from(operations))
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    if let Err(error) = result {
+        error!(%error, "failed to report usage");
+    }
+}
+
+/// A batch of operations in the format expected by the usage endpoint
+#[derive(Serialize)]
+struct Report {
+    size: usize,
+    map: HashMap<String, OperationDefinition>,
+    operations: Vec<OperationExecution>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OperationDefinition {
+    operation: String,
+    operation_name: Option<String>,
+    fields: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OperationExecution {
+    operation_map_key: String,
+    timestamp: u128,
+    execution: Execution,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Execution {
+    ok: bool,
+    duration: u128,
+    errors_total: usize,
+}
+
+impl From<Vec<Operation>> for Report {
+    fn from(operations: Vec<Operation>) -> Self {
+        let mut map = HashMap::new();
+        let mut executions = Vec::with_capacity(operations.len());
+
+        for operation in operations {
+            let key = operation.key();
+            let timestamp = operation
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+
+            executions.push(OperationExecution {
+                operation_map_key: key.clone(),
+                timestamp,
+                execution: Execution {
+                    ok: operation.errors == 0,
+                    duration: operation.duration.as_nanos(),
+                    errors_total: operation.errors,
+                },
+            });
+            map.entry(key).or_insert_with(|| OperationDefinition {
+                operation: operation.query,
+                operation_name: operation.name,
+                fields: operation.fields.into_iter().collect(),
+            });
+        }
+
+        Report {
+            size: executions.len(),
+            map,
+            operations: executions,
+        }
+    }
+}
@@ -1,18 +1,69 @@
-use reqwest::RequestBuilder;
+use crate::Publisher;
+use chrono::{DateTime, Utc};
+use database::{
+    DeliveryOutcome, EventExtensionRequest, Participant, PgConnection, PgPool, User, Webhook,
+    WebhookDelivery, WebhookEvent, WebhookOutboxEntry, RESPONSE_SNIPPET_LENGTH,
+};
+use hmac::{Hmac, Mac};
 use serde::Serialize;
-use std::{sync::Arc, time::Duration};
-use tracing::{error, instrument, span, Instrument, Level, Span};
-use url::Url;
+use serde_json::Value;
+use sha2::Sha256;
+use state::{RequestId, TraceContext, REQUEST_ID_HEADER};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+use tracing::{error, instrument, warn, Span};
 
-/// A webhook client for the portal service
+/// How long a delivery can be worked on before another worker may pick it up
+const LEASE_SECONDS: i32 = 60;
+/// How many times an event is attempted before it is dropped from the outbox
+const MAX_ATTEMPTS: i32 = 10;
+/// How long to wait before the first retry of a failed delivery
+const RETRY_BASE_SECONDS: i32 = 30;
+/// The longest to wait between retries of a failed delivery
+const MAX_RETRY_SECONDS: i32 = 60 * 60;
+
+/// How a participant changed
+#[derive(Clone, Copy, Debug)]
+pub enum ParticipantChange {
+    /// The user joined or was added to an event
+    Added,
+    /// The user was removed from an event
+    Removed,
+    /// The user's details or participation changed
+    Updated,
+}
+
+impl ParticipantChange {
+    /// Get the name of the change
+    fn as_str(&self) -> &'static str {
+        match self {
+            ParticipantChange::Added => "added",
+            ParticipantChange::Removed => "removed",
+            ParticipantChange::Updated => "updated",
+        }
+    }
+}
+
+/// Delivers events to the webhooks subscribed to them
+///
+/// Events are queued in the outbox by the transaction that caused them, along with a job to deliver
+/// them, so a slow receiver never delays a response. Each webhook receives its events one at a
+/// time in the order they were queued. Failed deliveries are retried with exponential backoff.
 #[derive(Clone)]
 pub struct Client {
     client: reqwest::Client,
-    url: Arc<Url>,
+    db: PgPool,
+    notify: Arc<Notify>,
+    publisher: Option<Publisher>,
 }
 
 impl Client {
-    pub fn new(url: Url) -> Self {
+    /// Create a client for delivering events, also publishing them to the message bus if one is
+    /// configured
+    pub fn new(db: PgPool, publisher: Option<Publisher>) -> Self {
         let client = reqwest::Client::builder()
             .user_agent("the-hacker-app/identity")
             .timeout(Duration::from_secs(3))
@@ -21,51 +72,358 @@ impl Client {
 
         Self {
             client,
-            url: Arc::new(url),
+            db,
+            notify: Arc::new(Notify::new()),
+            publisher,
         }
     }
 
-    /// Notify of a participant's information changing
-    #[instrument(name = "Client::on_participant_changed", skip(self))]
-    pub fn on_participant_changed(&self, id: i32, email: &str) {
-        let request = self
-            .client
-            .post(
-                self.url
-                    .join("/webhooks/participant")
-                    .expect("url is always valid"),
-            )
-            .json(&Participant {
-                id,
-                primary_email: email,
-            });
+    /// Queue a participant's information changing as part of the transaction that changed it. The
+    /// participation is omitted when the change applies to the user across all events.
+    #[instrument(
+        name = "Client::on_participant_changed",
+        skip_all,
+        fields(?change, %user.id, actor.id = actor.id)
+    )]
+    pub async fn on_participant_changed(
+        &self,
+        change: ParticipantChange,
+        user: &User,
+        participant: Option<&Participant>,
+        actor: &User,
+        db: &mut PgConnection,
+    ) -> Result<QueuedEvent, database::Error> {
+        let payload = ParticipantPayload {
+            id: &user.public_id,
+            primary_email: &user.primary_email,
+            given_name: &user.given_name,
+            family_name: &user.family_name,
+            change: change.as_str(),
+            actor_id: &actor.public_id,
+            participation: participant.map(|participant| Participation {
+                event: &participant.event,
+                role: participant.role.as_str(),
+                created_at: participant.created_at,
+                updated_at: participant.updated_at,
+            }),
+            occurred_at: Utc::now(),
+        };
+
+        enqueue(WebhookEvent::ParticipantChanged, &payload, db).await
+    }
+
+    /// Queue an event extension being requested or reviewed as part of the transaction that
+    /// changed it
+    #[instrument(name = "Client::on_event_extension_changed", skip_all, fields(%request.id))]
+    pub async fn on_event_extension_changed(
+        &self,
+        request: &EventExtensionRequest,
+        db: &mut PgConnection,
+    ) -> Result<QueuedEvent, database::Error> {
+        let requested_by = public_id(request.requested_by, &mut *db).await?;
+        let reviewed_by = public_id(request.reviewed_by, &mut *db).await?;
+
+        let payload = EventExtension {
+            id: request.id,
+            event: &request.event,
+            status: request.status.as_str(),
+            expires_on: request.expires_on,
+            requested_by: requested_by.as_deref(),
+            reviewed_by: reviewed_by.as_deref(),
+        };
+
+        enqueue(WebhookEvent::EventExtensionChanged, &payload, db).await
+    }
+
+    /// Queue a user finishing their registration as part of the transaction that created them,
+    /// including the event they registered through, if any
+    #[instrument(name = "Client::on_user_created", skip_all, fields(%user.id, event))]
+    pub async fn on_user_created(
+        &self,
+        user: &User,
+        event: Option<&str>,
+        db: &mut PgConnection,
+    ) -> Result<QueuedEvent, database::Error> {
+        let payload = UserCreated {
+            id: &user.public_id,
+            primary_email: &user.primary_email,
+            given_name: &user.given_name,
+            family_name: &user.family_name,
+            event,
+            created_at: user.created_at,
+        };
+
+        enqueue(WebhookEvent::UserCreated, &payload, db).await
+    }
+
+    /// Announce an event once the transaction that queued it has committed, publishing it to the
+    /// message bus and waking the job queue to deliver it
+    pub fn committed(&self, queued: QueuedEvent) {
+        if let Some(publisher) = &self.publisher {
+            publisher.publish(queued.event, &queued.payload);
+        }
 
-        self.dispatch("participant", request);
+        if queued.deliveries > 0 {
+            self.notify.notify_one();
+        }
+    }
+
+    /// Wait until an event committed by this instance is ready to be delivered
+    pub async fn queued(&self) {
+        self.notify.notified().await
     }
 
-    /// Dispatch an event in a background task
-    fn dispatch(&self, kind: &'static str, request: RequestBuilder) {
-        let span = span!(Level::INFO, "Client::dispatch", %kind);
-        span.follows_from(Span::current());
+    /// Deliver the webhook's queued events one at a time until none are left. If a delivery
+    /// fails, later events wait behind it, and when to try again is returned.
+    #[instrument(name = "Client::deliver_queued", skip(self))]
+    pub async fn deliver_queued(
+        &self,
+        webhook_id: i32,
+    ) -> Result<Option<DateTime<Utc>>, database::Error> {
+        let webhook = match Webhook::find(webhook_id, &self.db).await? {
+            Some(webhook) if webhook.enabled => webhook,
+            _ => return Ok(None),
+        };
 
-        tokio::task::spawn(
-            async move {
-                let result = request
-                    .send()
-                    .await
-                    .and_then(|response| response.error_for_status());
+        while let Some(entry) =
+            WebhookOutboxEntry::claim(webhook.id, LEASE_SECONDS, &self.db).await?
+        {
+            let result = deliver(
+                &self.client,
+                &self.db,
+                &webhook,
+                entry.event,
+                &entry.payload.0,
+                entry.request_id.as_deref(),
+                Some(TraceContext::from(entry.trace_context.0.clone())),
+            )
+            .await;
+            let delivered = match result {
+                Ok(delivery) => delivery.is_successful(),
+                Err(error) => {
+                    error!(%error, "failed to record delivery");
+                    false
+                }
+            };
 
-                if let Err(error) = result {
-                    error!(%error, "failed to send webhook")
+            let attempts = entry.attempts + 1;
+            if delivered || attempts >= MAX_ATTEMPTS {
+                if !delivered {
+                    warn!(entry.id = entry.id, attempts, "giving up on delivery");
                 }
+                entry.complete(&self.db).await?;
+                continue;
             }
-            .instrument(span),
-        );
+
+            // Later events for the webhook wait behind this one so they are delivered in order
+            let delay = retry_delay(entry.attempts);
+            warn!(
+                entry.id = entry.id,
+                attempts, delay, "retrying delivery later"
+            );
+            entry.retry(delay, &self.db).await?;
+
+            let delay = chrono::Duration::try_seconds(delay.into()).unwrap();
+            return Ok(Some(Utc::now() + delay));
+        }
+
+        Ok(None)
     }
+
+    /// Send a previous delivery's payload to its webhook again, as part of the current trace
+    #[instrument(name = "Client::redeliver", skip_all, fields(%delivery.id))]
+    pub async fn redeliver(
+        &self,
+        webhook: &Webhook,
+        delivery: &WebhookDelivery,
+    ) -> Result<WebhookDelivery, database::Error> {
+        let request_id = RequestId::current();
+        deliver(
+            &self.client,
+            &self.db,
+            webhook,
+            delivery.event,
+            &delivery.payload.0,
+            request_id.as_ref().map(RequestId::as_str),
+            None,
+        )
+        .await
+    }
+}
+
+/// An event queued for delivery as part of a transaction, which must be announced with
+/// [`Client::committed`] once the transaction commits
+#[must_use = "the event must be announced once its transaction commits"]
+#[derive(Debug)]
+pub struct QueuedEvent {
+    event: WebhookEvent,
+    payload: Value,
+    deliveries: u64,
+}
+
+/// Queue an event for every subscribed webhook, so it is only delivered if the transaction that
+/// caused it commits
+async fn enqueue<P: Serialize>(
+    event: WebhookEvent,
+    payload: &P,
+    db: &mut PgConnection,
+) -> Result<QueuedEvent, database::Error> {
+    let payload = serde_json::to_value(payload).expect("payload must serialize");
+
+    let request_id = RequestId::current();
+    let request_id = request_id.as_ref().map(RequestId::as_str);
+    let trace_context = TraceContext::current().into_inner();
+    let deliveries =
+        WebhookOutboxEntry::enqueue(event, &payload, request_id, &trace_context, db).await?;
+
+    Ok(QueuedEvent {
+        event,
+        payload,
+        deliveries,
+    })
+}
+
+/// Get the public ID of a user to include in a payload, if they still exist
+async fn public_id(
+    user_id: Option<i32>,
+    db: &mut PgConnection,
+) -> Result<Option<String>, database::Error> {
+    let Some(user_id) = user_id else {
+        return Ok(None);
+    };
+
+    let user = User::find(user_id, db).await?;
+    Ok(user.map(|user| user.public_id))
+}
+
+/// How many seconds to wait before retrying a delivery, doubling with each failed attempt
+fn retry_delay(attempts: i32) -> i32 {
+    let delay = RETRY_BASE_SECONDS.saturating_mul(1 << attempts.clamp(0, 16));
+    delay.min(MAX_RETRY_SECONDS)
+}
+
+/// Send the payload to the webhook, signed with its secret, and record the attempt. The ID of the
+/// request that caused the event is forwarded so the receiver can correlate it. Queued deliveries
+/// continue the trace of the request that caused them, while others are part of the current trace.
+#[instrument(skip(client, db, payload, trace_context), fields(webhook.id = webhook.id))]
+async fn deliver(
+    client: &reqwest::Client,
+    db: &PgPool,
+    webhook: &Webhook,
+    event: WebhookEvent,
+    payload: &Value,
+    request_id: Option<&str>,
+    trace_context: Option<TraceContext>,
+) -> Result<WebhookDelivery, database::Error> {
+    if let Some(trace_context) = trace_context {
+        trace_context.continue_in(&Span::current());
+    }
+
+    let body = serde_json::to_vec(payload).expect("payload must serialize");
+    let signature = sign(&webhook.secret, &body);
+
+    let mut request = client
+        .post(&webhook.url)
+        .header("content-type", "application/json")
+        .header("x-webhook-event", event.as_str())
+        .header("x-webhook-signature", signature)
+        .body(body);
+    if let Some(request_id) = request_id {
+        request = request.header(REQUEST_ID_HEADER, request_id);
+    }
+    for (name, value) in TraceContext::current().headers() {
+        request = request.header(name, value);
+    }
+
+    let start = Instant::now();
+    let result = request.send().await;
+
+    let mut outcome = DeliveryOutcome::default();
+    match result {
+        Ok(response) => {
+            let status = response.status();
+            if !status.is_success() {
+                warn!(%status, "webhook rejected delivery");
+            }
+
+            outcome.status_code = Some(status.as_u16());
+            outcome.response = read_snippet(response).await;
+        }
+        Err(error) => {
+            error!(%error, "failed to send webhook");
+            outcome.error = Some(error.to_string());
+        }
+    }
+    outcome.latency_ms = start.elapsed().as_millis();
+
+    WebhookDelivery::record(webhook.id, event, payload, outcome, db).await
+}
+
+/// Read the start of a response body, without buffering more than is kept for the delivery
+async fn read_snippet(mut response: reqwest::Response) -> Option<String> {
+    let mut body = Vec::new();
+    while body.len() < RESPONSE_SNIPPET_LENGTH {
+        match response.chunk().await {
+            Ok(Some(chunk)) => {
+                let remaining = RESPONSE_SNIPPET_LENGTH - body.len();
+                body.extend_from_slice(&chunk[..chunk.len().min(remaining)]);
+            }
+            Ok(None) => break,
+            Err(error) => {
+                warn!(%error, "failed to read webhook response");
+                return None;
+            }
+        }
+    }
+
+    Some(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Compute the signature of the body, allowing receivers to verify its authenticity
+fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(body);
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
 }
 
 #[derive(Serialize)]
-struct Participant<'p> {
+struct EventExtension<'e> {
     id: i32,
+    event: &'e str,
+    status: &'static str,
+    expires_on: DateTime<Utc>,
+    requested_by: Option<&'e str>,
+    reviewed_by: Option<&'e str>,
+}
+
+#[derive(Serialize)]
+struct ParticipantPayload<'p> {
+    id: &'p str,
     primary_email: &'p str,
+    given_name: &'p str,
+    family_name: &'p str,
+    change: &'static str,
+    actor_id: &'p str,
+    participation: Option<Participation<'p>>,
+    occurred_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct Participation<'p> {
+    event: &'p str,
+    role: &'static str,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+struct UserCreated<'u> {
+    id: &'u str,
+    primary_email: &'u str,
+    given_name: &'u str,
+    family_name: &'u str,
+    event: Option<&'u str>,
+    created_at: DateTime<Utc>,
 }
@@ -0,0 +1,5 @@
+ALTER TABLE events
+    DROP CONSTRAINT events_registration_window_check,
+    DROP COLUMN registration_opens_at,
+    DROP COLUMN registration_closes_at,
+    DROP COLUMN capacity;
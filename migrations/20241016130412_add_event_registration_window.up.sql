@@ -0,0 +1,5 @@
+ALTER TABLE events
+    ADD COLUMN registration_opens_at timestamp with time zone,
+    ADD COLUMN registration_closes_at timestamp with time zone,
+    ADD COLUMN capacity int CHECK (capacity >= 0),
+    ADD CONSTRAINT events_registration_window_check CHECK (registration_opens_at < registration_closes_at);
@@ -0,0 +1,9 @@
+ALTER TABLE organizations ADD COLUMN slug text;
+
+UPDATE organizations SET slug = trim(BOTH '-' FROM regexp_replace(lower(name), '[^a-z0-9]+', '-', 'g'));
+UPDATE organizations SET slug = concat_ws('-', nullif(slug, ''), id)
+WHERE slug = '' OR slug IN (SELECT slug FROM organizations GROUP BY slug HAVING count(*) > 1);
+
+ALTER TABLE organizations
+    ALTER COLUMN slug SET NOT NULL,
+    ADD CONSTRAINT organizations_slug_key UNIQUE (slug);
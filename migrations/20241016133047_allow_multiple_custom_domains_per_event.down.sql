@@ -0,0 +1,11 @@
+DELETE FROM custom_domains WHERE NOT is_primary;
+
+DROP INDEX custom_domains_primary_idx;
+DROP INDEX custom_domains_event_idx;
+
+CREATE UNIQUE INDEX ON custom_domains (name);
+
+ALTER TABLE custom_domains
+    DROP CONSTRAINT custom_domains_pkey,
+    ADD PRIMARY KEY (event),
+    DROP COLUMN is_primary;
@@ -0,0 +1,12 @@
+ALTER TABLE custom_domains
+    DROP CONSTRAINT custom_domains_pkey,
+    ADD PRIMARY KEY (name),
+    ADD COLUMN is_primary boolean not null default false;
+
+DROP INDEX custom_domains_name_idx;
+
+-- Every event previously had at most one custom domain
+UPDATE custom_domains SET is_primary = true;
+
+CREATE INDEX ON custom_domains (event);
+CREATE UNIQUE INDEX custom_domains_primary_idx ON custom_domains (event) WHERE is_primary;
@@ -0,0 +1,10 @@
+CREATE TABLE audit_log (
+    id bigint primary key generated always as identity,
+    actor_id int references users (id) ON DELETE SET NULL,
+    action text not null,
+    target text not null,
+    details jsonb not null default '{}',
+    created_at timestamp with time zone not null default now()
+);
+
+CREATE INDEX ON audit_log (target);
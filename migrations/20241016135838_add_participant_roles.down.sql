@@ -0,0 +1,3 @@
+ALTER TABLE participants DROP COLUMN role;
+
+DROP TYPE participant_role;
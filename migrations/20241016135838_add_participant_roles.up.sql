@@ -0,0 +1,3 @@
+CREATE TYPE participant_role AS ENUM ('hacker', 'mentor', 'judge', 'volunteer', 'sponsor');
+
+ALTER TABLE participants ADD COLUMN role participant_role NOT NULL DEFAULT 'hacker';
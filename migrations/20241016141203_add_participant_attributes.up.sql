@@ -0,0 +1 @@
+ALTER TABLE participants ADD COLUMN attributes jsonb NOT NULL DEFAULT '{}';
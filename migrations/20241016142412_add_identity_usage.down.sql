@@ -0,0 +1,3 @@
+ALTER TABLE identities
+    DROP COLUMN last_login_at,
+    DROP COLUMN login_count;
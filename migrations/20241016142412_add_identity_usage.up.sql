@@ -0,0 +1,3 @@
+ALTER TABLE identities
+    ADD COLUMN last_login_at timestamptz,
+    ADD COLUMN login_count integer NOT NULL DEFAULT 0;
@@ -0,0 +1,2 @@
+DROP TABLE event_extension_requests;
+DROP TYPE extension_request_status;
@@ -0,0 +1,19 @@
+CREATE TYPE extension_request_status AS ENUM ('pending', 'approved', 'denied');
+
+CREATE TABLE event_extension_requests (
+    id serial primary key,
+    event text not null references events (slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    requested_by int references users (id) ON DELETE SET NULL,
+    expires_on timestamp with time zone not null,
+    reason text not null,
+    status extension_request_status not null default 'pending',
+    reviewed_by int references users (id) ON DELETE SET NULL,
+    review_note text,
+    reviewed_at timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+CREATE INDEX ON event_extension_requests (event);
+
+-- Only one request can be waiting for review per event
+CREATE UNIQUE INDEX ON event_extension_requests (event) WHERE status = 'pending';
@@ -0,0 +1,9 @@
+CREATE TABLE organization_email_domains (
+    organization_id int not null references organizations (id) ON DELETE CASCADE,
+    domain text not null,
+    role organizer_role not null default 'organizer',
+    created_at timestamp with time zone not null default now(),
+    primary key (organization_id, domain)
+);
+
+CREATE INDEX ON organization_email_domains (domain);
@@ -0,0 +1 @@
+ALTER TABLE events ADD COLUMN allowed_email_domains text[] not null default '{}';
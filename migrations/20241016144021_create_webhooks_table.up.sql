@@ -0,0 +1,15 @@
+CREATE TYPE webhook_event AS ENUM ('participant_changed', 'event_extension_changed');
+
+CREATE TABLE webhooks (
+    id serial primary key,
+    url text not null,
+    secret text not null,
+    events webhook_event[] not null default '{}',
+    enabled bool not null default true,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+CREATE TRIGGER set_webhooks_updated_at_timestamp
+    BEFORE UPDATE ON webhooks
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
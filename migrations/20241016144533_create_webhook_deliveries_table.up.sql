@@ -0,0 +1,13 @@
+CREATE TABLE webhook_deliveries (
+    id serial primary key,
+    webhook_id int not null references webhooks (id) ON DELETE CASCADE,
+    event webhook_event not null,
+    payload jsonb not null,
+    status_code int,
+    latency_ms int not null,
+    response text,
+    error text,
+    created_at timestamp with time zone not null default now()
+);
+
+CREATE INDEX ON webhook_deliveries (webhook_id, id DESC);
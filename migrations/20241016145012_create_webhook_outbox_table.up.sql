@@ -0,0 +1,10 @@
+CREATE TABLE webhook_outbox (
+    id serial primary key,
+    webhook_id int not null references webhooks (id) ON DELETE CASCADE,
+    event webhook_event not null,
+    payload jsonb not null,
+    locked_until timestamp with time zone,
+    created_at timestamp with time zone not null default now()
+);
+
+CREATE INDEX ON webhook_outbox (webhook_id, id);
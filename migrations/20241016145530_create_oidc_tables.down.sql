@@ -0,0 +1,2 @@
+DROP TABLE oidc_authorization_codes;
+DROP TABLE oidc_clients;
@@ -0,0 +1,24 @@
+CREATE TABLE oidc_clients (
+    id text primary key,
+    name text not null,
+    secret_hash text not null,
+    redirect_uris text[] not null default '{}',
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+CREATE TRIGGER set_oidc_clients_updated_at_timestamp
+    BEFORE UPDATE ON oidc_clients
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
+
+CREATE TABLE oidc_authorization_codes (
+    code_hash text primary key,
+    client_id text not null references oidc_clients (id) ON DELETE CASCADE,
+    user_id int not null references users (id) ON DELETE CASCADE,
+    redirect_uri text not null,
+    scope text not null,
+    nonce text,
+    code_challenge text,
+    expires_at timestamp with time zone not null,
+    created_at timestamp with time zone not null default now()
+);
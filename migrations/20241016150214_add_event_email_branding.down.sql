@@ -0,0 +1,4 @@
+ALTER TABLE events
+    DROP COLUMN email_logo_url,
+    DROP COLUMN email_accent_color,
+    DROP COLUMN email_reply_to;
@@ -0,0 +1,4 @@
+ALTER TABLE events
+    ADD COLUMN email_logo_url text,
+    ADD COLUMN email_accent_color text,
+    ADD COLUMN email_reply_to text;
@@ -0,0 +1,20 @@
+ALTER TABLE events DROP COLUMN email_welcome_message;
+
+-- Postgres can't remove a value from an enum, so the type must be rebuilt without it
+DELETE FROM webhook_outbox WHERE event = 'user_created';
+DELETE FROM webhook_deliveries WHERE event = 'user_created';
+UPDATE webhooks SET events = array_remove(events, 'user_created');
+
+ALTER TYPE webhook_event RENAME TO webhook_event_old;
+CREATE TYPE webhook_event AS ENUM ('participant_changed', 'event_extension_changed');
+
+ALTER TABLE webhooks
+    ALTER COLUMN events DROP DEFAULT,
+    ALTER COLUMN events TYPE webhook_event[] USING events::text[]::webhook_event[],
+    ALTER COLUMN events SET DEFAULT '{}';
+ALTER TABLE webhook_deliveries
+    ALTER COLUMN event TYPE webhook_event USING event::text::webhook_event;
+ALTER TABLE webhook_outbox
+    ALTER COLUMN event TYPE webhook_event USING event::text::webhook_event;
+
+DROP TYPE webhook_event_old;
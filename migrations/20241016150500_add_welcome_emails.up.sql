@@ -0,0 +1,3 @@
+ALTER TYPE webhook_event ADD VALUE 'user_created';
+
+ALTER TABLE events ADD COLUMN email_welcome_message text;
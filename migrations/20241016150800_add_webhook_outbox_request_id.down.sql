@@ -0,0 +1 @@
+ALTER TABLE webhook_outbox DROP COLUMN request_id;
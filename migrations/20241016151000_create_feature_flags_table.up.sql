@@ -0,0 +1,12 @@
+CREATE TABLE feature_flags (
+    key text primary key,
+    description text not null default '',
+    enabled boolean not null default false,
+    enabled_events text[] not null default '{}',
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now()
+);
+
+CREATE TRIGGER set_feature_flags_updated_at_timestamp
+    BEFORE UPDATE ON feature_flags
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
@@ -0,0 +1,5 @@
+CREATE TABLE allowed_redirect_domains (
+    pattern text primary key,
+    description text not null default '',
+    created_at timestamp with time zone not null default now()
+);
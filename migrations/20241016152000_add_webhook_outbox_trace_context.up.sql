@@ -0,0 +1 @@
+ALTER TABLE webhook_outbox ADD COLUMN trace_context jsonb NOT NULL DEFAULT '{}';
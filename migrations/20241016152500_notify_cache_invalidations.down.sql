@@ -0,0 +1,5 @@
+DROP TRIGGER notify_feature_flags_cache_invalidation ON feature_flags;
+DROP TRIGGER notify_custom_domains_cache_invalidation ON custom_domains;
+DROP TRIGGER notify_allowed_redirect_domains_cache_invalidation ON allowed_redirect_domains;
+
+DROP FUNCTION notify_cache_invalidation();
@@ -0,0 +1,31 @@
+CREATE FUNCTION notify_cache_invalidation()
+RETURNS TRIGGER AS $$
+    BEGIN
+        -- The key column is passed as an argument so the function can be shared between tables
+        IF TG_OP IN ('UPDATE', 'DELETE') THEN
+            PERFORM pg_notify(
+                'cache_invalidation',
+                json_build_object('table', TG_TABLE_NAME, 'key', to_jsonb(old) ->> TG_ARGV[0])::text
+            );
+        END IF;
+        IF TG_OP IN ('INSERT', 'UPDATE') THEN
+            PERFORM pg_notify(
+                'cache_invalidation',
+                json_build_object('table', TG_TABLE_NAME, 'key', to_jsonb(new) ->> TG_ARGV[0])::text
+            );
+        END IF;
+        RETURN NULL;
+    END;
+$$ LANGUAGE 'plpgsql';
+
+CREATE TRIGGER notify_allowed_redirect_domains_cache_invalidation
+    AFTER INSERT OR UPDATE OR DELETE ON allowed_redirect_domains
+    FOR EACH ROW EXECUTE PROCEDURE notify_cache_invalidation('pattern');
+
+CREATE TRIGGER notify_custom_domains_cache_invalidation
+    AFTER INSERT OR UPDATE OR DELETE ON custom_domains
+    FOR EACH ROW EXECUTE PROCEDURE notify_cache_invalidation('name');
+
+CREATE TRIGGER notify_feature_flags_cache_invalidation
+    AFTER INSERT OR UPDATE OR DELETE ON feature_flags
+    FOR EACH ROW EXECUTE PROCEDURE notify_cache_invalidation('key');
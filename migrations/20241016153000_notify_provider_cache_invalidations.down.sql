@@ -0,0 +1 @@
+DROP TRIGGER notify_providers_cache_invalidation ON providers;
@@ -0,0 +1,3 @@
+CREATE TRIGGER notify_providers_cache_invalidation
+    AFTER INSERT OR UPDATE OR DELETE ON providers
+    FOR EACH ROW EXECUTE PROCEDURE notify_cache_invalidation('slug');
@@ -0,0 +1,26 @@
+CREATE TABLE emails (
+    address text primary key,
+    user_id int not null references users (id) ON DELETE CASCADE,
+    is_primary boolean not null default false,
+    verified boolean not null default false,
+    verification_code_hash text,
+    verification_expires_at timestamp with time zone,
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now(),
+    constraint emails_primary_verified_check check (not is_primary or verified)
+);
+
+CREATE INDEX ON emails (user_id);
+CREATE UNIQUE INDEX emails_one_primary_per_user ON emails (user_id) WHERE is_primary;
+
+CREATE TRIGGER set_emails_updated_at_timestamp
+    BEFORE UPDATE ON emails
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
+
+-- Every existing primary email was provided by an identity, as are the identities' own emails
+INSERT INTO emails (address, user_id, is_primary, verified)
+SELECT primary_email, id, true, true FROM users;
+
+INSERT INTO emails (address, user_id, verified)
+SELECT email, user_id, true FROM identities
+ON CONFLICT (address) DO NOTHING;
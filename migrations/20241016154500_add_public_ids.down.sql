@@ -0,0 +1,3 @@
+ALTER TABLE organizations DROP COLUMN public_id;
+
+ALTER TABLE users DROP COLUMN public_id;
@@ -0,0 +1,9 @@
+-- Opaque IDs that are safe to expose, as the integer IDs leak how many rows exist and in what
+-- order they were created. Existing rows are given a random ID as the column is added.
+ALTER TABLE users
+    ADD COLUMN public_id text not null unique
+        default 'usr_' || replace(gen_random_uuid()::text, '-', '');
+
+ALTER TABLE organizations
+    ADD COLUMN public_id text not null unique
+        default 'org_' || replace(gen_random_uuid()::text, '-', '');
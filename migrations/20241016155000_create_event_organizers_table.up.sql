@@ -0,0 +1,14 @@
+CREATE TABLE event_organizers (
+    event text not null references events (slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    user_id int not null references users (id) ON DELETE CASCADE,
+    role organizer_role not null default 'organizer',
+    created_at timestamp with time zone not null default now(),
+    updated_at timestamp with time zone not null default now(),
+    primary key (event, user_id)
+);
+
+CREATE INDEX ON event_organizers (user_id);
+
+CREATE TRIGGER set_event_organizers_updated_at_timestamp
+    BEFORE UPDATE ON event_organizers
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
@@ -0,0 +1,18 @@
+DROP TRIGGER set_organizations_updated_at_timestamp ON organizations;
+CREATE TRIGGER set_organizations_updated_at_timestamp
+    BEFORE UPDATE ON organizations
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
+
+DROP TRIGGER set_events_updated_at_timestamp ON events;
+CREATE TRIGGER set_events_updated_at_timestamp
+    BEFORE UPDATE ON events
+    FOR EACH ROW EXECUTE PROCEDURE set_updated_at_timestamp();
+
+DROP TRIGGER count_organizers ON organizers;
+DROP FUNCTION count_organizers;
+
+DROP TRIGGER count_participants ON participants;
+DROP FUNCTION count_participants;
+
+ALTER TABLE organizations DROP COLUMN organizer_count;
+ALTER TABLE events DROP COLUMN participant_count;
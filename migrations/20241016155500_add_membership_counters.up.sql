@@ -0,0 +1,61 @@
+ALTER TABLE events ADD COLUMN participant_count bigint NOT NULL DEFAULT 0;
+ALTER TABLE organizations ADD COLUMN organizer_count bigint NOT NULL DEFAULT 0;
+
+UPDATE events SET participant_count = counts.count
+FROM (SELECT event, count(*) FROM participants GROUP BY event) counts
+WHERE events.slug = counts.event;
+
+UPDATE organizations SET organizer_count = counts.count
+FROM (SELECT organization_id, count(*) FROM organizers GROUP BY organization_id) counts
+WHERE organizations.id = counts.organization_id;
+
+CREATE FUNCTION count_participants()
+RETURNS TRIGGER AS $$
+    BEGIN
+        IF TG_OP IN ('UPDATE', 'DELETE') THEN
+            UPDATE events SET participant_count = participant_count - 1 WHERE slug = old.event;
+        END IF;
+        IF TG_OP IN ('INSERT', 'UPDATE') THEN
+            UPDATE events SET participant_count = participant_count + 1 WHERE slug = new.event;
+        END IF;
+        RETURN NULL;
+    END;
+$$ LANGUAGE 'plpgsql';
+
+CREATE TRIGGER count_participants
+    AFTER INSERT OR DELETE OR UPDATE OF event ON participants
+    FOR EACH ROW EXECUTE PROCEDURE count_participants();
+
+CREATE FUNCTION count_organizers()
+RETURNS TRIGGER AS $$
+    BEGIN
+        IF TG_OP IN ('UPDATE', 'DELETE') THEN
+            UPDATE organizations SET organizer_count = organizer_count - 1
+            WHERE id = old.organization_id;
+        END IF;
+        IF TG_OP IN ('INSERT', 'UPDATE') THEN
+            UPDATE organizations SET organizer_count = organizer_count + 1
+            WHERE id = new.organization_id;
+        END IF;
+        RETURN NULL;
+    END;
+$$ LANGUAGE 'plpgsql';
+
+CREATE TRIGGER count_organizers
+    AFTER INSERT OR DELETE OR UPDATE OF organization_id ON organizers
+    FOR EACH ROW EXECUTE PROCEDURE count_organizers();
+
+-- Changes to the counters are not changes to the event or organization itself
+DROP TRIGGER set_events_updated_at_timestamp ON events;
+CREATE TRIGGER set_events_updated_at_timestamp
+    BEFORE UPDATE ON events
+    FOR EACH ROW
+    WHEN (old.participant_count = new.participant_count)
+    EXECUTE PROCEDURE set_updated_at_timestamp();
+
+DROP TRIGGER set_organizations_updated_at_timestamp ON organizations;
+CREATE TRIGGER set_organizations_updated_at_timestamp
+    BEFORE UPDATE ON organizations
+    FOR EACH ROW
+    WHEN (old.organizer_count = new.organizer_count)
+    EXECUTE PROCEDURE set_updated_at_timestamp();
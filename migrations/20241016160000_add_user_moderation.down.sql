@@ -0,0 +1,5 @@
+ALTER TABLE users
+    DROP COLUMN banned_at,
+    DROP COLUMN banned_until,
+    DROP COLUMN ban_reason,
+    DROP COLUMN moderation_notes;
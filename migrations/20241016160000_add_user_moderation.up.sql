@@ -0,0 +1,5 @@
+ALTER TABLE users
+    ADD COLUMN banned_at timestamp with time zone,
+    ADD COLUMN banned_until timestamp with time zone,
+    ADD COLUMN ban_reason text,
+    ADD COLUMN moderation_notes text;
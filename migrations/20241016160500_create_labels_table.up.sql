@@ -0,0 +1,14 @@
+CREATE TABLE labels (
+    event text references events (slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    organization_id int references organizations (id) ON DELETE CASCADE,
+    key text not null,
+    value text,
+    created_at timestamp with time zone not null default now(),
+    -- A label is attached to exactly one event or organization
+    check ((event IS NULL) != (organization_id IS NULL))
+);
+
+CREATE UNIQUE INDEX labels_event_key ON labels (event, key) WHERE event IS NOT NULL;
+CREATE UNIQUE INDEX labels_organization_key ON labels (organization_id, key)
+    WHERE organization_id IS NOT NULL;
+CREATE INDEX ON labels (key, value);
@@ -0,0 +1,8 @@
+DROP TRIGGER record_event_history ON events;
+DROP FUNCTION record_event_history;
+
+DROP TRIGGER record_user_history ON users;
+DROP FUNCTION record_user_history;
+
+DROP TABLE event_history;
+DROP TABLE user_history;
@@ -0,0 +1,48 @@
+CREATE TABLE user_history (
+    user_id int not null references users (id) ON DELETE CASCADE,
+    data jsonb not null,
+    valid_from timestamp with time zone not null,
+    valid_to timestamp with time zone not null
+);
+
+CREATE INDEX ON user_history (user_id, valid_to);
+
+CREATE TABLE event_history (
+    event text not null references events (slug) ON DELETE CASCADE ON UPDATE CASCADE,
+    data jsonb not null,
+    valid_from timestamp with time zone not null,
+    valid_to timestamp with time zone not null
+);
+
+CREATE INDEX ON event_history (event, valid_to);
+
+-- The previous version of a row was valid from when it was last updated until now. Deletions
+-- are not recorded as the history is removed along with the row.
+CREATE FUNCTION record_user_history()
+RETURNS TRIGGER AS $$
+    BEGIN
+        INSERT INTO user_history (user_id, data, valid_from, valid_to)
+        VALUES (old.id, to_jsonb(old), old.updated_at, now());
+        RETURN NULL;
+    END;
+$$ LANGUAGE 'plpgsql';
+
+CREATE TRIGGER record_user_history
+    AFTER UPDATE ON users
+    FOR EACH ROW EXECUTE PROCEDURE record_user_history();
+
+CREATE FUNCTION record_event_history()
+RETURNS TRIGGER AS $$
+    BEGIN
+        INSERT INTO event_history (event, data, valid_from, valid_to)
+        VALUES (new.slug, to_jsonb(old), old.updated_at, now());
+        RETURN NULL;
+    END;
+$$ LANGUAGE 'plpgsql';
+
+-- Changes to the participant counter are not changes to the event itself
+CREATE TRIGGER record_event_history
+    AFTER UPDATE ON events
+    FOR EACH ROW
+    WHEN (old.participant_count = new.participant_count)
+    EXECUTE PROCEDURE record_event_history();
@@ -0,0 +1,7 @@
+ALTER TABLE custom_domains
+    DROP COLUMN certificate_status,
+    DROP COLUMN certificate_error,
+    DROP COLUMN certificate_expires_at,
+    DROP COLUMN certificate_checked_at;
+
+DROP TYPE certificate_status;
@@ -0,0 +1,8 @@
+CREATE TYPE certificate_status AS ENUM ('pending', 'issuing', 'issued', 'failed', 'expired');
+
+-- Reported by the edge proxy as it provisions certificates through ACME
+ALTER TABLE custom_domains
+    ADD COLUMN certificate_status certificate_status not null default 'pending',
+    ADD COLUMN certificate_error text,
+    ADD COLUMN certificate_expires_at timestamp with time zone,
+    ADD COLUMN certificate_checked_at timestamp with time zone;
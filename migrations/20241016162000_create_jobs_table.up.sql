@@ -0,0 +1,17 @@
+CREATE TYPE job_kind AS ENUM ('purge_authorization_codes', 'purge_unverified_emails', 'expire_certificates');
+
+CREATE TABLE jobs (
+    id bigserial primary key,
+    kind job_kind not null,
+    -- Jobs with a key are deduplicated, so at most one with the key can be queued
+    key text,
+    payload jsonb not null default '{}',
+    run_at timestamp with time zone not null default now(),
+    attempts int not null default 0,
+    locked_until timestamp with time zone,
+    last_error text,
+    created_at timestamp with time zone not null default now()
+);
+
+CREATE UNIQUE INDEX ON jobs (key) WHERE key IS NOT NULL;
+CREATE INDEX ON jobs (run_at);
@@ -0,0 +1 @@
+ALTER TABLE organization_email_domains DROP CONSTRAINT organization_email_domains_role_check;
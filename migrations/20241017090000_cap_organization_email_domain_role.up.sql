@@ -0,0 +1,4 @@
+-- Users joining through an email domain never get more than the organizer role
+UPDATE organization_email_domains SET role = 'organizer' WHERE role <> 'organizer';
+ALTER TABLE organization_email_domains
+    ADD CONSTRAINT organization_email_domains_role_check CHECK (role = 'organizer');
@@ -0,0 +1 @@
+ALTER TABLE webhook_outbox ADD COLUMN attempts int NOT NULL DEFAULT 0;
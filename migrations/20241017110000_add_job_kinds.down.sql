@@ -0,0 +1,12 @@
+-- Postgres can't remove a value from an enum, so the type must be rebuilt without them
+DELETE FROM jobs
+WHERE kind IN ('deliver_webhook', 'schedule_webhook_deliveries', 'revoke_sessions');
+
+ALTER TYPE job_kind RENAME TO job_kind_old;
+CREATE TYPE job_kind AS ENUM (
+    'purge_authorization_codes', 'purge_unverified_emails', 'expire_certificates'
+);
+
+ALTER TABLE jobs ALTER COLUMN kind TYPE job_kind USING kind::text::job_kind;
+
+DROP TYPE job_kind_old;
@@ -0,0 +1,3 @@
+ALTER TYPE job_kind ADD VALUE 'deliver_webhook';
+ALTER TYPE job_kind ADD VALUE 'schedule_webhook_deliveries';
+ALTER TYPE job_kind ADD VALUE 'revoke_sessions';
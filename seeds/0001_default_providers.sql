@@ -0,0 +1,7 @@
+-- The common providers, disabled until real credentials are configured
+INSERT INTO providers (slug, name, config)
+VALUES
+    ('google', 'Google', '{"kind": "google", "client_id": "change-me", "client_secret": "change-me"}'),
+    ('github', 'GitHub', '{"kind": "github", "client_id": "change-me", "client_secret": "change-me"}'),
+    ('discord', 'Discord', '{"kind": "discord", "client_id": "change-me", "client_secret": "change-me"}')
+ON CONFLICT (slug) DO NOTHING;
@@ -0,0 +1,19 @@
+-- An organization to test against, directed by an administrator
+WITH owner AS (
+    INSERT INTO users (given_name, family_name, primary_email, is_admin)
+    VALUES ('Test', 'Admin', 'admin@example.com', true)
+    ON CONFLICT (primary_email) DO UPDATE SET is_admin = true
+    RETURNING id
+), email AS (
+    INSERT INTO emails (address, user_id, is_primary, verified)
+    SELECT 'admin@example.com', id, true, true FROM owner
+    ON CONFLICT (address) DO NOTHING
+), organization AS (
+    INSERT INTO organizations (name, slug, owner_id)
+    SELECT 'Test Organization', 'test-organization', id FROM owner
+    ON CONFLICT (slug) DO NOTHING
+    RETURNING id, owner_id
+)
+INSERT INTO organizers (organization_id, user_id, role)
+SELECT id, owner_id, 'director' FROM organization
+ON CONFLICT DO NOTHING;
@@ -38,6 +38,22 @@ impl std::error::Error for Error {
     }
 }
 
+#[cfg(feature = "graphql")]
+impl async_graphql::ErrorExtensions for Error {
+    fn extend(&self) -> async_graphql::Error {
+        use async_graphql::ErrorExtensions as _;
+        use std::error::Error as _;
+
+        match self.source() {
+            Some(e) => tracing::error!(error = %self, source = %e, "unexpected session error"),
+            None => tracing::error!(error = %self, "unexpected session error"),
+        }
+
+        async_graphql::Error::new("internal server error")
+            .extend_with(|_, extensions| extensions.set("code", "INTERNAL_SERVER_ERROR"))
+    }
+}
+
 impl From<RedisError> for Error {
     fn from(error: RedisError) -> Self {
         Self::Redis(error)
@@ -0,0 +1,26 @@
+use crate::{CurrentSessionId, Manager};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use axum_extra::extract::CookieJar;
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CurrentSessionId
+where
+    S: Send + Sync,
+    Manager: FromRef<S>,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let manager = Manager::from_ref(state);
+        let jar = CookieJar::from_headers(&parts.headers);
+
+        manager
+            .id_from_cookie(&jar)
+            .map(CurrentSessionId)
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
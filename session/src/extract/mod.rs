@@ -6,16 +6,15 @@ use axum::{
 use serde::Serialize;
 
 mod base;
+mod id;
 mod oauth;
 mod registration_needed;
 mod unauthenticated;
-mod user;
 
-pub use base::{Immutable, Mutable};
+pub use base::{HasSessionState, Immutable, Mutable};
 pub use oauth::OAuthSession;
 pub use registration_needed::RegistrationNeededSession;
 pub use unauthenticated::UnauthenticatedSession;
-pub use user::CurrentUser;
 
 /// A rejection generated when the requested session state did not match the
 /// provided session state.
@@ -28,7 +27,7 @@ pub struct InvalidSessionState {
 
 impl InvalidSessionState {
     /// Create a rejection from the app state and a session
-    fn from(session: &SessionState) -> Self {
+    pub fn from(session: &SessionState) -> Self {
         let (status, message) = match session {
             SessionState::Unauthenticated | SessionState::OAuth(_) => {
                 (StatusCode::UNAUTHORIZED, "unauthorized")
@@ -13,18 +13,23 @@ use tracing::debug;
 pub struct OAuthSession(OwnedRwLockWriteGuard<Session>);
 
 impl OAuthSession {
+    /// Get the ID of the session
+    pub fn session_id(&self) -> &str {
+        self.0.id()
+    }
+
     /// Make the current session as authenticated
     pub fn into_authenticated(mut self, id: i32) {
         self.0.state = SessionState::authenticated(id);
     }
 
     /// Mark the current session as needing to complete registration
-    pub fn into_registration_needed(mut self, id: String, email: String) {
+    pub fn into_registration_needed(mut self, id: String, email: String, email_verified: bool) {
         // Create a new registration needed state without a return to URL, we'll set the actual
         // value later to get around the borrow checker
         let SessionState::OAuth(old_state) = std::mem::replace(
             &mut self.0.state,
-            SessionState::registration_needed(id, email),
+            SessionState::registration_needed(id, email, email_verified),
         ) else {
             unreachable!()
         };
@@ -33,6 +38,7 @@ impl OAuthSession {
             SessionState::RegistrationNeeded(state) => {
                 state.return_to = old_state.return_to;
                 state.provider = old_state.provider;
+                state.oauth_state = Some(old_state.state);
             }
             _ => unreachable!(),
         }
@@ -13,6 +13,11 @@ where
     T: HasSessionState;
 
 impl RegistrationNeededSession<Mutable> {
+    /// Get the ID of the session
+    pub fn session_id(&self) -> &str {
+        self.0.id()
+    }
+
     /// Make the current session authenticated for the newly created user
     pub fn into_authenticated(mut self, id: i32) {
         self.0.state = SessionState::authenticated(id)
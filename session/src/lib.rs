@@ -5,6 +5,7 @@ use cookie::{Cookie, SameSite};
 use hmac::{Hmac, Mac};
 use rand::RngCore;
 use redis::aio::ConnectionManager;
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use sha2::Sha256;
 use std::sync::Arc;
@@ -23,13 +24,20 @@ mod store;
 pub use error::Error;
 use error::Result;
 #[cfg(feature = "server")]
-pub use middleware::SessionLayer;
+pub use middleware::{client_ip, user_agent, SessionLayer};
 use store::Store;
 
 /// A shared reference to a session
 pub type Handle = Arc<RwLock<Session>>;
 
+/// The ID of the session making the current request, derived from the session cookie
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CurrentSessionId(pub String);
+
 const COOKIE_NAME: &str = "session";
+/// The cookie binding a custom domain session handoff to the browser that started it
+#[cfg(feature = "server")]
+const HANDOFF_NONCE_COOKIE_NAME: &str = "session-handoff";
 
 /// length of the deserialized cookie in bytes
 const COOKIE_SIZE: usize = 96;
@@ -37,6 +45,12 @@ const COOKIE_SIZE: usize = 96;
 pub const SERIALIZED_LENGTH: usize = 128;
 /// start position of the signature in the signed cookie
 const SIGNATURE_START_INDEX: usize = 64;
+/// how long a handoff code can be used for, in seconds
+#[cfg(feature = "server")]
+const HANDOFF_EXPIRATION: u64 = 60;
+/// how long a browser has to log in after starting a handoff, in minutes
+#[cfg(feature = "server")]
+const HANDOFF_NONCE_EXPIRATION: i64 = 15;
 
 #[cfg(feature = "server")]
 /// Create a new session layer
@@ -53,6 +67,22 @@ pub struct Session {
     expiry: DateTime<Utc>,
     pub state: SessionState,
 
+    /// When the session was first created
+    #[serde(default = "Utc::now")]
+    created_at: DateTime<Utc>,
+    /// When the session was last used
+    #[serde(default = "Utc::now")]
+    last_seen_at: DateTime<Utc>,
+    /// The IP address the session was last used from
+    #[serde(default)]
+    ip: Option<String>,
+    /// The user agent the session was last used from
+    #[serde(default)]
+    user_agent: Option<String>,
+    /// The session on the main domain that a custom domain session was handed off from
+    #[serde(default)]
+    parent: Option<String>,
+
     /// The value stored in the cookie
     #[serde(skip)]
     cookie_value: Option<Vec<u8>>,
@@ -75,6 +105,49 @@ impl Session {
         self.expiry
     }
 
+    /// Get when the session was first created
+    pub fn created_at(&self) -> DateTime<Utc> {
+        self.created_at
+    }
+
+    /// Get when the session was last used
+    pub fn last_seen_at(&self) -> DateTime<Utc> {
+        self.last_seen_at
+    }
+
+    /// Get the IP address the session was last used from
+    pub fn ip(&self) -> Option<&str> {
+        self.ip.as_deref()
+    }
+
+    /// Get the user agent the session was last used from
+    pub fn user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref()
+    }
+
+    /// Record that the session was used, returning whether the changes need to be saved. The last
+    /// seen timestamp is only updated once a minute to avoid writing on every request.
+    pub fn touch(&mut self, ip: Option<&str>, user_agent: Option<&str>) -> bool {
+        let mut changed = false;
+
+        let now = Utc::now();
+        if now - self.last_seen_at > Duration::try_minutes(1).unwrap() {
+            self.last_seen_at = now;
+            changed = true;
+        }
+
+        if ip.is_some() && self.ip.as_deref() != ip {
+            self.ip = ip.map(ToOwned::to_owned);
+            changed = true;
+        }
+        if user_agent.is_some() && self.user_agent.as_deref() != user_agent {
+            self.user_agent = user_agent.map(ToOwned::to_owned);
+            changed = true;
+        }
+
+        changed
+    }
+
     /// Generate the token for the session
     pub fn token(&self, signing_key: &[u8]) -> Option<String> {
         let cookie_value = self.cookie_value.as_ref()?;
@@ -107,10 +180,16 @@ impl Default for Session {
         let mut cookie_value = vec![0; 64];
         rand::thread_rng().fill_bytes(&mut cookie_value);
 
+        let now = Utc::now();
         Self {
             id: Self::generate_id(&cookie_value),
-            expiry: Utc::now() + Duration::try_days(14).unwrap(),
+            expiry: now + Duration::try_days(14).unwrap(),
             state: SessionState::default(),
+            created_at: now,
+            last_seen_at: now,
+            ip: None,
+            user_agent: None,
+            parent: None,
             cookie_value: Some(cookie_value),
         }
     }
@@ -126,7 +205,7 @@ pub struct Manager {
 #[derive(Debug)]
 pub(crate) struct CookieSettings {
     pub domain: String,
-    pub key: String,
+    pub key: SecretString,
     pub secure: bool,
 }
 
@@ -137,7 +216,7 @@ impl Manager {
         let settings = Arc::new(CookieSettings {
             domain: domain.to_owned(),
             secure,
-            key: signing_key.to_owned(),
+            key: SecretString::new(signing_key.to_owned()),
         });
 
         Self { store, settings }
@@ -148,55 +227,318 @@ impl Manager {
         self.store.load(id).await
     }
 
-    /// Load the session from it's token
-    #[instrument(name = "Manager::load_from_token", skip(self))]
-    pub async fn load_from_token(&self, token: &str) -> Result<Option<Session>> {
+    /// Verify a session token and get the ID of the session it references
+    #[instrument(name = "Manager::id_from_token", skip_all)]
+    pub fn id_from_token(&self, token: &str) -> Option<String> {
         if token.is_empty() {
-            return Ok(None);
+            return None;
         }
         if token.len() != SERIALIZED_LENGTH {
             warn!(length = token.len(), "invalid session token length");
-            return Ok(None);
+            return None;
         }
 
         let mut data = Vec::with_capacity(COOKIE_SIZE);
         if BASE64_URL_SAFE_NO_PAD.decode_vec(token, &mut data).is_err() {
             warn!("invalid base64 token");
-            return Ok(None);
+            return None;
         }
 
         let (value, signature) = data.split_at(SIGNATURE_START_INDEX);
 
-        let mut mac = Hmac::<Sha256>::new_from_slice(self.settings.key.as_bytes())
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.settings.key.expose_secret().as_bytes())
             .expect("key must be valid");
         mac.update(value);
         if mac.verify(signature.into()).is_err() {
             warn!("invalid HMAC");
-            return Ok(None);
+            return None;
         }
 
-        let id = Session::generate_id(value);
-        self.load_from_id(&id).await
+        Some(Session::generate_id(value))
+    }
+
+    /// Get the ID of the session referenced by the cookies
+    pub fn id_from_cookie(&self, jar: &CookieJar) -> Option<String> {
+        let cookie = jar.get(COOKIE_NAME)?;
+        self.id_from_token(cookie.value())
+    }
+
+    /// Load the session from it's token
+    #[instrument(name = "Manager::load_from_token", skip(self))]
+    pub async fn load_from_token(&self, token: &str) -> Result<Option<Session>> {
+        match self.id_from_token(token) {
+            Some(id) => self.load_from_id(&id).await,
+            None => Ok(None),
+        }
     }
 
     /// Load the session from cookies
     #[instrument(name = "Manager::load_from_cookie", skip_all)]
     pub async fn load_from_cookie(&self, jar: &CookieJar) -> Result<Option<Session>> {
-        match jar.get(COOKIE_NAME) {
-            Some(cookie) => self.load_from_token(cookie.value()).await,
+        match self.id_from_cookie(jar) {
+            Some(id) => self.load_from_id(&id).await,
             None => Ok(None),
         }
     }
 
+    /// Get all the active sessions for a user
+    #[instrument(name = "Manager::for_user", skip(self))]
+    pub async fn for_user(&self, user_id: i32) -> Result<Vec<Session>> {
+        self.store.load_for_user(user_id).await
+    }
+
+    /// Revoke one of a user's sessions, returning whether it existed
+    #[instrument(name = "Manager::revoke", skip(self))]
+    pub async fn revoke(&self, user_id: i32, id: &str) -> Result<bool> {
+        match self.store.load(id).await? {
+            Some(session) if session.state.id() == Some(user_id) => {
+                self.store.delete(id, Some(user_id)).await?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Revoke a session no matter who it belongs to, returning whether it existed
+    #[instrument(name = "Manager::revoke_any", skip(self))]
+    pub async fn revoke_any(&self, id: &str) -> Result<bool> {
+        match self.store.load(id).await? {
+            Some(session) => {
+                self.store.delete(id, session.state.id()).await?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Revoke the custom domain sessions that were handed off from one of a user's sessions, so
+    /// logging out of the main domain also logs out of the custom domains
+    #[instrument(name = "Manager::revoke_handed_off", skip(self))]
+    pub async fn revoke_handed_off(&self, user_id: i32, parent: &str) -> Result<usize> {
+        let sessions = self.store.load_for_user(user_id).await?;
+
+        let mut revoked = 0;
+        for session in sessions {
+            if session.parent.as_deref() == Some(parent) {
+                self.store.delete(session.id(), Some(user_id)).await?;
+                revoked += 1;
+            }
+        }
+
+        Ok(revoked)
+    }
+
+    /// Revoke all of a user's sessions, returning how many were revoked
+    #[instrument(name = "Manager::revoke_all", skip(self))]
+    pub async fn revoke_all(&self, user_id: i32) -> Result<usize> {
+        let sessions = self.store.load_for_user(user_id).await?;
+        for session in &sessions {
+            self.store.delete(session.id(), Some(user_id)).await?;
+        }
+
+        Ok(sessions.len())
+    }
+
+    /// Generate a token that allows revoking one of a user's sessions without being logged in,
+    /// such as from a link in an email. The token expires after a week.
+    pub fn revocation_token(&self, user_id: i32, id: &str) -> String {
+        let expires_at = (Utc::now() + Duration::try_days(7).unwrap()).timestamp();
+        let payload = format!("{user_id}:{expires_at}:{id}");
+        let signature = self
+            .revocation_mac(payload.as_bytes())
+            .finalize()
+            .into_bytes();
+
+        format!(
+            "{}.{}",
+            BASE64_URL_SAFE_NO_PAD.encode(payload),
+            BASE64_URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// Revoke the session referenced by a revocation token, returning whether it existed
+    #[instrument(name = "Manager::revoke_with_token", skip_all)]
+    pub async fn revoke_with_token(&self, token: &str) -> Result<bool> {
+        let Some((user_id, id)) = self.verify_revocation_token(token) else {
+            warn!("invalid revocation token");
+            return Ok(false);
+        };
+
+        self.revoke(user_id, &id).await
+    }
+
+    /// Whether a revocation token is authentic and has not expired
+    pub fn is_revocation_token_valid(&self, token: &str) -> bool {
+        self.verify_revocation_token(token).is_some()
+    }
+
+    /// Verify a revocation token and get the user and session it references
+    fn verify_revocation_token(&self, token: &str) -> Option<(i32, String)> {
+        let (payload, signature) = token.split_once('.')?;
+        let payload = BASE64_URL_SAFE_NO_PAD.decode(payload).ok()?;
+        let signature = BASE64_URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        self.revocation_mac(&payload)
+            .verify_slice(&signature)
+            .ok()?;
+
+        let mut parts = std::str::from_utf8(&payload).ok()?.splitn(3, ':');
+        let user_id = parts.next()?.parse().ok()?;
+        let expires_at = parts.next()?.parse::<i64>().ok()?;
+        let id = parts.next()?;
+
+        if expires_at < Utc::now().timestamp() {
+            return None;
+        }
+
+        Some((user_id, id.to_owned()))
+    }
+
+    /// Start the signature for a revocation token. The payload is prefixed so a revocation token
+    /// can never be mistaken for a session token signed with the same key.
+    fn revocation_mac(&self, payload: &[u8]) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.settings.key.expose_secret().as_bytes())
+            .expect("key must be valid");
+        mac.update(b"revoke:");
+        mac.update(payload);
+        mac
+    }
+
+    /// Generate the state for an OAuth flow, binding it to the URL the user will be returned to so
+    /// the URL cannot be swapped out before the callback
+    pub fn oauth_state(&self, return_to: Option<&Url>) -> String {
+        let mut nonce = [0; 24];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let nonce = BASE64_URL_SAFE_NO_PAD.encode(nonce);
+
+        let signature = self
+            .oauth_state_mac(&nonce, return_to)
+            .finalize()
+            .into_bytes();
+
+        format!("{nonce}.{}", BASE64_URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    /// Verify that the state of an OAuth flow was generated for the URL the user is being returned
+    /// to
+    pub fn verify_oauth_state(&self, state: &str, return_to: Option<&Url>) -> bool {
+        let Some((nonce, signature)) = state.split_once('.') else {
+            return false;
+        };
+        let Ok(signature) = BASE64_URL_SAFE_NO_PAD.decode(signature) else {
+            return false;
+        };
+
+        self.oauth_state_mac(nonce, return_to)
+            .verify_slice(&signature)
+            .is_ok()
+    }
+
+    /// Start the signature for an OAuth state. The payload is prefixed so a state can never be
+    /// mistaken for a session or revocation token signed with the same key.
+    fn oauth_state_mac(&self, nonce: &str, return_to: Option<&Url>) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.settings.key.expose_secret().as_bytes())
+            .expect("key must be valid");
+        mac.update(b"oauth-state:");
+        mac.update(nonce.as_bytes());
+        mac.update(b":");
+        mac.update(return_to.map(Url::as_str).unwrap_or_default().as_bytes());
+        mac
+    }
+
     /// Save the session to the store
     #[instrument(name = "Manager::save", skip_all, fields(session.id = % session.id()))]
     pub async fn save(&self, session: &Session) -> Result<()> {
         self.store.save(session).await
     }
 
+    /// Start handing a session off to a custom domain, getting the nonce to carry through the
+    /// login and the cookie that binds it to the browser on the custom domain
+    #[cfg(feature = "server")]
+    pub fn start_handoff(&self) -> (String, Cookie<'static>) {
+        let mut nonce = [0; 32];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let nonce = BASE64_URL_SAFE_NO_PAD.encode(nonce);
+
+        let cookie = Cookie::build((HANDOFF_NONCE_COOKIE_NAME, nonce.clone()))
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(self.settings.secure)
+            .max_age(time::Duration::minutes(HANDOFF_NONCE_EXPIRATION))
+            .path("/oauth/session")
+            .build();
+
+        (nonce, cookie)
+    }
+
+    /// Create a single-use code that starts a session for the user on a custom domain, allowing
+    /// it to have a first-party session cookie. The code can only be redeemed by the browser
+    /// holding the nonce, and the session is revoked along with the parent it was handed off from.
+    #[cfg(feature = "server")]
+    #[instrument(name = "Manager::create_handoff", skip(self, nonce))]
+    pub async fn create_handoff(
+        &self,
+        user_id: i32,
+        domain: &str,
+        parent: &str,
+        nonce: &str,
+    ) -> Result<String> {
+        let mut code = [0; 32];
+        rand::thread_rng().fill_bytes(&mut code);
+        let code = BASE64_URL_SAFE_NO_PAD.encode(code);
+
+        self.store
+            .save_handoff(&code, user_id, domain, parent, nonce, HANDOFF_EXPIRATION)
+            .await?;
+
+        Ok(code)
+    }
+
+    /// Start a new session on a custom domain from a handoff code, getting the cookies to set on
+    /// the domain. Codes can only be used once, only on the domain they were created for, and only
+    /// by the browser that started the handoff.
+    #[cfg(feature = "server")]
+    #[instrument(name = "Manager::redeem_handoff", skip_all, fields(%domain))]
+    pub async fn redeem_handoff(
+        &self,
+        code: &str,
+        domain: &str,
+        headers: &axum::http::HeaderMap,
+    ) -> Result<Option<[Cookie<'static>; 2]>> {
+        let jar = CookieJar::from_headers(headers);
+        let Some(nonce) = jar.get(HANDOFF_NONCE_COOKIE_NAME) else {
+            return Ok(None);
+        };
+        let Some((user_id, parent)) = self.store.take_handoff(code, domain, nonce.value()).await?
+        else {
+            return Ok(None);
+        };
+
+        let mut session = Session::default();
+        session.state = SessionState::authenticated(user_id);
+        session.parent = Some(parent);
+        self.store.save(&session).await?;
+
+        let Some(cookie) = self.cookie(session, None) else {
+            return Ok(None);
+        };
+        let mut used = Cookie::build((HANDOFF_NONCE_COOKIE_NAME, ""))
+            .path("/oauth/session")
+            .build();
+        used.make_removal();
+
+        Ok(Some([cookie, used]))
+    }
+
     /// Build a cookie from the session
     pub fn build_cookie(&self, session: Session) -> Option<Cookie<'static>> {
-        let session_token = session.token(self.settings.key.as_bytes())?;
+        self.cookie(session, Some(self.settings.domain.clone()))
+    }
+
+    /// Build a cookie from the session that is sent to the domain, or only to the host that set it
+    /// if there is none
+    fn cookie(&self, session: Session, domain: Option<String>) -> Option<Cookie<'static>> {
+        let session_token = session.token(self.settings.key.expose_secret().as_bytes())?;
 
         let (expiry, max_age) = {
             let nanos = session
@@ -209,17 +551,18 @@ impl Manager {
             (expiry, max_age)
         };
 
-        Some(
-            Cookie::build((COOKIE_NAME, session_token))
-                .http_only(true)
-                .same_site(SameSite::Lax)
-                .secure(self.settings.secure)
-                .domain(self.settings.domain.clone())
-                .expires(expiry)
-                .max_age(max_age)
-                .path("/")
-                .build(),
-        )
+        let mut cookie = Cookie::build((COOKIE_NAME, session_token))
+            .http_only(true)
+            .same_site(SameSite::Lax)
+            .secure(self.settings.secure)
+            .expires(expiry)
+            .max_age(max_age)
+            .path("/");
+        if let Some(domain) = domain {
+            cookie = cookie.domain(domain);
+        }
+
+        Some(cookie.build())
     }
 }
 
@@ -271,12 +614,14 @@ impl SessionState {
 
     /// Construct a new registration needed state
     #[cfg(feature = "server")]
-    pub(crate) fn registration_needed(id: String, email: String) -> Self {
+    pub(crate) fn registration_needed(id: String, email: String, email_verified: bool) -> Self {
         Self::RegistrationNeeded(RegistrationNeededState {
             id,
             email,
+            email_verified,
             return_to: None,
             provider: String::default(),
+            oauth_state: None,
         })
     }
 
@@ -307,8 +652,15 @@ pub struct RegistrationNeededState {
     pub id: String,
     /// The user's primary email
     pub email: String,
+    /// Whether the provider has verified the user owns the email
+    #[serde(default)]
+    pub email_verified: bool,
     /// Where the user was redirected from
     pub return_to: Option<Url>,
+    /// The signed state of the OAuth flow, binding `return_to` so it can be verified again when
+    /// registration is completed
+    #[serde(default)]
+    pub oauth_state: Option<String>,
 }
 
 /// Associated data for an authenticated user
@@ -1,6 +1,6 @@
 use crate::{Handle, Manager};
 use axum::{
-    http::{Request, StatusCode},
+    http::{header::USER_AGENT, HeaderMap, Request, StatusCode},
     response::{IntoResponse, Response},
 };
 use axum_extra::extract::CookieJar;
@@ -85,6 +85,7 @@ where
 
         Box::pin(async move {
             let jar = CookieJar::from_headers(req.headers());
+            let headers = req.headers().clone();
             let session = layer.load_or_create(&jar).await;
 
             {
@@ -103,6 +104,7 @@ where
                 .expect("session still has owners")
                 .into_inner();
             session.extend_if_expiring();
+            session.touch(client_ip(&headers), user_agent(&headers));
 
             if let Err(error) = layer.manager.save(&session).await {
                 use std::error::Error;
@@ -125,3 +127,30 @@ where
         })
     }
 }
+
+/// Get the IP address of the client as reported by the reverse proxy. Fly's `Fly-Client-IP` is
+/// preferred, otherwise the right-most `X-Forwarded-For` entry is used since it is the one appended
+/// by the proxy, while any before it are provided by the client.
+pub fn client_ip(headers: &HeaderMap) -> Option<&str> {
+    if let Some(ip) = headers.get("fly-client-ip") {
+        return ip.to_str().ok().map(str::trim);
+    }
+
+    let forwarded_for = headers.get("x-forwarded-for")?.to_str().ok()?;
+    forwarded_for
+        .rsplit(',')
+        .map(str::trim)
+        .find(|entry| !entry.is_empty())
+}
+
+/// Get the user agent of the client
+pub fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(USER_AGENT)?.to_str().ok()
+}
+
+impl Manager {
+    /// Get the ID of the session referenced by the request's cookies
+    pub fn id_from_headers(&self, headers: &HeaderMap) -> Option<String> {
+        self.id_from_cookie(&CookieJar::from_headers(headers))
+    }
+}
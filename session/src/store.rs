@@ -7,6 +7,9 @@ use chrono::Utc;
 use redis::{aio::ConnectionManager, AsyncCommands};
 use tracing::instrument;
 
+/// How long to keep the index of a user's sessions around after it was last updated
+const USER_INDEX_EXPIRATION: i64 = 60 * 60 * 24 * 14;
+
 /// The session storage backend
 #[derive(Clone)]
 pub(crate) struct Store {
@@ -27,13 +30,45 @@ impl Store {
             .get::<_, Option<Bytes>>(format!("identity:session:{id}"))
             .await?;
 
-        raw.map(|bytes| {
-            serde_json::from_slice(&bytes).map_err(|e| Error::Json {
-                source: e,
-                content: bytes,
-            })
-        })
-        .transpose()
+        raw.map(deserialize).transpose()
+    }
+
+    /// Load all the sessions belonging to a user. Sessions that no longer exist or that belong to
+    /// someone else are removed from the user's index.
+    #[instrument(name = "Store::load_for_user", skip(self))]
+    pub async fn load_for_user(&self, user_id: i32) -> Result<Vec<Session>> {
+        let mut conn = self.manager.clone();
+        let ids = conn
+            .smembers::<_, Vec<String>>(format!("identity:user-sessions:{user_id}"))
+            .await?;
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let keys = ids
+            .iter()
+            .map(|id| format!("identity:session:{id}"))
+            .collect::<Vec<_>>();
+        let raw = redis::cmd("MGET")
+            .arg(&keys)
+            .query_async::<_, Vec<Option<Bytes>>>(&mut conn)
+            .await?;
+
+        let mut sessions = Vec::with_capacity(ids.len());
+        let mut stale = Vec::new();
+        for (id, raw) in ids.into_iter().zip(raw) {
+            match raw.map(deserialize).transpose()? {
+                Some(session) if session.state.id() == Some(user_id) => sessions.push(session),
+                _ => stale.push(id),
+            }
+        }
+
+        if !stale.is_empty() {
+            conn.srem::<_, _, ()>(format!("identity:user-sessions:{user_id}"), stale)
+                .await?;
+        }
+
+        Ok(sessions)
     }
 
     /// Persist a session
@@ -50,14 +85,94 @@ impl Store {
             }
         };
 
-        let mut conn = self.manager.clone();
-        conn.set_ex(
+        let mut pipeline = redis::pipe();
+        pipeline.set_ex(
             format!("identity:session:{}", session.id),
             value,
             expiration,
+        );
+        if let Some(user_id) = session.state.id() {
+            let index = format!("identity:user-sessions:{user_id}");
+            pipeline
+                .sadd(&index, &session.id)
+                .expire(&index, USER_INDEX_EXPIRATION);
+        }
+
+        let mut conn = self.manager.clone();
+        pipeline.query_async::<_, ()>(&mut conn).await?;
+
+        Ok(())
+    }
+
+    /// Save a code that starts a session for the user on the domain, expiring after the TTL
+    #[cfg(feature = "server")]
+    #[instrument(name = "Store::save_handoff", skip(self, code, nonce))]
+    pub async fn save_handoff(
+        &self,
+        code: &str,
+        user_id: i32,
+        domain: &str,
+        parent: &str,
+        nonce: &str,
+        ttl: u64,
+    ) -> Result<()> {
+        let mut conn = self.manager.clone();
+        conn.set_ex::<_, _, ()>(
+            format!("identity:session-handoff:{code}"),
+            format!("{user_id}:{parent}:{nonce}:{domain}"),
+            ttl,
         )
         .await?;
 
         Ok(())
     }
+
+    /// Remove a handoff code, returning the user it was for and the session it was handed off
+    /// from if it was created for the domain and nonce
+    #[cfg(feature = "server")]
+    #[instrument(name = "Store::take_handoff", skip(self, code, nonce))]
+    pub async fn take_handoff(
+        &self,
+        code: &str,
+        domain: &str,
+        nonce: &str,
+    ) -> Result<Option<(i32, String)>> {
+        let mut conn = self.manager.clone();
+        let raw = redis::cmd("GETDEL")
+            .arg(format!("identity:session-handoff:{code}"))
+            .query_async::<_, Option<String>>(&mut conn)
+            .await?;
+
+        let handoff = raw.and_then(|raw| {
+            let mut parts = raw.splitn(4, ':');
+            let user_id = parts.next()?.parse().ok()?;
+            let parent = parts.next()?;
+            let (for_nonce, for_domain) = (parts.next()?, parts.next()?);
+
+            (for_nonce == nonce && for_domain == domain).then(|| (user_id, parent.to_owned()))
+        });
+        Ok(handoff)
+    }
+
+    /// Delete a session and remove it from the user's index, if it belongs to a user
+    #[instrument(name = "Store::delete", skip(self))]
+    pub async fn delete(&self, id: &str, user_id: Option<i32>) -> Result<()> {
+        let mut conn = self.manager.clone();
+        let mut pipe = redis::pipe();
+        pipe.del(format!("identity:session:{id}"));
+        if let Some(user_id) = user_id {
+            pipe.srem(format!("identity:user-sessions:{user_id}"), id);
+        }
+        pipe.query_async::<_, ()>(&mut conn).await?;
+
+        Ok(())
+    }
+}
+
+/// Deserialize a stored session
+fn deserialize(bytes: Bytes) -> Result<Session> {
+    serde_json::from_slice(&bytes).map_err(|e| Error::Json {
+        source: e,
+        content: bytes,
+    })
 }
@@ -0,0 +1,355 @@
+use crate::handlers::{
+    context::{self, Memberships},
+    error::Error,
+    is_fresh, RequestLimiter, SharedSecret, SIGNATURE_HEADER, TIMESTAMP_HEADER,
+};
+use ::context::{Scope, User as UserContext, UserRole};
+use authz::{Action, Actor, Resource};
+use database::{ParticipantRole, Pool};
+use graphql::ScopeCache;
+use state::Domains;
+use tonic::{
+    metadata::MetadataValue, server::NamedService, service::Interceptor, Request, Response, Status,
+};
+use tracing::{error, field, instrument, warn, Span};
+
+mod proto {
+    tonic::include_proto!("identity.v1");
+}
+
+pub(crate) use proto::context_server::ContextServer;
+use proto::{
+    authorize_request, resolve_request, scope, user, AuthorizeRequest, AuthorizeResponse,
+    ResolveRequest, ResolveResponse, ResourceKind,
+};
+
+/// The route whose request limit the service shares, so a token has the same quota over HTTP
+const LIMIT_ROUTE: &str = "context";
+
+/// Resolves the scope and user context for requests, without the overhead of HTTP and JSON
+pub(crate) struct ContextService {
+    db: Pool,
+    domains: Domains,
+    scope_cache: Option<ScopeCache>,
+    sessions: session::Manager,
+    limiter: Option<RequestLimiter>,
+}
+
+impl ContextService {
+    pub fn new(
+        db: Pool,
+        domains: Domains,
+        scope_cache: Option<ScopeCache>,
+        sessions: session::Manager,
+        limiter: Option<RequestLimiter>,
+    ) -> Self {
+        Self {
+            db,
+            domains,
+            scope_cache,
+            sessions,
+            limiter,
+        }
+    }
+
+    /// Count the request against the token's quota, if limited
+    async fn limit(&self, token: &str) -> Result<(), Status> {
+        let Some(limiter) = &self.limiter else {
+            return Ok(());
+        };
+        // Requests without a token can't be attributed to a client, as over HTTP
+        if token.is_empty() {
+            return Ok(());
+        }
+
+        match limiter.check_token(LIMIT_ROUTE, token).await {
+            Some(retry_after) => {
+                let mut status = Status::resource_exhausted("rate limit exceeded");
+                status
+                    .metadata_mut()
+                    .insert("retry-after", MetadataValue::from(retry_after));
+                Err(status)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Determine the scope a request was made for
+    async fn scope(&self, lookup: Option<Lookup>) -> Result<Scope, Status> {
+        let db = &self.db;
+        let cache = self.scope_cache.as_ref();
+
+        let scope = match lookup {
+            Some(Lookup::Domain(domain)) => {
+                context::scope_for_domain(&domain, db, self.domains.clone(), cache).await?
+            }
+            Some(Lookup::Slug(slug)) => context::scope_for_slug(&slug, db, cache).await?,
+            None => return Err(Status::invalid_argument("a domain or slug is required")),
+        };
+
+        Ok(scope)
+    }
+}
+
+/// What the scope of a request is determined by
+enum Lookup {
+    Domain(String),
+    Slug(String),
+}
+
+impl From<resolve_request::Scope> for Lookup {
+    fn from(scope: resolve_request::Scope) -> Self {
+        match scope {
+            resolve_request::Scope::Domain(domain) => Lookup::Domain(domain),
+            resolve_request::Scope::Slug(slug) => Lookup::Slug(slug),
+        }
+    }
+}
+
+impl From<authorize_request::Scope> for Lookup {
+    fn from(scope: authorize_request::Scope) -> Self {
+        match scope {
+            authorize_request::Scope::Domain(domain) => Lookup::Domain(domain),
+            authorize_request::Scope::Slug(slug) => Lookup::Slug(slug),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl proto::context_server::Context for ContextService {
+    #[instrument(name = "grpc::resolve", skip_all)]
+    async fn resolve(
+        &self,
+        request: Request<ResolveRequest>,
+    ) -> Result<Response<ResolveResponse>, Status> {
+        let request = request.into_inner();
+        self.limit(&request.token).await?;
+
+        let scope = self.scope(request.scope.map(Lookup::from)).await?;
+        let (user, participant_role, expires_at) =
+            context::user_for_token(&request.token, &scope, &self.db, &self.sessions).await?;
+        let memberships = match &user {
+            UserContext::Authenticated(user) => {
+                Memberships::load(user.id, self.db.reader()).await?
+            }
+            _ => Memberships::default(),
+        };
+
+        Ok(Response::new(ResolveResponse {
+            scope: Some(scope.into()),
+            user: Some(user_message(user, participant_role, memberships)),
+            session_expires_at: expires_at.map(|expires_at| expires_at.timestamp()),
+        }))
+    }
+
+    #[instrument(name = "grpc::authorize", skip_all, fields(action, resource, allowed))]
+    async fn authorize(
+        &self,
+        request: Request<AuthorizeRequest>,
+    ) -> Result<Response<AuthorizeResponse>, Status> {
+        let request = request.into_inner();
+        let action = action(request.action())?;
+        let resource = resource(request.resource.as_ref())?;
+        Span::current()
+            .record("action", field::debug(action))
+            .record("resource", field::debug(resource));
+
+        self.limit(&request.token).await?;
+
+        let scope = self.scope(request.scope.map(Lookup::from)).await?;
+        let (user, _, _) =
+            context::user_for_token(&request.token, &scope, &self.db, &self.sessions).await?;
+
+        let decision = authz::decide(&Actor::from_context(&scope, &user), action, &resource);
+        Span::current().record("allowed", decision.allowed);
+
+        Ok(Response::new(AuthorizeResponse {
+            allowed: decision.allowed,
+            rule: decision.rule.to_owned(),
+        }))
+    }
+}
+
+/// Convert the requested action into its policy equivalent
+fn action(action: proto::Action) -> Result<Action, Status> {
+    Ok(match action {
+        proto::Action::Unspecified => {
+            return Err(Status::invalid_argument("an action is required"))
+        }
+        proto::Action::Read => Action::Read,
+        proto::Action::ReadPrivate => Action::ReadPrivate,
+        proto::Action::Create => Action::Create,
+        proto::Action::Update => Action::Update,
+        proto::Action::Archive => Action::Archive,
+        proto::Action::Restore => Action::Restore,
+        proto::Action::Extend => Action::Extend,
+        proto::Action::Delete => Action::Delete,
+        proto::Action::ManageMembers => Action::ManageMembers,
+        proto::Action::ManageParticipants => Action::ManageParticipants,
+        proto::Action::TransferOwnership => Action::TransferOwnership,
+        proto::Action::GrantAdmin => Action::GrantAdmin,
+    })
+}
+
+/// Convert the requested resource into its policy equivalent
+fn resource(resource: Option<&proto::Resource>) -> Result<Resource, Status> {
+    let resource = resource.ok_or_else(|| Status::invalid_argument("a resource is required"))?;
+    let user_id = || {
+        resource
+            .user_id
+            .ok_or_else(|| Status::invalid_argument("the resource requires a user id"))
+    };
+
+    Ok(match resource.kind() {
+        ResourceKind::Unspecified => {
+            return Err(Status::invalid_argument("a resource kind is required"))
+        }
+        ResourceKind::Provider => Resource::Provider,
+        ResourceKind::User => Resource::User { id: user_id()? },
+        ResourceKind::Session => Resource::Session {
+            user_id: user_id()?,
+        },
+        ResourceKind::Organization => Resource::Organization {
+            owner_id: resource.owner_id,
+        },
+        ResourceKind::Event => Resource::Event,
+        ResourceKind::Participant => Resource::Participant {
+            user_id: user_id()?,
+        },
+        ResourceKind::Webhook => Resource::Webhook,
+        ResourceKind::OidcClient => Resource::OidcClient,
+        ResourceKind::FeatureFlag => Resource::FeatureFlag,
+        ResourceKind::AllowedRedirectDomain => Resource::AllowedRedirectDomain,
+        ResourceKind::Moderation => Resource::Moderation,
+        ResourceKind::Label => Resource::Label,
+        ResourceKind::History => Resource::History,
+        ResourceKind::EmailDomain => Resource::EmailDomain,
+        ResourceKind::Directory => Resource::Directory,
+    })
+}
+
+/// Rejects calls that are not signed with the shared secret, if one is configured
+///
+/// Interceptors only have access to the metadata, so the signature is the hex-encoded
+/// HMAC-SHA256 of the timestamp and the fully-qualified service name, separated by a newline. It
+/// is sent as `sha256=<signature>` in the same metadata keys as the HTTP headers.
+#[derive(Clone)]
+pub(crate) struct Authenticate(pub Option<SharedSecret>);
+
+impl Interceptor for Authenticate {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(secret) = &self.0 else {
+            return Ok(request);
+        };
+
+        let metadata = request.metadata();
+        let timestamp = metadata
+            .get(TIMESTAMP_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .ok_or_else(|| Status::unauthenticated("missing request signature"))?;
+        let signature = metadata
+            .get(SIGNATURE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("sha256="))
+            .and_then(|value| hex::decode(value).ok())
+            .ok_or_else(|| Status::unauthenticated("missing request signature"))?;
+
+        if !is_fresh(timestamp) {
+            warn!(timestamp, "request signature expired");
+            return Err(Status::unauthenticated("invalid request signature"));
+        }
+
+        let service = <ContextServer<ContextService> as NamedService>::NAME;
+        if !secret.matches(format!("{timestamp}\n{service}").as_bytes(), &signature) {
+            warn!("invalid request signature");
+            return Err(Status::unauthenticated("invalid request signature"));
+        }
+
+        Ok(request)
+    }
+}
+
+impl From<Scope> for proto::Scope {
+    fn from(scope: Scope) -> Self {
+        let kind = match scope {
+            Scope::Admin => scope::Kind::Admin(proto::AdminScope {}),
+            Scope::User => scope::Kind::User(proto::UserScope {}),
+            Scope::Event(event) => scope::Kind::Event(proto::EventScope {
+                event: event.event,
+                organization_id: event.organization_id,
+            }),
+        };
+
+        proto::Scope { kind: Some(kind) }
+    }
+}
+
+impl From<UserRole> for proto::Role {
+    fn from(role: UserRole) -> Self {
+        match role {
+            UserRole::Participant => proto::Role::Participant,
+            UserRole::Organizer => proto::Role::Organizer,
+            UserRole::Manager => proto::Role::Manager,
+            UserRole::Director => proto::Role::Director,
+        }
+    }
+}
+
+/// Build the user message, including the participant role and memberships that are sent as
+/// headers over HTTP
+fn user_message(
+    user: UserContext,
+    participant_role: Option<ParticipantRole>,
+    memberships: Memberships,
+) -> proto::User {
+    let kind = match user {
+        UserContext::Unauthenticated => user::Kind::Unauthenticated(proto::UnauthenticatedUser {}),
+        UserContext::OAuth => user::Kind::Oauth(proto::OAuthUser {}),
+        UserContext::RegistrationNeeded(user) => {
+            user::Kind::RegistrationNeeded(proto::RegistrationNeededUser {
+                provider: user.provider,
+                id: user.id,
+                email: user.email,
+            })
+        }
+        UserContext::Authenticated(user) => user::Kind::Authenticated(proto::AuthenticatedUser {
+            id: user.id,
+            given_name: user.given_name,
+            family_name: user.family_name,
+            email: user.email,
+            role: user.role.map(|role| proto::Role::from(role) as i32),
+            is_admin: user.is_admin,
+            participant_role: participant_role.map(|role| role.as_str().to_owned()),
+            organizations: memberships
+                .organizations
+                .into_iter()
+                .map(|(organization_id, role)| proto::OrganizationRole {
+                    organization_id,
+                    role: proto::Role::from(UserRole::from(role)) as i32,
+                })
+                .collect(),
+            events: memberships.events,
+        }),
+    };
+
+    proto::User { kind: Some(kind) }
+}
+
+impl From<Error> for Status {
+    fn from(error: Error) -> Self {
+        match error {
+            Error::EventNotFound => Status::not_found("unknown event"),
+            Error::EventArchived => Status::failed_precondition("event archived"),
+            Error::BatchTooLarge => Status::invalid_argument("too many requests in batch"),
+            Error::Database(error) => {
+                error!(%error, "unexpected database error");
+                Status::internal("internal server error")
+            }
+            Error::Session(error) => {
+                error!(%error, "unexpected session error");
+                Status::internal("internal server error")
+            }
+        }
+    }
+}
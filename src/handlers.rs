@@ -1,28 +1,59 @@
 use crate::AppState;
 use ::context::{Scope, User};
-use async_graphql::http::{playground_source, GraphQLPlaygroundConfig};
-use async_graphql_axum::{GraphQLRequest, GraphQLResponse};
+use async_graphql::{
+    http::{playground_source, GraphQLPlaygroundConfig},
+    BatchRequest, BatchResponse, Response, ServerError,
+};
+use async_graphql_axum::{GraphQLBatchRequest, GraphQLResponse};
 use axum::http::StatusCode;
 use axum::{
-    extract::State,
+    extract::{Host, State},
     http::{
         header::{HeaderValue, CONTENT_TYPE},
-        Method,
+        HeaderMap, Method,
     },
     response::Html,
     routing::{get, post},
-    Router,
+    Json, Router,
 };
+use chrono::{DateTime, Utc};
+use database::Pool;
+use futures::future;
+use serde::Serialize;
+use session::{extract::Immutable, CurrentSessionId};
+use state::{Domains, FrontendUrl};
 use tower_http::cors::CorsLayer;
 use tracing::instrument;
 use url::Url;
+use utoipa::ToSchema;
 
-mod context;
-mod error;
+pub(crate) mod context;
+mod csrf;
+mod custom_domains;
+pub(crate) mod error;
+mod etag;
+mod metrics;
 mod oauth;
+mod oidc;
+mod openapi;
+mod rate_limit;
+mod request_id;
+mod signature;
+
+pub(crate) use context::{context, ParticipantContext};
+pub(crate) use custom_domains::report_certificate;
+pub(crate) use etag::etag;
+pub(crate) use metrics::metrics;
+pub(crate) use oauth::{custom_domain_session, start_custom_domain_session, Client as OAuthClient};
+pub use oidc::SigningKey;
+pub(crate) use openapi::openapi;
+pub(crate) use rate_limit::{limit, Key};
+pub use rate_limit::{Quota, RequestLimiter};
+pub(crate) use request_id::request_id;
+pub(crate) use signature::{is_fresh, protect, SharedSecret, SIGNATURE_HEADER, TIMESTAMP_HEADER};
 
-pub(crate) use context::context;
-pub(crate) use oauth::Client as OAuthClient;
+/// The maximum number of operations that can be sent in a single batch
+const MAX_BATCH_SIZE: usize = 20;
 
 /// Create router for handling OAuth
 pub(crate) fn oauth(frontend_url: &Url) -> Router<AppState> {
@@ -41,19 +72,111 @@ pub(crate) fn oauth(frontend_url: &Url) -> Router<AppState> {
                     .allow_origin(origin),
             ),
         )
+        .route(
+            "/revoke-session",
+            get(oauth::confirm_revoke_session).post(oauth::revoke_session),
+        )
         .route("/logout", get(oauth::logout))
 }
 
+/// Create router for acting as an OpenID Connect provider
+pub(crate) fn oidc(sessions: session::Manager, secret: Option<&SharedSecret>) -> Router<AppState> {
+    Router::new()
+        .route("/.well-known/openid-configuration", get(oidc::discovery))
+        .route("/.well-known/jwks.json", get(oidc::jwks))
+        .route(
+            "/authorize",
+            get(oidc::authorize).layer(session::layer(sessions)),
+        )
+        .route("/token", post(oidc::token))
+        .route("/introspect", protect(post(oidc::introspect), secret))
+        .route("/userinfo", get(oidc::userinfo).post(oidc::userinfo))
+}
+
 /// Handle graphql requests
 #[instrument(name = "graphql", skip_all)]
 pub(crate) async fn graphql(
     State(schema): State<graphql::Schema>,
     scope: Scope,
     user: User,
-    req: GraphQLRequest,
+    participant: ParticipantContext,
+    session: Option<CurrentSessionId>,
+    headers: HeaderMap,
+    req: GraphQLBatchRequest,
 ) -> GraphQLResponse {
-    let req = req.into_inner().data(scope).data(user);
-    schema.execute(req).await.into()
+    let mut req = req.into_inner().data(scope).data(user);
+    if let Some(role) = participant.0 {
+        req = req.data(role);
+    }
+    if let Some(session) = session {
+        req = req.data(session);
+    }
+    if let Some(token) = rate_limit::token(&headers, None) {
+        req = req.data(graphql::ServiceToken(token));
+    }
+
+    execute(&schema, req).await.into()
+}
+
+/// Handle graphql requests without a gateway, deriving the scope and user from the request itself
+#[instrument(name = "graphql::standalone", skip_all)]
+pub(crate) async fn standalone_graphql(
+    State(schema): State<graphql::Schema>,
+    State(db): State<Pool>,
+    State(domains): State<Domains>,
+    State(scope_cache): State<Option<graphql::ScopeCache>>,
+    State(sessions): State<session::Manager>,
+    State(frontend_url): State<FrontendUrl>,
+    Host(host): Host,
+    headers: HeaderMap,
+    session: Immutable,
+    session_id: Option<CurrentSessionId>,
+    req: GraphQLBatchRequest,
+) -> Result<GraphQLResponse, error::Error> {
+    csrf::check(&headers, &host, &frontend_url)?;
+
+    let (scope, user, participant) = context::standalone(
+        &host,
+        &session.state,
+        &db,
+        domains,
+        scope_cache.as_ref(),
+        &sessions,
+    )
+    .await?;
+    drop(session);
+
+    let mut req = req.into_inner().data(scope).data(user);
+    if let Some(role) = participant.0 {
+        req = req.data(role);
+    }
+    if let Some(session_id) = session_id {
+        req = req.data(session_id);
+    }
+    if let Some(token) = rate_limit::token(&headers, None) {
+        req = req.data(graphql::ServiceToken(token));
+    }
+
+    Ok(execute(&schema, req).await.into())
+}
+
+/// Execute a single or batched request. Operations within a batch are run concurrently so their
+/// dataloader calls can be coalesced.
+async fn execute(schema: &graphql::Schema, req: BatchRequest) -> BatchResponse {
+    match req {
+        BatchRequest::Single(req) => BatchResponse::Single(schema.execute(req).await),
+        BatchRequest::Batch(requests) if requests.len() > MAX_BATCH_SIZE => {
+            let error = ServerError::new(
+                format!("batches may contain at most {MAX_BATCH_SIZE} operations"),
+                None,
+            );
+            BatchResponse::Single(Response::from_errors(vec![error]))
+        }
+        BatchRequest::Batch(requests) => {
+            let responses = future::join_all(requests.into_iter().map(|req| schema.execute(req)));
+            BatchResponse::Batch(responses.await)
+        }
+    }
 }
 
 /// Serve the GraphQL playground for development
@@ -64,6 +187,58 @@ pub(crate) async fn playground() -> Html<String> {
 }
 
 /// Check that the service is alive
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "meta",
+    responses((status = 204, description = "The service is alive")),
+)]
 pub(crate) async fn health() -> StatusCode {
     StatusCode::NO_CONTENT
 }
+
+/// Get which build is serving requests
+#[utoipa::path(
+    get,
+    path = "/version",
+    tag = "meta",
+    responses((status = 200, description = "The build information", body = Version)),
+)]
+pub(crate) async fn version() -> Json<Version> {
+    let built_at = env!("BUILD_TIMESTAMP")
+        .parse()
+        .ok()
+        .and_then(|timestamp| DateTime::from_timestamp(timestamp, 0))
+        .unwrap_or_default();
+
+    Json(Version {
+        version: env!("CARGO_PKG_VERSION"),
+        sha: env!("GIT_SHA"),
+        built_at,
+        features: FEATURES
+            .iter()
+            .filter_map(|&(name, enabled)| enabled.then_some(name))
+            .collect(),
+    })
+}
+
+/// The optional features the service can be built with, and whether they are enabled
+const FEATURES: &[(&str, bool)] = &[
+    ("kafka", cfg!(feature = "kafka")),
+    ("nats", cfg!(feature = "nats")),
+    ("ses", cfg!(feature = "ses")),
+    ("sqs", cfg!(feature = "sqs")),
+];
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Version {
+    /// The version of the package
+    version: &'static str,
+    /// The commit the binary was built from
+    sha: &'static str,
+    /// When the binary was built
+    built_at: DateTime<Utc>,
+    /// The optional features that are enabled
+    features: Vec<&'static str>,
+}
@@ -1,17 +1,86 @@
-use super::error::{Error, Result};
+use super::{
+    error::{Error, Result},
+    RequestLimiter,
+};
 use axum::{
-    extract::{Query, State},
-    http::uri::Authority,
+    async_trait,
+    extract::{FromRequestParts, Query, State},
+    http::{header::CACHE_CONTROL, request::Parts, uri::Authority, HeaderName, HeaderValue},
+    response::{AppendHeaders, IntoResponseParts, ResponseParts},
+    Json,
 };
+use chrono::{DateTime, Utc};
 use context::{
     AuthenticatedUser, EventScope, Scope, ScopeParams, User as UserContext, UserParams,
     UserRegistrationNeeded, UserRole,
 };
-use database::{Event, PgPool, User};
-use serde::Deserialize;
+use database::{Event, Organizer, Participant, ParticipantRole, PgPool, Pool, Role, User};
+use futures::future;
+use graphql::{Resolution, ScopeCache};
+use serde::{Deserialize, Serialize};
 use session::SessionState;
 use state::Domains;
-use tracing::{info, instrument, Span};
+use std::{
+    collections::{HashMap, HashSet},
+    convert::Infallible,
+};
+use tracing::{error, info, instrument, warn, Span};
+use utoipa::ToSchema;
+
+/// The maximum number of requests that can be resolved in a single batch
+const MAX_BATCH_SIZE: usize = 100;
+/// The route whose request limit batches share, so a token has the same quota as with `/context`
+const LIMIT_ROUTE: &str = "context";
+
+/// The header containing the user's role within the event, if they are a participant
+static PARTICIPANT_ROLE_HEADER: HeaderName = HeaderName::from_static("participant-role");
+/// The header containing the user's role in every organization they are part of, as a
+/// comma-separated list of `<organization id>:<role>` pairs
+static ORGANIZATION_ROLES_HEADER: HeaderName = HeaderName::from_static("organization-roles");
+/// The header containing the slugs of every event the user participates in, comma-separated
+static PARTICIPANT_EVENTS_HEADER: HeaderName = HeaderName::from_static("participant-events");
+/// The header containing when the session expires, if there is one, in RFC 3339 format
+static SESSION_EXPIRES_HEADER: HeaderName = HeaderName::from_static("session-expires");
+
+/// The user's role within the event, if they are a participant
+///
+/// Returned from `/context` alongside the scope and user context, and read back from the requests
+/// the gateway forwards so GraphQL resolvers can access it.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ParticipantContext(pub Option<ParticipantRole>);
+
+impl IntoResponseParts for ParticipantContext {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if let Some(role) = self.0 {
+            res.headers_mut().insert(
+                PARTICIPANT_ROLE_HEADER.clone(),
+                HeaderValue::from_static(role.as_str()),
+            );
+        }
+
+        Ok(res)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ParticipantContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let role = parts
+            .headers
+            .get(&PARTICIPANT_ROLE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(ParticipantRole::from_name);
+
+        Ok(Self(role))
+    }
+}
 
 #[derive(Deserialize)]
 pub(crate) struct Params<'p> {
@@ -22,137 +91,699 @@ pub(crate) struct Params<'p> {
 }
 
 /// Determine the scope and user context for a request
+#[utoipa::path(
+    get,
+    path = "/context",
+    tag = "context",
+    params(
+        ("domain" = Option<String>, Query, description = "The domain the request was made to"),
+        ("slug" = Option<String>, Query, description = "The slug of the event the request was made for"),
+        ("token" = String, Query, description = "The session token sent with the request"),
+        ("if-none-match" = Option<String>, Header, description = "The ETag of a previous response for the request"),
+    ),
+    responses(
+        (
+            status = 200,
+            description = "The scope and user context, encoded as response headers",
+            headers(
+                ("participant-role" = Option<String>, description = "The user's role within the event, if they are a participant"),
+                ("organization-roles" = Option<String>, description = "The user's role in each organization they are part of, as comma-separated `<organization id>:<role>` pairs. Only set for authenticated users"),
+                ("participant-events" = Option<String>, description = "The comma-separated slugs of the events the user participates in. Only set for authenticated users"),
+                ("session-expires" = Option<String>, description = "When the session expires, in RFC 3339 format. Only set when the token belongs to a session"),
+                ("cache-control" = String, description = "Whether the context can be cached. Cached contexts must be revalidated with the ETag before being reused"),
+                ("etag" = String, description = "A weak tag identifying the scope and user context"),
+            ),
+        ),
+        (
+            status = 304,
+            description = "The scope and user context match the ETag sent in if-none-match",
+            headers(("etag" = String, description = "A weak tag identifying the scope and user context")),
+        ),
+        (status = 401, description = "The request signature is missing or invalid"),
+        (status = 410, description = "The event has been archived"),
+        (status = 422, description = "The event does not exist"),
+    ),
+)]
 #[instrument(name = "context", skip_all)]
 pub(crate) async fn context(
     Query(params): Query<Params<'_>>,
-    State(db): State<PgPool>,
+    State(db): State<Pool>,
     State(domains): State<Domains>,
+    State(scope_cache): State<Option<ScopeCache>>,
     State(sessions): State<session::Manager>,
-) -> Result<(Scope, UserContext)> {
-    let scope = determine_scope_context(params.scope, &db, domains).await?;
-    let user = determine_user_context(params.user, &db, &scope, sessions).await?;
+) -> Result<(
+    AppendHeaders<Vec<(HeaderName, String)>>,
+    Scope,
+    UserContext,
+    ParticipantContext,
+)> {
+    let scope = determine_scope_context(params.scope, &db, domains, scope_cache.as_ref()).await?;
+    let (user, participant_role, expires_at) =
+        user_for_token(&params.user.token, &scope, &db, &sessions).await?;
+
+    let mut headers = Vec::new();
+    match expires_at {
+        // Role changes, bans, and archived events change the context while the session stays the
+        // same, so the gateway must revalidate with the ETag before reusing a cached context
+        Some(expires_at) => {
+            headers.push((CACHE_CONTROL, String::from("private, no-cache")));
+            headers.push((SESSION_EXPIRES_HEADER.clone(), expires_at.to_rfc3339()));
+        }
+        None => headers.push((CACHE_CONTROL, String::from("no-store"))),
+    }
+    if let UserContext::Authenticated(user) = &user {
+        let memberships = Memberships::load(user.id, db.reader()).await?;
+        headers.extend(memberships.headers());
+    }
 
-    Ok((scope, user))
+    Ok((
+        AppendHeaders(headers),
+        scope,
+        user,
+        ParticipantContext(participant_role),
+    ))
+}
+
+/// Determine the scope and user context for many requests at once
+///
+/// Requests that share a scope, token, or user only look them up once. Each distinct token counts
+/// once against the same request limit as `/context`. Requests whose event does not exist or is
+/// archived, or whose token is over its limit, get an error in their place instead of failing the
+/// batch.
+#[utoipa::path(
+    post,
+    path = "/context/batch",
+    tag = "context",
+    request_body = Vec<BatchRequest>,
+    responses(
+        (status = 200, description = "The context for each request, in the order they were sent", body = Vec<BatchResolution>),
+        (status = 401, description = "The request signature is missing or invalid"),
+        (status = 413, description = "Too many requests were sent in the batch"),
+    ),
+)]
+#[instrument(name = "context::batch", skip_all, fields(size = requests.len()))]
+pub(crate) async fn batch(
+    State(db): State<Pool>,
+    State(domains): State<Domains>,
+    State(scope_cache): State<Option<ScopeCache>>,
+    State(sessions): State<session::Manager>,
+    State(limiter): State<Option<RequestLimiter>>,
+    Json(requests): Json<Vec<BatchRequest>>,
+) -> Result<Json<Vec<BatchResolution>>> {
+    if requests.len() > MAX_BATCH_SIZE {
+        return Err(Error::BatchTooLarge);
+    }
+
+    let (db, domains, cache, sessions) = (&db, &domains, scope_cache.as_ref(), &sessions);
+    let limiter = limiter.as_ref();
+
+    let keys = requests
+        .iter()
+        .filter_map(BatchRequest::scope_key)
+        .collect::<HashSet<_>>();
+    let scopes = future::try_join_all(keys.into_iter().map(|key| async move {
+        let scope = match key {
+            ScopeKey::Domain(domain) => scope_for_domain(domain, db, domains.clone(), cache).await,
+            ScopeKey::Slug(slug) => scope_for_slug(slug, db, cache).await,
+        };
+        let scope = match scope {
+            Ok(scope) => Ok(scope),
+            Err(error @ (Error::EventNotFound | Error::EventArchived)) => Err(error.to_string()),
+            Err(error) => return Err(error),
+        };
+
+        Ok::<_, Error>((key, scope))
+    }))
+    .await?
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    // Each token is counted against the same limit as `/context`, and skipped once it is over
+    let tokens = requests
+        .iter()
+        .map(|request| request.token.as_str())
+        .collect::<HashSet<_>>();
+    let states = future::try_join_all(tokens.into_iter().map(|token| async move {
+        if let Some(limiter) = limiter {
+            if !token.is_empty() && limiter.check_token(LIMIT_ROUTE, token).await.is_some() {
+                return Ok((token, None));
+            }
+        }
+
+        let session = session_for_token(token, sessions).await?;
+        Ok::<_, Error>((token, Some(session)))
+    }))
+    .await?
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    let (scopes, states) = (&scopes, &states);
+    let pairs = requests
+        .iter()
+        .filter_map(|request| Some((request.token.as_str(), request.scope_key()?)))
+        .collect::<HashSet<_>>();
+    let users = future::try_join_all(pairs.into_iter().filter_map(|(token, key)| {
+        let (state, _) = states[token].as_ref()?;
+        let scope = scopes[&key].as_ref().ok()?;
+        Some(async move {
+            let user = determine_user_context(state, db, sessions, scope).await?;
+            Ok::<_, Error>(((token, key), user))
+        })
+    }))
+    .await?
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    let user_ids = users
+        .values()
+        .filter_map(|(user, _)| match user {
+            UserContext::Authenticated(user) => Some(user.id),
+            _ => None,
+        })
+        .collect::<HashSet<_>>();
+    let memberships = future::try_join_all(user_ids.into_iter().map(|id| async move {
+        let memberships = Memberships::load(id, db.reader()).await?;
+        Ok::<_, Error>((id, memberships))
+    }))
+    .await?
+    .into_iter()
+    .collect::<HashMap<_, _>>();
+
+    let no_memberships = Memberships::default();
+    let resolutions = requests
+        .iter()
+        .map(|request| {
+            let Some(key) = request.scope_key() else {
+                return BatchResolution::failed("a domain or slug is required");
+            };
+            let scope = match &scopes[&key] {
+                Ok(scope) => scope,
+                Err(error) => return BatchResolution::failed(error),
+            };
+            let token = request.token.as_str();
+            let Some((_, expires_at)) = &states[token] else {
+                return BatchResolution::failed("rate limit exceeded");
+            };
+
+            let (user, participant_role) = &users[&(token, key)];
+            let memberships = match user {
+                UserContext::Authenticated(user) => &memberships[&user.id],
+                _ => &no_memberships,
+            };
+
+            BatchResolution {
+                scope: Some(scope.into()),
+                user: Some(BatchUser::new(user, memberships)),
+                participant_role: participant_role.map(|role| role.as_str()),
+                session_expires_at: *expires_at,
+                error: None,
+            }
+        })
+        .collect();
+
+    Ok(Json(resolutions))
+}
+
+/// The organizations a user is part of and the events they participate in, allowing services to
+/// authorize requests outside the current scope without querying for them
+#[derive(Debug, Default)]
+pub(crate) struct Memberships {
+    /// The user's role in each organization they are part of
+    pub organizations: Vec<(i32, Role)>,
+    /// The slugs of the events the user participates in
+    pub events: Vec<String>,
+}
+
+impl Memberships {
+    /// Load the memberships for a user
+    #[instrument(name = "Memberships::load", skip(db))]
+    pub(crate) async fn load(user_id: i32, db: &PgPool) -> Result<Self> {
+        let (organizers, participants) = future::try_join(
+            Organizer::for_user(user_id, db),
+            Participant::for_user(user_id, db),
+        )
+        .await?;
+
+        Ok(Self {
+            organizations: organizers
+                .into_iter()
+                .map(|organizer| (organizer.organization_id, organizer.role))
+                .collect(),
+            events: participants
+                .into_iter()
+                .map(|participant| participant.event)
+                .collect(),
+        })
+    }
+
+    /// Encode the memberships as response headers
+    fn headers(&self) -> [(HeaderName, String); 2] {
+        let organizations = self
+            .organizations
+            .iter()
+            .map(|(id, role)| format!("{id}:{}", role.as_str()))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        [
+            (ORGANIZATION_ROLES_HEADER.clone(), organizations),
+            (PARTICIPANT_EVENTS_HEADER.clone(), self.events.join(",")),
+        ]
+    }
 }
 
 /// Determine the scope context for the request
 #[instrument(name = "scope", skip_all, fields(domain, slug))]
 async fn determine_scope_context(
     params: ScopeParams<'_>,
-    db: &PgPool,
+    db: &Pool,
     domains: Domains,
+    cache: Option<&ScopeCache>,
 ) -> Result<Scope> {
-    let scope = match params {
-        ScopeParams::Slug(slug) => {
-            Span::current().record("slug", &*slug);
-            let Some(event) = Event::find(&slug, db).await? else {
-                return Err(Error::EventNotFound);
-            };
+    match params {
+        ScopeParams::Slug(slug) => scope_for_slug(&slug, db, cache).await,
+        ScopeParams::Domain(domain) => scope_for_domain(&domain, db, domains, cache).await,
+    }
+}
 
-            info!(scope = "event", %event.slug, %event.organization_id);
+/// Determine the scope context from the slug of the event the request was made for
+pub(crate) async fn scope_for_slug(
+    slug: &str,
+    db: &Pool,
+    cache: Option<&ScopeCache>,
+) -> Result<Scope> {
+    Span::current().record("slug", slug);
 
-            Scope::Event(EventScope {
-                event: event.slug,
-                organization_id: event.organization_id,
-            })
-        }
-        ScopeParams::Domain(domain) => {
-            let authority = Authority::try_from(&*domain)?;
-            let host = authority.host();
-
-            Span::current().record("domain", host);
-
-            if domains.requires_admin(host) {
-                info!(scope = "admin");
-                Scope::Admin
-            } else if domains.requires_user(host) {
-                info!(scope = "user");
-                Scope::User
-            } else {
-                let event = if let Some(slug) = domains.extract_slug_for_subdomain(host) {
-                    info!(%slug, "handling hosted domain");
-                    Event::find(slug, db).await?
-                } else {
-                    info!("handling custom domain");
-                    Event::find_by_custom_domain(host, db).await?
-                };
-                let Some(event) = event else {
-                    return Err(Error::EventNotFound);
-                };
-
-                info!(scope = "event", %event.slug, %event.organization_id);
-
-                Scope::Event(EventScope {
-                    event: event.slug,
-                    organization_id: event.organization_id,
-                })
+    let resolution = resolve_slug(slug, db, cache).await?;
+    event_scope(slug.to_owned(), resolution)
+}
+
+/// Determine the scope context from the domain the request was made to
+pub(crate) async fn scope_for_domain(
+    domain: &str,
+    db: &Pool,
+    domains: Domains,
+    cache: Option<&ScopeCache>,
+) -> Result<Scope> {
+    let authority = Authority::try_from(domain)?;
+    let host = authority.host();
+
+    Span::current().record("domain", host);
+
+    if domains.requires_admin(host) {
+        info!(scope = "admin");
+        return Ok(Scope::Admin);
+    } else if domains.requires_user(host) {
+        info!(scope = "user");
+        return Ok(Scope::User);
+    }
+
+    if let Some(slug) = domains.extract_slug_for_subdomain(host) {
+        info!(%slug, "handling hosted domain");
+        let resolution = resolve_slug(slug, db, cache).await?;
+        return event_scope(slug.to_owned(), resolution);
+    }
+
+    info!("handling custom domain");
+    let Some(slug) = resolve_custom_domain(host, db, cache).await? else {
+        return Err(Error::EventNotFound);
+    };
+    let resolution = resolve_slug(&slug, db, cache).await?;
+
+    event_scope(slug, resolution)
+}
+
+/// Determine what the event slug refers to, preferring the cached resolution
+///
+/// Resolutions are cached until a change to the event invalidates them, so they are read from the
+/// primary to avoid caching what a lagging replica has yet to see.
+async fn resolve_slug(slug: &str, db: &Pool, cache: Option<&ScopeCache>) -> Result<Resolution> {
+    let Some(cache) = cache else {
+        let event = Event::find(slug, db.reader()).await?;
+        return Ok(Resolution::from(event.as_ref()));
+    };
+
+    if let Some(resolution) = cache.event(slug).await {
+        return Ok(resolution);
+    }
+
+    let event = Event::find(slug, db.writer()).await?;
+    let resolution = Resolution::from(event.as_ref());
+    cache.store_event(slug, resolution).await;
+
+    Ok(resolution)
+}
+
+/// Determine the slug of the event a custom domain belongs to, preferring the cached mapping. Like
+/// event resolutions, mappings are read from the primary before being cached.
+async fn resolve_custom_domain(
+    domain: &str,
+    db: &Pool,
+    cache: Option<&ScopeCache>,
+) -> Result<Option<String>> {
+    let Some(cache) = cache else {
+        let event = Event::find_by_custom_domain(domain, db.reader()).await?;
+        return Ok(event.map(|event| event.slug));
+    };
+
+    if let Some(slug) = cache.custom_domain(domain).await {
+        return Ok(slug);
+    }
+
+    let event = Event::find_by_custom_domain(domain, db.writer()).await?;
+    let slug = event.map(|event| event.slug);
+    cache.store_custom_domain(domain, slug.as_deref()).await;
+
+    Ok(slug)
+}
+
+/// Build the scope for an event, ensuring it is still active
+fn event_scope(slug: String, resolution: Resolution) -> Result<Scope> {
+    let organization_id = match resolution {
+        Resolution::Active { organization_id } => organization_id,
+        Resolution::Archived => return Err(Error::EventArchived),
+        Resolution::Missing => return Err(Error::EventNotFound),
+    };
+
+    info!(scope = "event", %slug, %organization_id);
+
+    Ok(Scope::Event(EventScope {
+        event: slug,
+        organization_id,
+    }))
+}
+
+/// Determine the scope and user context for a request that did not go through the gateway, using
+/// the domain it was made to and the session from the cookie
+#[instrument(name = "context::standalone", skip_all, fields(domain))]
+pub(crate) async fn standalone(
+    domain: &str,
+    state: &SessionState,
+    db: &Pool,
+    domains: Domains,
+    cache: Option<&ScopeCache>,
+    sessions: &session::Manager,
+) -> Result<(Scope, UserContext, ParticipantContext)> {
+    let scope = scope_for_domain(domain, db, domains, cache).await?;
+    let (user, participant_role) = determine_user_context(state, db, sessions, &scope).await?;
+
+    Ok((scope, user, ParticipantContext(participant_role)))
+}
+
+/// Get the user context from the session token provided by the gateway, extending the session
+/// if it is still active. Also returns when the session expires, if there is one.
+pub(crate) async fn user_for_token(
+    token: &str,
+    scope: &Scope,
+    db: &Pool,
+    sessions: &session::Manager,
+) -> Result<(UserContext, Option<ParticipantRole>, Option<DateTime<Utc>>)> {
+    let (state, expires_at) = session_for_token(token, sessions).await?;
+    let (user, participant_role) = determine_user_context(&state, db, sessions, scope).await?;
+
+    Ok((user, participant_role, expires_at))
+}
+
+/// Load the session state for a token along with when it expires, extending the session if it
+/// is still active
+async fn session_for_token(
+    token: &str,
+    sessions: &session::Manager,
+) -> Result<(SessionState, Option<DateTime<Utc>>)> {
+    let session = match sessions.load_from_token(token).await? {
+        Some(mut session) => {
+            if session.touch(None, None) {
+                sessions.save(&session).await?;
             }
+            let expires_at = session.expiry();
+            (session.state, Some(expires_at))
         }
+        None => (SessionState::default(), None),
     };
 
-    Ok(scope)
+    Ok(session)
 }
 
 /// Get the user context for the request
 #[instrument(name = "user", skip_all)]
 async fn determine_user_context(
-    params: UserParams<'_>,
-    db: &PgPool,
+    state: &SessionState,
+    db: &Pool,
+    sessions: &session::Manager,
     scope: &Scope,
-    sessions: session::Manager,
-) -> Result<UserContext> {
-    let session = sessions
-        .load_from_token(&params.token)
-        .await?
-        .map(|s| s.state)
-        .unwrap_or_default();
-
-    let context = match session {
-        SessionState::Unauthenticated => UserContext::Unauthenticated,
-        SessionState::OAuth(_) => UserContext::OAuth,
-        SessionState::RegistrationNeeded(state) => {
+) -> Result<(UserContext, Option<ParticipantRole>)> {
+    let context = match state {
+        SessionState::Unauthenticated => (UserContext::Unauthenticated, None),
+        SessionState::OAuth(_) => (UserContext::OAuth, None),
+        SessionState::RegistrationNeeded(state) => (
             UserContext::RegistrationNeeded(UserRegistrationNeeded {
-                provider: state.provider,
-                id: state.id,
-                email: state.email,
-            })
-        }
+                provider: state.provider.clone(),
+                id: state.id.clone(),
+                email: state.email.clone(),
+            }),
+            None,
+        ),
         SessionState::Authenticated(state) => {
-            // TODO: handle user not existing
-            let user = User::find(state.id, db).await?.expect("user must exist");
-            let role = determine_role(scope, &user, db).await?;
+            let Some(user) = find_user(state.id, db).await? else {
+                // The user was deleted while they were logged in, so their sessions are no
+                // longer valid
+                warn!(user.id = state.id, "session belongs to a deleted user");
+                if let Err(error) = sessions.revoke_all(state.id).await {
+                    error!(%error, user.id = state.id, "failed to revoke sessions for deleted user");
+                }
 
-            UserContext::Authenticated(AuthenticatedUser {
+                return Ok((UserContext::Unauthenticated, None));
+            };
+            if user.is_banned() {
+                info!(user.id = user.id, "session belongs to a banned user");
+                return Ok((UserContext::Unauthenticated, None));
+            }
+            let (role, participant_role) = determine_role(scope, &user, db.reader()).await?;
+
+            let context = UserContext::Authenticated(AuthenticatedUser {
                 id: user.id,
                 given_name: user.given_name,
                 family_name: user.family_name,
                 email: user.primary_email,
                 role,
                 is_admin: user.is_admin,
-            })
+            });
+            (context, participant_role)
         }
     };
 
     Ok(context)
 }
 
-/// Determine the role for the current user
-#[instrument(skip_all, fields(%user.id, role))]
-async fn determine_role(scope: &Scope, user: &User, db: &PgPool) -> Result<Option<UserRole>> {
+/// Find the user a session belongs to. A replica may not have seen a user who just registered, so
+/// the primary is checked before concluding they don't exist.
+async fn find_user(id: i32, db: &Pool) -> Result<Option<User>> {
+    if let Some(user) = User::find(id, db.reader()).await? {
+        return Ok(Some(user));
+    }
+
+    Ok(User::find(id, db.writer()).await?)
+}
+
+/// Determine the role for the current user, along with their participant role if they are one
+#[instrument(skip_all, fields(%user.id, role, participant_role))]
+async fn determine_role(
+    scope: &Scope,
+    user: &User,
+    db: &PgPool,
+) -> Result<(Option<UserRole>, Option<ParticipantRole>)> {
     let Scope::Event(event) = scope else {
-        return Ok(None);
+        return Ok((None, None));
     };
 
     // Being a participant takes precedence over being an organizer as it is more granular
-    if User::is_participant(user.id, &event.event, db).await? {
+    if let Some(participant) = Participant::find(user.id, &event.event, db).await? {
         Span::current().record("role", "participant");
-        return Ok(Some(UserRole::Participant));
+        Span::current().record("participant_role", participant.role.as_str());
+        return Ok((Some(UserRole::Participant), Some(participant.role)));
     }
 
-    if let Some(role) = User::is_organizer(user.id, event.organization_id, db).await? {
+    let role =
+        User::organizer_role_for_event(user.id, &event.event, event.organization_id, db).await?;
+    if let Some(role) = role {
         Span::current().record("role", tracing::field::debug(role));
-        return Ok(Some(role.into()));
+        return Ok((Some(role.into()), None));
+    }
+
+    Ok((None, None))
+}
+
+/// A request to determine the context for as part of a batch
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BatchRequest {
+    /// The domain the request was made to
+    domain: Option<String>,
+    /// The slug of the event the request was made for, only used when there is no domain
+    slug: Option<String>,
+    /// The session token sent with the request
+    token: String,
+}
+
+impl BatchRequest {
+    /// Get what the scope of the request is determined from
+    fn scope_key(&self) -> Option<ScopeKey<'_>> {
+        match (&self.domain, &self.slug) {
+            (Some(domain), _) => Some(ScopeKey::Domain(domain)),
+            (None, Some(slug)) => Some(ScopeKey::Slug(slug)),
+            (None, None) => None,
+        }
     }
+}
+
+/// What the scope of a request in a batch is determined from
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum ScopeKey<'r> {
+    Domain(&'r str),
+    Slug(&'r str),
+}
+
+/// The context for a request in a batch, or why it could not be determined
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchResolution {
+    /// The scope of the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scope: Option<BatchScope>,
+    /// The user making the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    user: Option<BatchUser>,
+    /// The user's role within the event, if they are a participant
+    #[serde(skip_serializing_if = "Option::is_none")]
+    participant_role: Option<&'static str>,
+    /// When the session expires, if the token belongs to one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_expires_at: Option<DateTime<Utc>>,
+    /// Why the context could not be determined
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BatchResolution {
+    fn failed(error: impl Into<String>) -> Self {
+        Self {
+            scope: None,
+            user: None,
+            participant_role: None,
+            session_expires_at: None,
+            error: Some(error.into()),
+        }
+    }
+}
+
+/// The scope of a request in a batch
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub(crate) enum BatchScope {
+    Admin,
+    User,
+    Event {
+        /// The slug of the event
+        event: String,
+        /// The organization putting on the event
+        #[serde(rename = "organizationId")]
+        organization_id: i32,
+    },
+}
+
+impl From<&Scope> for BatchScope {
+    fn from(scope: &Scope) -> Self {
+        match scope {
+            Scope::Admin => BatchScope::Admin,
+            Scope::User => BatchScope::User,
+            Scope::Event(event) => BatchScope::Event {
+                event: event.event.clone(),
+                organization_id: event.organization_id,
+            },
+        }
+    }
+}
+
+/// The user making a request in a batch
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub(crate) enum BatchUser {
+    Unauthenticated,
+    #[serde(rename = "oauth")]
+    OAuth,
+    RegistrationNeeded {
+        /// The provider the user authenticated with
+        provider: String,
+        /// The user's ID with the provider
+        id: String,
+        /// The email the provider reported
+        email: String,
+    },
+    Authenticated {
+        /// The user's ID
+        id: i32,
+        /// The user's given/first name
+        #[serde(rename = "givenName")]
+        given_name: String,
+        /// The user's family/last name
+        #[serde(rename = "familyName")]
+        family_name: String,
+        /// The user's primary email
+        email: String,
+        /// The user's role within the scope, if they have one
+        role: Option<&'static str>,
+        /// Whether the user is an administrator
+        #[serde(rename = "isAdmin")]
+        is_admin: bool,
+        /// The user's role in each organization they are part of
+        organizations: Vec<BatchOrganizationRole>,
+        /// The slugs of the events the user participates in
+        events: Vec<String>,
+    },
+}
+
+impl BatchUser {
+    /// Build the user, including their memberships if they are authenticated
+    fn new(user: &UserContext, memberships: &Memberships) -> Self {
+        match user {
+            UserContext::Unauthenticated => BatchUser::Unauthenticated,
+            UserContext::OAuth => BatchUser::OAuth,
+            UserContext::RegistrationNeeded(user) => BatchUser::RegistrationNeeded {
+                provider: user.provider.clone(),
+                id: user.id.clone(),
+                email: user.email.clone(),
+            },
+            UserContext::Authenticated(user) => BatchUser::Authenticated {
+                id: user.id,
+                given_name: user.given_name.clone(),
+                family_name: user.family_name.clone(),
+                email: user.email.clone(),
+                role: user.role.as_ref().map(|role| match role {
+                    UserRole::Participant => "participant",
+                    UserRole::Organizer => "organizer",
+                    UserRole::Manager => "manager",
+                    UserRole::Director => "director",
+                }),
+                is_admin: user.is_admin,
+                organizations: memberships
+                    .organizations
+                    .iter()
+                    .map(|&(organization_id, role)| BatchOrganizationRole {
+                        organization_id,
+                        role: role.as_str(),
+                    })
+                    .collect(),
+                events: memberships.events.clone(),
+            },
+        }
+    }
+}
 
-    Ok(None)
+/// A user's role in an organization they are part of
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BatchOrganizationRole {
+    /// The organization's ID
+    organization_id: i32,
+    /// The user's role in the organization
+    role: &'static str,
 }
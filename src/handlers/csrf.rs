@@ -0,0 +1,101 @@
+use super::error::{Error, Result};
+use axum::http::{
+    header::{CONTENT_TYPE, ORIGIN},
+    HeaderMap,
+};
+use state::FrontendUrl;
+use url::Url;
+
+/// Ensure a request authenticated by the session cookie could not have been forged by another site
+///
+/// The session cookie is `SameSite=Lax`, so browsers also attach it to requests from sibling event
+/// subdomains. Requiring a JSON body forces browsers to preflight any cross-origin request, and
+/// the origin, when sent, must be either the domain being requested or the frontend.
+pub(crate) fn check(headers: &HeaderMap, host: &str, frontend_url: &FrontendUrl) -> Result<()> {
+    let is_json = headers
+        .get(CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.split(';').next())
+        .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"));
+    if !is_json {
+        return Err(Error::UnsupportedContentType);
+    }
+
+    let Some(origin) = headers.get(ORIGIN) else {
+        // Browsers always send an origin with POST requests, so this must be some other client
+        return Ok(());
+    };
+    let origin = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| Url::parse(origin).ok());
+    match origin {
+        Some(origin) if is_allowed(&origin, host, frontend_url) => Ok(()),
+        _ => Err(Error::CrossSiteRequest),
+    }
+}
+
+/// Whether the origin is the host the request was sent to, or the frontend
+fn is_allowed(origin: &Url, host: &str, frontend_url: &FrontendUrl) -> bool {
+    if origin.origin() == frontend_url.origin() {
+        return true;
+    }
+
+    match (origin.host_str(), origin.port()) {
+        (Some(name), Some(port)) => host.eq_ignore_ascii_case(&format!("{name}:{port}")),
+        (Some(name), None) => host.eq_ignore_ascii_case(name),
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    const HOST: &str = "api.example.com";
+
+    fn frontend() -> FrontendUrl {
+        Url::parse("https://accounts.example.com/").unwrap().into()
+    }
+
+    fn headers(content_type: &'static str, origin: Option<&'static str>) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        if let Some(origin) = origin {
+            headers.insert(ORIGIN, HeaderValue::from_static(origin));
+        }
+        headers
+    }
+
+    #[test]
+    fn requires_json() {
+        let cases = [
+            ("application/json", true),
+            ("application/json; charset=utf-8", true),
+            ("text/plain", false),
+            ("application/x-www-form-urlencoded", false),
+            ("multipart/form-data; boundary=abc", false),
+        ];
+        for (content_type, allowed) in cases {
+            let result = check(&headers(content_type, None), HOST, &frontend());
+            assert_eq!(result.is_ok(), allowed, "{content_type}");
+        }
+    }
+
+    #[test]
+    fn allows_same_origin_and_frontend() {
+        let cases = [
+            (None, true),
+            (Some("https://api.example.com"), true),
+            (Some("https://accounts.example.com"), true),
+            (Some("https://hackathon.example.com"), false),
+            (Some("https://api.example.com:8443"), false),
+            (Some("null"), false),
+        ];
+        for (origin, allowed) in cases {
+            let result = check(&headers("application/json", origin), HOST, &frontend());
+            assert_eq!(result.is_ok(), allowed, "{origin:?}");
+        }
+    }
+}
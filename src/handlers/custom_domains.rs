@@ -0,0 +1,84 @@
+use super::error::{Error, Result};
+use axum::{
+    extract::{Json, Path, State},
+    http::StatusCode,
+};
+use chrono::{DateTime, Utc};
+use database::{CertificateStatus, CustomDomain, Pool};
+use serde::Deserialize;
+use tracing::{info, instrument, Span};
+use utoipa::ToSchema;
+
+/// Record the status of a custom domain's TLS certificate, as reported by the edge proxy
+#[utoipa::path(
+    put,
+    path = "/custom-domains/{name}/certificate",
+    tag = "custom-domains",
+    params(("name" = String, Path, description = "The custom domain the certificate is for")),
+    request_body = CertificateReport,
+    responses(
+        (status = 204, description = "The status was recorded"),
+        (status = 401, description = "The request signature is missing or invalid"),
+        (status = 404, description = "The custom domain does not exist"),
+    ),
+)]
+#[instrument(name = "custom_domains::report_certificate", skip_all, fields(%name, status))]
+pub(crate) async fn report_certificate(
+    Path(name): Path<String>,
+    State(db): State<Pool>,
+    Json(report): Json<CertificateReport>,
+) -> Result<StatusCode> {
+    let status = CertificateStatus::from(report.status);
+    Span::current().record("status", status.as_str());
+
+    let error = report.error.as_deref().filter(|error| !error.is_empty());
+    if CustomDomain::report_certificate(&name, status, error, report.expires_at, db.writer())
+        .await?
+        .is_none()
+    {
+        return Err(Error::CustomDomainNotFound);
+    }
+
+    info!("recorded certificate status");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The state of a custom domain's certificate
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct CertificateReport {
+    /// Where the certificate is in the provisioning process
+    status: ReportedStatus,
+    /// Why the certificate could not be provisioned, if it failed
+    error: Option<String>,
+    /// When the current certificate expires, once one has been issued
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Where a certificate is in the provisioning process
+#[derive(Clone, Copy, Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ReportedStatus {
+    /// A certificate has not been requested yet
+    Pending,
+    /// A certificate is being requested from the certificate authority
+    Issuing,
+    /// The certificate was issued and the domain is serving HTTPS
+    Issued,
+    /// The certificate could not be issued
+    Failed,
+    /// The certificate expired without being renewed
+    Expired,
+}
+
+impl From<ReportedStatus> for CertificateStatus {
+    fn from(status: ReportedStatus) -> Self {
+        match status {
+            ReportedStatus::Pending => CertificateStatus::Pending,
+            ReportedStatus::Issuing => CertificateStatus::Issuing,
+            ReportedStatus::Issued => CertificateStatus::Issued,
+            ReportedStatus::Failed => CertificateStatus::Failed,
+            ReportedStatus::Expired => CertificateStatus::Expired,
+        }
+    }
+}
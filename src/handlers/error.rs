@@ -3,6 +3,7 @@ use axum::{
     response::{IntoResponse, Json, Response},
 };
 use serde::Serialize;
+use state::RequestId;
 use std::fmt::{Display, Formatter};
 use tracing::error;
 
@@ -13,6 +14,16 @@ pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
 pub(crate) enum Error {
     /// Could not find the specified event
     EventNotFound,
+    /// The specified event has been archived
+    EventArchived,
+    /// Too many requests were sent in a batch
+    BatchTooLarge,
+    /// Could not find the specified custom domain
+    CustomDomainNotFound,
+    /// The request body was not JSON
+    UnsupportedContentType,
+    /// The request was sent from an origin that is not allowed
+    CrossSiteRequest,
     Database(database::Error),
     Session(session::Error),
 }
@@ -21,6 +32,11 @@ impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::EventNotFound => write!(f, "unknown event"),
+            Self::EventArchived => write!(f, "event archived"),
+            Self::BatchTooLarge => write!(f, "too many requests in batch"),
+            Self::CustomDomainNotFound => write!(f, "unknown custom domain"),
+            Self::UnsupportedContentType => write!(f, "unsupported content type"),
+            Self::CrossSiteRequest => write!(f, "cross-site request"),
             Self::Database(_) => write!(f, "unexpected database error"),
             Self::Session(_) => write!(f, "unexpected session error"),
         }
@@ -32,7 +48,12 @@ impl std::error::Error for Error {
         match self {
             Self::Database(e) => Some(e),
             Self::Session(e) => Some(e),
-            Self::EventNotFound => None,
+            Self::EventNotFound
+            | Self::EventArchived
+            | Self::BatchTooLarge
+            | Self::CustomDomainNotFound
+            | Self::UnsupportedContentType
+            | Self::CrossSiteRequest => None,
         }
     }
 }
@@ -45,6 +66,25 @@ impl IntoResponse for Error {
             Self::EventNotFound => {
                 return ApiError::response("unknown event", StatusCode::UNPROCESSABLE_ENTITY)
             }
+            Self::EventArchived => return ApiError::response("event archived", StatusCode::GONE),
+            Self::BatchTooLarge => {
+                return ApiError::response(
+                    "too many requests in batch",
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                )
+            }
+            Self::CustomDomainNotFound => {
+                return ApiError::response("unknown custom domain", StatusCode::NOT_FOUND)
+            }
+            Self::UnsupportedContentType => {
+                return ApiError::response(
+                    "unsupported content type",
+                    StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                )
+            }
+            Self::CrossSiteRequest => {
+                return ApiError::response("cross-site request", StatusCode::FORBIDDEN)
+            }
             Self::Database(error) => match error.source() {
                 Some(source) => error!(%error, %source, "unexpected database error"),
                 None => error!(%error, "unexpected database error"),
@@ -79,22 +119,23 @@ impl From<InvalidUri> for Error {
 }
 
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ApiError {
     message: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl ApiError {
     fn response(message: &'static str, status: StatusCode) -> Response {
-        (status, Json(ApiError { message })).into_response()
+        let body = ApiError {
+            message,
+            request_id: RequestId::current().map(|id| id.to_string()),
+        };
+        (status, Json(body)).into_response()
     }
 
     fn internal_server_error() -> Response {
-        (
-            StatusCode::INTERNAL_SERVER_ERROR,
-            Json(ApiError {
-                message: "internal server error",
-            }),
-        )
-            .into_response()
+        ApiError::response("internal server error", StatusCode::INTERNAL_SERVER_ERROR)
     }
 }
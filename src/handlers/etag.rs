@@ -0,0 +1,96 @@
+use axum::{
+    body::{self, Body},
+    extract::Request,
+    http::{
+        header::{ETAG, IF_NONE_MATCH},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use sha2::{Digest, Sha256};
+use tracing::error;
+
+/// Headers describing how long a response is fresh for, which change between otherwise identical
+/// responses
+const FRESHNESS_HEADERS: [&str; 3] = ["cache-control", "expires", "session-expires"];
+
+/// Tag successful responses with a weak ETag computed from their headers and body, replying with
+/// `304 Not Modified` and no content when the client already has the same response
+///
+/// This allows the gateway to cheaply revalidate the context it has for a request. Weak tags are
+/// used as the representation is only semantically equivalent, header order may differ.
+pub(crate) async fn etag(request: Request, next: Next) -> Response {
+    let if_none_match = request.headers().get(IF_NONE_MATCH).cloned();
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let body = match body::to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(error) => {
+            error!(%error, "failed to read response body");
+            return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+        }
+    };
+
+    let tag = compute(&parts.headers, &body);
+    if if_none_match.is_some_and(|header| matches(&header, &tag)) {
+        // Freshness must be repeated so the client can update its stored response
+        let mut response = (StatusCode::NOT_MODIFIED, [(ETAG, tag)]).into_response();
+        for name in FRESHNESS_HEADERS {
+            if let Some(value) = parts.headers.remove(name) {
+                response.headers_mut().insert(name, value);
+            }
+        }
+        return response;
+    }
+
+    parts.headers.insert(ETAG, tag);
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Compute the weak ETag for a response, independent of the order of its headers and how long
+/// it is fresh for
+fn compute(headers: &HeaderMap, body: &[u8]) -> HeaderValue {
+    let mut entries = headers
+        .iter()
+        .filter(|(name, _)| !FRESHNESS_HEADERS.contains(&name.as_str()))
+        .map(|(name, value)| (name.as_str(), value.as_bytes()))
+        .collect::<Vec<_>>();
+    entries.sort_unstable();
+
+    let mut hasher = Sha256::new();
+    for (name, value) in entries {
+        hasher.update(name.as_bytes());
+        hasher.update(b":");
+        hasher.update(value);
+        hasher.update(b"\n");
+    }
+    hasher.update(body);
+
+    let digest = hasher.finalize();
+    let tag = format!("W/\"{}\"", hex::encode(&digest[..16]));
+    HeaderValue::try_from(tag).expect("etag must be a valid header")
+}
+
+/// Check whether an `If-None-Match` header matches the tag using weak comparison
+fn matches(header: &HeaderValue, tag: &HeaderValue) -> bool {
+    let Ok(header) = header.to_str() else {
+        return false;
+    };
+
+    let tag = opaque(tag.to_str().expect("etag must be ascii"));
+    header
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == tag)
+}
+
+/// Strip the weakness indicator from a tag
+fn opaque(tag: &str) -> &str {
+    let tag = tag.trim();
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
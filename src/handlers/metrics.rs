@@ -0,0 +1,66 @@
+use axum::{extract::State, http::header::CONTENT_TYPE, response::IntoResponse};
+use database::{Pool, Utilization};
+use std::fmt::Write;
+
+/// The content type of the Prometheus text exposition format
+const CONTENT_TYPE_PROMETHEUS: &str = "text/plain; version=0.0.4";
+
+/// Get how the database connection pools are being used, in the Prometheus text format
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "meta",
+    responses((status = 200, description = "The metrics for the service", content_type = "text/plain")),
+)]
+pub(crate) async fn metrics(State(db): State<Pool>) -> impl IntoResponse {
+    let utilization = db.utilization().await;
+
+    let mut body = String::new();
+    gauge(
+        &mut body,
+        "database_pool_connections",
+        "How many connections are open",
+        &utilization,
+        |pool| Some(f64::from(pool.size)),
+    );
+    gauge(
+        &mut body,
+        "database_pool_idle_connections",
+        "How many open connections are not in use",
+        &utilization,
+        |pool| Some(pool.idle as f64),
+    );
+    gauge(
+        &mut body,
+        "database_pool_max_connections",
+        "The most connections that can be open at once",
+        &utilization,
+        |pool| Some(f64::from(pool.max_connections)),
+    );
+    gauge(
+        &mut body,
+        "database_pool_acquire_seconds",
+        "How long it took to acquire a connection when scraped",
+        &utilization,
+        |pool| pool.acquire_time.map(|time| time.as_secs_f64()),
+    );
+
+    ([(CONTENT_TYPE, CONTENT_TYPE_PROMETHEUS)], body)
+}
+
+/// Write a gauge with a sample for each pool, skipping those without a value
+fn gauge<F>(body: &mut String, name: &str, help: &str, utilization: &[Utilization], value: F)
+where
+    F: Fn(&Utilization) -> Option<f64>,
+{
+    writeln!(body, "# HELP {name} {help}").unwrap();
+    writeln!(body, "# TYPE {name} gauge").unwrap();
+    for pool in utilization {
+        let Some(value) = value(pool) else { continue };
+        match pool.replica {
+            Some(replica) => writeln!(body, "{name}{{pool=\"replica-{replica}\"}} {value}"),
+            None => writeln!(body, "{name}{{pool=\"primary\"}} {value}"),
+        }
+        .unwrap();
+    }
+}
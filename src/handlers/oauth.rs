@@ -1,24 +1,47 @@
 use crate::state::AppState;
 use axum::{
-    extract::{Json, Path, Query, State},
-    response::Redirect,
+    extract::{Form, Host as ExtractHost, Json, Path, Query, State},
+    http::{header::SET_COOKIE, HeaderMap},
+    response::{AppendHeaders, Html, IntoResponse, Redirect, Response},
 };
-use database::{CustomDomain, Identity, PgPool, Provider, User};
+use chrono::Utc;
+use database::{Event, Identity, Organization, OrganizationEmailDomain, PgPool, User};
+use email::{Branding, NewLogin, Welcome};
+use graphql::{FeatureFlags, Flag, ProviderCache};
 use serde::{Deserialize, Serialize};
-use session::extract::{
-    CurrentUser, Mutable, OAuthSession, RegistrationNeededSession, UnauthenticatedSession,
-};
-use state::{AllowedRedirectDomains, ApiUrl, FrontendUrl};
-use tracing::{error, info, instrument, Span};
+use session::extract::{Mutable, OAuthSession, RegistrationNeededSession, UnauthenticatedSession};
+use state::{AllowedRedirectDomains, ApiUrl, Domains, FrontendUrl};
+use tracing::{error, info, instrument, warn, Span};
 use url::{Host, Url};
+use utoipa::{IntoParams, ToSchema};
 
 mod client;
 mod error;
+mod user;
 
 pub(crate) use client::Client;
 use error::{Error, Result};
+use user::CurrentUser;
+
+/// The parameter of the return URL that carries the nonce binding a custom domain session handoff
+/// to the browser that started it
+const HANDOFF_NONCE_PARAM: &str = "session-handoff";
 
 /// Start the OAuth2 login flow
+#[utoipa::path(
+    get,
+    path = "/oauth/launch/{provider}",
+    tag = "oauth",
+    params(
+        ("provider" = String, Path, description = "The slug of the provider to login with"),
+        LaunchParams,
+    ),
+    responses(
+        (status = 303, description = "Redirect to the provider to authenticate"),
+        (status = 400, description = "The return URL is not allowed"),
+        (status = 404, description = "The provider does not exist"),
+    ),
+)]
 #[instrument(
 name = "oauth::launch", skip_all,
 fields(
@@ -30,10 +53,14 @@ pub(crate) async fn launch(
     Path(slug): Path<String>,
     Query(params): Query<LaunchParams>,
     session: UnauthenticatedSession<Mutable>,
+    State(sessions): State<session::Manager>,
     State(url): State<ApiUrl>,
     State(client): State<Client>,
     State(db): State<PgPool>,
     State(allowed_redirect_domains): State<AllowedRedirectDomains>,
+    State(domains): State<Domains>,
+    State(feature_flags): State<FeatureFlags>,
+    State(providers): State<ProviderCache>,
 ) -> Result<Redirect> {
     if let Some(return_to) = &params.return_to {
         if !redirect_url_is_valid(return_to, &db, allowed_redirect_domains).await? {
@@ -41,9 +68,20 @@ pub(crate) async fn launch(
         }
     }
 
-    if let Some(provider) = Provider::find_enabled(&slug, &db).await? {
+    // Providers can be rolled out to specific events before being enabled everywhere
+    let event = match &params.return_to {
+        Some(url) => event_for_url(url, &domains, &db).await?,
+        None => None,
+    };
+    let event = event.as_ref().map(|event| event.slug.as_str());
+    if !feature_flags.is_enabled(Flag::Provider(&slug), event).await {
+        return Err(Error::UnknownProvider);
+    }
+
+    if let Some(provider) = providers.find_enabled(&slug).await? {
         let redirect_url = url.join("/oauth/callback");
-        let (url, state) = client.build_authorization_url(&provider.config, redirect_url.as_str());
+        let state = sessions.oauth_state(params.return_to.as_ref());
+        let url = client.build_authorization_url(&provider.config, redirect_url.as_str(), &state);
 
         session.into_oauth(provider.slug, state, params.return_to);
 
@@ -76,11 +114,14 @@ async fn redirect_url_is_valid(
     if allowed_redirect_domains.matches(domain) {
         Ok(true)
     } else {
-        Ok(CustomDomain::exists(domain, db).await?)
+        // Logins for archived events are not allowed
+        let event = Event::find_by_custom_domain(domain, db).await?;
+        Ok(event.is_some_and(|event| !event.is_archived()))
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 #[serde(rename_all = "kebab-case")]
 pub(crate) struct LaunchParams {
     /// The URL to redirect the user back to
@@ -88,6 +129,24 @@ pub(crate) struct LaunchParams {
 }
 
 /// Handle provider redirects and complete the login flow
+#[utoipa::path(
+    get,
+    path = "/oauth/callback",
+    tag = "oauth",
+    params(
+        ("state" = String, Query, description = "The state sent to the provider"),
+        ("code" = Option<String>, Query, description = "The authorization code, if successful"),
+        ("error" = Option<String>, Query, description = "Why authorization failed, if unsuccessful"),
+        ("error_description" = Option<String>, Query),
+        ("error_uri" = Option<String>, Query),
+    ),
+    responses(
+        (status = 303, description = "Redirect to where the user came from, or to complete their registration"),
+        (status = 400, description = "The state does not match"),
+        (status = 403, description = "The user is banned or suspended"),
+        (status = 404, description = "The provider does not exist"),
+    ),
+)]
 #[instrument(
 name = "oauth::callback",
 skip_all,
@@ -101,17 +160,38 @@ return_to = session.return_to.as_ref().map(| u | u.as_str()).unwrap_or_default()
 )]
 pub(crate) async fn callback(
     Query(params): Query<CallbackParams>,
+    headers: HeaderMap,
     session: OAuthSession,
     State(state): State<AppState>,
 ) -> Result<Redirect> {
+    // Repeated failures could mean the provider is misconfigured or someone is tampering with logins
+    let record_failure = || {
+        if let Some(alerter) = &state.alerter {
+            alerter.on_oauth_failure(&session.provider);
+        }
+    };
+
     if params.state != session.state {
+        record_failure();
+        return Err(Error::InvalidState);
+    }
+
+    // Guards against the stored return URL being changed after it was validated
+    if !state
+        .sessions
+        .verify_oauth_state(&session.state, session.return_to.as_ref())
+    {
+        warn!("oauth state signature does not match the return url");
+        record_failure();
         return Err(Error::InvalidState);
     }
 
     let code = params.result.into_code(&state.frontend_url)?;
 
     // Allow in-flight OAuth2 flows to finish even if it the provider was disabled
-    let provider = Provider::find(&session.provider, &state.db)
+    let provider = state
+        .providers
+        .find(&session.provider)
         .await?
         .ok_or(Error::UnknownProvider)?;
 
@@ -122,42 +202,134 @@ pub(crate) async fn callback(
             state.api_url.join("/oauth/callback").as_str(),
             &provider.config,
         )
-        .await?;
+        .await
+        .inspect_err(|_| record_failure())?;
 
     let user_info = state
         .oauth_client
         .user_info(&token, &provider.config)
-        .await?;
+        .await
+        .inspect_err(|_| record_failure())?;
 
     Span::current().record("provider.id", &user_info.id);
     info!("oauth2 flow complete");
 
-    match Identity::find_by_remote_id(&session.provider, &user_info.id, &state.db).await? {
-        Some(identity) => {
+    match Identity::find_by_remote_id(&session.provider, &user_info.id, state.db.writer()).await? {
+        Some(mut identity) => {
             info!(user.id = identity.user_id, "found existing user");
 
-            // TODO: handle updating identity email & user primary email if necessary
+            let user = User::find(identity.user_id, state.db.writer()).await?;
+            if user.is_some_and(|user| user.is_banned()) {
+                info!(user.id = identity.user_id, "refusing login for banned user");
+                return Err(Error::Banned);
+            }
 
-            let url = session
-                .return_to
-                .as_ref()
-                .map(|u| u.as_str())
-                .unwrap_or_else(|| state.frontend_url.as_str())
-                .to_owned(); // satisfying the borrow checker :(
+            identity.record_login(state.db.writer()).await?;
 
+            // TODO: handle updating identity email & user primary email if necessary
+
+            let url = match &session.return_to {
+                Some(return_to) => {
+                    hand_off_to_custom_domain(
+                        &state,
+                        identity.user_id,
+                        session.session_id(),
+                        return_to,
+                    )
+                    .await?
+                }
+                None => state.frontend_url.as_str().to_owned(),
+            };
+
+            // A failure to notify shouldn't prevent the user from logging in
+            let session_id = session.session_id();
+            if let Err(error) =
+                notify_if_unfamiliar(&state, identity.user_id, session_id, &headers).await
+            {
+                error!(?error, "failed to send new login notification");
+            }
             session.into_authenticated(identity.user_id);
 
             Ok(Redirect::to(&url))
         }
         None => {
             info!("user does not yet exist");
-            session.into_registration_needed(user_info.id, user_info.email);
+            session.into_registration_needed(
+                user_info.id,
+                user_info.email,
+                user_info.email_verified,
+            );
 
             Ok(Redirect::to(state.frontend_url.join("/signup").as_str()))
         }
     }
 }
 
+/// Email the user if they logged in from a device or location none of their other sessions have
+/// been used from, allowing them to revoke the new session if it wasn't them
+#[instrument(skip_all, fields(unfamiliar))]
+async fn notify_if_unfamiliar(
+    state: &AppState,
+    user_id: i32,
+    session_id: &str,
+    headers: &HeaderMap,
+) -> Result<()> {
+    let Some(mailer) = &state.mailer else {
+        return Ok(());
+    };
+
+    let ip = session::client_ip(headers);
+    let user_agent = session::user_agent(headers);
+
+    // A failure to check shouldn't prevent the user from logging in
+    let others = match state.sessions.for_user(user_id).await {
+        Ok(sessions) => sessions,
+        Err(error) => {
+            error!(%error, "failed to load sessions");
+            return Ok(());
+        }
+    };
+
+    // The first session can't be compared against anything
+    let others = others
+        .iter()
+        .filter(|other| other.id() != session_id)
+        .collect::<Vec<_>>();
+    let unfamiliar = !others.is_empty()
+        && !others.iter().any(|other| {
+            (ip.is_some() && other.ip() == ip)
+                || (user_agent.is_some() && other.user_agent() == user_agent)
+        });
+    Span::current().record("unfamiliar", unfamiliar);
+    if !unfamiliar {
+        return Ok(());
+    }
+
+    let Some(user) = User::find(user_id, state.db.writer()).await? else {
+        return Ok(());
+    };
+
+    let mut revoke_url = state.api_url.join("/oauth/revoke-session");
+    revoke_url.query_pairs_mut().append_pair(
+        "token",
+        &state.sessions.revocation_token(user_id, session_id),
+    );
+
+    mailer.send(
+        &user.primary_email,
+        &NewLogin {
+            given_name: user.given_name,
+            logged_in_at: Utc::now().format("%B %-d, %Y at %H:%M UTC").to_string(),
+            ip: ip.map(ToOwned::to_owned),
+            user_agent: user_agent.map(ToOwned::to_owned),
+            revoke_url: revoke_url.into(),
+        },
+    );
+    info!("sent new login notification");
+
+    Ok(())
+}
+
 /// Params for an OAuth2 authorization code callback as defined by
 /// [RFC6479 Section 4.1.2](https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.2)
 #[derive(Debug, Deserialize)]
@@ -219,6 +391,18 @@ impl CallbackResult {
     }
 }
 
+/// Create an account for a user who authenticated with a provider for the first time
+#[utoipa::path(
+    post,
+    path = "/oauth/complete-registration",
+    tag = "oauth",
+    request_body = RegistrationForm,
+    responses(
+        (status = 200, description = "The account was created", body = RegistrationResponse),
+        (status = 400, description = "A name is missing, or the return URL was tampered with"),
+        (status = 409, description = "The email already belongs to another user"),
+    ),
+)]
 #[instrument(name = "oauth::complete_registration", skip(state, session), fields(user.id = session.id))]
 pub(crate) async fn complete_registration(
     State(state): State<AppState>,
@@ -234,6 +418,18 @@ pub(crate) async fn complete_registration(
         return Err(Error::InvalidParameter("familyName"));
     }
 
+    // The return URL is used for redirects and handoffs below, so it must still be the one the
+    // OAuth state was signed for
+    if session.return_to.is_some()
+        && !state.sessions.verify_oauth_state(
+            session.oauth_state.as_deref().unwrap_or_default(),
+            session.return_to.as_ref(),
+        )
+    {
+        warn!("oauth state signature does not match the return url");
+        return Err(Error::InvalidState);
+    }
+
     let return_to = session
         .return_to
         .as_ref()
@@ -241,34 +437,330 @@ pub(crate) async fn complete_registration(
         .unwrap_or_else(|| state.frontend_url.as_str())
         .to_owned(); // satisfying the borrow checker :(
 
-    let mut txn = state.db.begin().await?;
+    let registered_through = session.return_to.clone();
+    let session_id = session.session_id().to_owned();
+    let event = match &registered_through {
+        Some(url) => event_for_url(url, &state.domains, state.db.writer()).await?,
+        None => None,
+    };
 
-    let maybe_user = User::create(given_name, family_name, &session.email, &mut *txn).await;
-    match maybe_user {
-        Ok(user) => {
-            Identity::link(
-                &session.provider,
-                user.id,
-                &session.id,
-                &session.email,
-                &mut *txn,
-            )
-            .await?;
+    let (given_name, family_name) = (given_name.to_owned(), family_name.to_owned());
+    let (provider, id, email) = (
+        session.provider.clone(),
+        session.id.clone(),
+        session.email.clone(),
+    );
+    let email_verified = session.email_verified;
+    let slug = event.as_ref().map(|event| event.slug.clone());
+    let webhooks = state.webhooks.clone();
+    let result = database::with_txn(state.db.writer(), move |txn| {
+        Box::pin(async move {
+            // Accounts created ahead of time, such as by an import, are claimed by the first
+            // login that proves it owns the email
+            let unclaimed = if email_verified {
+                User::find_unclaimed_by_email(&email, &mut *txn).await?
+            } else {
+                None
+            };
+            let user = match unclaimed {
+                Some(user) => {
+                    info!(user.id, "claimed existing account");
+                    user
+                }
+                None => User::create(&given_name, &family_name, &email, &mut *txn).await?,
+            };
+            Identity::link(&provider, user.id, &id, &email, &mut *txn).await?;
+
+            if email_verified {
+                let organizations =
+                    OrganizationEmailDomain::auto_join(user.id, &email, &mut *txn).await?;
+                if !organizations.is_empty() {
+                    info!(
+                        user.id,
+                        ?organizations,
+                        "automatically joined organizations"
+                    );
+                }
+            }
 
+            let queued = webhooks
+                .on_user_created(&user, slug.as_deref(), txn)
+                .await?;
+            Ok((user, queued))
+        })
+    })
+    .await;
+    let user = match result {
+        Ok((user, queued)) => {
+            state.webhooks.committed(queued);
             session.into_authenticated(user.id);
+            Some(user)
+        }
+        Err(e) if e.is_unique_violation() => {
+            // The identity only exists if the account was created by a concurrent request,
+            // otherwise the email is one of another user's addresses
+            let existing =
+                Identity::find_by_remote_id(&session.provider, &session.id, state.db.writer())
+                    .await?;
+            if existing.is_none() {
+                return Err(Error::EmailInUse);
+            }
+
+            None
         }
-        Err(e) if e.is_unique_violation() => {}
         Err(e) => return Err(Error::Database(e)),
+    };
+
+    // The account already exists, so failing to welcome the user shouldn't fail their registration
+    let mut redirect_uri = return_to.clone();
+    if let Some(user) = user {
+        if let Err(error) = welcome(&state, &user, event, &return_to).await {
+            error!(?error, "failed to welcome user");
+        }
+
+        if let Some(registered_through) = &registered_through {
+            redirect_uri =
+                hand_off_to_custom_domain(&state, user.id, &session_id, registered_through).await?;
+        }
     }
 
-    txn.commit().await?;
+    Ok(Json(RegistrationResponse { redirect_uri }))
+}
 
-    Ok(Json(RegistrationResponse {
-        redirect_uri: return_to,
-    }))
+/// Get where to send a user after logging in. When custom domain sessions are enabled and the login
+/// was started on an event's custom domain, they are sent through the domain's session endpoint
+/// first so it gets its own session.
+async fn hand_off_to_custom_domain(
+    state: &AppState,
+    user_id: i32,
+    session_id: &str,
+    return_to: &Url,
+) -> Result<String> {
+    if !state.custom_domain_sessions {
+        return Ok(return_to.to_string());
+    }
+
+    // Only the browser that started the login on the custom domain can redeem the handoff
+    let Some(nonce) = return_to
+        .query_pairs()
+        .find(|(key, _)| key == HANDOFF_NONCE_PARAM)
+        .map(|(_, value)| value.into_owned())
+    else {
+        return Ok(return_to.to_string());
+    };
+    let mut return_to = return_to.clone();
+    let query = return_to
+        .query_pairs()
+        .filter(|(key, _)| key != HANDOFF_NONCE_PARAM)
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect::<Vec<_>>();
+    return_to.set_query(None);
+    if !query.is_empty() {
+        return_to.query_pairs_mut().extend_pairs(query);
+    }
+
+    let Some(Host::Domain(domain)) = return_to.host() else {
+        return Ok(return_to.to_string());
+    };
+    if state.domains.extract_slug_for_subdomain(domain).is_some()
+        || Event::find_by_custom_domain(domain, state.db.writer())
+            .await?
+            .is_none()
+    {
+        return Ok(return_to.to_string());
+    }
+
+    let code = state
+        .sessions
+        .create_handoff(user_id, domain, session_id, &nonce)
+        .await?;
+
+    let mut url = return_to
+        .join("/oauth/session")
+        .expect("path must be valid");
+    url.query_pairs_mut()
+        .append_pair("code", &code)
+        .append_pair("return-to", return_to.as_str());
+    Ok(url.into())
 }
 
-#[derive(Debug, Deserialize)]
+/// Begin logging in from an event's custom domain, binding the session that will be handed back
+/// to this browser before sending it to login
+#[utoipa::path(
+    get,
+    path = "/oauth/session/start",
+    tag = "oauth",
+    params(StartCustomDomainSessionParams),
+    responses(
+        (status = 303, description = "Redirect to login with the handoff nonce cookie set"),
+        (status = 400, description = "The domain is not an event's custom domain"),
+    ),
+)]
+#[instrument(name = "oauth::start_custom_domain_session", skip_all, fields(%host))]
+pub(crate) async fn start_custom_domain_session(
+    ExtractHost(host): ExtractHost,
+    Query(params): Query<StartCustomDomainSessionParams>,
+    State(db): State<PgPool>,
+    State(frontend_url): State<FrontendUrl>,
+    State(sessions): State<session::Manager>,
+) -> Result<impl IntoResponse> {
+    let domain = host.split(':').next().unwrap_or_default();
+    if Event::find_by_custom_domain(domain, &db).await?.is_none() {
+        return Err(Error::InvalidParameter("host"));
+    }
+
+    // Only return to the domain the session is being started on
+    let mut return_to = params
+        .return_to
+        .filter(|url| url.host_str() == Some(domain))
+        .or_else(|| Url::parse(&format!("https://{domain}/")).ok())
+        .ok_or(Error::InvalidParameter("host"))?;
+
+    let (nonce, cookie) = sessions.start_handoff();
+    return_to
+        .query_pairs_mut()
+        .append_pair(HANDOFF_NONCE_PARAM, &nonce);
+
+    let mut login = frontend_url.join("/login");
+    login
+        .query_pairs_mut()
+        .append_pair("return-to", return_to.as_str());
+
+    Ok((
+        [(SET_COOKIE, cookie.to_string())],
+        Redirect::to(login.as_str()),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct StartCustomDomainSessionParams {
+    /// The URL on the custom domain to return the user to once they are logged in
+    return_to: Option<Url>,
+}
+
+/// Start a session on an event's custom domain after the user logged in, setting a first-party
+/// cookie on it
+#[utoipa::path(
+    get,
+    path = "/oauth/session",
+    tag = "oauth",
+    params(CustomDomainSessionParams),
+    responses(
+        (status = 303, description = "Redirect to the return URL with the session cookie set"),
+        (status = 400, description = "The code is invalid, expired, for a different domain, or was not started by this browser"),
+    ),
+)]
+#[instrument(name = "oauth::custom_domain_session", skip_all, fields(%host))]
+pub(crate) async fn custom_domain_session(
+    ExtractHost(host): ExtractHost,
+    headers: HeaderMap,
+    Query(params): Query<CustomDomainSessionParams>,
+    State(sessions): State<session::Manager>,
+) -> Result<impl IntoResponse> {
+    let domain = host.split(':').next().unwrap_or_default();
+    let Some([session, nonce]) = sessions
+        .redeem_handoff(&params.code, domain, &headers)
+        .await?
+    else {
+        return Err(Error::InvalidParameter("code"));
+    };
+
+    // Only return to the domain the session was started on
+    let url = params
+        .return_to
+        .filter(|url| url.host_str() == Some(domain))
+        .map(String::from)
+        .unwrap_or_else(|| String::from("/"));
+
+    Ok((
+        AppendHeaders([
+            (SET_COOKIE, session.to_string()),
+            (SET_COOKIE, nonce.to_string()),
+        ]),
+        Redirect::to(&url),
+    ))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct CustomDomainSessionParams {
+    /// The single-use code created after logging in
+    code: String,
+    /// The URL on the custom domain to redirect the user to
+    return_to: Option<Url>,
+}
+
+/// Send a newly registered user a welcome email, branded for the event they registered through if
+/// there is one
+#[instrument(skip_all, fields(%user.id, event))]
+async fn welcome(
+    state: &AppState,
+    user: &User,
+    event: Option<Event>,
+    continue_url: &str,
+) -> Result<()> {
+    let slug = event.as_ref().map(|event| event.slug.as_str());
+    Span::current().record("event", slug);
+
+    let Some(mailer) = &state.mailer else {
+        return Ok(());
+    };
+
+    let email = Welcome {
+        given_name: user.given_name.clone(),
+        event_name: event.as_ref().map(|event| event.name.clone()),
+        message: event
+            .as_ref()
+            .and_then(|event| event.email_welcome_message.clone()),
+        continue_url: continue_url.to_owned(),
+    };
+    match event {
+        Some(event) => {
+            let branding = branding(&event, state.db.writer()).await?;
+            mailer.send_branded(&user.primary_email, &email, &branding);
+        }
+        None => mailer.send(&user.primary_email, &email),
+    }
+    info!("sent welcome email");
+
+    Ok(())
+}
+
+/// Find the event a URL belongs to, either by its subdomain or its custom domain
+async fn event_for_url(url: &Url, domains: &Domains, db: &PgPool) -> Result<Option<Event>> {
+    let Some(Host::Domain(domain)) = url.host() else {
+        return Ok(None);
+    };
+
+    let event = match domains.extract_slug_for_subdomain(domain) {
+        Some(slug) => Event::find(slug, db).await?,
+        None => Event::find_by_custom_domain(domain, db).await?,
+    };
+    Ok(event)
+}
+
+/// Get how emails sent on behalf of the event should look, using the organization's logo if the
+/// event doesn't have its own
+async fn branding(event: &Event, db: &PgPool) -> Result<Branding> {
+    let logo_url = match &event.email_logo_url {
+        Some(url) => Some(url.clone()),
+        None => Organization::find(event.organization_id, db)
+            .await?
+            .and_then(|organization| organization.logo),
+    };
+
+    Ok(Branding {
+        name: Some(event.name.clone()),
+        logo_url,
+        accent_color: event.email_accent_color.clone(),
+        reply_to: event.email_reply_to.clone(),
+    })
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RegistrationForm {
     /// The user's given/first name
@@ -277,19 +769,136 @@ pub(crate) struct RegistrationForm {
     family_name: String,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub(crate) struct RegistrationResponse {
     /// The URL the client should redirect to
     redirect_uri: String,
 }
 
-#[instrument(name = "oauth::logout", skip_all, fields(user.id = session.id))]
+/// The page confirming a session should be revoked, so following the link alone changes nothing
+const REVOKE_SESSION_PAGE: &str = include_str!("oauth/revoke_session.html");
+
+/// Confirm revoking a session from the link in a new login notification
+#[utoipa::path(
+    get,
+    path = "/oauth/revoke-session",
+    tag = "oauth",
+    params(RevokeSessionParams),
+    responses(
+        (status = 200, description = "A page asking the user to confirm revoking the session"),
+        (status = 303, description = "Redirect to the login page if the token is invalid"),
+    ),
+)]
+#[instrument(name = "oauth::confirm_revoke_session", skip_all)]
+pub(crate) async fn confirm_revoke_session(
+    Query(params): Query<RevokeSessionParams>,
+    State(sessions): State<session::Manager>,
+    State(frontend_url): State<FrontendUrl>,
+) -> Response {
+    // Only valid tokens are rendered, which are always URL-safe base64
+    if !sessions.is_revocation_token_valid(&params.token) {
+        return revoke_session_outcome(&frontend_url, false).into_response();
+    }
+
+    Html(REVOKE_SESSION_PAGE.replace("{token}", &params.token)).into_response()
+}
+
+/// Revoke a session once the user confirms it, without needing to be logged in
+#[utoipa::path(
+    post,
+    path = "/oauth/revoke-session",
+    tag = "oauth",
+    request_body(content = RevokeSessionParams, content_type = "application/x-www-form-urlencoded"),
+    responses((status = 303, description = "Redirect to the login page with the outcome")),
+)]
+#[instrument(name = "oauth::revoke_session", skip_all, fields(revoked))]
+pub(crate) async fn revoke_session(
+    State(sessions): State<session::Manager>,
+    State(frontend_url): State<FrontendUrl>,
+    Form(params): Form<RevokeSessionParams>,
+) -> Result<Redirect> {
+    let revoked = sessions.revoke_with_token(&params.token).await?;
+    Span::current().record("revoked", revoked);
+
+    Ok(revoke_session_outcome(&frontend_url, revoked))
+}
+
+/// Send the user to the login page, telling them whether the session was revoked
+fn revoke_session_outcome(frontend_url: &FrontendUrl, revoked: bool) -> Redirect {
+    let mut url = frontend_url.join("/login");
+    url.query_pairs_mut().append_pair(
+        "status",
+        if revoked {
+            "session-revoked"
+        } else {
+            "session-not-found"
+        },
+    );
+
+    Redirect::to(url.as_str())
+}
+
+#[derive(Debug, Deserialize, IntoParams, ToSchema)]
+#[into_params(parameter_in = Query)]
+pub(crate) struct RevokeSessionParams {
+    /// The token from the notification email
+    token: String,
+}
+
+/// End the current user's session
+#[utoipa::path(
+    get,
+    path = "/oauth/logout",
+    tag = "oauth",
+    params(LogoutParams),
+    responses(
+        (status = 303, description = "Redirect to the return URL, or the login page if there is none"),
+        (status = 400, description = "The return URL is not allowed"),
+    ),
+)]
+#[instrument(
+    name = "oauth::logout",
+    skip_all,
+    fields(
+        user.id = session.id,
+        return_to = params.return_to.as_ref().map(|u| u.as_str()).unwrap_or_default(),
+    )
+)]
 pub(crate) async fn logout(
+    Query(params): Query<LogoutParams>,
     session: CurrentUser<Mutable>,
     State(frontend_url): State<FrontendUrl>,
-) -> Redirect {
+    State(db): State<PgPool>,
+    State(sessions): State<session::Manager>,
+    State(allowed_redirect_domains): State<AllowedRedirectDomains>,
+) -> Result<Redirect> {
+    if let Some(return_to) = &params.return_to {
+        if !redirect_url_is_valid(return_to, &db, allowed_redirect_domains).await? {
+            return Err(Error::InvalidParameter("return-to"));
+        }
+    }
+
+    // Custom domains have their own cookies, so their sessions must be ended here too
+    let revoked = sessions
+        .revoke_handed_off(session.id, session.session_id())
+        .await?;
+    if revoked > 0 {
+        info!(revoked, "logged out of custom domains");
+    }
+
     session.logout();
 
-    Redirect::to(frontend_url.join("/login").as_str())
+    let url = params
+        .return_to
+        .unwrap_or_else(|| frontend_url.join("/login"));
+    Ok(Redirect::to(url.as_str()))
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) struct LogoutParams {
+    /// The URL to redirect the user to after logging out
+    return_to: Option<Url>,
 }
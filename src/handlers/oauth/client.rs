@@ -1,10 +1,11 @@
 use database::ProviderConfiguration;
-use rand::distributions::{Alphanumeric, DistString};
 use reqwest::{
     header::{HeaderMap, HeaderValue, ACCEPT},
-    Response, StatusCode,
+    Method, RequestBuilder, Response, StatusCode,
 };
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use secrecy::{ExposeSecret, SecretString};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use state::{RequestId, TraceContext, REQUEST_ID_HEADER};
 use std::{
     fmt::{Display, Formatter},
     time::Duration,
@@ -43,13 +44,12 @@ impl Client {
         &self,
         config: &ProviderConfiguration,
         redirect_url: &str,
-    ) -> (String, String) {
-        let state = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
-
+        state: &str,
+    ) -> String {
         let mut params = form_urlencoded::Serializer::new(String::new());
         params.append_pair("response_type", "code");
         params.append_pair("redirect_uri", redirect_url);
-        params.append_pair("state", &state);
+        params.append_pair("state", state);
 
         let url = match config {
             ProviderConfiguration::Google { client_id, .. } => {
@@ -70,7 +70,7 @@ impl Client {
         };
 
         let params = params.finish();
-        (format!("{url}?{params}"), state)
+        format!("{url}?{params}")
     }
 
     /// Perform the access token exchange, returning a bearer token
@@ -80,16 +80,20 @@ impl Client {
         code: &str,
         redirect_uri: &str,
         provider: &ProviderConfiguration,
-    ) -> Result<String> {
+    ) -> Result<SecretString> {
         let config = ExchangeConfig::from(provider);
         let params = ExchangeRequest {
             code,
             grant_type: "authorization_code",
             client_id: config.client_id,
-            client_secret: config.client_secret,
+            client_secret: config.client_secret.expose_secret(),
             redirect_uri,
         };
-        let response = self.client.post(config.url).form(&params).send().await?;
+        let response = self
+            .request(Method::POST, config.url)
+            .form(&params)
+            .send()
+            .await?;
 
         let creds = deserialize_if_successful::<ExchangeResponse>(response).await?;
 
@@ -104,7 +108,7 @@ impl Client {
     #[instrument(name = "Client::user_info", skip_all, fields(kind = %provider.kind()))]
     pub async fn user_info(
         &self,
-        token: &str,
+        token: &SecretString,
         provider: &ProviderConfiguration,
     ) -> Result<UserInfo> {
         match provider {
@@ -131,15 +135,15 @@ impl Client {
                     )
                 )?;
 
-                let email = emails
+                let primary = emails
                     .into_iter()
                     .find(|e| e.primary)
-                    .map(|e| e.email)
                     .expect("user must have a primary email");
 
                 Ok(UserInfo {
                     id: user_info.id.to_string(),
-                    email,
+                    email: primary.email,
+                    email_verified: primary.verified,
                 })
             }
         }
@@ -147,14 +151,13 @@ impl Client {
 
     /// Fetch user info that simply requires data transformation
     #[instrument(name = "Client::simple_user_info", skip(self, token))]
-    async fn simple_user_info<P>(&self, url: &str, token: &str) -> Result<UserInfo>
+    async fn simple_user_info<P>(&self, url: &str, token: &SecretString) -> Result<UserInfo>
     where
         P: DeserializeOwned + Into<UserInfo>,
     {
         let response = self
-            .client
-            .get(url)
-            .header("Authorization", format!("Bearer {token}"))
+            .request(Method::GET, url)
+            .header("Authorization", format!("Bearer {}", token.expose_secret()))
             .send()
             .await?;
         let provider_specific = deserialize_if_successful::<P>(response).await?;
@@ -164,17 +167,34 @@ impl Client {
 
     /// Send an authenticated request to GitHub
     #[instrument(name = "Client::github_request", skip(self, token))]
-    async fn github_request<R: DeserializeOwned>(&self, url: &str, token: &str) -> Result<R> {
+    async fn github_request<R: DeserializeOwned>(
+        &self,
+        url: &str,
+        token: &SecretString,
+    ) -> Result<R> {
         let response = self
-            .client
-            .get(url)
-            .header("Authorization", format!("Bearer {token}"))
+            .request(Method::GET, url)
+            .header("Authorization", format!("Bearer {}", token.expose_secret()))
             .header("Accept", "application/vnd.github+json")
             .header("X-Github-Api-Version", "2022-11-28")
             .send()
             .await?;
         deserialize_if_successful(response).await
     }
+
+    /// Start a request to the provider, forwarding the ID of the request being handled and the
+    /// trace it is part of
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        let mut builder = self.client.request(method, url);
+        if let Some(id) = RequestId::current() {
+            builder = builder.header(REQUEST_ID_HEADER, id.as_str());
+        }
+        for (name, value) in TraceContext::current().headers() {
+            builder = builder.header(name, value);
+        }
+
+        builder
+    }
 }
 
 impl Default for Client {
@@ -190,6 +210,8 @@ pub(crate) struct UserInfo {
     pub id: String,
     /// The user's preferred email
     pub email: String,
+    /// Whether the provider has verified the user owns the email
+    pub email_verified: bool,
 }
 
 impl From<OpenIDConnectUserInfo> for UserInfo {
@@ -197,6 +219,7 @@ impl From<OpenIDConnectUserInfo> for UserInfo {
         UserInfo {
             id: user_info.sub,
             email: user_info.email,
+            email_verified: user_info.email_verified,
         }
     }
 }
@@ -206,6 +229,7 @@ impl From<DiscordUserInfo> for UserInfo {
         UserInfo {
             id: user_info.id,
             email: user_info.email,
+            email_verified: user_info.verified,
         }
     }
 }
@@ -272,6 +296,26 @@ impl From<reqwest::Error> for Error {
 struct OpenIDConnectUserInfo {
     sub: String,
     email: String,
+    #[serde(default, deserialize_with = "lenient_bool")]
+    email_verified: bool,
+}
+
+/// Deserialize a boolean that some providers send as a string, i.e. `"true"`
+fn lenient_bool<'de, D>(deserializer: D) -> Result<bool, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Lenient {
+        Bool(bool),
+        String(String),
+    }
+
+    match Lenient::deserialize(deserializer)? {
+        Lenient::Bool(value) => Ok(value),
+        Lenient::String(value) => Ok(value.eq_ignore_ascii_case("true")),
+    }
 }
 
 /// User info from Discord
@@ -279,6 +323,8 @@ struct OpenIDConnectUserInfo {
 struct DiscordUserInfo {
     id: String,
     email: String,
+    #[serde(default)]
+    verified: bool,
 }
 
 /// User info from GitHub
@@ -292,13 +338,14 @@ struct GitHubUserInfo {
 struct GitHubEmail {
     email: String,
     primary: bool,
+    verified: bool,
 }
 
 #[derive(Debug)]
 struct ExchangeConfig<'e> {
     url: &'e str,
     client_id: &'e str,
-    client_secret: &'e str,
+    client_secret: &'e SecretString,
 }
 
 impl<'e> From<&'e ProviderConfiguration> for ExchangeConfig<'e> {
@@ -332,7 +379,8 @@ impl<'e> From<&'e ProviderConfiguration> for ExchangeConfig<'e> {
     }
 }
 
-#[derive(Debug, Serialize)]
+// Not `Debug` as it holds the exposed client secret
+#[derive(Serialize)]
 struct ExchangeRequest<'e> {
     code: &'e str,
     grant_type: &'e str,
@@ -343,7 +391,7 @@ struct ExchangeRequest<'e> {
 
 #[derive(Debug, Deserialize)]
 struct ExchangeResponse {
-    access_token: String,
+    access_token: SecretString,
     token_type: String,
 }
 
@@ -363,47 +411,68 @@ where
 
 #[cfg(test)]
 mod tests {
-    use super::Client;
+    use super::{Client, OpenIDConnectUserInfo};
     use database::ProviderConfiguration;
+    use secrecy::SecretString;
 
     const ENCODED_REDIRECT_URI: &str = "https%3A%2F%2Fredirect.com%2Foauth%2Fcallback";
+    const STATE: &str = "test-state";
 
     #[test]
     fn build_authorize_url_google() {
         let config = ProviderConfiguration::Google {
             client_id: String::from("test-client-id"),
-            client_secret: String::from("test-client-secret"),
+            client_secret: SecretString::new(String::from("test-client-secret")),
         };
 
         let client = Client::default();
-        let (url, state) =
-            client.build_authorization_url(&config, "https://redirect.com/oauth/callback");
-        assert_eq!(url, format!("https://accounts.google.com/o/oauth2/v2/auth?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={state}&client_id=test-client-id&scope=openid+profile+email"));
+        let url =
+            client.build_authorization_url(&config, "https://redirect.com/oauth/callback", STATE);
+        assert_eq!(url, format!("https://accounts.google.com/o/oauth2/v2/auth?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={STATE}&client_id=test-client-id&scope=openid+profile+email"));
     }
 
     #[test]
     fn build_authorize_url_github() {
         let config = ProviderConfiguration::GitHub {
             client_id: String::from("test-client-id"),
-            client_secret: String::from("test-client-secret"),
+            client_secret: SecretString::new(String::from("test-client-secret")),
         };
 
         let client = Client::default();
-        let (url, state) =
-            client.build_authorization_url(&config, "https://redirect.com/oauth/callback");
-        assert_eq!(url, format!("https://github.com/login/oauth/authorize?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={state}&client_id=test-client-id&scope=read%3Auser+user%3Aemail"));
+        let url =
+            client.build_authorization_url(&config, "https://redirect.com/oauth/callback", STATE);
+        assert_eq!(url, format!("https://github.com/login/oauth/authorize?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={STATE}&client_id=test-client-id&scope=read%3Auser+user%3Aemail"));
     }
 
     #[test]
     fn build_authorize_url_discord() {
         let config = ProviderConfiguration::Discord {
             client_id: String::from("test-client-id"),
-            client_secret: String::from("test-client-secret"),
+            client_secret: SecretString::new(String::from("test-client-secret")),
         };
 
         let client = Client::default();
-        let (url, state) =
-            client.build_authorization_url(&config, "https://redirect.com/oauth/callback");
-        assert_eq!(url, format!("https://discord.com/oauth2/authorize?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={state}&client_id=test-client-id&scope=identify+email"));
+        let url =
+            client.build_authorization_url(&config, "https://redirect.com/oauth/callback", STATE);
+        assert_eq!(url, format!("https://discord.com/oauth2/authorize?response_type=code&redirect_uri={ENCODED_REDIRECT_URI}&state={STATE}&client_id=test-client-id&scope=identify+email"));
+    }
+
+    #[test]
+    fn openid_connect_email_verified_as_bool_or_string() {
+        for (raw, expected) in [
+            (r#"true"#, true),
+            (r#"false"#, false),
+            (r#""true""#, true),
+            (r#""false""#, false),
+        ] {
+            let body = format!(r#"{{"sub":"1","email":"a@example.com","email_verified":{raw}}}"#);
+            let user_info = serde_json::from_str::<OpenIDConnectUserInfo>(&body).unwrap();
+            assert_eq!(user_info.email_verified, expected, "{raw}");
+        }
+
+        let user_info =
+            serde_json::from_str::<OpenIDConnectUserInfo>(r#"{"sub":"1","email":"a@example.com"}"#)
+                .unwrap();
+        assert!(!user_info.email_verified);
     }
 }
@@ -4,6 +4,7 @@ use axum::{
     response::{IntoResponse, Json, Redirect, Response},
 };
 use serde::Serialize;
+use state::RequestId;
 use tracing::error;
 use url::Url;
 
@@ -23,6 +24,12 @@ pub(crate) enum Error {
     ProviderInteraction(client::Error),
     /// The value provided for the parameter was invalid
     InvalidParameter(&'static str),
+    /// The email address already belongs to another user
+    EmailInUse,
+    /// The user is banned or suspended
+    Banned,
+    /// An error occurred while managing sessions
+    Session(session::Error),
 }
 
 impl From<database::SqlxError> for Error {
@@ -37,6 +44,12 @@ impl From<database::Error> for Error {
     }
 }
 
+impl From<session::Error> for Error {
+    fn from(error: session::Error) -> Self {
+        Self::Session(error)
+    }
+}
+
 impl From<client::Error> for Error {
     fn from(error: client::Error) -> Self {
         Self::ProviderInteraction(error)
@@ -67,18 +80,30 @@ impl IntoResponse for Error {
                 }
                 response("internal error", StatusCode::INTERNAL_SERVER_ERROR)
             }
+            Self::Session(error) => {
+                match error.source() {
+                    Some(source) => error!(%error, %source, "a session error occurred"),
+                    None => error!(%error, "a session error occurred"),
+                }
+                response("internal error", StatusCode::INTERNAL_SERVER_ERROR)
+            }
             Self::InvalidParameter(param) => response(
                 format!("invalid value for parameter {param:?}"),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::EmailInUse => response("email already in use", StatusCode::CONFLICT),
+            Self::Banned => response("account is banned", StatusCode::FORBIDDEN),
         }
     }
 }
 
 /// A generic API error
 #[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
 struct ApiError<'m> {
     message: &'m str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 /// Generate an error response
@@ -88,6 +113,7 @@ fn response<S: AsRef<str>>(message: S, code: StatusCode) -> Response {
         code,
         Json(ApiError {
             message: message.as_ref(),
+            request_id: RequestId::current().map(|id| id.to_string()),
         }),
     )
         .into_response()
@@ -1,4 +1,3 @@
-use super::{base::HasSessionState, InvalidSessionState, Mutable, SessionState};
 use axum::{
     async_trait,
     extract::{FromRef, FromRequestParts},
@@ -6,12 +5,16 @@ use axum::{
     response::{IntoResponse, Response},
 };
 use database::{PgPool, User};
+use session::{
+    extract::{HasSessionState, InvalidSessionState, Mutable},
+    SessionState,
+};
 use std::fmt::Debug;
 use tracing::error;
 
 /// Retrieve the current user from the session
 #[derive(Debug)]
-pub struct CurrentUser<T>
+pub(crate) struct CurrentUser<T>
 where
     T: HasSessionState,
 {
@@ -20,8 +23,13 @@ where
 }
 
 impl CurrentUser<Mutable> {
+    /// Get the ID of the session
+    pub(crate) fn session_id(&self) -> &str {
+        self.session.id()
+    }
+
     /// Logout the current user
-    pub fn logout(mut self) {
+    pub(crate) fn logout(mut self) {
         self.session.state = SessionState::Unauthenticated
     }
 }
@@ -74,7 +82,7 @@ where
 }
 
 #[derive(Debug)]
-pub enum CurrentUserRejection {
+pub(crate) enum CurrentUserRejection {
     /// Propagate a session state error
     InvalidSessionState(InvalidSessionState),
     /// An unexpected database error
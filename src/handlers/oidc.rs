@@ -0,0 +1,640 @@
+use axum::{
+    extract::{Form, Json, Query, RawQuery, State},
+    http::{header, HeaderMap},
+    response::{IntoResponse, Redirect},
+};
+use base64::prelude::{Engine, BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD};
+use database::{AuthorizationCode, OidcClient, PgPool, User};
+use graphql::{FeatureFlags, Flag};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use session::extract::{HasSessionState, Immutable};
+use sha2::{Digest, Sha256};
+use state::{ApiUrl, FrontendUrl};
+use tracing::{info, instrument, Span};
+use url::Url;
+use utoipa::ToSchema;
+
+mod error;
+mod keys;
+
+use error::{Error, Result};
+pub use keys::SigningKey;
+
+/// How long issued tokens are valid for, in seconds
+const TOKEN_LIFETIME: u64 = 60 * 60;
+
+/// The `typ` header of access tokens, as defined by
+/// [RFC 9068](https://datatracker.ietf.org/doc/html/rfc9068)
+const ACCESS_TOKEN_TYPE: &str = "at+jwt";
+/// The `typ` header of ID tokens
+const ID_TOKEN_TYPE: &str = "JWT";
+
+/// The scopes that can be granted
+const SUPPORTED_SCOPES: [&str; 3] = ["openid", "profile", "email"];
+
+/// Describe the provider so relying parties can configure themselves, as defined by
+/// [OpenID Connect Discovery](https://openid.net/specs/openid-connect-discovery-1_0.html)
+#[utoipa::path(
+    get,
+    path = "/.well-known/openid-configuration",
+    tag = "oidc",
+    responses((status = 200, description = "The provider metadata", body = Object)),
+)]
+#[instrument(name = "oidc::discovery", skip_all)]
+pub(crate) async fn discovery(State(api_url): State<ApiUrl>) -> Json<Value> {
+    Json(json!({
+        "issuer": issuer(&api_url),
+        "authorization_endpoint": api_url.join("/authorize").as_str(),
+        "token_endpoint": api_url.join("/token").as_str(),
+        "userinfo_endpoint": api_url.join("/userinfo").as_str(),
+        "introspection_endpoint": api_url.join("/introspect").as_str(),
+        "jwks_uri": api_url.join("/.well-known/jwks.json").as_str(),
+        "response_types_supported": ["code"],
+        "grant_types_supported": ["authorization_code"],
+        "subject_types_supported": ["public"],
+        "id_token_signing_alg_values_supported": ["RS256"],
+        "scopes_supported": SUPPORTED_SCOPES,
+        "token_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+        "introspection_endpoint_auth_methods_supported": ["client_secret_basic", "client_secret_post"],
+        "claims_supported": ["sub", "name", "given_name", "family_name", "email"],
+        "code_challenge_methods_supported": ["S256"],
+    }))
+}
+
+/// Publish the keys tokens are signed with
+#[utoipa::path(
+    get,
+    path = "/.well-known/jwks.json",
+    tag = "oidc",
+    responses((status = 200, description = "The JSON web key set", body = Object)),
+)]
+#[instrument(name = "oidc::jwks", skip_all)]
+pub(crate) async fn jwks(State(key): State<SigningKey>) -> Json<Value> {
+    Json(json!({ "keys": [key.jwk()] }))
+}
+
+/// Sign the current user into a client, sending them back with an authorization code. Users
+/// without a session are sent to login first and returned here afterwards.
+#[utoipa::path(
+    get,
+    path = "/authorize",
+    tag = "oidc",
+    params(
+        ("response_type" = String, Query, description = "Must be `code`"),
+        ("client_id" = String, Query),
+        ("redirect_uri" = String, Query),
+        ("scope" = String, Query, description = "Must include `openid`"),
+        ("state" = Option<String>, Query),
+        ("nonce" = Option<String>, Query),
+        ("prompt" = Option<String>, Query),
+        ("code_challenge" = Option<String>, Query),
+        ("code_challenge_method" = Option<String>, Query, description = "Must be `S256` when a challenge is sent"),
+    ),
+    responses(
+        (status = 303, description = "Redirect back to the client, or to login first"),
+        (status = 400, description = "The client or redirect URI is invalid"),
+    ),
+)]
+#[instrument(
+    name = "oidc::authorize",
+    skip_all,
+    fields(client.id = params.client_id, user.id)
+)]
+pub(crate) async fn authorize(
+    Query(params): Query<AuthorizeParams>,
+    RawQuery(query): RawQuery,
+    session: Immutable,
+    State(db): State<PgPool>,
+    State(api_url): State<ApiUrl>,
+    State(frontend_url): State<FrontendUrl>,
+    State(feature_flags): State<FeatureFlags>,
+) -> Result<Redirect> {
+    let client = OidcClient::find(&params.client_id, &db)
+        .await?
+        .ok_or(Error::UnknownClient)?;
+
+    // Errors can only be sent back to the client once we know the redirect URI belongs to it
+    if !client.allows_redirect_to(params.redirect_uri.as_str()) {
+        return Err(Error::InvalidRedirectUri);
+    }
+
+    let redirect = |pairs: &[(&str, &str)]| {
+        let mut url = params.redirect_uri.clone();
+        {
+            let mut query = url.query_pairs_mut();
+            query.extend_pairs(pairs);
+            if let Some(state) = &params.state {
+                query.append_pair("state", state);
+            }
+        }
+
+        Redirect::to(url.as_str())
+    };
+
+    if !feature_flags.is_enabled(Flag::OidcProvider, None).await {
+        return Ok(redirect(&[
+            ("error", "temporarily_unavailable"),
+            ("error_description", "the provider is disabled"),
+        ]));
+    }
+
+    if params.response_type != "code" {
+        return Ok(redirect(&[("error", "unsupported_response_type")]));
+    }
+
+    let Some(scope) = granted_scope(&params.scope) else {
+        return Ok(redirect(&[
+            ("error", "invalid_scope"),
+            ("error_description", "the openid scope is required"),
+        ]));
+    };
+
+    // Only S256 is accepted as the plain method offers no protection
+    match (
+        &params.code_challenge,
+        params.code_challenge_method.as_deref(),
+    ) {
+        (None, None) | (Some(_), Some("S256")) => {}
+        _ => {
+            return Ok(redirect(&[
+                ("error", "invalid_request"),
+                (
+                    "error_description",
+                    "code challenge must use the S256 method",
+                ),
+            ]))
+        }
+    }
+
+    let Some(user_id) = session.state().id() else {
+        if params.prompt.as_deref() == Some("none") {
+            return Ok(redirect(&[("error", "login_required")]));
+        }
+
+        let mut return_to = api_url.join("/authorize");
+        return_to.set_query(query.as_deref());
+
+        let mut login = frontend_url.join("/login");
+        login
+            .query_pairs_mut()
+            .append_pair("return-to", return_to.as_str());
+
+        return Ok(Redirect::to(login.as_str()));
+    };
+    Span::current().record("user.id", user_id);
+
+    let code = AuthorizationCode::issue(
+        &client.id,
+        user_id,
+        params.redirect_uri.as_str(),
+        &scope,
+        params.nonce.as_deref(),
+        params.code_challenge.as_deref(),
+        &db,
+    )
+    .await?;
+    info!("issued authorization code");
+
+    Ok(redirect(&[("code", &code)]))
+}
+
+/// Params for an authorization request as defined by
+/// [OpenID Connect Core Section 3.1.2.1](https://openid.net/specs/openid-connect-core-1_0.html#AuthRequest)
+#[derive(Debug, Deserialize)]
+pub(crate) struct AuthorizeParams {
+    response_type: String,
+    client_id: String,
+    redirect_uri: Url,
+    scope: String,
+    state: Option<String>,
+    nonce: Option<String>,
+    prompt: Option<String>,
+    code_challenge: Option<String>,
+    code_challenge_method: Option<String>,
+}
+
+/// Exchange an authorization code for an ID token and access token
+#[utoipa::path(
+    post,
+    path = "/token",
+    tag = "oidc",
+    request_body(content_type = "application/x-www-form-urlencoded", content = Object),
+    responses(
+        (status = 200, description = "The issued tokens", body = TokenResponse),
+        (status = 400, description = "The grant is invalid"),
+        (status = 401, description = "The client could not be authenticated"),
+    ),
+)]
+#[instrument(name = "oidc::token", skip_all, fields(client.id, user.id))]
+pub(crate) async fn token(
+    headers: HeaderMap,
+    State(db): State<PgPool>,
+    State(api_url): State<ApiUrl>,
+    State(key): State<SigningKey>,
+    Form(params): Form<TokenParams>,
+) -> Result<impl IntoResponse> {
+    let client = authenticate_client(
+        &headers,
+        params.client_id.as_deref(),
+        params.client_secret.as_deref(),
+        &db,
+    )
+    .await?;
+    Span::current().record("client.id", &client.id);
+
+    if params.grant_type != "authorization_code" {
+        return Err(Error::UnsupportedGrantType);
+    }
+
+    let code = params
+        .code
+        .as_deref()
+        .ok_or(Error::MissingParameter("code"))?;
+    let redirect_uri = params
+        .redirect_uri
+        .as_deref()
+        .ok_or(Error::MissingParameter("redirect_uri"))?;
+
+    // Only a request that matches the code consumes it, so a stolen code can't be burned by
+    // presenting it from another client
+    let challenge = params.code_verifier.as_deref().map(code_challenge);
+    let code = AuthorizationCode::redeem(code, &client.id, redirect_uri, challenge.as_deref(), &db)
+        .await?
+        .ok_or(Error::InvalidGrant)?;
+
+    let user = User::find(code.user_id, &db)
+        .await?
+        .ok_or(Error::InvalidGrant)?;
+    Span::current().record("user.id", user.id);
+
+    let issuer = issuer(&api_url);
+    let issued_at = jsonwebtoken::get_current_timestamp();
+    let expires_at = issued_at + TOKEN_LIFETIME;
+
+    let id_token = key.sign(
+        ID_TOKEN_TYPE,
+        &IdTokenClaims {
+            iss: issuer,
+            aud: &client.id,
+            iat: issued_at,
+            exp: expires_at,
+            nonce: code.nonce.as_deref(),
+            user: UserClaims::new(&user, &code.scope),
+        },
+    );
+    let access_token = key.sign(
+        ACCESS_TOKEN_TYPE,
+        &AccessTokenClaims {
+            iss: issuer.to_owned(),
+            sub: user.public_id.clone(),
+            aud: client.id.clone(),
+            client_id: client.id,
+            scope: code.scope.clone(),
+            iat: issued_at,
+            exp: expires_at,
+        },
+    );
+    info!("issued tokens");
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(TokenResponse {
+            access_token,
+            token_type: "Bearer",
+            expires_in: TOKEN_LIFETIME,
+            id_token,
+            scope: code.scope,
+        }),
+    ))
+}
+
+/// Params for a token request as defined by
+/// [RFC 6749 Section 4.1.3](https://datatracker.ietf.org/doc/html/rfc6749#section-4.1.3)
+#[derive(Debug, Deserialize)]
+pub(crate) struct TokenParams {
+    grant_type: String,
+    code: Option<String>,
+    redirect_uri: Option<String>,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+    code_verifier: Option<String>,
+}
+
+/// A successful token response
+#[derive(Serialize, ToSchema)]
+pub(crate) struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    id_token: String,
+    scope: String,
+}
+
+/// Get the claims about the user the access token was issued for
+#[utoipa::path(
+    get,
+    path = "/userinfo",
+    tag = "oidc",
+    responses(
+        (status = 200, description = "The claims about the user", body = UserClaims),
+        (status = 401, description = "The access token is invalid"),
+    ),
+    security(("bearer" = [])),
+)]
+#[instrument(name = "oidc::userinfo", skip_all, fields(user.id))]
+pub(crate) async fn userinfo(
+    headers: HeaderMap,
+    State(db): State<PgPool>,
+    State(api_url): State<ApiUrl>,
+    State(key): State<SigningKey>,
+) -> Result<Json<UserClaims>> {
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .ok_or(Error::InvalidToken)?;
+
+    let claims = key
+        .verify::<AccessTokenClaims>(ACCESS_TOKEN_TYPE, issuer(&api_url), token)
+        .ok_or(Error::InvalidToken)?;
+    let user = User::find_by_public_id(&claims.sub, &db)
+        .await?
+        .ok_or(Error::InvalidToken)?;
+    Span::current().record("user.id", user.id);
+
+    Ok(Json(UserClaims::new(&user, &claims.scope)))
+}
+
+/// Describe the state of a token to a client, as defined by
+/// [RFC 7662](https://datatracker.ietf.org/doc/html/rfc7662). Any authenticated client may
+/// introspect any token, allowing services to validate tokens issued to other clients.
+#[utoipa::path(
+    post,
+    path = "/introspect",
+    tag = "oidc",
+    request_body(content_type = "application/x-www-form-urlencoded", content = Object),
+    responses(
+        (status = 200, description = "The state of the token", body = IntrospectionResponse),
+        (status = 401, description = "The client or request signature is invalid"),
+    ),
+)]
+#[instrument(name = "oidc::introspect", skip_all, fields(client.id))]
+pub(crate) async fn introspect(
+    headers: HeaderMap,
+    State(db): State<PgPool>,
+    State(api_url): State<ApiUrl>,
+    State(key): State<SigningKey>,
+    Form(params): Form<IntrospectParams>,
+) -> Result<impl IntoResponse> {
+    let client = authenticate_client(
+        &headers,
+        params.client_id.as_deref(),
+        params.client_secret.as_deref(),
+        &db,
+    )
+    .await?;
+    Span::current().record("client.id", &client.id);
+
+    // Access tokens are the only kind issued, so any `token_type_hint` is ignored
+    let claims =
+        key.verify::<AccessTokenClaims>(ACCESS_TOKEN_TYPE, issuer(&api_url), &params.token);
+
+    // Tokens stop being active once the user they were issued for is deleted
+    let claims = match claims {
+        Some(claims) => User::find_by_public_id(&claims.sub, &db)
+            .await?
+            .map(|_| claims),
+        None => None,
+    };
+
+    Ok((
+        [(header::CACHE_CONTROL, "no-store")],
+        Json(IntrospectionResponse {
+            active: claims.is_some(),
+            token_type: claims.as_ref().map(|_| "Bearer"),
+            claims,
+        }),
+    ))
+}
+
+/// Params for an introspection request as defined by
+/// [RFC 7662 Section 2.1](https://datatracker.ietf.org/doc/html/rfc7662#section-2.1)
+#[derive(Debug, Deserialize)]
+pub(crate) struct IntrospectParams {
+    token: String,
+    client_id: Option<String>,
+    client_secret: Option<String>,
+}
+
+/// An introspection response, only describing the token when it is active
+#[derive(Serialize, ToSchema)]
+pub(crate) struct IntrospectionResponse {
+    active: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token_type: Option<&'static str>,
+    #[serde(flatten)]
+    claims: Option<AccessTokenClaims>,
+}
+
+/// Claims about a user, limited to those allowed by the granted scopes
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct UserClaims {
+    sub: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    given_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    family_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    email: Option<String>,
+}
+
+impl UserClaims {
+    fn new(user: &User, scope: &str) -> Self {
+        let mut claims = UserClaims {
+            sub: user.public_id.clone(),
+            name: None,
+            given_name: None,
+            family_name: None,
+            email: None,
+        };
+
+        for scope in scope.split_whitespace() {
+            match scope {
+                "profile" => {
+                    claims.name = Some(format!("{} {}", user.given_name, user.family_name));
+                    claims.given_name = Some(user.given_name.clone());
+                    claims.family_name = Some(user.family_name.clone());
+                }
+                "email" => claims.email = Some(user.primary_email.clone()),
+                _ => {}
+            }
+        }
+
+        claims
+    }
+}
+
+#[derive(Serialize)]
+struct IdTokenClaims<'c> {
+    iss: &'c str,
+    aud: &'c str,
+    iat: u64,
+    exp: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    nonce: Option<&'c str>,
+    #[serde(flatten)]
+    user: UserClaims,
+}
+
+#[derive(Deserialize, Serialize, ToSchema)]
+pub(crate) struct AccessTokenClaims {
+    iss: String,
+    sub: String,
+    aud: String,
+    client_id: String,
+    scope: String,
+    iat: u64,
+    exp: u64,
+}
+
+/// The identifier for the provider, used in the `iss` claim
+fn issuer(api_url: &ApiUrl) -> &str {
+    api_url.as_str().trim_end_matches('/')
+}
+
+/// Reduce the requested scopes to those that are supported, requiring that `openid` is present
+fn granted_scope(requested: &str) -> Option<String> {
+    let scopes = requested
+        .split_whitespace()
+        .filter(|scope| SUPPORTED_SCOPES.contains(scope))
+        .collect::<Vec<_>>();
+
+    if scopes.contains(&"openid") {
+        Some(scopes.join(" "))
+    } else {
+        None
+    }
+}
+
+/// Compute the S256 PKCE challenge for a verifier, as defined by
+/// [RFC 7636 Section 4.2](https://datatracker.ietf.org/doc/html/rfc7636#section-4.2)
+fn code_challenge(verifier: &str) -> String {
+    BASE64_URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+/// Authenticate the client making the request, using its credentials from either the
+/// authorization header or the request body
+async fn authenticate_client(
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+    db: &PgPool,
+) -> Result<OidcClient> {
+    let (id, secret) =
+        client_credentials(headers, client_id, client_secret).ok_or(Error::InvalidClient)?;
+
+    OidcClient::authenticate(&id, &secret, db)
+        .await?
+        .ok_or(Error::InvalidClient)
+}
+
+/// Get the client's credentials from either the authorization header or the request body
+fn client_credentials(
+    headers: &HeaderMap,
+    client_id: Option<&str>,
+    client_secret: Option<&str>,
+) -> Option<(String, String)> {
+    let basic = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Basic "));
+
+    match basic {
+        Some(encoded) => {
+            let decoded = BASE64_STANDARD.decode(encoded).ok()?;
+            let (id, secret) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+            Some((form_decode(id), form_decode(secret)))
+        }
+        None => Some((client_id?.to_owned(), client_secret?.to_owned())),
+    }
+}
+
+/// Decode a credential from the authorization header, which clients must form-encode before
+/// joining, as required by
+/// [RFC 6749 Section 2.3.1](https://datatracker.ietf.org/doc/html/rfc6749#section-2.3.1)
+fn form_decode(value: &str) -> String {
+    // Treating the whole credential as a pair's value keeps any `=` it contains
+    form_urlencoded::parse(format!("={value}").as_bytes())
+        .next()
+        .map(|(_, value)| value.into_owned())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{client_credentials, code_challenge};
+    use axum::http::{header, HeaderMap, HeaderValue};
+    use base64::prelude::{Engine, BASE64_STANDARD};
+
+    fn basic(credentials: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        let value = format!("Basic {}", BASE64_STANDARD.encode(credentials));
+        headers.insert(
+            header::AUTHORIZATION,
+            HeaderValue::from_str(&value).unwrap(),
+        );
+        headers
+    }
+
+    #[test]
+    fn challenge_matches_rfc_example() {
+        // From RFC 7636 Appendix B
+        let challenge = code_challenge("dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk");
+        assert_eq!(challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn challenge_differs_for_other_verifiers() {
+        let challenge = code_challenge("some-other-verifier");
+        assert_ne!(challenge, "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM");
+    }
+
+    #[test]
+    fn basic_credentials_are_form_decoded() {
+        let headers = basic("my%3Aclient:s3cr%2Bt+value%3D");
+        let credentials = client_credentials(&headers, None, None);
+        assert_eq!(
+            credentials,
+            Some((String::from("my:client"), String::from("s3cr+t value=")))
+        );
+    }
+
+    #[test]
+    fn basic_credentials_keep_unencoded_values() {
+        let headers = basic("client:abc123");
+        let credentials = client_credentials(&headers, None, None);
+        assert_eq!(
+            credentials,
+            Some((String::from("client"), String::from("abc123")))
+        );
+    }
+
+    #[test]
+    fn body_credentials_are_used_without_header() {
+        let credentials = client_credentials(&HeaderMap::new(), Some("client"), Some("secret"));
+        assert_eq!(
+            credentials,
+            Some((String::from("client"), String::from("secret")))
+        );
+    }
+
+    #[test]
+    fn malformed_basic_credentials_are_rejected() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::AUTHORIZATION, HeaderValue::from_static("Basic !!!"));
+        assert_eq!(client_credentials(&headers, Some("a"), Some("b")), None);
+    }
+}
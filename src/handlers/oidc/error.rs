@@ -0,0 +1,104 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use serde::Serialize;
+use state::RequestId;
+use tracing::error;
+
+pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the OpenID Connect provider, in the format defined by
+/// [RFC 6749 Section 5.2](https://datatracker.ietf.org/doc/html/rfc6749#section-5.2)
+#[derive(Debug)]
+pub(crate) enum Error {
+    /// A database error
+    Database(database::Error),
+    /// The requested client does not exist
+    UnknownClient,
+    /// The redirect URI is not registered for the client
+    InvalidRedirectUri,
+    /// A required parameter is missing
+    MissingParameter(&'static str),
+    /// The client's credentials are missing or incorrect
+    InvalidClient,
+    /// The authorization code is invalid, expired, or was issued to another client
+    InvalidGrant,
+    /// The grant type is not supported
+    UnsupportedGrantType,
+    /// The access token is missing, invalid, or expired
+    InvalidToken,
+}
+
+impl From<database::Error> for Error {
+    fn from(error: database::Error) -> Self {
+        Self::Database(error)
+    }
+}
+
+impl IntoResponse for Error {
+    fn into_response(self) -> Response {
+        use std::error::Error;
+
+        match self {
+            Self::Database(error) => {
+                match error.source() {
+                    Some(source) => error!(%error, %source, "a database error occurred"),
+                    None => error!(%error, "a database error occurred"),
+                }
+                response(StatusCode::INTERNAL_SERVER_ERROR, "server_error", None)
+            }
+            Self::UnknownClient => response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                Some(String::from("unknown client")),
+            ),
+            Self::InvalidRedirectUri => response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                Some(String::from(
+                    "redirect uri is not registered for the client",
+                )),
+            ),
+            Self::MissingParameter(param) => response(
+                StatusCode::BAD_REQUEST,
+                "invalid_request",
+                Some(format!("missing parameter {param:?}")),
+            ),
+            Self::InvalidClient => (
+                [(header::WWW_AUTHENTICATE, "Basic")],
+                response(StatusCode::UNAUTHORIZED, "invalid_client", None),
+            )
+                .into_response(),
+            Self::InvalidGrant => response(StatusCode::BAD_REQUEST, "invalid_grant", None),
+            Self::UnsupportedGrantType => {
+                response(StatusCode::BAD_REQUEST, "unsupported_grant_type", None)
+            }
+            Self::InvalidToken => (
+                [(header::WWW_AUTHENTICATE, r#"Bearer error="invalid_token""#)],
+                response(StatusCode::UNAUTHORIZED, "invalid_token", None),
+            )
+                .into_response(),
+        }
+    }
+}
+
+/// An OAuth 2.0 error
+#[derive(Serialize)]
+struct OAuthError {
+    error: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// Generate an error response
+fn response(status: StatusCode, error: &'static str, description: Option<String>) -> Response {
+    let body = OAuthError {
+        error,
+        error_description: description,
+        request_id: RequestId::current().map(|id| id.to_string()),
+    };
+    (status, [(header::CACHE_CONTROL, "no-store")], Json(body)).into_response()
+}
@@ -0,0 +1,103 @@
+use base64::prelude::{Engine, BASE64_URL_SAFE_NO_PAD};
+use eyre::WrapErr;
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use rsa::{
+    pkcs1::DecodeRsaPrivateKey, pkcs8::DecodePrivateKey, traits::PublicKeyParts, RsaPrivateKey,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+/// The key used to sign the tokens issued by the OpenID Connect provider
+#[derive(Clone)]
+pub struct SigningKey(Arc<Inner>);
+
+struct Inner {
+    id: String,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    modulus: String,
+    exponent: String,
+}
+
+impl SigningKey {
+    /// Load a PEM-encoded RSA private key, in either PKCS#1 or PKCS#8 format
+    pub fn from_pem(pem: &str) -> eyre::Result<Self> {
+        let key = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .wrap_err("invalid rsa private key")?;
+
+        let modulus = BASE64_URL_SAFE_NO_PAD.encode(key.n().to_bytes_be());
+        let exponent = BASE64_URL_SAFE_NO_PAD.encode(key.e().to_bytes_be());
+
+        // Derived from the public key so relying parties notice when the key is rotated
+        let digest = Sha256::digest(format!("{modulus}.{exponent}"));
+        let id = BASE64_URL_SAFE_NO_PAD.encode(&digest[..12]);
+
+        let encoding =
+            EncodingKey::from_rsa_pem(pem.as_bytes()).wrap_err("invalid rsa private key")?;
+        let decoding = DecodingKey::from_rsa_components(&modulus, &exponent)
+            .wrap_err("invalid rsa public key")?;
+
+        Ok(Self(Arc::new(Inner {
+            id,
+            encoding,
+            decoding,
+            modulus,
+            exponent,
+        })))
+    }
+
+    /// Sign the claims, marking the token with its type
+    pub(crate) fn sign<C: Serialize>(&self, typ: &str, claims: &C) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(self.0.id.clone());
+        header.typ = Some(typ.to_owned());
+
+        jsonwebtoken::encode(&header, claims, &self.0.encoding).expect("claims must serialize")
+    }
+
+    /// Get the claims from a token of the type, if it was issued by us and has not expired
+    pub(crate) fn verify<C: DeserializeOwned>(
+        &self,
+        typ: &str,
+        issuer: &str,
+        token: &str,
+    ) -> Option<C> {
+        let header = jsonwebtoken::decode_header(token).ok()?;
+        if header.typ.as_deref() != Some(typ) {
+            return None;
+        }
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[issuer]);
+        validation.validate_aud = false;
+
+        let data = jsonwebtoken::decode(token, &self.0.decoding, &validation).ok()?;
+        Some(data.claims)
+    }
+
+    /// The public key in JSON Web Key format
+    pub(crate) fn jwk(&self) -> Jwk<'_> {
+        Jwk {
+            kty: "RSA",
+            usage: "sig",
+            alg: "RS256",
+            kid: &self.0.id,
+            n: &self.0.modulus,
+            e: &self.0.exponent,
+        }
+    }
+}
+
+/// A public key as defined by [RFC 7517](https://datatracker.ietf.org/doc/html/rfc7517)
+#[derive(Serialize)]
+pub(crate) struct Jwk<'k> {
+    kty: &'static str,
+    #[serde(rename = "use")]
+    usage: &'static str,
+    alg: &'static str,
+    kid: &'k str,
+    n: &'k str,
+    e: &'k str,
+}
@@ -0,0 +1,89 @@
+use super::{context, custom_domains, metrics, oauth, oidc};
+use axum::Json;
+use utoipa::{
+    openapi::{
+        self,
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+    },
+    Modify, OpenApi,
+};
+
+/// Describes the REST endpoints, GraphQL is described by its own schema
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "Identity",
+        description = "The authentication and authorization service for the hacker app"
+    ),
+    paths(
+        super::health,
+        super::version,
+        metrics::metrics,
+        context::context,
+        context::batch,
+        custom_domains::report_certificate,
+        oauth::launch,
+        oauth::callback,
+        oauth::complete_registration,
+        oauth::confirm_revoke_session,
+        oauth::revoke_session,
+        oauth::logout,
+        oauth::start_custom_domain_session,
+        oauth::custom_domain_session,
+        oidc::discovery,
+        oidc::jwks,
+        oidc::authorize,
+        oidc::token,
+        oidc::introspect,
+        oidc::userinfo,
+    ),
+    components(schemas(
+        super::Version,
+        context::BatchOrganizationRole,
+        context::BatchRequest,
+        context::BatchResolution,
+        context::BatchScope,
+        context::BatchUser,
+        custom_domains::CertificateReport,
+        custom_domains::ReportedStatus,
+        oauth::RegistrationForm,
+        oauth::RegistrationResponse,
+        oauth::RevokeSessionParams,
+        oidc::AccessTokenClaims,
+        oidc::IntrospectionResponse,
+        oidc::TokenResponse,
+        oidc::UserClaims,
+    )),
+    modifiers(&BearerToken),
+    tags(
+        (name = "meta", description = "Service health, build information, and metrics"),
+        (name = "context", description = "Resolving request context for the gateway"),
+        (name = "custom-domains", description = "Reporting the state of custom domains from the edge proxy"),
+        (name = "oauth", description = "Logging in with an OAuth2 provider"),
+        (name = "oidc", description = "Acting as an OpenID Connect provider, only when enabled"),
+    ),
+)]
+struct ApiDoc;
+
+/// Adds the bearer access token security scheme used by the userinfo endpoint
+struct BearerToken;
+
+impl Modify for BearerToken {
+    fn modify(&self, openapi: &mut openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "bearer",
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .bearer_format("JWT")
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// Serve the OpenAPI specification for the REST endpoints
+pub(crate) async fn openapi() -> Json<openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
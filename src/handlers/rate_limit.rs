@@ -0,0 +1,412 @@
+use axum::{
+    extract::{Request, State},
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        HeaderMap, HeaderName, HeaderValue, StatusCode,
+    },
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::MethodRouter,
+};
+use chrono::Utc;
+use redis::{aio::ConnectionManager, RedisResult};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use state::RequestId;
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+use tracing::{error, instrument, warn};
+
+/// The number of requests allowed in the current period
+static RATE_LIMIT_LIMIT: HeaderName = HeaderName::from_static("ratelimit-limit");
+/// How many requests are left in the current period
+static RATE_LIMIT_REMAINING: HeaderName = HeaderName::from_static("ratelimit-remaining");
+/// How many seconds until the current period resets
+static RATE_LIMIT_RESET: HeaderName = HeaderName::from_static("ratelimit-reset");
+
+/// What requests are grouped by when counting them against a limit
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Key {
+    /// The bearer token in the authorization header, or the `token` query parameter
+    Token,
+    /// The user logged in to the session referenced by the request's cookies or bearer token.
+    /// Anonymous sessions are free to create, so they are not counted by this key.
+    User,
+    /// The IP address of the client
+    Ip,
+}
+
+/// How many requests are allowed within a period, formatted as `<requests>/<seconds>`
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Quota {
+    /// The number of requests allowed in each period
+    pub requests: u64,
+    /// The length of each period in seconds
+    pub period: u64,
+}
+
+impl FromStr for Quota {
+    type Err = InvalidQuota;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (requests, period) = s.split_once('/').ok_or(InvalidQuota)?;
+        let requests = requests.trim().parse().map_err(|_| InvalidQuota)?;
+        let period = period.trim().parse().map_err(|_| InvalidQuota)?;
+        if period == 0 {
+            return Err(InvalidQuota);
+        }
+
+        Ok(Self { requests, period })
+    }
+}
+
+/// The quota was not formatted as `<requests>/<seconds>`
+#[derive(Debug)]
+pub struct InvalidQuota;
+
+impl Display for InvalidQuota {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "quota must be formatted as <requests>/<seconds>")
+    }
+}
+
+impl std::error::Error for InvalidQuota {}
+
+/// Limits how many requests each client can make to a route, counted in the cache so the limit is
+/// shared between instances
+#[derive(Clone)]
+pub struct RequestLimiter {
+    cache: ConnectionManager,
+    quota: Quota,
+}
+
+impl RequestLimiter {
+    /// Create a new limiter allowing the quota of requests
+    pub fn new(cache: ConnectionManager, quota: Quota) -> Self {
+        Self { cache, quota }
+    }
+
+    /// Count the request against the client's quota for the route
+    #[instrument(name = "RequestLimiter::consume", skip(self))]
+    async fn consume(&self, route: &str, client: &str) -> RedisResult<Usage> {
+        let Quota { requests, period } = self.quota;
+        let now = Utc::now().timestamp() as u64;
+        let key = format!("identity:request-limit:{route}:{client}:{}", now / period);
+
+        let mut conn = self.cache.clone();
+        let (used,) = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .expire(&key, period as i64)
+            .ignore()
+            .query_async::<_, (u64,)>(&mut conn)
+            .await?;
+
+        Ok(Usage::new(self.quota, used, now))
+    }
+
+    /// Count a request made with the token against its quota for the route, returning how many
+    /// seconds until it can be retried if the quota was exceeded. Like the middleware, this fails
+    /// open when the cache is unavailable.
+    pub(crate) async fn check_token(&self, route: &str, token: &str) -> Option<u64> {
+        match self.consume(route, &token_client(token)).await {
+            Ok(usage) if usage.exceeded => {
+                warn!(retry_after = usage.reset, "request limit exceeded");
+                Some(usage.reset)
+            }
+            Ok(_) => None,
+            Err(error) => {
+                error!(%error, "failed to check request limit");
+                None
+            }
+        }
+    }
+}
+
+/// How much of a client's quota has been used in the current period
+#[derive(Clone, Copy, Debug)]
+struct Usage {
+    /// The number of requests allowed in the period
+    limit: u64,
+    /// How many requests are left in the period
+    remaining: u64,
+    /// How many seconds until the period resets
+    reset: u64,
+    /// Whether the request went over the quota
+    exceeded: bool,
+}
+
+impl Usage {
+    /// Determine the usage after the quota has been used `used` times in the period containing
+    /// `now`
+    fn new(quota: Quota, used: u64, now: u64) -> Self {
+        Self {
+            limit: quota.requests,
+            remaining: quota.requests.saturating_sub(used),
+            reset: quota.period - now % quota.period,
+            exceeded: used > quota.requests,
+        }
+    }
+
+    /// Describe the usage in the response headers, unless a more specific limiter inside this one
+    /// already has
+    fn apply(&self, headers: &mut HeaderMap) {
+        if headers.contains_key(&RATE_LIMIT_LIMIT) {
+            return;
+        }
+
+        headers.insert(&RATE_LIMIT_LIMIT, HeaderValue::from(self.limit));
+        headers.insert(&RATE_LIMIT_REMAINING, HeaderValue::from(self.remaining));
+        headers.insert(&RATE_LIMIT_RESET, HeaderValue::from(self.reset));
+    }
+
+    /// Build the response for a request over the quota
+    fn throttled(&self) -> Response {
+        let body = Throttled {
+            message: "rate limit exceeded, try again later",
+            limit: self.limit,
+            retry_after: self.reset,
+            request_id: RequestId::current().map(|id| id.to_string()),
+        };
+
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, HeaderValue::from(self.reset))],
+            Json(body),
+        )
+            .into_response();
+        self.apply(response.headers_mut());
+        response
+    }
+}
+
+/// Tells the client they have been rate limited and when they can try again
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Throttled {
+    message: &'static str,
+    limit: u64,
+    retry_after: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// The limit applied to a single route
+#[derive(Clone)]
+struct Limit {
+    limiter: RequestLimiter,
+    route: &'static str,
+    keys: &'static [Key],
+    sessions: session::Manager,
+}
+
+impl Limit {
+    /// Identify the client using the first key the request has
+    async fn client(&self, request: &Request) -> Option<String> {
+        let headers = request.headers();
+        let query = request.uri().query();
+
+        for key in self.keys {
+            let client = match key {
+                Key::Token => token(headers, query).map(|token| token_client(&token)),
+                Key::User => self
+                    .user(headers, query)
+                    .await
+                    .map(|id| format!("user:{id}")),
+                Key::Ip => session::client_ip(headers).map(|ip| format!("ip:{ip}")),
+            };
+            if client.is_some() {
+                return client;
+            }
+        }
+
+        None
+    }
+
+    /// Get the user logged in to the session referenced by the request, if any
+    async fn user(&self, headers: &HeaderMap, query: Option<&str>) -> Option<i32> {
+        let id = self.sessions.id_from_headers(headers).or_else(|| {
+            token(headers, query).and_then(|token| self.sessions.id_from_token(&token))
+        })?;
+
+        match self.sessions.load_from_id(&id).await {
+            Ok(session) => session?.state.id(),
+            Err(error) => {
+                error!(%error, "failed to load session for request limit");
+                None
+            }
+        }
+    }
+}
+
+/// Limit how many requests each client can make to the route, if a limiter is configured. Clients
+/// are identified by the first of the keys present on the request.
+pub(crate) fn limit<S>(
+    route: MethodRouter<S>,
+    name: &'static str,
+    keys: &'static [Key],
+    limiter: Option<&RequestLimiter>,
+    sessions: &session::Manager,
+) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    match limiter {
+        Some(limiter) => {
+            let limit = Limit {
+                limiter: limiter.clone(),
+                route: name,
+                keys,
+                sessions: sessions.clone(),
+            };
+            route.layer(middleware::from_fn_with_state(limit, enforce))
+        }
+        None => route,
+    }
+}
+
+/// Reject requests from clients that have exceeded their quota
+#[instrument(name = "rate_limit::enforce", skip_all, fields(route = limit.route))]
+async fn enforce(State(limit): State<Limit>, request: Request, next: Next) -> Response {
+    // Requests that can't be attributed to a client can't be counted
+    let Some(client) = limit.client(&request).await else {
+        return next.run(request).await;
+    };
+
+    // Fail open so an unavailable cache does not take down the API
+    match limit.limiter.consume(limit.route, &client).await {
+        Ok(usage) if usage.exceeded => {
+            warn!(retry_after = usage.reset, "request limit exceeded");
+            usage.throttled()
+        }
+        Ok(usage) => {
+            let mut response = next.run(request).await;
+            usage.apply(response.headers_mut());
+            response
+        }
+        Err(error) => {
+            error!(%error, "failed to check request limit");
+            next.run(request).await
+        }
+    }
+}
+
+/// Identify a client by its token, without storing the token itself
+fn token_client(token: &str) -> String {
+    let digest = Sha256::digest(token.as_bytes());
+    format!("token:{}", hex::encode(&digest[..16]))
+}
+
+/// Get the token the request was authenticated with, if any
+pub(crate) fn token(headers: &HeaderMap, query: Option<&str>) -> Option<String> {
+    let bearer = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = bearer {
+        return Some(token.to_owned());
+    }
+
+    form_urlencoded::parse(query?.as_bytes())
+        .find(|(name, _)| name == "token")
+        .map(|(_, value)| value.into_owned())
+        .filter(|token| !token.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_quotas() {
+        let cases = [
+            ("600/60", Some((600, 60))),
+            (" 10 / 1 ", Some((10, 1))),
+            ("0/60", Some((0, 60))),
+            ("600/0", None),
+            ("600", None),
+            ("600/minute", None),
+            ("-1/60", None),
+            ("", None),
+        ];
+        for (input, expected) in cases {
+            let quota = input.parse::<Quota>().ok();
+            let expected = expected.map(|(requests, period)| Quota { requests, period });
+            assert_eq!(quota, expected, "{input:?}");
+        }
+    }
+
+    #[test]
+    fn usage_is_exceeded_past_the_quota() {
+        let quota = Quota {
+            requests: 2,
+            period: 60,
+        };
+
+        let cases = [(1, 1, false), (2, 0, false), (3, 0, true), (100, 0, true)];
+        for (used, remaining, exceeded) in cases {
+            let usage = Usage::new(quota, used, 125);
+            assert_eq!(usage.limit, 2);
+            assert_eq!(usage.remaining, remaining, "{used}");
+            assert_eq!(usage.reset, 55);
+            assert_eq!(usage.exceeded, exceeded, "{used}");
+        }
+    }
+
+    #[test]
+    fn throttled_response_says_when_to_retry() {
+        let quota = Quota {
+            requests: 5,
+            period: 60,
+        };
+        let response = Usage::new(quota, 6, 30).throttled();
+
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let headers = response.headers();
+        assert_eq!(headers[RETRY_AFTER], "30");
+        assert_eq!(headers[&RATE_LIMIT_LIMIT], "5");
+        assert_eq!(headers[&RATE_LIMIT_REMAINING], "0");
+        assert_eq!(headers[&RATE_LIMIT_RESET], "30");
+    }
+
+    #[test]
+    fn usage_headers_are_set_by_the_innermost_limit() {
+        let quota = Quota {
+            requests: 5,
+            period: 60,
+        };
+        let mut headers = HeaderMap::new();
+        Usage::new(quota, 1, 0).apply(&mut headers);
+        Usage::new(quota, 3, 0).apply(&mut headers);
+
+        assert_eq!(headers[&RATE_LIMIT_REMAINING], "4");
+    }
+
+    #[test]
+    fn tokens_are_read_from_the_header_before_the_query() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(
+            token(&headers, Some("token=query")),
+            Some("query".to_owned())
+        );
+        assert_eq!(token(&headers, Some("token=")), None);
+        assert_eq!(token(&headers, None), None);
+
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer header"));
+        assert_eq!(
+            token(&headers, Some("token=query")),
+            Some("header".to_owned())
+        );
+    }
+
+    #[test]
+    fn token_clients_do_not_contain_the_token() {
+        let client = token_client("secret-token");
+        assert!(client.starts_with("token:"));
+        assert!(!client.contains("secret-token"));
+        assert_eq!(client, token_client("secret-token"));
+        assert_ne!(client, token_client("other-token"));
+    }
+}
@@ -0,0 +1,25 @@
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use state::{RequestId, REQUEST_ID_HEADER};
+use tracing::{info_span, Instrument};
+
+/// Identify each request by the ID it was sent with, or a newly generated one, so its logs can be
+/// correlated across services
+///
+/// The ID is attached to the request's span, available to handlers through [`RequestId::current`],
+/// and returned in the response headers.
+pub(crate) async fn request_id(request: Request, next: Next) -> Response {
+    let id = request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(RequestId::parse)
+        .unwrap_or_else(RequestId::generate);
+
+    let span = info_span!("request", request.id = %id);
+    let mut response = id.clone().scope(next.run(request)).instrument(span).await;
+
+    let value = HeaderValue::from_str(id.as_str()).expect("request id must be a valid header");
+    response.headers_mut().insert(REQUEST_ID_HEADER, value);
+
+    response
+}
@@ -0,0 +1,264 @@
+use axum::{
+    body::{self, Body},
+    extract::{Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::{self, Next},
+    response::Response,
+    routing::MethodRouter,
+};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::{
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::{instrument, warn};
+
+/// The header containing when the request was signed, in seconds since the unix epoch
+pub(crate) const TIMESTAMP_HEADER: &str = "x-signature-timestamp";
+/// The header containing the signature of the request
+pub(crate) const SIGNATURE_HEADER: &str = "x-signature";
+
+/// How far a request's timestamp can be from the current time, in seconds. Kept short since the
+/// same request can be replayed within it, so it only needs to allow for clock skew and latency.
+const TOLERANCE: u64 = 30;
+/// The largest body that will be buffered to verify its signature
+const MAX_BODY_SIZE: usize = 64 * 1024;
+
+/// A secret shared with internal services that they sign their requests with
+#[derive(Clone)]
+pub(crate) struct SharedSecret(Arc<[u8]>);
+
+impl SharedSecret {
+    pub fn new(secret: &str) -> Self {
+        Self(Arc::from(secret.as_bytes()))
+    }
+
+    /// Check that the message was signed with the secret
+    pub fn matches(&self, message: &[u8], signature: &[u8]) -> bool {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.0).expect("hmac accepts any key length");
+        mac.update(message);
+        mac.verify_slice(signature).is_ok()
+    }
+}
+
+/// Check that a signature's timestamp is close enough to the current time. Prevents captured
+/// requests from being replayed indefinitely.
+pub(crate) fn is_fresh(timestamp: u64) -> bool {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("current time must be after the unix epoch")
+        .as_secs();
+    now.abs_diff(timestamp) <= TOLERANCE
+}
+
+/// Require requests to the route be signed with the secret, if one is configured
+pub(crate) fn protect<S>(route: MethodRouter<S>, secret: Option<&SharedSecret>) -> MethodRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    match secret {
+        Some(secret) => route.layer(middleware::from_fn_with_state(secret.clone(), verify)),
+        None => route,
+    }
+}
+
+/// Reject requests that are not signed with the shared secret
+///
+/// The signature is the hex-encoded HMAC-SHA256 of the timestamp, method, path and query, and
+/// hex-encoded SHA256 of the body, each separated by a newline. It is sent as `sha256=<signature>`.
+#[instrument(name = "signature::verify", skip_all)]
+async fn verify(
+    State(secret): State<SharedSecret>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (parts, body) = request.into_parts();
+    let body = body::to_bytes(body, MAX_BODY_SIZE)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    check(&secret, &parts, &body)?;
+
+    Ok(next.run(Request::from_parts(parts, Body::from(body))).await)
+}
+
+/// Check the request's signature against its contents
+fn check(secret: &SharedSecret, parts: &Parts, body: &[u8]) -> Result<(), StatusCode> {
+    let timestamp = header(&parts.headers, TIMESTAMP_HEADER)
+        .and_then(|value| value.parse::<u64>().ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+    let signature = header(&parts.headers, SIGNATURE_HEADER)
+        .and_then(|value| value.strip_prefix("sha256="))
+        .and_then(|value| hex::decode(value).ok())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !is_fresh(timestamp) {
+        warn!(timestamp, "request signature expired");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let path = parts
+        .uri
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+
+    let message = format!(
+        "{timestamp}\n{}\n{path}\n{}",
+        parts.method,
+        hex::encode(Sha256::digest(body))
+    );
+    if !secret.matches(message.as_bytes(), &signature) {
+        warn!("invalid request signature");
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    Ok(())
+}
+
+/// Get the value of a header as a string
+fn header<'h>(headers: &'h HeaderMap, name: &str) -> Option<&'h str> {
+    headers.get(name).and_then(|value| value.to_str().ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::Method;
+
+    const SECRET: &str = "super-secret";
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+    }
+
+    /// Sign a request the way internal services do
+    fn sign(timestamp: u64, method: &Method, path: &str, body: &[u8]) -> String {
+        let message = format!(
+            "{timestamp}\n{method}\n{path}\n{}",
+            hex::encode(Sha256::digest(body))
+        );
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        mac.update(message.as_bytes());
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+    }
+
+    fn parts(method: Method, path: &str, headers: &[(&str, String)]) -> Parts {
+        let mut builder = Request::builder().method(method).uri(path);
+        for (name, value) in headers {
+            builder = builder.header(*name, value);
+        }
+        builder.body(()).unwrap().into_parts().0
+    }
+
+    #[test]
+    fn accepts_valid_signature() {
+        let secret = SharedSecret::new(SECRET);
+        let timestamp = now();
+        let body = br#"{"domain":"example.com"}"#;
+        let signature = sign(timestamp, &Method::POST, "/context?x=1", body);
+
+        let headers = [
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (SIGNATURE_HEADER, signature),
+        ];
+        let parts = parts(Method::POST, "/context?x=1", &headers);
+        assert_eq!(check(&secret, &parts, body), Ok(()));
+    }
+
+    #[test]
+    fn rejects_expired_signature() {
+        let secret = SharedSecret::new(SECRET);
+        let body = b"";
+
+        for timestamp in [now() - TOLERANCE - 5, now() + TOLERANCE + 5] {
+            let headers = [
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+                (
+                    SIGNATURE_HEADER,
+                    sign(timestamp, &Method::GET, "/context", body),
+                ),
+            ];
+            let parts = parts(Method::GET, "/context", &headers);
+            assert_eq!(
+                check(&secret, &parts, body),
+                Err(StatusCode::UNAUTHORIZED),
+                "{timestamp}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_tampered_request() {
+        let secret = SharedSecret::new(SECRET);
+        let timestamp = now();
+        let body = b"original";
+        let signature = sign(timestamp, &Method::POST, "/context", body);
+
+        let cases = [
+            (Method::POST, "/context", b"modified".as_slice(), timestamp),
+            (Method::PUT, "/context", body.as_slice(), timestamp),
+            (Method::POST, "/introspect", body.as_slice(), timestamp),
+            (Method::POST, "/context?extra=1", body.as_slice(), timestamp),
+            (Method::POST, "/context", body.as_slice(), timestamp - 1),
+        ];
+        for (method, path, body, timestamp) in cases {
+            let headers = [
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+                (SIGNATURE_HEADER, signature.clone()),
+            ];
+            let parts = parts(method.clone(), path, &headers);
+            assert_eq!(
+                check(&secret, &parts, body),
+                Err(StatusCode::UNAUTHORIZED),
+                "{method} {path}"
+            );
+        }
+
+        let other = SharedSecret::new("another-secret");
+        let headers = [
+            (TIMESTAMP_HEADER, timestamp.to_string()),
+            (SIGNATURE_HEADER, signature),
+        ];
+        let parts = parts(Method::POST, "/context", &headers);
+        assert_eq!(check(&other, &parts, body), Err(StatusCode::UNAUTHORIZED));
+    }
+
+    #[test]
+    fn rejects_missing_or_malformed_signature() {
+        let secret = SharedSecret::new(SECRET);
+        let timestamp = now();
+        let signature = sign(timestamp, &Method::GET, "/context", b"");
+        let unprefixed = signature.trim_start_matches("sha256=").to_string();
+
+        let cases = [
+            vec![],
+            vec![(TIMESTAMP_HEADER, timestamp.to_string())],
+            vec![(SIGNATURE_HEADER, signature.clone())],
+            vec![
+                (TIMESTAMP_HEADER, "yesterday".to_string()),
+                (SIGNATURE_HEADER, signature.clone()),
+            ],
+            vec![
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+                (SIGNATURE_HEADER, unprefixed),
+            ],
+            vec![
+                (TIMESTAMP_HEADER, timestamp.to_string()),
+                (SIGNATURE_HEADER, "sha256=not-hex".to_string()),
+            ],
+        ];
+        for headers in cases {
+            let parts = parts(Method::GET, "/context", &headers);
+            assert_eq!(
+                check(&secret, &parts, b""),
+                Err(StatusCode::UNAUTHORIZED),
+                "{headers:?}"
+            );
+        }
+    }
+}
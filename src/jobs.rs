@@ -0,0 +1,212 @@
+use chrono::{DateTime, Duration, Utc};
+use database::{AuthorizationCode, CustomDomain, Email, Job, JobKind, PgPool, WebhookOutboxEntry};
+use serde::Deserialize;
+use serde_json::json;
+use std::fmt::{Display, Formatter};
+use tokio::{task::JoinSet, time};
+use tracing::{error, info, instrument, warn};
+
+/// How often to check for jobs that are due without being woken
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+/// How long a job can run before another worker may pick it up
+const LEASE_SECONDS: i32 = 5 * 60;
+/// How many times a job is attempted before it is given up on
+const MAX_ATTEMPTS: i32 = 5;
+/// How long to wait before retrying a failed job, doubled after each attempt
+const RETRY_BASE_SECONDS: i64 = 30;
+
+/// The maintenance jobs that are run periodically, and how many seconds apart
+const RECURRING: [(JobKind, i64); 4] = [
+    (JobKind::PurgeAuthorizationCodes, 60 * 60),
+    (JobKind::PurgeUnverifiedEmails, 6 * 60 * 60),
+    (JobKind::ExpireCertificates, 60 * 60),
+    // Catches events whose delivery job finished just before they were committed
+    (JobKind::ScheduleWebhookDeliveries, 60),
+];
+
+/// Runs the jobs queued in the database in the background
+///
+/// Jobs are leased while they run, so each is only run by one instance at a time, and failed
+/// jobs are retried with backoff. Recurring jobs are keyed by their kind, so every instance can
+/// schedule them without duplicating work. Webhook deliveries are keyed by their webhook, so each
+/// webhook still receives its events in order.
+#[derive(Clone)]
+pub struct Scheduler {
+    db: PgPool,
+    webhooks: graphql::Webhooks,
+    sessions: session::Manager,
+    concurrency: usize,
+}
+
+impl Scheduler {
+    /// Create a scheduler for the jobs in the database, running at most `concurrency` at once.
+    /// Jobs are only queued on the primary, so the pool must not be for a replica.
+    pub fn new(
+        db: PgPool,
+        webhooks: graphql::Webhooks,
+        sessions: session::Manager,
+        concurrency: usize,
+    ) -> Self {
+        Self {
+            db,
+            webhooks,
+            sessions,
+            concurrency: concurrency.max(1),
+        }
+    }
+
+    /// Start running jobs in the background
+    pub fn start(self) {
+        tokio::task::spawn(self.work());
+    }
+
+    async fn work(self) {
+        for (kind, _) in RECURRING {
+            let result = Job::schedule(kind, Some(kind.as_str()), &json!({}), Utc::now(), &self.db);
+            if let Err(error) = result.await {
+                error!(%error, kind = kind.as_str(), "failed to schedule recurring job");
+            }
+        }
+
+        let mut workers = JoinSet::new();
+        loop {
+            while workers.len() < self.concurrency {
+                match Job::claim(LEASE_SECONDS, &self.db).await {
+                    Ok(Some(job)) => {
+                        let scheduler = self.clone();
+                        workers.spawn(async move { scheduler.process(job).await });
+                    }
+                    Ok(None) => break,
+                    Err(error) => {
+                        error!(%error, "failed to claim job");
+                        break;
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = self.webhooks.queued() => {},
+                _ = time::sleep(POLL_INTERVAL) => {},
+                Some(result) = workers.join_next() => {
+                    if let Err(error) = result {
+                        error!(%error, "job worker failed");
+                    }
+                },
+            }
+        }
+    }
+
+    /// Run the job, then remove it or release it to be retried
+    #[instrument(
+        skip_all,
+        fields(job.id = job.id, job.kind = job.kind.as_str(), job.attempts = job.attempts)
+    )]
+    async fn process(&self, job: Job) {
+        let result = match self.run(&job).await {
+            Ok(run_again_at) => {
+                let next_run = run_again_at.or_else(|| next_run(job.kind));
+                job.complete(next_run, &self.db).await
+            }
+            Err(error) if job.attempts >= MAX_ATTEMPTS => {
+                error!(%error, "job failed too many times, giving up");
+                job.complete(next_run(job.kind), &self.db).await
+            }
+            Err(error) => {
+                warn!(%error, "job failed, retrying later");
+                let delay = RETRY_BASE_SECONDS << (job.attempts - 1).clamp(0, 10);
+                let run_at = Utc::now() + Duration::try_seconds(delay).unwrap();
+                job.retry(&error.to_string(), run_at, &self.db).await
+            }
+        };
+
+        if let Err(error) = result {
+            error!(%error, "failed to record job result");
+        }
+    }
+
+    /// Perform the work for a job, returning when it must run again to finish its work
+    async fn run(&self, job: &Job) -> Result<Option<DateTime<Utc>>, Error> {
+        let affected = match job.kind {
+            JobKind::PurgeAuthorizationCodes => AuthorizationCode::purge_expired(&self.db).await?,
+            JobKind::PurgeUnverifiedEmails => Email::purge_unverified(&self.db).await?,
+            JobKind::ExpireCertificates => CustomDomain::expire_certificates(&self.db).await?,
+            JobKind::DeliverWebhook => {
+                let args = serde_json::from_value::<WebhookArgs>(job.payload.0.clone())?;
+                return Ok(self.webhooks.deliver_queued(args.webhook).await?);
+            }
+            JobKind::ScheduleWebhookDeliveries => {
+                let webhook_ids = WebhookOutboxEntry::pending_webhooks(&self.db).await?;
+                for &webhook_id in &webhook_ids {
+                    Job::deliver_webhook(webhook_id, &self.db).await?;
+                }
+                webhook_ids.len() as u64
+            }
+            JobKind::RevokeSessions => {
+                let args = serde_json::from_value::<UserArgs>(job.payload.0.clone())?;
+                self.sessions.revoke_all(args.user).await? as u64
+            }
+        };
+        info!(affected, "job finished");
+
+        Ok(None)
+    }
+}
+
+/// When a job should run next, if it is recurring
+fn next_run(kind: JobKind) -> Option<DateTime<Utc>> {
+    RECURRING
+        .iter()
+        .find(|(recurring, _)| *recurring == kind)
+        .map(|&(_, interval)| Utc::now() + Duration::try_seconds(interval).unwrap())
+}
+
+/// The arguments for delivering a webhook's events
+#[derive(Deserialize)]
+struct WebhookArgs {
+    webhook: i32,
+}
+
+/// The arguments for jobs acting on a user
+#[derive(Deserialize)]
+struct UserArgs {
+    user: i32,
+}
+
+/// Why a job failed
+#[derive(Debug)]
+enum Error {
+    /// Error while interacting with the database
+    Database(database::Error),
+    /// Error while interacting with the session store
+    Session(session::Error),
+    /// The job's payload is not valid for its kind
+    Payload(serde_json::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Database(error) => write!(f, "database error: {error}"),
+            Self::Session(error) => write!(f, "session error: {error}"),
+            Self::Payload(error) => write!(f, "invalid payload: {error}"),
+        }
+    }
+}
+
+impl From<database::Error> for Error {
+    fn from(error: database::Error) -> Self {
+        Self::Database(error)
+    }
+}
+
+impl From<session::Error> for Error {
+    fn from(error: session::Error) -> Self {
+        Self::Session(error)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(error: serde_json::Error) -> Self {
+        Self::Payload(error)
+    }
+}
@@ -1,46 +1,153 @@
 use ::state::{AllowedRedirectDomains, Domains};
-use axum::{routing::get, Router};
-use database::PgPool;
+use axum::{
+    middleware,
+    routing::{get, post, put},
+    Router,
+};
+use database::Pool;
+use handlers::Key;
 use url::Url;
 
+mod grpc;
 mod handlers;
+mod jobs;
 mod state;
+mod tls;
 
+pub use handlers::{Quota, RequestLimiter, SigningKey};
+pub use jobs::Scheduler;
 pub(crate) use state::AppState;
+pub use tls::load_certificate;
+
+/// Options for configuring the routes
+pub struct Options {
+    /// The publicly accessible URL for the API
+    pub api_url: Url,
+    /// The publicly accessible URL for the frontend
+    pub frontend_url: Url,
+    /// The domains that the OAuth flow is allowed to return to
+    pub allowed_redirect_domains: AllowedRedirectDomains,
+    /// The domains used to determine the scope of a request
+    pub domains: Domains,
+    /// Whether to authenticate GraphQL requests from the session cookie instead of relying on the
+    /// gateway. Such requests must have a JSON body and come from the requested domain or the
+    /// frontend.
+    pub standalone: bool,
+    /// Whether events on custom domains get their own session cookie after logging in
+    pub custom_domain_sessions: bool,
+    /// Where to report GraphQL usage to, if anywhere
+    pub usage: Option<graphql::UsageReporter>,
+    /// How much work each user or service token can request per minute, if limited
+    pub rate_limit: Option<graphql::RateLimiter>,
+    /// How many requests each client can make to `/graphql`, if limited
+    pub graphql_request_limit: Option<RequestLimiter>,
+    /// How many requests each token can be resolved with through `/context`, `/context/batch`, and
+    /// gRPC, if limited
+    pub context_request_limit: Option<RequestLimiter>,
+    /// Queues events for webhooks, whose deliveries are run by the [`Scheduler`]
+    pub webhooks: graphql::Webhooks,
+    /// How to send emails, disabled if not set
+    pub mailer: Option<email::Mailer>,
+    /// Where to post security alerts, disabled if not set
+    pub alerter: Option<graphql::Alerter>,
+    /// Where to cache the events that slugs and domains resolve to, disabled if not set
+    pub scope_cache: Option<graphql::ScopeCache>,
+    /// The key to sign tokens with when acting as an OpenID Connect provider, disabled if not set
+    pub oidc_signing_key: Option<SigningKey>,
+    /// The secret internal services must sign their requests to `/context`, `/introspect`, and
+    /// `/custom-domains` with, unsigned requests are accepted if not set
+    pub internal_signing_secret: Option<String>,
+}
 
 /// Setup the routes
-pub fn router(
-    api_url: Url,
-    db: PgPool,
-    frontend_url: Url,
-    portal_url: Url,
-    allowed_redirect_domains: AllowedRedirectDomains,
-    domains: Domains,
-    sessions: session::Manager,
-) -> Router {
-    let router = Router::new()
-        .route("/context", get(handlers::context))
+pub fn router(db: Pool, sessions: session::Manager, options: Options) -> Router {
+    // In standalone mode, there is no gateway to provide the scope and user context
+    let graphql = if options.standalone {
+        post(handlers::standalone_graphql).layer(session::layer(sessions.clone()))
+    } else {
+        post(handlers::graphql)
+    };
+    let graphql = handlers::limit(
+        graphql,
+        "graphql",
+        &[Key::User, Key::Ip],
+        options.graphql_request_limit.as_ref(),
+        &sessions,
+    );
+
+    let secret = options
+        .internal_signing_secret
+        .as_deref()
+        .map(handlers::SharedSecret::new);
+    let context = handlers::limit(
+        handlers::protect(
+            get(handlers::context).layer(middleware::from_fn(handlers::etag)),
+            secret.as_ref(),
+        ),
+        "context",
+        &[Key::Token],
+        options.context_request_limit.as_ref(),
+        &sessions,
+    );
+
+    let mut router = Router::new()
+        .route("/context", context)
+        .route(
+            "/context/batch",
+            handlers::protect(post(handlers::context::batch), secret.as_ref()),
+        )
         .route(
-            "/graphql",
-            get(handlers::playground).post(handlers::graphql),
+            "/custom-domains/:name/certificate",
+            handlers::protect(put(handlers::report_certificate), secret.as_ref()),
         )
+        .route("/graphql", get(handlers::playground).merge(graphql))
+        .route("/openapi.json", get(handlers::openapi))
         .nest(
             "/oauth",
-            handlers::oauth(&frontend_url).layer(session::layer(sessions.clone())),
-        )
-        .with_state(AppState::new(
-            api_url,
-            db,
-            frontend_url,
-            portal_url,
-            sessions,
-            allowed_redirect_domains,
-            domains,
-        ))
-        .layer(logging::http());
-
-    // Excludes the healthcheck from logging
+            handlers::oauth(&options.frontend_url).layer(session::layer(sessions.clone())),
+        );
+    if options.custom_domain_sessions {
+        router = router
+            .route("/oauth/session", get(handlers::custom_domain_session))
+            .route(
+                "/oauth/session/start",
+                get(handlers::start_custom_domain_session),
+            );
+    }
+    if options.oidc_signing_key.is_some() {
+        router = router.merge(handlers::oidc(sessions.clone(), secret.as_ref()));
+    }
+
+    router
+        .with_state(AppState::new(db, sessions, options))
+        .layer(logging::http())
+        .layer(middleware::from_fn(handlers::request_id))
+}
+
+/// Setup the routes for operating the service, which can be served separately from the public
+/// routes. Requests to them are not logged.
+pub fn ops(db: Pool) -> Router {
     Router::new()
         .route("/health", get(handlers::health))
-        .merge(router)
+        .route("/version", get(handlers::version))
+        .route("/metrics", get(handlers::metrics))
+        .with_state(db)
+}
+
+/// Setup the gRPC services. Calls must be signed with the internal signing secret, if one is set,
+/// and share the request limit of `/context`.
+pub fn grpc(
+    db: Pool,
+    sessions: session::Manager,
+    domains: Domains,
+    scope_cache: Option<graphql::ScopeCache>,
+    internal_signing_secret: Option<&str>,
+    context_request_limit: Option<RequestLimiter>,
+) -> tonic::transport::server::Router {
+    let context =
+        grpc::ContextService::new(db, domains, scope_cache, sessions, context_request_limit);
+    let authenticate = grpc::Authenticate(internal_signing_secret.map(handlers::SharedSecret::new));
+
+    tonic::transport::Server::builder()
+        .add_service(grpc::ContextServer::with_interceptor(context, authenticate))
 }
@@ -3,9 +3,11 @@ use eyre::{eyre, WrapErr};
 use logging::OpenTelemetryProtocol;
 use redis::aio::ConnectionManager as RedisConnectionManager;
 use state::{AllowedRedirectDomains, Domains};
-use std::net::SocketAddr;
+use std::{
+    collections::HashMap, future::IntoFuture, net::SocketAddr, path::PathBuf, time::Duration,
+};
 use tokio::{net::TcpListener, signal};
-use tracing::{info, Level};
+use tracing::{info, warn, Level};
 use url::Url;
 
 #[tokio::main]
@@ -14,6 +16,9 @@ async fn main() -> eyre::Result<()> {
     dotenv()?;
 
     let config = Config::parse();
+    if config.check_config {
+        return check(&config).await;
+    }
 
     let mut logging = logging::config().default_directive(config.log_level);
     if let Some(endpoint) = &config.opentelemetry_endpoint {
@@ -21,9 +26,33 @@ async fn main() -> eyre::Result<()> {
     }
     logging.init()?;
 
-    let db = database::connect(&config.database_url).await?;
+    validate_security(&config)?;
+    authz::set_audit(config.audit_authorization);
+
+    let db = connect_to_database(&config).await?;
+    if config.migrate == Migrate::OnStart {
+        // Migrations are locked while applying, so replicas starting together apply them once
+        migrator::apply(&database::MIGRATOR, db.writer())
+            .await
+            .wrap_err("failed to apply migrations")?;
+        info!("applied pending migrations");
+    }
 
     let cache = connect_to_cache(&config.cache_url).await?;
+    let rate_limit = config
+        .rate_limit
+        .map(|budget| graphql::RateLimiter::new(cache.clone(), budget));
+    let graphql_request_limit = config
+        .graphql_request_limit
+        .map(|quota| identity::RequestLimiter::new(cache.clone(), quota));
+    let context_request_limit = config
+        .context_request_limit
+        .map(|quota| identity::RequestLimiter::new(cache.clone(), quota));
+    let scope_cache = (config.scope_cache_ttl > 0)
+        .then(|| graphql::ScopeCache::new(cache.clone(), config.scope_cache_ttl));
+    let alerter = config
+        .security_alert_url
+        .map(|url| graphql::Alerter::new(url, cache.clone()));
     let sessions = session::Manager::new(
         cache,
         &config.cookie_domain,
@@ -32,7 +61,7 @@ async fn main() -> eyre::Result<()> {
     );
 
     let domains = Domains::new(
-        config.domain_suffix,
+        config.domain_suffixes,
         config.admin_domains,
         config.user_domains,
     );
@@ -40,29 +69,169 @@ async fn main() -> eyre::Result<()> {
         AllowedRedirectDomains::try_from(config.allowed_redirect_domains)
             .wrap_err("invalid allowed redirect domains")?;
 
-    let router = identity::router(
-        config.api_url,
-        db,
-        config.frontend_url,
-        config.portal_url,
-        allowed_redirect_domains,
-        domains,
+    let usage = config.usage_reporting_token.as_deref().map(|token| {
+        info!(endpoint = %config.usage_reporting_endpoint, "reporting graphql usage");
+        graphql::UsageReporter::new(config.usage_reporting_endpoint.clone(), token)
+    });
+
+    let publisher = match &config.event_bus_url {
+        Some(url) => {
+            let publisher = graphql::Publisher::connect(url)
+                .await
+                .map_err(|error| eyre!(error))
+                .wrap_err("failed to connect to the message bus")?;
+            info!(bus = url.scheme(), "publishing events to message bus");
+            Some(publisher)
+        }
+        None => None,
+    };
+
+    let webhooks = graphql::Webhooks::new(db.writer().clone(), publisher);
+    identity::Scheduler::new(
+        db.writer().clone(),
+        webhooks.clone(),
+        sessions.clone(),
+        config.job_concurrency,
+    )
+    .start();
+
+    let mailer = match &config.email_url {
+        Some(url) => {
+            let mailer = email::Mailer::connect(url, &config.email_from)
+                .await
+                .map_err(|error| eyre!(error))
+                .wrap_err("failed to connect to the email service")?;
+            info!(backend = url.scheme(), "sending emails");
+            Some(mailer)
+        }
+        None => None,
+    };
+
+    let oidc_signing_key = config
+        .oidc_signing_key
+        .as_deref()
+        .map(identity::SigningKey::from_pem)
+        .transpose()
+        .wrap_err("invalid openid connect signing key")?;
+
+    let grpc = config.grpc_address.map(|address| {
+        let server = identity::grpc(
+            db.clone(),
+            sessions.clone(),
+            domains.clone(),
+            scope_cache.clone(),
+            config.internal_signing_secret.as_deref(),
+            context_request_limit.clone(),
+        );
+        info!(%address, "serving grpc");
+        tokio::spawn(server.serve_with_shutdown(address, terminated()))
+    });
+
+    let ops = match config.ops_address {
+        Some(address) => {
+            let listener = TcpListener::bind(&address)
+                .await
+                .wrap_err("failed to bind operations listener")?;
+            info!(%address, "serving operations endpoints");
+
+            let server = axum::serve(listener, identity::ops(db.clone()))
+                .with_graceful_shutdown(terminated());
+            Some(tokio::spawn(server.into_future()))
+        }
+        None => None,
+    };
+
+    let mut router = identity::router(
+        db.clone(),
         sessions,
+        identity::Options {
+            api_url: config.api_url,
+            frontend_url: config.frontend_url,
+            allowed_redirect_domains,
+            domains,
+            standalone: config.standalone,
+            custom_domain_sessions: config.custom_domain_sessions,
+            usage,
+            rate_limit,
+            graphql_request_limit,
+            context_request_limit,
+            webhooks,
+            mailer,
+            alerter,
+            scope_cache,
+            oidc_signing_key,
+            internal_signing_secret: config.internal_signing_secret,
+        },
     );
 
-    let listener = TcpListener::bind(&config.address)
-        .await
-        .wrap_err("failed to bind listener")?;
-    info!(address = %config.address, "listening and ready to handle requests");
+    if ops.is_none() {
+        router = router.merge(identity::ops(db));
+    }
 
-    axum::serve(listener, router)
-        .with_graceful_shutdown(shutdown())
-        .await
-        .wrap_err("failed to start server")?;
+    match (config.tls_cert, config.tls_key) {
+        (Some(cert), Some(key)) => {
+            let tls = identity::load_certificate(cert, key)
+                .await
+                .wrap_err("failed to load tls certificate")?;
+            info!(address = %config.address, "listening with tls and ready to handle requests");
+
+            let handle = axum_server::Handle::new();
+            tokio::spawn({
+                let handle = handle.clone();
+                async move {
+                    shutdown().await;
+                    handle.graceful_shutdown(None);
+                }
+            });
+
+            axum_server::bind_rustls(config.address, tls)
+                .handle(handle)
+                .serve(router.into_make_service())
+                .await
+                .wrap_err("failed to start server")?;
+        }
+        _ => {
+            let listener = TcpListener::bind(&config.address)
+                .await
+                .wrap_err("failed to bind listener")?;
+            info!(address = %config.address, "listening and ready to handle requests");
+
+            axum::serve(listener, router)
+                .with_graceful_shutdown(shutdown())
+                .await
+                .wrap_err("failed to start server")?;
+        }
+    }
+
+    if let Some(ops) = ops {
+        ops.await
+            .wrap_err("operations server panicked")?
+            .wrap_err("failed to start operations server")?;
+    }
+
+    if let Some(grpc) = grpc {
+        grpc.await
+            .wrap_err("grpc server panicked")?
+            .wrap_err("failed to start grpc server")?;
+    }
 
     Ok(())
 }
 
+/// Connect to the database and any read replicas, sizing their pools from the configuration
+async fn connect_to_database(config: &Config) -> eyre::Result<database::Pool> {
+    // A timeout of 0 disables it
+    let timeout = |seconds: u64| (seconds > 0).then_some(Duration::from_secs(seconds));
+
+    let options = database::PoolOptions {
+        max_connections: config.database_max_connections,
+        min_connections: config.database_min_connections,
+        idle_timeout: timeout(config.database_idle_timeout),
+        max_lifetime: timeout(config.database_max_lifetime),
+    };
+    database::connect(&config.database_url, &config.database_replica_urls, options).await
+}
+
 /// Connect to the specified cache instance
 async fn connect_to_cache(url: &str) -> eyre::Result<RedisConnectionManager> {
     let client = redis::Client::open(url).wrap_err("invalid cache URL format")?;
@@ -73,8 +242,207 @@ async fn connect_to_cache(url: &str) -> eyre::Result<RedisConnectionManager> {
     Ok(manager)
 }
 
+/// The minimum length of secrets used as HMAC keys
+const MIN_SECRET_LENGTH: usize = 32;
+/// The minimum estimated entropy, in bits, of secrets used as HMAC keys
+const MIN_SECRET_ENTROPY: f64 = 96.0;
+/// Secrets that are given as examples, and so must never be used
+const KNOWN_DEFAULT_SECRETS: [&str; 1] = ["random-string-here"];
+
+/// Validate the configuration and that the services it points to are reachable, printing a report
+/// of each check. Fails if any check fails.
+async fn check(config: &Config) -> eyre::Result<()> {
+    let mut checks = vec![
+        ("api url", check_public_url(&config.api_url)),
+        ("frontend url", check_public_url(&config.frontend_url)),
+        ("cookie domain", check_cookie_domain(config)),
+        (
+            "allowed redirect domains",
+            AllowedRedirectDomains::try_from(config.allowed_redirect_domains.clone())
+                .map(|_| ())
+                .map_err(|error| eyre!(error)),
+        ),
+        (
+            "cookie signing key",
+            check_secret_strength(&config.cookie_signing_key),
+        ),
+    ];
+
+    if let Some(secret) = &config.internal_signing_secret {
+        checks.push(("internal signing secret", check_secret_strength(secret)));
+    }
+    if config.production {
+        checks.push(("secure cookies", check_secure_cookies(config)));
+    }
+    if let Some(key) = &config.oidc_signing_key {
+        checks.push((
+            "openid connect signing key",
+            identity::SigningKey::from_pem(key).map(|_| ()),
+        ));
+    }
+    if let (Some(cert), Some(key)) = (&config.tls_cert, &config.tls_key) {
+        let result = identity::load_certificate(cert.clone(), key.clone()).await;
+        checks.push((
+            "tls certificate",
+            result.map(|_| ()).map_err(|error| eyre!(error)),
+        ));
+    }
+
+    let database = connect_to_database(config).await;
+    checks.push(("database", database.map(|_| ())));
+    let cache = connect_to_cache(&config.cache_url).await;
+    checks.push(("cache", cache.map(|_| ())));
+
+    if let Some(url) = &config.event_bus_url {
+        let publisher = graphql::Publisher::connect(url).await;
+        checks.push((
+            "message bus",
+            publisher.map(|_| ()).map_err(|error| eyre!(error)),
+        ));
+    }
+    if let Some(url) = &config.email_url {
+        let mailer = email::Mailer::connect(url, &config.email_from).await;
+        checks.push((
+            "email service",
+            mailer.map(|_| ()).map_err(|error| eyre!(error)),
+        ));
+    }
+
+    let mut failed = 0;
+    for (name, result) in checks {
+        match result {
+            Ok(()) => println!("ok    {name}"),
+            Err(error) => {
+                failed += 1;
+                println!("error {name}: {error:#}");
+            }
+        }
+    }
+
+    match failed {
+        0 => Ok(()),
+        1 => Err(eyre!("1 configuration check failed")),
+        n => Err(eyre!("{n} configuration checks failed")),
+    }
+}
+
+/// Ensure a publicly accessible URL can be used as a base for links and redirects
+fn check_public_url(url: &Url) -> eyre::Result<()> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(eyre!("must use http or https"));
+    }
+    if url.host_str().is_none() {
+        return Err(eyre!("must include a host"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(eyre!("must not include a query or fragment"));
+    }
+
+    Ok(())
+}
+
+/// Ensure the session cookie will be sent to both the API and the frontend
+fn check_cookie_domain(config: &Config) -> eyre::Result<()> {
+    let domain = config.cookie_domain.trim_start_matches('.');
+    for url in [&config.api_url, &config.frontend_url] {
+        let host = url.host_str().unwrap_or_default();
+        if host != domain && !host.ends_with(&format!(".{domain}")) {
+            return Err(eyre!("{host} is not within {domain}"));
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensure a secret is long and random enough to be used as an HMAC key
+fn check_secret_strength(secret: &str) -> eyre::Result<()> {
+    if KNOWN_DEFAULT_SECRETS.contains(&secret) {
+        return Err(eyre!("must be changed from the example value"));
+    }
+    if secret.len() < MIN_SECRET_LENGTH {
+        return Err(eyre!(
+            "must be at least {MIN_SECRET_LENGTH} characters, found {}",
+            secret.len()
+        ));
+    }
+
+    let entropy = estimate_entropy(secret);
+    if entropy < MIN_SECRET_ENTROPY {
+        return Err(eyre!(
+            "must be more random, estimated {entropy:.0} bits of entropy but at least \
+             {MIN_SECRET_ENTROPY} are required"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Estimate the total entropy of a secret in bits from the frequency of its characters
+fn estimate_entropy(secret: &str) -> f64 {
+    let mut frequencies = HashMap::<u8, usize>::new();
+    for byte in secret.bytes() {
+        *frequencies.entry(byte).or_default() += 1;
+    }
+
+    let length = secret.len() as f64;
+    let per_character = frequencies
+        .values()
+        .map(|&count| {
+            let probability = count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum::<f64>();
+
+    per_character * length
+}
+
+/// Ensure the session cookie is only ever sent over HTTPS
+fn check_secure_cookies(config: &Config) -> eyre::Result<()> {
+    if config.frontend_url.scheme() != "https" {
+        return Err(eyre!(
+            "the frontend url must use https for cookies to be secure"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Check the secrets and cookie settings before starting. Weak settings are refused in
+/// production and warned about otherwise.
+fn validate_security(config: &Config) -> eyre::Result<()> {
+    let mut checks = vec![
+        (
+            "cookie signing key",
+            check_secret_strength(&config.cookie_signing_key),
+        ),
+        ("secure cookies", check_secure_cookies(config)),
+    ];
+    if let Some(secret) = &config.internal_signing_secret {
+        checks.push(("internal signing secret", check_secret_strength(secret)));
+    }
+
+    for (name, result) in checks {
+        let Err(error) = result else { continue };
+        if config.production {
+            return Err(error).wrap_err_with(|| format!("insecure {name}"));
+        }
+
+        warn!(%error, "insecure {name}, this is not allowed in production");
+    }
+
+    Ok(())
+}
+
 /// Setup hyper graceful shutdown for SIGINT (ctrl+c) and SIGTERM
 async fn shutdown() {
+    terminated().await;
+
+    info!("server successfully shutdown");
+    info!("goodbye! o/");
+}
+
+/// Wait for SIGINT (ctrl+c) or SIGTERM
+async fn terminated() {
     let ctrl_c = async {
         signal::ctrl_c()
             .await
@@ -91,27 +459,99 @@ async fn shutdown() {
         _ = ctrl_c => {},
         _ = terminate => {},
     }
-
-    info!("server successfully shutdown");
-    info!("goodbye! o/");
 }
 
 /// The authentication and authorization service for the hacker app
 #[derive(Debug, Parser)]
 #[command(author, version, about)]
 struct Config {
+    /// Validate the configuration and that the services it depends on are reachable, then exit
+    /// with a report instead of starting the server
+    #[arg(long)]
+    check_config: bool,
+
     /// The address for the server to listen on
     #[arg(long, default_value = "127.0.0.1:4243", env = "ADDRESS")]
     address: SocketAddr,
 
+    /// The PEM-encoded certificate chain to terminate TLS with
+    ///
+    /// Requests are served over plain HTTP when not set. The certificate and key are reloaded
+    /// whenever they change
+    #[arg(long, env = "TLS_CERT", requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// The PEM-encoded private key for the TLS certificate
+    #[arg(long, env = "TLS_KEY", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// The address for the gRPC server to listen on
+    ///
+    /// The gRPC server is disabled when not set
+    #[arg(long, env = "GRPC_ADDRESS")]
+    grpc_address: Option<SocketAddr>,
+
+    /// The address to serve the health check and build information on, separately from the
+    /// public routes
+    ///
+    /// They are served alongside the public routes when not set
+    #[arg(long, env = "OPS_ADDRESS")]
+    ops_address: Option<SocketAddr>,
+
     /// The database to run migrations on
     #[arg(long, env = "DATABASE_URL")]
     database_url: String,
 
+    /// A comma-separated list of read replicas of the database
+    ///
+    /// Context resolution, GraphQL queries, and dataloaders read from them in turn, so may briefly
+    /// lag behind writes, which always go to the primary. Reads use the primary when not set
+    #[arg(
+        long = "database-replica-url",
+        value_delimiter = ',',
+        env = "DATABASE_REPLICA_URLS"
+    )]
+    database_replica_urls: Vec<String>,
+
+    /// The most connections to open to the database, and to each replica
+    #[arg(long, default_value_t = 10, env = "DATABASE_MAX_CONNECTIONS")]
+    database_max_connections: u32,
+
+    /// How many connections to keep open to the database, and to each replica, even when idle
+    #[arg(long, default_value_t = 0, env = "DATABASE_MIN_CONNECTIONS")]
+    database_min_connections: u32,
+
+    /// How many seconds a database connection can be idle for before it is closed
+    ///
+    /// Idle connections are kept open when set to 0
+    #[arg(long, default_value_t = 600, env = "DATABASE_IDLE_TIMEOUT")]
+    database_idle_timeout: u64,
+
+    /// How many seconds a database connection can be open for before it is replaced
+    ///
+    /// Connections are never replaced when set to 0
+    #[arg(long, default_value_t = 1800, env = "DATABASE_MAX_LIFETIME")]
+    database_max_lifetime: u64,
+
+    /// When to apply pending database migrations
+    ///
+    /// Applying them on start removes the need for a separate migration step in single-container
+    /// deployments
+    #[arg(long, value_enum, default_value_t = Migrate::Never, env = "MIGRATE")]
+    migrate: Migrate,
+
     /// The Redis cache to store sessions in
     #[arg(long, env = "CACHE_URL")]
     cache_url: String,
 
+    /// How many seconds to cache the events that slugs and domains resolve to for `/context`
+    ///
+    /// Custom domains that move between events are invalidated as soon as they change, but can
+    /// resolve to the old event for this long if the change is missed. Caching is disabled when set
+    /// to 0
+    #[arg(long, default_value_t = 30, env = "SCOPE_CACHE_TTL")]
+    scope_cache_ttl: u64,
+
     /// The default level to log at
     #[arg(long, default_value_t = Level::INFO, env = "LOG_LEVEL")]
     log_level: Level,
@@ -124,14 +564,17 @@ struct Config {
     #[arg(long, env = "FRONTEND_URL")]
     frontend_url: Url,
 
-    /// The domain suffix where non-custom domains are hosted
+    /// A comma-separated list of domain suffixes where non-custom domains are hosted
+    ///
+    /// Events are accessible under all of them, the first is used when linking to an event
     #[arg(
-    long,
+    long = "domain-suffix",
     default_value = ".thehacker.app",
+    value_delimiter = ',',
     value_parser = valid_domain_suffix,
     env = "DOMAIN_SUFFIX",
     )]
-    domain_suffix: String,
+    domain_suffixes: Vec<String>,
 
     /// A comma-separated list of domains which require the admin scope
     #[arg(long, value_delimiter = ',', env = "ADMIN_DOMAINS")]
@@ -144,6 +587,7 @@ struct Config {
     /// A comma-separated list of domains that the OAuth flow is allowed to return to
     ///
     /// Allows globs in individual domains. Also automatically includes any registered custom domains
+    /// and those allowed at runtime through the API
     #[arg(long, value_delimiter = ',', env = "ALLOWED_REDIRECT_DOMAINS")]
     allowed_redirect_domains: Vec<String>,
 
@@ -153,17 +597,117 @@ struct Config {
     #[arg(long, env = "COOKIE_DOMAIN")]
     cookie_domain: String,
 
-    /// The internal URL to the portal service
-    // TODO: remove in favor of generic webhook solution
-    #[arg(long, env = "PORTAL_URL")]
-    portal_url: Url,
-
     /// A secret to sign the session cookie with
     ///
     /// This should be a long, random string
     #[arg(long, env = "COOKIE_SIGNING_KEY")]
     cookie_signing_key: String,
 
+    /// Refuse to start with insecure settings, such as a weak cookie signing key or cookies that
+    /// can be sent over plain HTTP
+    ///
+    /// They are only warned about when not set
+    #[arg(long, env = "PRODUCTION")]
+    production: bool,
+
+    /// Give events on custom domains their own session cookie after logging in, by handing the
+    /// session off through `/oauth/session` on the custom domain. Logins must be started from
+    /// `/oauth/session/start` on the custom domain for the handoff to happen.
+    ///
+    /// Requires the gateway to route `/oauth/session` and `/oauth/session/start` on custom domains
+    /// to this service
+    #[arg(long, env = "CUSTOM_DOMAIN_SESSIONS")]
+    custom_domain_sessions: bool,
+
+    /// Authenticate GraphQL requests directly from the session cookie instead of relying on the
+    /// gateway to provide the request context
+    #[arg(long, env = "STANDALONE")]
+    standalone: bool,
+
+    /// The token used to authenticate with the usage reporting endpoint
+    ///
+    /// Usage reporting is disabled when not set
+    #[arg(long, env = "USAGE_REPORTING_TOKEN")]
+    usage_reporting_token: Option<String>,
+
+    /// Where to report GraphQL operation statistics to, must be compatible with GraphQL Hive
+    #[arg(
+        long,
+        default_value = "https://app.graphql-hive.com/usage",
+        env = "USAGE_REPORTING_ENDPOINT"
+    )]
+    usage_reporting_endpoint: Url,
+
+    /// The total query complexity each user or service token can request per minute
+    ///
+    /// Rate limiting is disabled when not set
+    #[arg(long, env = "RATE_LIMIT")]
+    rate_limit: Option<u64>,
+
+    /// How many requests each client can make to `/graphql`, formatted as `<requests>/<seconds>`
+    ///
+    /// Clients are identified by the user they are logged in as, or their IP address otherwise. Not
+    /// limited when not set
+    #[arg(long, env = "GRAPHQL_REQUEST_LIMIT")]
+    graphql_request_limit: Option<identity::Quota>,
+
+    /// How many times each token can be resolved through `/context`, `/context/batch`, and gRPC,
+    /// formatted as `<requests>/<seconds>`
+    ///
+    /// Not limited when not set
+    #[arg(long, env = "CONTEXT_REQUEST_LIMIT")]
+    context_request_limit: Option<identity::Quota>,
+
+    /// Log every authorization denial along with the actor, resource, and rule that caused it
+    #[arg(long, env = "AUDIT_AUTHORIZATION")]
+    audit_authorization: bool,
+
+    /// How many background jobs, such as webhook deliveries, can run at once
+    #[arg(long, default_value_t = 8, env = "JOB_CONCURRENCY")]
+    job_concurrency: usize,
+
+    /// A message bus to publish events to, in addition to webhooks
+    ///
+    /// Supports `nats://`, `kafka://`, and `sqs://` URLs when the corresponding feature is enabled
+    #[arg(long, env = "EVENT_BUS_URL")]
+    event_bus_url: Option<Url>,
+
+    /// The service to send emails through
+    ///
+    /// Supports `smtp://`, `smtps://`, and `ses://` (when the feature is enabled) URLs. Emails are
+    /// not sent when not set
+    #[arg(long, env = "EMAIL_URL")]
+    email_url: Option<Url>,
+
+    /// The address emails are sent from
+    #[arg(
+        long,
+        default_value = "The Hacker App <noreply@thehacker.app>",
+        env = "EMAIL_FROM"
+    )]
+    email_from: String,
+
+    /// A Slack or Discord incoming webhook to post security alerts to
+    ///
+    /// Alerts are sent for high-severity events such as administrators being granted, provider
+    /// secrets changing, and repeated login failures. No alerts are sent when not set
+    #[arg(long, env = "SECURITY_ALERT_URL")]
+    security_alert_url: Option<Url>,
+
+    /// The PEM-encoded RSA private key to sign tokens with when acting as an OpenID Connect provider
+    ///
+    /// The provider is disabled when not set
+    #[arg(long, env = "OIDC_SIGNING_KEY")]
+    oidc_signing_key: Option<String>,
+
+    /// A secret shared with internal services that they must sign requests to the context,
+    /// introspection, and certificate reporting endpoints, and calls to the gRPC service, with
+    ///
+    /// Signatures are only accepted within 30 seconds of being made, so the services' clocks must
+    /// be kept in sync. Requests are not required to be signed when not set
+    #[arg(long, env = "INTERNAL_SIGNING_SECRET")]
+    internal_signing_secret: Option<String>,
+
     /// The OpenTelemetry endpoint to send traces to
     #[arg(long, env = "OTEL_EXPORTER_OTLP_ENDPOINT")]
     opentelemetry_endpoint: Option<String>,
@@ -178,6 +722,15 @@ struct Config {
     opentelemetry_protocol: OpenTelemetryProtocol,
 }
 
+/// When to apply pending database migrations
+#[derive(Clone, Copy, Debug, Eq, PartialEq, clap::ValueEnum)]
+enum Migrate {
+    /// Migrations are applied separately
+    Never,
+    /// Migrations are applied before the server starts listening
+    OnStart,
+}
+
 /// Load environment variables from a .env file, if it exists.
 fn dotenv() -> eyre::Result<()> {
     if let Err(error) = dotenvy::dotenv() {
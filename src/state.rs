@@ -1,8 +1,7 @@
-use crate::handlers::OAuthClient;
+use crate::{handlers::OAuthClient, Options, RequestLimiter, SigningKey};
 use axum::extract::FromRef;
-use database::PgPool;
+use database::{PgPool, Pool};
 use state::{AllowedRedirectDomains, ApiUrl, Domains, FrontendUrl};
-use url::Url;
 
 macro_rules! state {
     ( $( $field:ident : $type:ty ),+ $(,)? ) => {
@@ -23,35 +22,93 @@ macro_rules! state {
 }
 
 state! {
+    alerter: Option<graphql::Alerter>,
     allowed_redirect_domains: AllowedRedirectDomains,
     api_url: ApiUrl,
-    db: PgPool,
+    context_request_limit: Option<RequestLimiter>,
+    custom_domain_sessions: bool,
+    db: Pool,
     domains: Domains,
+    feature_flags: graphql::FeatureFlags,
     frontend_url: FrontendUrl,
+    mailer: Option<email::Mailer>,
     oauth_client: OAuthClient,
+    oidc_signing_key: Option<SigningKey>,
+    providers: graphql::ProviderCache,
     schema: graphql::Schema,
+    scope_cache: Option<graphql::ScopeCache>,
     sessions: session::Manager,
+    webhooks: graphql::Webhooks,
 }
 
 impl AppState {
-    pub fn new(
-        api_url: Url,
-        db: PgPool,
-        frontend_url: Url,
-        portal_url: Url,
-        sessions: session::Manager,
-        allowed_redirect_domains: AllowedRedirectDomains,
-        domains: Domains,
-    ) -> AppState {
+    pub fn new(db: Pool, sessions: session::Manager, options: Options) -> AppState {
+        let primary = db.writer();
+        let webhooks = options.webhooks;
+        let feature_flags = graphql::FeatureFlags::new(primary.clone());
+        let redirect_domains = graphql::RedirectDomains::new(
+            options.allowed_redirect_domains.clone(),
+            primary.clone(),
+        );
+        let providers = graphql::ProviderCache::new(primary.clone());
+        graphql::Invalidator::new(
+            feature_flags.clone(),
+            providers.clone(),
+            redirect_domains.clone(),
+            options.scope_cache.clone(),
+        )
+        .listen(primary.clone());
+        let schema = graphql::schema(
+            db.clone(),
+            sessions.clone(),
+            graphql::Options {
+                domains: options.domains.clone(),
+                usage: options.usage,
+                rate_limit: options.rate_limit,
+                webhooks: webhooks.clone(),
+                mailer: options.mailer.clone(),
+                alerter: options.alerter.clone(),
+                scope_cache: options.scope_cache.clone(),
+                feature_flags: feature_flags.clone(),
+                redirect_domains,
+                providers: providers.clone(),
+            },
+        );
+
         AppState {
-            allowed_redirect_domains,
-            api_url: api_url.into(),
-            db: db.clone(),
-            domains: domains.clone(),
-            frontend_url: frontend_url.into(),
+            alerter: options.alerter,
+            allowed_redirect_domains: options.allowed_redirect_domains,
+            api_url: options.api_url.into(),
+            context_request_limit: options.context_request_limit,
+            custom_domain_sessions: options.custom_domain_sessions,
+            db,
+            domains: options.domains,
+            feature_flags,
+            frontend_url: options.frontend_url.into(),
+            mailer: options.mailer,
             oauth_client: OAuthClient::default(),
-            schema: graphql::schema(db, domains, portal_url),
+            oidc_signing_key: options.oidc_signing_key,
+            providers,
+            schema,
+            scope_cache: options.scope_cache,
             sessions,
+            webhooks,
         }
     }
 }
+
+/// Handlers that write to the database, or must see the latest writes, use the primary
+impl FromRef<AppState> for PgPool {
+    fn from_ref(state: &AppState) -> Self {
+        state.db.writer().clone()
+    }
+}
+
+impl FromRef<AppState> for SigningKey {
+    fn from_ref(state: &AppState) -> Self {
+        state
+            .oidc_signing_key
+            .clone()
+            .expect("openid connect routes are only mounted when a signing key is set")
+    }
+}
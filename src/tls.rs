@@ -0,0 +1,65 @@
+use axum_server::tls_rustls::RustlsConfig;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+use tokio::{fs, time};
+use tracing::{error, info, instrument};
+
+/// How often to check whether the certificate or key changed
+const RELOAD_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Load the PEM-encoded certificate chain and private key to terminate TLS with
+///
+/// The files are watched in the background and reloaded whenever either one changes, so renewed
+/// certificates are picked up without a restart. If a reload fails, the previous certificate
+/// continues to be used.
+pub async fn load_certificate(cert: PathBuf, key: PathBuf) -> io::Result<RustlsConfig> {
+    let config = RustlsConfig::from_pem_file(&cert, &key).await?;
+
+    let modified = last_modified(&cert, &key).await?;
+    tokio::spawn(reload_on_change(config.clone(), cert, key, modified));
+
+    Ok(config)
+}
+
+/// Reload the certificate and key whenever either file is modified
+#[instrument(skip(config, modified))]
+async fn reload_on_change(
+    config: RustlsConfig,
+    cert: PathBuf,
+    key: PathBuf,
+    mut modified: SystemTime,
+) {
+    loop {
+        time::sleep(RELOAD_INTERVAL).await;
+
+        let latest = match last_modified(&cert, &key).await {
+            Ok(latest) => latest,
+            Err(error) => {
+                error!(%error, "failed to check tls certificate for changes");
+                continue;
+            }
+        };
+        if latest == modified {
+            continue;
+        }
+
+        match config.reload_from_pem_file(&cert, &key).await {
+            Ok(()) => {
+                info!("reloaded tls certificate");
+                modified = latest;
+            }
+            Err(error) => error!(%error, "failed to reload tls certificate"),
+        }
+    }
+}
+
+/// Get when the certificate or key was most recently modified
+async fn last_modified(cert: &Path, key: &Path) -> io::Result<SystemTime> {
+    let cert = fs::metadata(cert).await?.modified()?;
+    let key = fs::metadata(key).await?.modified()?;
+
+    Ok(cert.max(key))
+}
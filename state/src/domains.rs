@@ -1,14 +1,42 @@
 use globset::{Glob, GlobSet};
-use std::{collections::HashSet, sync::Arc};
+use std::{
+    collections::HashSet,
+    sync::{Arc, RwLock},
+};
 
 /// Checks if the request domain is allowed to be redirected to
+///
+/// Domains come from the static configuration, along with any that are added at runtime. The
+/// runtime domains can be replaced without affecting the static ones.
 #[derive(Clone, Debug)]
-pub struct AllowedRedirectDomains(Arc<GlobSet>);
+pub struct AllowedRedirectDomains(Arc<AllowedRedirectDomainsInner>);
+
+#[derive(Debug)]
+struct AllowedRedirectDomainsInner {
+    fixed: Vec<Glob>,
+    set: RwLock<GlobSet>,
+}
 
 impl AllowedRedirectDomains {
     /// Test of a domain matches one that can be redirected to
     pub fn matches(&self, domain: &str) -> bool {
-        self.0.is_match(domain)
+        self.0.set.read().unwrap().is_match(domain)
+    }
+
+    /// Replace the domains added at runtime, keeping those from the static configuration. The
+    /// previous domains are kept if any of the new ones are invalid.
+    pub fn replace_dynamic<'p>(
+        &self,
+        patterns: impl IntoIterator<Item = &'p str>,
+    ) -> Result<(), globset::Error> {
+        let dynamic = patterns
+            .into_iter()
+            .map(Glob::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let set = build(self.0.fixed.iter().cloned().chain(dynamic))?;
+
+        *self.0.set.write().unwrap() = set;
+        Ok(())
     }
 }
 
@@ -16,16 +44,29 @@ impl TryFrom<Vec<String>> for AllowedRedirectDomains {
     type Error = globset::Error;
 
     fn try_from(raw: Vec<String>) -> Result<Self, Self::Error> {
-        let mut set = GlobSet::builder();
+        let fixed = raw
+            .iter()
+            .map(|glob| Glob::new(glob))
+            .collect::<Result<Vec<_>, _>>()?;
+        let set = build(fixed.iter().cloned())?;
 
-        for glob in raw {
-            let glob = Glob::new(&glob)?;
-            set.add(glob);
-        }
+        Ok(AllowedRedirectDomains(Arc::new(
+            AllowedRedirectDomainsInner {
+                fixed,
+                set: RwLock::new(set),
+            },
+        )))
+    }
+}
 
-        let set = set.build()?;
-        Ok(AllowedRedirectDomains(Arc::new(set)))
+/// Build a set to match against from the globs
+fn build(globs: impl IntoIterator<Item = Glob>) -> Result<GlobSet, globset::Error> {
+    let mut set = GlobSet::builder();
+    for glob in globs {
+        set.add(glob);
     }
+
+    set.build()
 }
 
 /// A collection of domains to validate against
@@ -34,34 +75,45 @@ pub struct Domains(Arc<DomainsInner>);
 
 #[derive(Debug)]
 struct DomainsInner {
-    event_suffix: String,
+    event_suffixes: Vec<String>,
     admin: HashSet<String>,
     user: HashSet<String>,
 }
 
 impl Domains {
-    /// Create a new domain set
+    /// Create a new domain set. The first event suffix is the primary one, used when building
+    /// event domains.
     pub fn new(
-        event_suffix: String,
+        event_suffixes: Vec<String>,
         admin_domains: Vec<String>,
         user_domains: Vec<String>,
     ) -> Self {
+        assert!(
+            !event_suffixes.is_empty(),
+            "at least one event suffix is required"
+        );
+
         let inner = DomainsInner {
-            event_suffix,
+            event_suffixes,
             admin: admin_domains.into_iter().collect(),
             user: user_domains.into_iter().collect(),
         };
         Domains(Arc::new(inner))
     }
 
-    /// Create an event domain from a slug
+    /// Create an event domain from a slug, using the primary suffix
     pub fn for_event(&self, slug: &str) -> String {
-        format!("{slug}{suffix}", suffix = &self.0.event_suffix)
+        format!("{slug}{suffix}", suffix = &self.0.event_suffixes[0])
     }
 
-    /// Get the subdomain of a domain with respect to the current suffix
+    /// Get the subdomain of a domain with respect to any of the suffixes. When multiple suffixes
+    /// match, the longest one is used.
     pub fn extract_slug_for_subdomain<'a>(&'a self, domain: &'a str) -> Option<&str> {
-        domain.strip_suffix(&self.0.event_suffix)
+        self.0
+            .event_suffixes
+            .iter()
+            .filter_map(|suffix| domain.strip_suffix(suffix.as_str()))
+            .min_by_key(|slug| slug.len())
     }
 
     /// Whether the domain requires admin permissions
@@ -1,5 +1,9 @@
 mod domains;
+mod request_id;
+mod trace_context;
 mod urls;
 
 pub use domains::{AllowedRedirectDomains, Domains};
+pub use request_id::{RequestId, REQUEST_ID_HEADER};
+pub use trace_context::TraceContext;
 pub use urls::{ApiUrl, FrontendUrl};
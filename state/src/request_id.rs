@@ -0,0 +1,55 @@
+use std::{
+    fmt::{Display, Formatter},
+    future::Future,
+    sync::Arc,
+};
+
+tokio::task_local! {
+    static CURRENT: RequestId;
+}
+
+/// The header the request ID is received and sent in
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// The longest request ID that will be accepted from a client
+const MAX_LENGTH: usize = 128;
+
+/// Identifies a request across services, allowing its logs to be correlated
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RequestId(Arc<str>);
+
+impl RequestId {
+    /// Generate a new random ID
+    pub fn generate() -> Self {
+        Self(Arc::from(format!("{:032x}", rand::random::<u128>())))
+    }
+
+    /// Use an ID provided by a client, as long as it is reasonable to log and forward
+    pub fn parse(raw: &str) -> Option<Self> {
+        let valid =
+            !raw.is_empty() && raw.len() <= MAX_LENGTH && raw.bytes().all(|b| b.is_ascii_graphic());
+
+        valid.then(|| Self(Arc::from(raw)))
+    }
+
+    /// Get the ID of the request currently being handled, if any
+    pub fn current() -> Option<Self> {
+        CURRENT.try_with(Clone::clone).ok()
+    }
+
+    /// Run the future with this as the ID of the current request
+    pub async fn scope<F: Future>(self, future: F) -> F::Output {
+        CURRENT.scope(self, future).await
+    }
+
+    /// Convert the ID to a string slice
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Display for RequestId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
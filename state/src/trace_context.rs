@@ -0,0 +1,50 @@
+use opentelemetry::propagation::TextMapPropagator;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use std::collections::HashMap;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+
+/// The W3C trace context of a span, as the `traceparent` and `tracestate` headers
+///
+/// Empty when the span is not being exported, so it can always be sent along with a request.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TraceContext(HashMap<String, String>);
+
+impl TraceContext {
+    /// Get the trace context of the current span
+    pub fn current() -> Self {
+        let context = Span::current().context();
+
+        let mut headers = HashMap::new();
+        TraceContextPropagator::new().inject_context(&context, &mut headers);
+        Self(headers)
+    }
+
+    /// Make the span a child of the trace context, continuing a trace that was started elsewhere
+    pub fn continue_in(&self, span: &Span) {
+        if self.0.is_empty() {
+            return;
+        }
+
+        let context = TraceContextPropagator::new().extract(&self.0);
+        span.set_parent(context);
+    }
+
+    /// Get the headers to send the trace context in
+    pub fn headers(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Get the raw headers, for storing the context
+    pub fn into_inner(self) -> HashMap<String, String> {
+        self.0
+    }
+}
+
+impl From<HashMap<String, String>> for TraceContext {
+    fn from(headers: HashMap<String, String>) -> Self {
+        Self(headers)
+    }
+}
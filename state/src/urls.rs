@@ -1,11 +1,16 @@
 use std::sync::Arc;
-use url::Url;
+use url::{Origin, Url};
 
 /// The publicly accessible URL for the API
 #[derive(Debug, Clone)]
 pub struct ApiUrl(Arc<Url>);
 
 impl ApiUrl {
+    /// Convert the URL to a string slice
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+
     /// Append a path segment to the URL
     pub fn join(&self, path: &str) -> Url {
         self.0.join(path).expect("path must be valid")
@@ -32,6 +37,11 @@ impl FrontendUrl {
     pub fn join(&self, path: &str) -> Url {
         self.0.join(path).expect("path must be valid")
     }
+
+    /// The origin browsers send requests from the frontend with
+    pub fn origin(&self) -> Origin {
+        self.0.origin()
+    }
 }
 
 impl From<Url> for FrontendUrl {
@@ -1,8 +1,14 @@
 use eyre::{eyre, WrapErr};
 use std::{fs::OpenOptions, io::Write, path::PathBuf};
-use tracing::info;
+use tracing::{error, info, warn};
+
+mod breaking;
 
 pub fn run(args: Args) -> eyre::Result<()> {
+    if args.check {
+        return check(&args);
+    }
+
     if args.output.exists() && !args.force {
         return Err(eyre!("file already exists, use --force to overwrite"));
     }
@@ -22,6 +28,28 @@ pub fn run(args: Args) -> eyre::Result<()> {
     Ok(())
 }
 
+/// Compare the generated schema against the exported one, failing if it would break clients
+fn check(args: &Args) -> eyre::Result<()> {
+    let existing = std::fs::read_to_string(&args.output).wrap_err("failed to read schema")?;
+    let generated = graphql::sdl();
+
+    let changes = breaking::changes(&existing, &generated)?;
+    for change in &changes {
+        error!(%change, "breaking change");
+    }
+    if !changes.is_empty() {
+        return Err(eyre!("found {} breaking changes", changes.len()));
+    }
+
+    if existing == generated {
+        info!(path = %args.output.display(), "schema is up to date");
+    } else {
+        warn!(path = %args.output.display(), "schema is out of date, export it with --force");
+    }
+
+    Ok(())
+}
+
 #[derive(clap::Args, Debug)]
 pub struct Args {
     /// Where to save the schema
@@ -30,4 +58,8 @@ pub struct Args {
     /// Whether to overwrite the output file if it already exists
     #[arg(short, long, default_value_t)]
     force: bool,
+    /// Compare against the schema in the output file instead of overwriting it, failing on any
+    /// breaking changes
+    #[arg(long, default_value_t, conflicts_with = "force")]
+    check: bool,
 }
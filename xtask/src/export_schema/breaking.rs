@@ -0,0 +1,167 @@
+use async_graphql::parser::{
+    parse_schema,
+    types::{
+        BaseType, FieldDefinition, InputValueDefinition, Type, TypeKind, TypeSystemDefinition,
+    },
+    Positioned,
+};
+use eyre::WrapErr;
+use std::collections::BTreeMap;
+
+/// Find the changes from the old schema to the new one that would break existing clients
+pub fn changes(old: &str, new: &str) -> eyre::Result<Vec<String>> {
+    let old = types(old).wrap_err("failed to parse the existing schema")?;
+    let new = types(new).wrap_err("failed to parse the generated schema")?;
+
+    let mut changes = Vec::new();
+    for (name, old) in &old {
+        let Some(new) = new.get(name) else {
+            changes.push(format!("type {name} was removed"));
+            continue;
+        };
+
+        match (old, new) {
+            (TypeKind::Object(old), TypeKind::Object(new)) => {
+                fields(name, &old.fields, &new.fields, &mut changes)
+            }
+            (TypeKind::Interface(old), TypeKind::Interface(new)) => {
+                fields(name, &old.fields, &new.fields, &mut changes)
+            }
+            (TypeKind::InputObject(old), TypeKind::InputObject(new)) => {
+                inputs(name, &old.fields, &new.fields, &mut changes)
+            }
+            (TypeKind::Enum(old), TypeKind::Enum(new)) => {
+                for value in &old.values {
+                    let value = &value.node.value.node;
+                    if !new
+                        .values
+                        .iter()
+                        .any(|other| other.node.value.node == *value)
+                    {
+                        changes.push(format!("enum value {name}.{value} was removed"));
+                    }
+                }
+            }
+            (TypeKind::Union(old), TypeKind::Union(new)) => {
+                for member in &old.members {
+                    if !new.members.iter().any(|other| other.node == member.node) {
+                        changes.push(format!("{} was removed from union {name}", member.node));
+                    }
+                }
+            }
+            (TypeKind::Scalar, TypeKind::Scalar) => {}
+            _ => changes.push(format!("type {name} changed kind")),
+        }
+    }
+
+    Ok(changes)
+}
+
+/// Parse the schema into its type definitions, keyed by name
+fn types(sdl: &str) -> eyre::Result<BTreeMap<String, TypeKind>> {
+    let document = parse_schema(sdl)?;
+    let types = document
+        .definitions
+        .into_iter()
+        .filter_map(|definition| match definition {
+            TypeSystemDefinition::Type(definition) if !definition.node.extend => {
+                let definition = definition.node;
+                Some((definition.name.node.to_string(), definition.kind))
+            }
+            _ => None,
+        })
+        .collect();
+
+    Ok(types)
+}
+
+/// Compare the fields of an object or interface
+fn fields(
+    parent: &str,
+    old: &[Positioned<FieldDefinition>],
+    new: &[Positioned<FieldDefinition>],
+    changes: &mut Vec<String>,
+) {
+    for old in old {
+        let old = &old.node;
+        let name = &old.name.node;
+        let Some(new) = new.iter().find(|other| other.node.name.node == *name) else {
+            changes.push(format!("field {parent}.{name} was removed"));
+            continue;
+        };
+        let new = &new.node;
+
+        if !output_compatible(&old.ty.node, &new.ty.node) {
+            changes.push(format!(
+                "field {parent}.{name} changed type from {} to {}",
+                old.ty.node, new.ty.node
+            ));
+        }
+        inputs(
+            &format!("{parent}.{name}"),
+            &old.arguments,
+            &new.arguments,
+            changes,
+        );
+    }
+}
+
+/// Compare the arguments of a field, or the fields of an input object
+fn inputs(
+    parent: &str,
+    old: &[Positioned<InputValueDefinition>],
+    new: &[Positioned<InputValueDefinition>],
+    changes: &mut Vec<String>,
+) {
+    for old in old {
+        let old = &old.node;
+        let name = &old.name.node;
+        match new.iter().find(|other| other.node.name.node == *name) {
+            Some(new) if !input_compatible(&old.ty.node, &new.node.ty.node) => {
+                changes.push(format!(
+                    "input {parent}.{name} changed type from {} to {}",
+                    old.ty.node, new.node.ty.node
+                ))
+            }
+            Some(_) => {}
+            None => changes.push(format!("input {parent}.{name} was removed")),
+        }
+    }
+
+    for new in new {
+        let new = &new.node;
+        let name = &new.name.node;
+        let required = !new.ty.node.nullable && new.default_value.is_none();
+        if required && !old.iter().any(|other| other.node.name.node == *name) {
+            changes.push(format!("required input {parent}.{name} was added"));
+        }
+    }
+}
+
+/// Whether clients expecting the old output type can handle the new one. Outputs may only become
+/// stricter.
+fn output_compatible(old: &Type, new: &Type) -> bool {
+    if !old.nullable && new.nullable {
+        return false;
+    }
+
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => output_compatible(old, new),
+        _ => false,
+    }
+}
+
+/// Whether inputs that were valid for the old type are still valid for the new one. Inputs may
+/// only become more lenient.
+fn input_compatible(old: &Type, new: &Type) -> bool {
+    if old.nullable && !new.nullable {
+        return false;
+    }
+
+    match (&old.base, &new.base) {
+        (BaseType::Named(old), BaseType::Named(new)) => old == new,
+        (BaseType::List(old), BaseType::List(new)) => input_compatible(old, new),
+        _ => false,
+    }
+}
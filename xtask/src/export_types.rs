@@ -0,0 +1,204 @@
+use async_graphql::parser::{
+    parse_schema,
+    types::{
+        BaseType, FieldDefinition, InputValueDefinition, Type, TypeKind, TypeSystemDefinition,
+    },
+    Positioned,
+};
+use eyre::{eyre, WrapErr};
+use std::{
+    fmt::{self, Write},
+    fs,
+    path::PathBuf,
+};
+use tracing::info;
+
+pub fn run(args: Args) -> eyre::Result<()> {
+    if args.output.exists() && !args.force {
+        return Err(eyre!("file already exists, use --force to overwrite"));
+    }
+
+    let sdl = fs::read_to_string(&args.schema).wrap_err("failed to read schema")?;
+    let types = typescript(&sdl)?;
+    fs::write(&args.output, types).wrap_err("failed to write types")?;
+
+    info!(path = %args.output.display(), "successfully exported types");
+
+    Ok(())
+}
+
+/// Convert the types in a schema to their TypeScript equivalents
+fn typescript(sdl: &str) -> eyre::Result<String> {
+    let document = parse_schema(sdl).wrap_err("failed to parse schema")?;
+
+    let mut output = String::from(
+        "// Generated from the GraphQL schema by `cargo xtask export-types`, do not edit\n",
+    );
+    for definition in document.definitions {
+        let TypeSystemDefinition::Type(definition) = definition else {
+            continue;
+        };
+        let definition = definition.node;
+        let name = definition.name.node.as_str();
+
+        output.push('\n');
+        comment(&mut output, definition.description.as_ref(), "")?;
+        match definition.kind {
+            TypeKind::Scalar => writeln!(output, "export type {name} = {};", scalar(name))?,
+            TypeKind::Object(object) => {
+                writeln!(
+                    output,
+                    "export interface {name}{} {{",
+                    extends(&object.implements)
+                )?;
+                writeln!(output, "  __typename?: \"{name}\";")?;
+                fields(&mut output, &object.fields)?;
+                output.push_str("}\n");
+            }
+            TypeKind::Interface(interface) => {
+                writeln!(
+                    output,
+                    "export interface {name}{} {{",
+                    extends(&interface.implements)
+                )?;
+                fields(&mut output, &interface.fields)?;
+                output.push_str("}\n");
+            }
+            TypeKind::Union(union) => {
+                let members = union
+                    .members
+                    .iter()
+                    .map(|member| member.node.as_str())
+                    .collect::<Vec<_>>();
+                writeln!(output, "export type {name} = {};", members.join(" | "))?;
+            }
+            TypeKind::Enum(enumeration) => {
+                writeln!(output, "export type {name} =")?;
+                for value in &enumeration.values {
+                    comment(&mut output, value.node.description.as_ref(), "  ")?;
+                    writeln!(output, "  | \"{}\"", value.node.value.node)?;
+                }
+                output.push_str(";\n");
+            }
+            TypeKind::InputObject(input) => {
+                writeln!(output, "export interface {name} {{")?;
+                inputs(&mut output, &input.fields)?;
+                output.push_str("}\n");
+            }
+        }
+    }
+
+    Ok(output)
+}
+
+/// Write the fields of an object or interface
+fn fields(output: &mut String, fields: &[Positioned<FieldDefinition>]) -> fmt::Result {
+    for field in fields {
+        let field = &field.node;
+        comment(output, field.description.as_ref(), "  ")?;
+        writeln!(
+            output,
+            "  {}: {};",
+            field.name.node,
+            reference(&field.ty.node)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write the fields of an input object. Fields that can be null or have a default can be omitted.
+fn inputs(output: &mut String, fields: &[Positioned<InputValueDefinition>]) -> fmt::Result {
+    for field in fields {
+        let field = &field.node;
+        let optional = field.ty.node.nullable || field.default_value.is_some();
+
+        comment(output, field.description.as_ref(), "  ")?;
+        writeln!(
+            output,
+            "  {}{}: {};",
+            field.name.node,
+            if optional { "?" } else { "" },
+            reference(&field.ty.node)
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write a description as a doc comment
+fn comment(
+    output: &mut String,
+    description: Option<&Positioned<String>>,
+    indent: &str,
+) -> fmt::Result {
+    let Some(description) = description else {
+        return Ok(());
+    };
+    let description = description.node.trim().replace("*/", "*\\/");
+
+    if description.lines().count() == 1 {
+        return writeln!(output, "{indent}/** {description} */");
+    }
+
+    writeln!(output, "{indent}/**")?;
+    for line in description.lines() {
+        let line = format!("{indent} * {line}");
+        writeln!(output, "{}", line.trim_end())?;
+    }
+    writeln!(output, "{indent} */")
+}
+
+/// The interfaces an object or interface extends
+fn extends(implements: &[Positioned<async_graphql::Name>]) -> String {
+    if implements.is_empty() {
+        return String::new();
+    }
+
+    let names = implements
+        .iter()
+        .map(|name| name.node.as_str())
+        .collect::<Vec<_>>();
+    format!(" extends {}", names.join(", "))
+}
+
+/// Get the TypeScript type for a reference to a GraphQL type
+fn reference(ty: &Type) -> String {
+    let base = match &ty.base {
+        BaseType::Named(name) => match name.as_str() {
+            "ID" | "String" => "string".to_owned(),
+            "Int" | "Float" => "number".to_owned(),
+            "Boolean" => "boolean".to_owned(),
+            name => name.to_owned(),
+        },
+        BaseType::List(inner) => format!("Array<{}>", reference(inner)),
+    };
+
+    if ty.nullable {
+        format!("{base} | null")
+    } else {
+        base
+    }
+}
+
+/// Get the TypeScript type that a custom scalar is serialized as
+fn scalar(name: &str) -> &'static str {
+    match name {
+        "JSON" => "unknown",
+        // Dates, email addresses, and URLs are all strings on the wire
+        _ => "string",
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    /// The exported GraphQL schema to generate types from
+    #[arg(long, default_value = "./schema.graphql")]
+    schema: PathBuf,
+    /// Where to save the types
+    #[arg(default_value = "./schema.ts")]
+    output: PathBuf,
+    /// Whether to overwrite the output file if it already exists
+    #[arg(short, long, default_value_t)]
+    force: bool,
+}
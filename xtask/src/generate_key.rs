@@ -0,0 +1,83 @@
+use base64::{engine::general_purpose::STANDARD, Engine};
+use eyre::WrapErr;
+use rand::RngCore;
+use std::{fs, io::ErrorKind, path::PathBuf};
+use tracing::info;
+
+/// How many random bytes are in a key, well above the minimum length and entropy the server
+/// requires
+const KEY_BYTES: usize = 48;
+
+pub fn run(args: Args) -> eyre::Result<()> {
+    let mut bytes = [0; KEY_BYTES];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let key = STANDARD.encode(bytes);
+
+    let Some(path) = args.write else {
+        println!("{key}");
+        return Ok(());
+    };
+
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(error).wrap_err("failed to read env file"),
+    };
+
+    // Replace the existing value, even if it is commented out, so the documentation around it
+    // is kept
+    let variable = args.kind.variable();
+    let assignment = format!("{variable}={key}");
+    let mut replaced = false;
+    let mut lines = contents
+        .lines()
+        .map(|line| {
+            let name = line.trim_start_matches('#').split('=').next();
+            if !replaced && name == Some(variable) {
+                replaced = true;
+                assignment.clone()
+            } else {
+                line.to_owned()
+            }
+        })
+        .collect::<Vec<_>>();
+    if !replaced {
+        lines.push(assignment);
+    }
+
+    fs::write(&path, lines.join("\n") + "\n").wrap_err("failed to write env file")?;
+    info!(path = %path.display(), variable, "wrote key");
+
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// What the key is used for
+    #[arg(value_enum, default_value_t = Kind::Cookie)]
+    kind: Kind,
+
+    /// Write the key to an env file instead of printing it, replacing any existing value
+    #[arg(long, value_name = "PATH", num_args = 0..=1, default_missing_value = ".env")]
+    write: Option<PathBuf>,
+}
+
+/// The secrets that can be generated
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Kind {
+    /// The key to sign session cookies with
+    Cookie,
+    /// The secret internal services sign their requests with
+    Internal,
+}
+
+impl Kind {
+    /// The environment variable the secret is configured by
+    fn variable(&self) -> &'static str {
+        match self {
+            Kind::Cookie => "COOKIE_SIGNING_KEY",
+            Kind::Internal => "INTERNAL_SIGNING_SECRET",
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::util;
+use hmac::{Hmac, Mac};
+use reqwest::{header::COOKIE, Client, RequestBuilder};
+use serde_json::json;
+use session::{AuthenticatedState, Session, SessionState};
+use sha2::{Digest, Sha256};
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use tokio::task::JoinSet;
+use tracing::info;
+use url::Url;
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    let cache = util::connect_to_cache(&args.cache_url).await?;
+
+    // Only tokens are generated, so the cookie settings don't matter
+    let manager = session::Manager::new(cache, "xtask", false, &args.signing_key);
+
+    let mut sessions = Vec::with_capacity(args.sessions);
+    for _ in 0..args.sessions.max(1) {
+        let mut session = Session::default();
+        session.state = SessionState::Authenticated(AuthenticatedState { id: args.user_id });
+        manager.save(&session).await?;
+        sessions.push(session);
+    }
+    info!(count = sessions.len(), "generated sessions");
+
+    let settings = Arc::new(Settings {
+        url: args.url,
+        domain: args.domain,
+        secret: args.internal_signing_secret,
+        tokens: sessions
+            .iter()
+            .map(|session| {
+                session
+                    .token(args.signing_key.as_bytes())
+                    .expect("session must have secret part")
+            })
+            .collect(),
+    });
+
+    let client = Client::new();
+    for target in args.targets {
+        let report = drive(
+            target,
+            settings.clone(),
+            client.clone(),
+            args.requests,
+            args.concurrency.max(1),
+        )
+        .await;
+        report.log(target);
+    }
+
+    for session in &sessions {
+        manager.revoke_any(session.id()).await?;
+    }
+
+    Ok(())
+}
+
+/// Send requests to the target from many workers at once, timing each of them
+async fn drive(
+    target: Target,
+    settings: Arc<Settings>,
+    client: Client,
+    requests: usize,
+    concurrency: usize,
+) -> Report {
+    let remaining = Arc::new(AtomicUsize::new(requests));
+    let started = Instant::now();
+
+    let mut workers = JoinSet::new();
+    for worker in 0..concurrency {
+        let settings = settings.clone();
+        let client = client.clone();
+        let remaining = remaining.clone();
+
+        workers.spawn(async move {
+            let mut latencies = Vec::new();
+            let mut failures = 0;
+
+            // Spread the workers across the sessions
+            let mut index = worker;
+            while remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+                .is_ok()
+            {
+                let token = &settings.tokens[index % settings.tokens.len()];
+                index += 1;
+
+                let request = settings.request(target, &client, token);
+                let start = Instant::now();
+                match request.send().await {
+                    Ok(response) if response.status().is_success() => {
+                        match response.bytes().await {
+                            Ok(_) => latencies.push(start.elapsed()),
+                            Err(_) => failures += 1,
+                        }
+                    }
+                    _ => failures += 1,
+                }
+            }
+
+            (latencies, failures)
+        });
+    }
+
+    let mut latencies = Vec::with_capacity(requests);
+    let mut failures = 0;
+    while let Some(result) = workers.join_next().await {
+        let (worker_latencies, worker_failures) = result.expect("worker must not panic");
+        latencies.extend(worker_latencies);
+        failures += worker_failures;
+    }
+    latencies.sort();
+
+    Report {
+        latencies,
+        failures,
+        elapsed: started.elapsed(),
+    }
+}
+
+/// What the requests are sent with
+struct Settings {
+    url: Url,
+    domain: String,
+    secret: Option<String>,
+    tokens: Vec<String>,
+}
+
+impl Settings {
+    /// Build a request to the target, authenticated with the session token
+    fn request(&self, target: Target, client: &Client, token: &str) -> RequestBuilder {
+        match target {
+            Target::Context => {
+                let mut url = self.url.join("/context").expect("path must be valid");
+                url.query_pairs_mut()
+                    .append_pair("domain", &self.domain)
+                    .append_pair("token", token);
+
+                let mut request = client.get(url.as_str());
+                if let Some(secret) = &self.secret {
+                    let path = format!("{}?{}", url.path(), url.query().unwrap_or_default());
+                    let (timestamp, signature) = sign(secret, "GET", &path, b"");
+                    request = request
+                        .header("x-signature-timestamp", timestamp)
+                        .header("x-signature", signature);
+                }
+
+                request
+            }
+            Target::Session | Target::Graphql => {
+                // The session middleware and scope resolution run for every query, but only
+                // `me` touches the database
+                let query = match target {
+                    Target::Session => "{ __typename }",
+                    _ => "{ me { id } }",
+                };
+
+                client
+                    .post(self.url.join("/graphql").expect("path must be valid"))
+                    .header("host", &self.domain)
+                    .header(COOKIE, format!("session={token}"))
+                    .json(&json!({ "query": query }))
+            }
+        }
+    }
+}
+
+/// Sign a request the same way internal services do
+fn sign(secret: &str, method: &str, path: &str, body: &[u8]) -> (String, String) {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("current time must be after the unix epoch")
+        .as_secs();
+
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac accepts any key length");
+    mac.update(
+        format!(
+            "{timestamp}\n{method}\n{path}\n{}",
+            hex::encode(Sha256::digest(body))
+        )
+        .as_bytes(),
+    );
+
+    (
+        timestamp.to_string(),
+        format!("sha256={}", hex::encode(mac.finalize().into_bytes())),
+    )
+}
+
+/// The results of driving traffic to a target
+struct Report {
+    /// How long each successful request took, from fastest to slowest
+    latencies: Vec<Duration>,
+    /// How many requests failed or returned an error status
+    failures: usize,
+    /// How long all the requests took
+    elapsed: Duration,
+}
+
+impl Report {
+    /// Log the throughput and latency percentiles
+    fn log(&self, target: Target) {
+        let total = self.latencies.len() + self.failures;
+        let throughput = total as f64 / self.elapsed.as_secs_f64();
+
+        info!(
+            target = target.name(),
+            requests = total,
+            failures = self.failures,
+            throughput = %format!("{throughput:.1}/s"),
+            p50 = ?self.percentile(0.50),
+            p90 = ?self.percentile(0.90),
+            p99 = ?self.percentile(0.99),
+            max = ?self.latencies.last().copied().unwrap_or_default(),
+            "finished load test"
+        );
+    }
+
+    /// Get the latency that the given fraction of requests were faster than
+    fn percentile(&self, fraction: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+
+        let index = ((self.latencies.len() - 1) as f64 * fraction).round() as usize;
+        self.latencies[index]
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The Redis cache the server stores sessions in
+    #[arg(long, env = "CACHE_URL")]
+    cache_url: String,
+
+    /// The secret the server signs session cookies with
+    #[arg(long, env = "COOKIE_SIGNING_KEY")]
+    signing_key: String,
+
+    /// The secret to sign requests to `/context` with, if the server requires it
+    #[arg(long, env = "INTERNAL_SIGNING_SECRET")]
+    internal_signing_secret: Option<String>,
+
+    /// Where the server is listening
+    #[arg(long, default_value = "http://127.0.0.1:4243")]
+    url: Url,
+
+    /// The domain the requests are made for, which determines their scope
+    #[arg(long)]
+    domain: String,
+
+    /// The user the sessions are authenticated as
+    #[arg(long)]
+    user_id: i32,
+
+    /// Which endpoints to send traffic to, comma-separated
+    ///
+    /// The session and graphql targets go through `/graphql`, so require a standalone server.
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "context,session,graphql"
+    )]
+    targets: Vec<Target>,
+
+    /// How many requests to send to each target
+    #[arg(long, default_value_t = 1000)]
+    requests: usize,
+
+    /// How many requests to have in flight at once
+    #[arg(long, default_value_t = 16)]
+    concurrency: usize,
+
+    /// How many sessions to spread the requests across
+    #[arg(long, default_value_t = 10)]
+    sessions: usize,
+}
+
+/// The endpoints that can be load tested
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Target {
+    /// Resolve the context for a session token, as the gateway does
+    Context,
+    /// Send a query that only requires the session middleware and scope resolution
+    Session,
+    /// Send a query that loads the current user
+    Graphql,
+}
+
+impl Target {
+    /// Get the name of the target
+    fn name(&self) -> &'static str {
+        match self {
+            Target::Context => "context",
+            Target::Session => "session",
+            Target::Graphql => "graphql",
+        }
+    }
+}
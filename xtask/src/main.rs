@@ -3,9 +3,18 @@ use eyre::WrapErr;
 use tracing::{debug, Level};
 
 mod export_schema;
+mod export_types;
+mod generate_key;
+mod loadtest;
 mod migrate;
+mod prepare;
+mod providers;
+mod schema;
+mod seed;
 mod sessions;
+mod users;
 mod util;
+mod webhooks;
 
 #[tokio::main]
 async fn main() -> eyre::Result<()> {
@@ -19,8 +28,17 @@ async fn main() -> eyre::Result<()> {
 
     match args.command {
         Command::ExportSchema(args) => export_schema::run(args),
+        Command::ExportTypes(args) => export_types::run(args),
+        Command::GenerateKey(args) => generate_key::run(args),
+        Command::Loadtest(args) => loadtest::run(args).await,
         Command::Migrate(args) => migrate::run(args).await,
+        Command::Prepare(args) => prepare::run(args),
+        Command::Providers(args) => providers::run(args).await,
+        Command::Schema(args) => schema::run(args).await,
+        Command::Seed(args) => seed::run(args).await,
         Command::Sessions(args) => sessions::run(args).await,
+        Command::Users(args) => users::run(args).await,
+        Command::Webhooks(args) => webhooks::run(args).await,
     }
 }
 
@@ -40,13 +58,40 @@ pub struct Args {
 pub enum Command {
     /// Export the GraphQL schema to a file
     ExportSchema(export_schema::Args),
+    /// Generate TypeScript types for the frontend from the exported GraphQL schema
+    ExportTypes(export_types::Args),
+    /// Generate a strong random key for signing cookies or internal requests
+    GenerateKey(generate_key::Args),
+    /// Measure the latency of the session and context paths under load
+    ///
+    /// Sessions are generated for an existing user and revoked once the test finishes.
+    Loadtest(loadtest::Args),
     /// Manage database migrations
     Migrate(migrate::Args),
+    /// Generate the offline query data for every crate in the workspace
+    ///
+    /// Requires sqlx-cli to be installed.
+    Prepare(prepare::Args),
+    /// Manage the providers users can log in with
+    ///
+    /// Changes are picked up by running servers without a restart.
+    Providers(providers::Args),
+    /// Interact with the schema registry
+    Schema(schema::Args),
+    /// Populate the database for local development
+    ///
+    /// Creates an admin user, a sample organization and event with a custom domain, and the
+    /// common providers with dummy credentials. Anything that already exists is left as is.
+    Seed(seed::Args),
     /// Generate sessions with custom attributes
     ///
     /// All session types, except for OAuth, can be created. An OAuth session cannot created due to
     /// its integration with 3rd-parties.
     Sessions(sessions::Args),
+    /// Manage users in bulk
+    Users(users::Args),
+    /// Manage the endpoints events are delivered to
+    Webhooks(webhooks::Args),
 }
 
 /// Load environment variables from a .env file, if it exists.
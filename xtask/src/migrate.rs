@@ -1,22 +1,328 @@
 use crate::util;
-use eyre::WrapErr;
-use sqlx::migrate::Migrator;
-use std::path::PathBuf;
+use eyre::{eyre, WrapErr};
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    Connection, PgPool,
+};
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+mod lint;
 
 pub async fn run(args: Args) -> eyre::Result<()> {
-    let migrator = Migrator::new(&*args.source)
-        .await
-        .wrap_err("failed to load migrations")?;
+    let loaded;
+    let migrator = match &args.source {
+        Some(source) => {
+            loaded = Migrator::new(&**source)
+                .await
+                .wrap_err("failed to load migrations")?;
+            &loaded
+        }
+        None => &database::MIGRATOR,
+    };
+
+    // Commands that change the migration files need a directory to work in
+    let directory = args.source.as_deref().unwrap_or(Path::new("./migrations"));
 
-    let db = util::connect_to_database(&args.database_url).await?;
+    // Squashing only touches the migration files, so it works without a database
+    if let Command::Squash { version } = args.command {
+        return squash(migrator, directory, version);
+    }
+
+    let mut settings = Vec::new();
+    if let Some(timeout) = &args.lock_timeout {
+        settings.push(("lock_timeout", timeout.as_str()));
+    }
+    if let Some(timeout) = &args.statement_timeout {
+        settings.push(("statement_timeout", timeout.as_str()));
+    }
+    let db = util::connect_to_database(&args.database_url, &settings).await?;
 
     match args.command {
-        Command::Add { name } => migrator::add(&args.source, &name.join("_"))?,
-        Command::Info => migrator::info(&migrator, &db).await?,
-        Command::Apply => migrator::apply(&migrator, &db).await?,
-        Command::Revert { target } => migrator::undo(&migrator, &db, target).await?,
+        Command::Add { name } => migrator::add(directory, &name.join("_"))?,
+        Command::Info => migrator::info(migrator, &db).await?,
+        Command::Apply {
+            target,
+            allow_unsafe,
+        } => {
+            lint::check(migrator, &db, target, allow_unsafe).await?;
+            apply(migrator, &db, target).await?;
+            if args.seed {
+                seed(&db, &args.seeds).await?;
+            }
+        }
+        Command::Revert { target } => migrator::undo(migrator, &db, target).await?,
+        Command::Redo { version } => redo(migrator, &db, version).await?,
+        Command::Baseline { version } => baseline(migrator, &db, version).await?,
+        Command::Squash { .. } => unreachable!(),
+    }
+
+    Ok(())
+}
+
+/// Apply the pending migrations up to and including the target version, or all of them if there
+/// is no target
+async fn apply(migrator: &Migrator, db: &PgPool, target: Option<i64>) -> eyre::Result<()> {
+    let Some(target) = target else {
+        return Ok(migrator::apply(migrator, db).await?);
+    };
+
+    if !migrator.iter().any(|migration| migration.version == target) {
+        return Err(eyre!("migration {target} does not exist"));
+    }
+
+    let mut conn = db.acquire().await?;
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+
+    if let Some(version) = conn.dirty_version().await? {
+        conn.unlock().await?;
+        return Err(eyre!(
+            "migration {version} is partially applied, fix it and revert it first"
+        ));
+    }
+
+    let applied = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| (migration.version, migration.checksum))
+        .collect::<HashMap<_, _>>();
+
+    let pending = migrator.iter().filter(|migration| {
+        !migration.migration_type.is_down_migration() && migration.version <= target
+    });
+    for migration in pending {
+        match applied.get(&migration.version) {
+            Some(checksum) if *checksum != migration.checksum => {
+                conn.unlock().await?;
+                return Err(eyre!(
+                    "migration {} was modified after it was applied",
+                    migration.version
+                ));
+            }
+            Some(_) => continue,
+            None => {
+                let elapsed = conn.apply(migration).await?;
+                info!(
+                    version = migration.version,
+                    description = %migration.description,
+                    ?elapsed,
+                    "applied migration"
+                );
+            }
+        }
+    }
+
+    conn.unlock().await?;
+
+    Ok(())
+}
+
+/// Revert the migration, and any applied after it, then apply them again
+async fn redo(migrator: &Migrator, db: &PgPool, version: Option<i64>) -> eyre::Result<()> {
+    let applied = {
+        let mut conn = db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        conn.list_applied_migrations().await?
+    };
+
+    let Some(latest) = applied.iter().map(|migration| migration.version).max() else {
+        return Err(eyre!("no migrations have been applied"));
+    };
+    let version = match version {
+        Some(version) if applied.iter().any(|migration| migration.version == version) => version,
+        Some(version) => return Err(eyre!("migration {version} has not been applied")),
+        None => latest,
+    };
+
+    // Reverting back to the migration before it leaves the migration itself unapplied
+    let target = migrator
+        .iter()
+        .map(|migration| migration.version)
+        .filter(|&other| other < version)
+        .max()
+        .unwrap_or(0);
+
+    info!(version, "redoing migration");
+    migrator::undo(migrator, db, Some(target)).await?;
+    // Only restore what was applied before, leaving any newer migrations pending
+    apply(migrator, db, Some(latest)).await?;
+
+    Ok(())
+}
+
+/// Run the seeds that have not been run yet, in order of their file names. Each seed runs once,
+/// in its own transaction, and is tracked separately from the migrations.
+async fn seed(db: &PgPool, source: &Path) -> eyre::Result<()> {
+    let mut seeds = fs::read_dir(source)
+        .wrap_err("failed to read seeds")?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()?;
+    seeds.retain(|path| path.extension().is_some_and(|extension| extension == "sql"));
+    seeds.sort();
+
+    let mut conn = db.acquire().await?;
+    conn.lock().await?;
+    sqlx::raw_sql(
+        r#"
+        CREATE TABLE IF NOT EXISTS _seeds (
+            name text primary key,
+            applied_on timestamp with time zone not null default now()
+        )
+        "#,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    let applied = sqlx::query_scalar::<_, String>("SELECT name FROM _seeds")
+        .fetch_all(&mut *conn)
+        .await?;
+
+    for path in seeds {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| eyre!("invalid seed file name {}", path.display()))?;
+        if applied.iter().any(|applied| applied == name) {
+            continue;
+        }
+
+        let sql = fs::read_to_string(&path)
+            .wrap_err_with(|| format!("failed to read seed {}", path.display()))?;
+
+        let mut txn = conn.begin().await?;
+        sqlx::raw_sql(&sql)
+            .execute(&mut *txn)
+            .await
+            .wrap_err_with(|| format!("failed to run seed {name}"))?;
+        sqlx::query("INSERT INTO _seeds (name) VALUES ($1)")
+            .bind(name)
+            .execute(&mut *txn)
+            .await?;
+        txn.commit().await?;
+
+        info!(name, "applied seed");
     }
 
+    conn.unlock().await?;
+
+    Ok(())
+}
+
+/// Mark every migration up to and including the version as applied, without running them
+async fn baseline(migrator: &Migrator, db: &PgPool, version: i64) -> eyre::Result<()> {
+    let migrations = migrator
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| migration.version <= version)
+        .collect::<Vec<_>>();
+    if !migrations
+        .iter()
+        .any(|migration| migration.version == version)
+    {
+        return Err(eyre!("migration {version} does not exist"));
+    }
+
+    let mut conn = db.acquire().await?;
+    conn.lock().await?;
+    conn.ensure_migrations_table().await?;
+
+    let mut txn = conn.begin().await?;
+    sqlx::query("DELETE FROM _sqlx_migrations WHERE version <= $1")
+        .bind(version)
+        .execute(&mut *txn)
+        .await?;
+    for migration in &migrations {
+        sqlx::query(
+            r#"
+            INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+            VALUES ($1, $2, TRUE, $3, 0)
+            "#,
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut *txn)
+        .await?;
+    }
+    txn.commit().await?;
+
+    conn.unlock().await?;
+
+    info!(version, count = migrations.len(), "baselined database");
+    Ok(())
+}
+
+/// Combine every migration up to and including the version into a single baseline migration
+fn squash(migrator: &Migrator, source: &Path, version: i64) -> eyre::Result<()> {
+    let migrations = migrator
+        .iter()
+        .filter(|migration| migration.version <= version)
+        .collect::<Vec<_>>();
+    if !migrations
+        .iter()
+        .any(|migration| migration.version == version)
+    {
+        return Err(eyre!("migration {version} does not exist"));
+    }
+
+    let mut up = format!("-- Squashed from the migrations up to and including {version}\n");
+    let mut down = up.clone();
+    for migration in &migrations {
+        if migration.migration_type.is_up_migration() {
+            write!(
+                up,
+                "\n-- {} {}\n{}\n",
+                migration.version,
+                migration.description,
+                migration.sql.trim()
+            )?;
+        }
+    }
+    // Down migrations must run newest first
+    for migration in migrations.iter().rev() {
+        if migration.migration_type.is_down_migration() {
+            write!(
+                down,
+                "\n-- {} {}\n{}\n",
+                migration.version,
+                migration.description,
+                migration.sql.trim()
+            )?;
+        }
+    }
+
+    let mut versions = migrations
+        .iter()
+        .map(|migration| format!("{}_", migration.version))
+        .collect::<Vec<_>>();
+    versions.dedup();
+    for entry in fs::read_dir(source).wrap_err("failed to read migrations")? {
+        let path = entry?.path();
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        if versions.iter().any(|prefix| name.starts_with(prefix)) {
+            fs::remove_file(&path)
+                .wrap_err_with(|| format!("failed to remove {}", path.display()))?;
+        }
+    }
+
+    fs::write(source.join(format!("{version}_baseline.up.sql")), up)?;
+    fs::write(source.join(format!("{version}_baseline.down.sql")), down)?;
+
+    info!(
+        version,
+        count = versions.len(),
+        "squashed migrations, run `migrate baseline {version}` on existing databases"
+    );
     Ok(())
 }
 
@@ -26,9 +332,31 @@ pub struct Args {
     #[arg(short, long, env = "DATABASE_URL")]
     database_url: String,
 
-    /// The migrations source
-    #[arg(short, long, default_value = "./migrations")]
-    source: PathBuf,
+    /// The directory to load migrations from at runtime
+    ///
+    /// Defaults to the migrations embedded when the binary was built. Commands that create or
+    /// modify migration files use `./migrations` if no directory is provided.
+    #[arg(short, long, env = "MIGRATIONS_SOURCE")]
+    source: Option<PathBuf>,
+
+    /// How long a statement may wait to acquire a lock before failing, i.e. `5s`
+    ///
+    /// This prevents a migration blocked on a busy table from holding up the queries queued
+    /// behind it. It also limits how long to wait for another migration run to finish.
+    #[arg(long, env = "MIGRATE_LOCK_TIMEOUT")]
+    lock_timeout: Option<String>,
+
+    /// How long a statement may run before it is cancelled, i.e. `5min`
+    #[arg(long, env = "MIGRATE_STATEMENT_TIMEOUT")]
+    statement_timeout: Option<String>,
+
+    /// Run the seeds after applying migrations, for populating development and staging databases
+    #[arg(long, env = "MIGRATE_SEED")]
+    seed: bool,
+
+    /// The seeds source
+    #[arg(long, default_value = "./seeds")]
+    seeds: PathBuf,
 
     #[command(subcommand)]
     command: Command,
@@ -44,8 +372,18 @@ pub enum Command {
     },
     /// List all available migrations
     Info,
-    /// Apply all pending migrations
-    Apply,
+    /// Apply pending migrations
+    ///
+    /// If no target is provided, all pending migrations are applied. Migrations that lock or
+    /// rewrite tables in use are refused unless explicitly allowed.
+    Apply {
+        /// The version to apply up to, inclusive
+        target: Option<i64>,
+
+        /// Apply migrations that lock or rewrite tables in use, instead of refusing to
+        #[arg(long)]
+        allow_unsafe: bool,
+    },
     /// Revert migrations
     ///
     /// If no target is provided, the most recent migration is reverted.
@@ -53,4 +391,27 @@ pub enum Command {
         /// The version to revert back to
         target: Option<i64>,
     },
+    /// Revert and re-apply a migration, along with any applied after it
+    ///
+    /// If no version is provided, the most recent migration is redone.
+    Redo {
+        /// The version of the migration to redo
+        version: Option<i64>,
+    },
+    /// Mark an existing database as migrated up to a version, without running the migrations
+    ///
+    /// Any migrations already recorded up to the version are replaced. Use this on existing
+    /// databases after squashing.
+    Baseline {
+        /// The version to mark as applied, inclusive
+        version: i64,
+    },
+    /// Combine the migrations up to a version into a single baseline migration
+    ///
+    /// The original migration files are removed. Databases that already applied them must be
+    /// baselined at the same version, as the checksums no longer match.
+    Squash {
+        /// The last version to include, inclusive
+        version: i64,
+    },
 }
@@ -0,0 +1,397 @@
+use eyre::eyre;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool,
+};
+use std::collections::HashSet;
+use tracing::warn;
+
+// Why each of the operations is unsafe to run while the table is in use
+const INDEX: &str = "creates an index without CONCURRENTLY, which blocks writes to the table. \
+    Migrations run in a transaction, so create it in its own migration starting with \
+    `-- no-transaction`";
+const CONCURRENTLY_IN_TRANSACTION: &str = "creates an index CONCURRENTLY, which fails inside the \
+    transaction the migration runs in unless it starts with `-- no-transaction`";
+const TYPE_CHANGE: &str =
+    "changes a column type, which can rewrite the table while blocking access";
+const SET_NOT_NULL: &str = "sets a column NOT NULL, which scans the table while blocking access";
+const ADD_NOT_NULL: &str =
+    "adds a NOT NULL column without a default, which fails if there are rows";
+const VOLATILE_DEFAULT: &str =
+    "adds a column with a volatile default, which rewrites the table while blocking access";
+
+/// Functions that return a different value for each row, so using one as a column's default
+/// requires filling in every existing row
+const VOLATILE_FUNCTIONS: [&str; 6] = [
+    "GEN_RANDOM_UUID(",
+    "UUID_GENERATE_V",
+    "RANDOM(",
+    "CLOCK_TIMESTAMP(",
+    "TIMEOFDAY(",
+    "NEXTVAL(",
+];
+/// Column types that are backed by a sequence, giving them a volatile default
+const SERIAL_TYPES: [&str; 3] = [" SERIAL", " BIGSERIAL", " SMALLSERIAL"];
+
+/// Check the pending migrations for operations that are dangerous to run while the database is
+/// serving traffic. Fails if any are found, unless unsafe operations are allowed.
+pub async fn check(
+    migrator: &Migrator,
+    db: &PgPool,
+    target: Option<i64>,
+    allow_unsafe: bool,
+) -> eyre::Result<()> {
+    let applied = {
+        let mut conn = db.acquire().await?;
+        conn.ensure_migrations_table().await?;
+        conn.list_applied_migrations().await?
+    };
+
+    // A database without any migrations applied is not serving traffic yet
+    if applied.is_empty() {
+        return Ok(());
+    }
+    let applied = applied
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect::<HashSet<_>>();
+
+    let pending = migrator.iter().filter(|migration| {
+        migration.migration_type.is_up_migration()
+            && !applied.contains(&migration.version)
+            && target.is_none_or(|target| migration.version <= target)
+    });
+
+    let mut found = 0;
+    for migration in pending {
+        for (table, problem) in lint(&migration.sql, migration.no_tx) {
+            warn!(
+                version = migration.version,
+                description = %migration.description,
+                %table,
+                "unsafe migration: {problem}"
+            );
+            found += 1;
+        }
+    }
+
+    if found > 0 && !allow_unsafe {
+        return Err(eyre!(
+            "found {found} unsafe operations, pass --allow-unsafe to apply them anyway"
+        ));
+    }
+
+    Ok(())
+}
+
+/// Find the operations in a migration that lock or rewrite tables that are in use, or that cannot
+/// run in the migration's transaction
+fn lint(sql: &str, no_tx: bool) -> Vec<(String, &'static str)> {
+    let statements = statements(sql);
+
+    // Tables created by the migration are empty and unused, so anything goes
+    let created = statements
+        .iter()
+        .filter_map(|statement| statement.strip_prefix("CREATE TABLE "))
+        .map(|rest| table_name(rest.split_whitespace()))
+        .collect::<HashSet<_>>();
+
+    let mut problems = Vec::new();
+    for statement in &statements {
+        if let Some(rest) = statement
+            .strip_prefix("CREATE INDEX ")
+            .or_else(|| statement.strip_prefix("CREATE UNIQUE INDEX "))
+        {
+            let table = table_name(
+                rest.split_whitespace()
+                    .skip_while(|word| *word != "ON")
+                    .skip(1),
+            );
+            if rest.starts_with("CONCURRENTLY") {
+                if !no_tx {
+                    problems.push((table, CONCURRENTLY_IN_TRANSACTION));
+                }
+            } else if !created.contains(&table) {
+                problems.push((table, INDEX));
+            }
+        } else if let Some(rest) = statement.strip_prefix("ALTER TABLE ") {
+            let table = table_name(rest.split_whitespace());
+            if created.contains(&table) {
+                continue;
+            }
+
+            for action in actions(rest) {
+                if action.contains("ALTER ") && action.contains(" TYPE ") {
+                    problems.push((table.clone(), TYPE_CHANGE));
+                }
+                if action.contains("SET NOT NULL") {
+                    problems.push((table.clone(), SET_NOT_NULL));
+                }
+                if action.contains("ADD ")
+                    && !action.contains("CONSTRAINT")
+                    && action.contains("NOT NULL")
+                    && !action.contains("DEFAULT")
+                {
+                    problems.push((table.clone(), ADD_NOT_NULL));
+                }
+                if action.contains("ADD ") && has_volatile_default(action) {
+                    problems.push((table.clone(), VOLATILE_DEFAULT));
+                }
+            }
+        }
+    }
+
+    problems
+}
+
+/// Split a migration into its statements, without comments, uppercased, and with the whitespace
+/// collapsed so they can be matched on. Semicolons within strings and function bodies don't end a
+/// statement.
+fn statements(sql: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut statement = String::new();
+
+    let mut rest = sql;
+    while let Some(c) = rest.chars().next() {
+        let length = if rest.starts_with("--") {
+            rest = &rest[rest.find('\n').unwrap_or(rest.len())..];
+            continue;
+        } else if c == ';' {
+            statements.push(normalize(&statement));
+            statement.clear();
+            c.len_utf8()
+        } else {
+            let length = quoted(rest).unwrap_or(c.len_utf8());
+            statement.push_str(&rest[..length]);
+            length
+        };
+        rest = &rest[length..];
+    }
+    statements.push(normalize(&statement));
+
+    statements.retain(|statement| !statement.is_empty());
+    statements
+}
+
+/// Uppercase the statement and collapse its whitespace
+fn normalize(statement: &str) -> String {
+    statement
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_uppercase()
+}
+
+/// Get the length of the string or dollar-quoted body at the start of the SQL, if there is one
+fn quoted(sql: &str) -> Option<usize> {
+    let delimiter = if sql.starts_with('\'') {
+        "'"
+    } else if let Some(rest) = sql.strip_prefix('$') {
+        // Dollar quotes have an optional tag, unlike positional parameters such as `$1`
+        let tag = rest.find(|c: char| !(c.is_alphanumeric() || c == '_'))?;
+        if !rest[tag..].starts_with('$') || rest.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        &sql[..tag + 2]
+    } else {
+        return None;
+    };
+
+    let body = sql[delimiter.len()..].find(delimiter)?;
+    Some(delimiter.len() * 2 + body)
+}
+
+/// Split the actions of an ALTER TABLE statement, ignoring commas within parentheses such as in
+/// `NUMERIC(10, 2)`
+fn actions(statement: &str) -> Vec<&str> {
+    let mut actions = Vec::new();
+    let mut depth = 0usize;
+    let mut start = 0;
+
+    for (i, c) in statement.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                actions.push(&statement[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    actions.push(&statement[start..]);
+
+    actions
+}
+
+/// Whether the column added by the action gets a different value for each existing row
+fn has_volatile_default(action: &str) -> bool {
+    let volatile = action.split_once(" DEFAULT ").is_some_and(|(_, default)| {
+        VOLATILE_FUNCTIONS
+            .iter()
+            .any(|function| default.contains(function))
+    });
+    let serial = SERIAL_TYPES
+        .iter()
+        .any(|kind| action.contains(&format!("{kind} ")) || action.ends_with(kind));
+
+    volatile || serial
+}
+
+/// Get the name of the table at the start of the words
+fn table_name<'w>(mut words: impl Iterator<Item = &'w str>) -> String {
+    words
+        .find(|word| !matches!(*word, "IF" | "NOT" | "EXISTS" | "ONLY"))
+        .and_then(|word| word.split('(').next())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The table and reason for each problem expected to be found
+    type Expected = &'static [(&'static str, &'static str)];
+
+    /// Check the SQL has exactly the expected problems, in order
+    fn assert_lints(sql: &str, no_tx: bool, expected: &[(&str, &str)], message: &str) {
+        let problems = lint(sql, no_tx);
+        let problems = problems
+            .iter()
+            .map(|(table, problem)| (table.as_str(), *problem))
+            .collect::<Vec<_>>();
+        assert_eq!(problems, expected, "{message}");
+    }
+
+    #[test]
+    fn lints_repo_migrations() {
+        // The migrations that would have been stopped, by version. The rest should pass.
+        let expected: [(i64, Expected); 3] = [
+            (20241016132109, &[("organizations", SET_NOT_NULL)]),
+            (
+                20241016133047,
+                &[("custom_domains", INDEX), ("custom_domains", INDEX)],
+            ),
+            (
+                20241016154500,
+                &[
+                    ("users", VOLATILE_DEFAULT),
+                    ("organizations", VOLATILE_DEFAULT),
+                ],
+            ),
+        ];
+
+        let migrations = database::MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .collect::<Vec<_>>();
+        for (version, _) in expected {
+            assert!(
+                migrations
+                    .iter()
+                    .any(|migration| migration.version == version),
+                "{version}"
+            );
+        }
+
+        for migration in migrations {
+            let expected = expected
+                .iter()
+                .find(|(version, _)| *version == migration.version)
+                .map(|(_, problems)| *problems)
+                .unwrap_or_default();
+
+            let message = format!("{} {}", migration.version, migration.description);
+            assert_lints(&migration.sql, migration.no_tx, expected, &message);
+        }
+    }
+
+    #[test]
+    fn lints_operations() {
+        let cases: [(&str, bool, Expected); 16] = [
+            ("CREATE INDEX ON users (email)", false, &[("users", INDEX)]),
+            (
+                "create unique index if not exists users_email on only users (email)",
+                false,
+                &[("users", INDEX)],
+            ),
+            (
+                "CREATE INDEX CONCURRENTLY users_email ON users (email)",
+                false,
+                &[("users", CONCURRENTLY_IN_TRANSACTION)],
+            ),
+            (
+                "-- no-transaction\nCREATE INDEX CONCURRENTLY users_email ON users (email)",
+                true,
+                &[],
+            ),
+            (
+                "CREATE TABLE things (id serial PRIMARY KEY); CREATE INDEX ON things (id)",
+                false,
+                &[],
+            ),
+            (
+                "ALTER TABLE prices ALTER COLUMN amount TYPE numeric(10, 2)",
+                false,
+                &[("prices", TYPE_CHANGE)],
+            ),
+            (
+                "ALTER TABLE prices ADD COLUMN amount numeric(10,2) NOT NULL",
+                false,
+                &[("prices", ADD_NOT_NULL)],
+            ),
+            (
+                "ALTER TABLE prices ADD COLUMN amount numeric(10,2) NOT NULL DEFAULT 0",
+                false,
+                &[],
+            ),
+            (
+                "ALTER TABLE users ADD COLUMN seen_at timestamptz NOT NULL DEFAULT now()",
+                false,
+                &[],
+            ),
+            (
+                "ALTER TABLE users ADD COLUMN token uuid NOT NULL DEFAULT gen_random_uuid()",
+                false,
+                &[("users", VOLATILE_DEFAULT)],
+            ),
+            (
+                "ALTER TABLE users ADD COLUMN number bigserial",
+                false,
+                &[("users", VOLATILE_DEFAULT)],
+            ),
+            (
+                "ALTER TABLE users ADD COLUMN a int, ALTER COLUMN b SET NOT NULL",
+                false,
+                &[("users", SET_NOT_NULL)],
+            ),
+            (
+                "ALTER TABLE users ADD CONSTRAINT positive CHECK (a > 0) NOT VALID",
+                false,
+                &[],
+            ),
+            (
+                "INSERT INTO settings VALUES ('a;ALTER TABLE users ADD b int NOT NULL')",
+                false,
+                &[],
+            ),
+            (
+                "CREATE FUNCTION f() RETURNS trigger AS $body$ BEGIN \
+                    ALTER TABLE users ADD b int NOT NULL; RETURN NEW; END $body$ \
+                    LANGUAGE plpgsql",
+                false,
+                &[],
+            ),
+            (
+                "ALTER TABLE users ADD COLUMN b int NOT NULL; -- ALTER TABLE events ADD c int",
+                false,
+                &[("users", ADD_NOT_NULL)],
+            ),
+        ];
+
+        for (sql, no_tx, expected) in cases {
+            assert_lints(sql, no_tx, expected, sql);
+        }
+    }
+}
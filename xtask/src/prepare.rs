@@ -0,0 +1,52 @@
+use eyre::{eyre, WrapErr};
+use std::{path::Path, process::Command};
+use tracing::info;
+
+/// The features that compile additional queries, which must be enabled for their offline data to
+/// be generated
+const FEATURES: &str = "database/graphql";
+
+pub fn run(args: Args) -> eyre::Result<()> {
+    let workspace = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("xtask must be within the workspace");
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+
+    let mut command = Command::new(cargo);
+    command
+        .current_dir(workspace)
+        .env("DATABASE_URL", &args.database_url)
+        .args(["sqlx", "prepare", "--workspace"]);
+    if args.check {
+        command.arg("--check");
+    }
+    command.args(["--", "--all-targets", "--features", FEATURES]);
+
+    info!(check = args.check, "preparing offline query data");
+    let status = command
+        .status()
+        .wrap_err("failed to run sqlx-cli, is it installed?")?;
+    if !status.success() {
+        return Err(if args.check {
+            eyre!("offline query data is out of date, run `cargo xtask prepare` to update it")
+        } else {
+            eyre!("failed to prepare offline query data")
+        });
+    }
+
+    info!("offline query data is up to date");
+
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The database to check the queries against, with all migrations applied
+    #[arg(short, long, env = "DATABASE_URL")]
+    database_url: String,
+
+    /// Verify the offline query data is current instead of regenerating it
+    #[arg(long, default_value_t)]
+    check: bool,
+}
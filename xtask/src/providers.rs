@@ -0,0 +1,174 @@
+use crate::util;
+use database::{PgPool, Provider, ProviderConfiguration};
+use eyre::eyre;
+use secrecy::SecretString;
+use tracing::info;
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    let db = util::connect_to_database(&args.database_url, &[]).await?;
+
+    match args.command {
+        Command::List => list(&db).await,
+        Command::Create {
+            slug,
+            name,
+            kind,
+            client_id,
+            client_secret,
+        } => {
+            let config = kind.configuration(client_id, SecretString::new(client_secret));
+            let provider = Provider::create(&slug, &name, config, &db).await?;
+            info!(slug = %provider.slug, "created provider, enable it once it is ready");
+            Ok(())
+        }
+        Command::Enable { slug } => set_enabled(&slug, true, &db).await,
+        Command::Disable { slug } => set_enabled(&slug, false, &db).await,
+        Command::SetSecret {
+            slug,
+            client_secret,
+        } => set_secret(&slug, SecretString::new(client_secret), &db).await,
+    }
+}
+
+/// Show all the providers
+async fn list(db: &PgPool) -> eyre::Result<()> {
+    for provider in Provider::all(db).await? {
+        info!(
+            slug = %provider.slug,
+            name = %provider.name,
+            kind = provider.config.kind(),
+            enabled = provider.enabled,
+        );
+    }
+
+    Ok(())
+}
+
+/// Allow or prevent users from logging in with a provider
+async fn set_enabled(slug: &str, enabled: bool, db: &PgPool) -> eyre::Result<()> {
+    let mut provider = find(slug, db).await?;
+    provider.update().enabled(enabled).save(db).await?;
+
+    info!(%slug, enabled, "updated provider");
+    Ok(())
+}
+
+/// Replace the client secret of a provider, keeping the rest of its configuration
+async fn set_secret(slug: &str, client_secret: SecretString, db: &PgPool) -> eyre::Result<()> {
+    let mut provider = find(slug, db).await?;
+
+    let config = match provider.config.0.clone() {
+        ProviderConfiguration::Google { client_id, .. } => ProviderConfiguration::Google {
+            client_id,
+            client_secret,
+        },
+        ProviderConfiguration::GitHub { client_id, .. } => ProviderConfiguration::GitHub {
+            client_id,
+            client_secret,
+        },
+        ProviderConfiguration::Discord { client_id, .. } => ProviderConfiguration::Discord {
+            client_id,
+            client_secret,
+        },
+    };
+    provider.update().config(config).save(db).await?;
+
+    info!(%slug, "rotated provider client secret");
+    Ok(())
+}
+
+/// Get a provider, failing if it does not exist
+async fn find(slug: &str, db: &PgPool) -> eyre::Result<Provider> {
+    Provider::find(slug, db)
+        .await?
+        .ok_or_else(|| eyre!("provider {slug} does not exist"))
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The database the providers are stored in
+    #[arg(short, long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+#[clap(rename_all = "kebab-case")]
+enum Command {
+    /// List all the providers
+    List,
+    /// Add a new provider, disabled until it is enabled
+    Create {
+        /// A unique identifier used in URLs
+        slug: String,
+        /// The name shown to users
+        #[arg(long)]
+        name: String,
+        /// Which service the provider authenticates with
+        #[arg(long, value_enum)]
+        kind: Kind,
+        /// The OAuth client ID
+        #[arg(long)]
+        client_id: String,
+        /// The OAuth client secret
+        #[arg(long, env = "PROVIDER_CLIENT_SECRET", hide_env_values = true)]
+        client_secret: String,
+    },
+    /// Allow users to log in with a provider
+    Enable {
+        /// The provider's slug
+        slug: String,
+    },
+    /// Prevent users from logging in with a provider
+    Disable {
+        /// The provider's slug
+        slug: String,
+    },
+    /// Replace the client secret of a provider
+    SetSecret {
+        /// The provider's slug
+        slug: String,
+        /// The new OAuth client secret
+        #[arg(long, env = "PROVIDER_CLIENT_SECRET", hide_env_values = true)]
+        client_secret: String,
+    },
+}
+
+/// The services a provider can authenticate with
+#[derive(Clone, Copy, Debug, clap::ValueEnum)]
+enum Kind {
+    /// Google OpenID Connect
+    Google,
+    /// GitHub OAuth2
+    #[value(name = "github")]
+    GitHub,
+    /// Discord OAuth2
+    Discord,
+}
+
+impl Kind {
+    /// Build the configuration for the kind of provider
+    fn configuration(
+        self,
+        client_id: String,
+        client_secret: SecretString,
+    ) -> ProviderConfiguration {
+        match self {
+            Kind::Google => ProviderConfiguration::Google {
+                client_id,
+                client_secret,
+            },
+            Kind::GitHub => ProviderConfiguration::GitHub {
+                client_id,
+                client_secret,
+            },
+            Kind::Discord => ProviderConfiguration::Discord {
+                client_id,
+                client_secret,
+            },
+        }
+    }
+}
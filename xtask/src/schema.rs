@@ -0,0 +1,138 @@
+use eyre::{eyre, WrapErr};
+use serde_json::{json, Value};
+use tracing::{error, info};
+use url::Url;
+
+const PUBLISH_MUTATION: &str = r#"
+mutation PublishSchema($input: SchemaPublishInput!) {
+  schemaPublish(input: $input) {
+    __typename
+    ... on SchemaPublishSuccess {
+      valid
+    }
+    ... on SchemaPublishError {
+      valid
+      errors {
+        nodes {
+          message
+        }
+      }
+    }
+    ... on SchemaPublishMissingServiceError {
+      message
+    }
+    ... on SchemaPublishMissingUrlError {
+      message
+    }
+  }
+}
+"#;
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    match args.command {
+        Command::Publish(args) => publish(args).await,
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+#[clap(rename_all = "kebab-case")]
+enum Command {
+    /// Publish the federated schema to the schema registry
+    ///
+    /// The registry must be compatible with GraphQL Hive
+    Publish(PublishArgs),
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+struct PublishArgs {
+    /// The registry's GraphQL API
+    #[arg(
+        long,
+        default_value = "https://app.graphql-hive.com/graphql",
+        env = "HIVE_REGISTRY"
+    )]
+    registry: Url,
+
+    /// The token used to authenticate with the registry
+    #[arg(long, env = "HIVE_TOKEN")]
+    token: String,
+
+    /// The name of the service in the supergraph
+    #[arg(long, default_value = "identity")]
+    service: String,
+
+    /// The URL the gateway uses to reach the service
+    #[arg(long, default_value = "http://tha-identity.flycast/graphql")]
+    url: Url,
+
+    /// The commit the schema was built from
+    #[arg(long, env = "GITHUB_SHA")]
+    commit: String,
+
+    /// Who published the schema
+    #[arg(long, env = "GITHUB_ACTOR")]
+    author: String,
+}
+
+async fn publish(args: PublishArgs) -> eyre::Result<()> {
+    let client = reqwest::Client::builder()
+        .user_agent("the-hacker-app/identity-xtask")
+        .build()
+        .wrap_err("failed to build client")?;
+
+    let body = json!({
+        "query": PUBLISH_MUTATION,
+        "operationName": "PublishSchema",
+        "variables": {
+            "input": {
+                "sdl": graphql::sdl(),
+                "service": args.service,
+                "url": args.url.as_str(),
+                "commit": args.commit,
+                "author": args.author,
+            },
+        },
+    });
+
+    let response = client
+        .post(args.registry)
+        .bearer_auth(&args.token)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .wrap_err("failed to send schema to the registry")?
+        .json::<Value>()
+        .await
+        .wrap_err("invalid response from the registry")?;
+
+    if let Some(errors) = response.get("errors").and_then(Value::as_array) {
+        for error in errors {
+            error!(message = %error["message"], "registry returned an error");
+        }
+        return Err(eyre!("failed to publish schema"));
+    }
+
+    let result = &response["data"]["schemaPublish"];
+    match result["__typename"].as_str() {
+        Some("SchemaPublishSuccess") => {
+            info!(service = %args.service, "successfully published schema");
+            Ok(())
+        }
+        Some("SchemaPublishError") => {
+            let errors = result["errors"]["nodes"].as_array().into_iter().flatten();
+            for error in errors {
+                error!(message = %error["message"], "schema is invalid");
+            }
+            Err(eyre!("schema was rejected by the registry"))
+        }
+        _ => Err(eyre!("failed to publish schema: {}", result["message"])),
+    }
+}
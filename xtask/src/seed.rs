@@ -0,0 +1,139 @@
+use crate::util;
+use database::{
+    CustomDomain, Event, Organization, Organizer, PgPool, Provider, ProviderConfiguration, Role,
+    User,
+};
+use secrecy::SecretString;
+use tracing::info;
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    let db = util::connect_to_database(&args.database_url, &[]).await?;
+
+    let admin = admin(&args.email, &db).await?;
+    providers(&db).await?;
+    let organization = organization(admin.id, &db).await?;
+    event(organization.id, &args.custom_domain, &db).await?;
+
+    info!(
+        admin = %admin.primary_email,
+        organization = %organization.slug,
+        event = EVENT_SLUG,
+        custom_domain = %args.custom_domain,
+        "development environment is ready"
+    );
+
+    Ok(())
+}
+
+/// The slug of the sample organization
+const ORGANIZATION_SLUG: &str = "sample-organization";
+/// The slug of the sample event
+const EVENT_SLUG: &str = "sample-event";
+
+/// Get or create the admin user
+async fn admin(email: &str, db: &PgPool) -> eyre::Result<User> {
+    let mut user = match User::find_by_primary_email(email, db).await? {
+        Some(user) => user,
+        None => {
+            info!(%email, "creating admin user");
+            User::create("Admin", "User", email, db).await?
+        }
+    };
+
+    if !user.is_admin {
+        user.update().is_admin(true).save(db).await?;
+    }
+
+    Ok(user)
+}
+
+/// Add the common providers with dummy credentials, leaving them disabled
+async fn providers(db: &PgPool) -> eyre::Result<()> {
+    let dummy = || SecretString::new(String::from("change-me"));
+    let providers = [
+        (
+            "google",
+            "Google",
+            ProviderConfiguration::Google {
+                client_id: String::from("change-me"),
+                client_secret: dummy(),
+            },
+        ),
+        (
+            "github",
+            "GitHub",
+            ProviderConfiguration::GitHub {
+                client_id: String::from("change-me"),
+                client_secret: dummy(),
+            },
+        ),
+        (
+            "discord",
+            "Discord",
+            ProviderConfiguration::Discord {
+                client_id: String::from("change-me"),
+                client_secret: dummy(),
+            },
+        ),
+    ];
+
+    for (slug, name, config) in providers {
+        if !Provider::exists(slug, db).await? {
+            info!(%slug, "creating provider");
+            Provider::create(slug, name, config, db).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get or create the sample organization, directed by the admin
+async fn organization(admin_id: i32, db: &PgPool) -> eyre::Result<Organization> {
+    let organization = match Organization::find_by_slug(ORGANIZATION_SLUG, db).await? {
+        Some(organization) => organization,
+        None => {
+            info!(slug = ORGANIZATION_SLUG, "creating organization");
+            Organization::create(ORGANIZATION_SLUG, "Sample Organization", admin_id, db).await?
+        }
+    };
+
+    if Organizer::find(admin_id, organization.id, db)
+        .await?
+        .is_none()
+    {
+        Organizer::add(organization.id, admin_id, Role::Director, db).await?;
+    }
+
+    Ok(organization)
+}
+
+/// Create the sample event and its custom domain, if they don't exist
+async fn event(organization_id: i32, custom_domain: &str, db: &PgPool) -> eyre::Result<()> {
+    if !Event::exists(EVENT_SLUG, db).await? {
+        info!(slug = EVENT_SLUG, "creating event");
+        Event::create(EVENT_SLUG, "Sample Event", organization_id, db).await?;
+    }
+
+    if !CustomDomain::exists_by_name(custom_domain, db).await? {
+        info!(name = %custom_domain, "creating custom domain");
+        CustomDomain::create(custom_domain, EVENT_SLUG, db).await?;
+    }
+
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The database to populate
+    #[arg(short, long, env = "DATABASE_URL")]
+    database_url: String,
+
+    /// The primary email of the admin user
+    #[arg(long, default_value = "admin@example.com")]
+    email: String,
+
+    /// The custom domain for the sample event
+    #[arg(long, default_value = "sample-event.localhost")]
+    custom_domain: String,
+}
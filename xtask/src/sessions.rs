@@ -7,7 +7,7 @@ use url::Url;
 
 pub async fn run(args: Args) -> eyre::Result<()> {
     let cache = util::connect_to_cache(&args.cache_url).await?;
-    let db = util::connect_to_database(&args.database_url).await?;
+    let db = util::connect_to_database(&args.database_url, &[]).await?;
 
     // We can set fake values for the domain, secure, and signing key options since we're only
     // generating session tokens, not cookies.
@@ -18,6 +18,12 @@ pub async fn run(args: Args) -> eyre::Result<()> {
             generate(session_type, args.signing_key, db, manager).await
         }
         Command::Info { value } => info(value, manager).await,
+        Command::Revoke { value } => revoke(value, manager).await,
+        Command::RevokeUser { user_id } => {
+            let count = manager.revoke_all(user_id).await?;
+            info!(user_id, count, "revoked sessions");
+            Ok(())
+        }
     }
 }
 
@@ -55,6 +61,21 @@ enum Command {
         value: String,
     },
 
+    /// Revoke a session
+    ///
+    /// Immediately log out a session by providing either an ID or signed cookie
+    Revoke {
+        /// A cookie value or session ID
+        #[clap(value_name = "ID_OR_COOKIE")]
+        value: String,
+    },
+
+    /// Revoke all of a user's sessions
+    RevokeUser {
+        /// The ID of the user
+        user_id: i32,
+    },
+
     /// Generate a new session
     ///
     /// Manually generate a new session ID and cookie value for the desired type
@@ -81,6 +102,8 @@ async fn generate(
                 provider,
                 id: opts.id,
                 email: opts.email,
+                email_verified: opts.email_verified,
+                oauth_state: Some(manager.oauth_state(opts.return_to.as_ref())),
                 return_to: opts.return_to,
             })
         }
@@ -103,14 +126,22 @@ async fn generate(
     Ok(())
 }
 
-async fn info(value: String, manager: session::Manager) -> eyre::Result<()> {
-    let session = if value.len() == session::SERIALIZED_LENGTH {
-        manager.load_from_token(&value).await?
+/// Get the session ID from a cookie value or session ID
+fn session_id(value: String, manager: &session::Manager) -> Option<String> {
+    if value.len() == session::SERIALIZED_LENGTH {
+        manager.id_from_token(&value)
     } else if value.len() == 43 {
-        manager.load_from_id(&value).await?
+        Some(value)
     } else {
         error!("value is not a cookie or session ID");
-        return Ok(());
+        None
+    }
+}
+
+async fn info(value: String, manager: session::Manager) -> eyre::Result<()> {
+    let session = match session_id(value, &manager) {
+        Some(id) => manager.load_from_id(&id).await?,
+        None => return Ok(()),
     };
 
     let Some(session) = session else {
@@ -141,6 +172,20 @@ async fn info(value: String, manager: session::Manager) -> eyre::Result<()> {
     Ok(())
 }
 
+async fn revoke(value: String, manager: session::Manager) -> eyre::Result<()> {
+    let Some(id) = session_id(value, &manager) else {
+        return Ok(());
+    };
+
+    if manager.revoke_any(&id).await? {
+        info!(%id, "revoked session");
+    } else {
+        error!("session does not exist");
+    }
+
+    Ok(())
+}
+
 #[derive(Debug, clap::Subcommand)]
 #[clap(rename_all = "kebab-case")]
 enum SessionType {
@@ -166,6 +211,9 @@ struct RegistrationNeededOptions {
     /// The user's primary email
     #[arg(short, long)]
     email: String,
+    /// Whether the provider verified the email
+    #[arg(long)]
+    email_verified: bool,
     /// Where the user was redirected from
     #[arg(short, long)]
     return_to: Option<Url>,
@@ -0,0 +1,218 @@
+use crate::util;
+use database::{Participant, ParticipantRole, PgPool, User};
+use eyre::{bail, eyre, WrapErr};
+use graphql::EmailAddress;
+use serde::Deserialize;
+use sqlx::{Connection, PgConnection};
+use std::path::{Path, PathBuf};
+use tracing::{error, info};
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    let db = util::connect_to_database(&args.database_url, &[]).await?;
+
+    match args.command {
+        Command::Import { csv, batch_size } => import(&csv, batch_size.max(1), &db).await,
+    }
+}
+
+/// Create users, and their participation in events, from the rows of a CSV file. Rows that fail
+/// are reported and skipped without affecting the rest of the import.
+async fn import(path: &Path, batch_size: usize, db: &PgPool) -> eyre::Result<()> {
+    let mut reader = csv::Reader::from_path(path).wrap_err("failed to open csv")?;
+    let rows = reader.deserialize::<Row>().collect::<Vec<_>>();
+
+    let mut summary = Summary::default();
+    for (index, batch) in rows.chunks(batch_size).enumerate() {
+        let mut txn = db.begin().await?;
+
+        for (offset, row) in batch.iter().enumerate() {
+            // Counting from the first row after the header
+            let number = index * batch_size + offset + 1;
+            let row = match row {
+                Ok(row) => row,
+                Err(error) => {
+                    error!(row = number, %error, "invalid row");
+                    summary.failed += 1;
+                    continue;
+                }
+            };
+
+            // Each row gets a savepoint so a failure only undoes its own changes
+            let mut savepoint = txn.begin().await?;
+            match import_row(row, &mut savepoint).await {
+                Ok(outcome) => {
+                    savepoint.commit().await?;
+                    summary.record(outcome);
+                }
+                Err(error) => {
+                    savepoint.rollback().await?;
+                    error!(row = number, email = %row.email, %error, "failed to import row");
+                    summary.failed += 1;
+                }
+            }
+        }
+
+        txn.commit().await?;
+        info!(batch = index + 1, rows = batch.len(), "imported batch");
+    }
+
+    info!(
+        created = summary.created,
+        existing = summary.existing,
+        participants = summary.participants,
+        failed = summary.failed,
+        "finished import"
+    );
+
+    Ok(())
+}
+
+/// Create the user for a row if they don't already exist, and add them to the row's event
+async fn import_row(row: &Row, db: &mut PgConnection) -> eyre::Result<Outcome> {
+    if row.given_name.trim().is_empty() || row.family_name.trim().is_empty() {
+        bail!("given and family names are required");
+    }
+    let email = row.email.trim();
+    if !EmailAddress::is_well_formed(email) {
+        bail!("invalid email address");
+    }
+
+    let (user, created) = match User::find_by_primary_email(email, &mut *db).await? {
+        Some(user) => (user, false),
+        None => {
+            let user = User::create(
+                row.given_name.trim(),
+                row.family_name.trim(),
+                email,
+                &mut *db,
+            )
+            .await
+            .map_err(|error| {
+                if error.is_unique_violation() {
+                    eyre!("email address belongs to another user")
+                } else {
+                    error.into()
+                }
+            })?;
+            (user, true)
+        }
+    };
+
+    let participant = match (&row.event, row.role) {
+        (Some(event), role) => {
+            // Existing participants keep their role unless the row gives one
+            let role = role.map(ParticipantRole::from);
+            match Participant::add(event, user.id, role, &mut *db).await {
+                Ok(_) => true,
+                Err(error) if error.is_foreign_key_violation() => {
+                    bail!("event {event} does not exist")
+                }
+                Err(error) => return Err(error.into()),
+            }
+        }
+        (None, Some(_)) => bail!("a role can only be given with an event"),
+        (None, None) => false,
+    };
+
+    Ok(Outcome {
+        created,
+        participant,
+    })
+}
+
+/// What importing a row did
+struct Outcome {
+    /// Whether a new user was created
+    created: bool,
+    /// Whether the user was added to an event
+    participant: bool,
+}
+
+/// The totals for an import
+#[derive(Default)]
+struct Summary {
+    created: usize,
+    existing: usize,
+    participants: usize,
+    failed: usize,
+}
+
+impl Summary {
+    /// Count a successfully imported row
+    fn record(&mut self, outcome: Outcome) {
+        if outcome.created {
+            self.created += 1;
+        } else {
+            self.existing += 1;
+        }
+        if outcome.participant {
+            self.participants += 1;
+        }
+    }
+}
+
+/// A row of the import
+#[derive(Debug, Deserialize)]
+struct Row {
+    given_name: String,
+    family_name: String,
+    email: String,
+    /// The slug of the event the user participated in
+    event: Option<String>,
+    /// The part the user played in the event, defaults to hacker
+    role: Option<Role>,
+}
+
+/// The part a participant played in an event
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Role {
+    Hacker,
+    Mentor,
+    Judge,
+    Volunteer,
+    Sponsor,
+}
+
+impl From<Role> for ParticipantRole {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Hacker => ParticipantRole::Hacker,
+            Role::Mentor => ParticipantRole::Mentor,
+            Role::Judge => ParticipantRole::Judge,
+            Role::Volunteer => ParticipantRole::Volunteer,
+            Role::Sponsor => ParticipantRole::Sponsor,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The database to import into
+    #[arg(short, long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+#[clap(rename_all = "kebab-case")]
+enum Command {
+    /// Import users and their event participation from a CSV file
+    ///
+    /// The file must have a header with the `given_name`, `family_name`, and `email` columns.
+    /// The optional `event` and `role` columns add the user to an event. Users are matched to
+    /// existing accounts by their primary email. New accounts are claimed by the first login with
+    /// a verified email matching the one they were imported with.
+    Import {
+        /// The CSV file to import
+        #[arg(long)]
+        csv: PathBuf,
+
+        /// How many rows to import in each transaction
+        #[arg(long, default_value_t = 100)]
+        batch_size: usize,
+    },
+}
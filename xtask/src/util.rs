@@ -20,10 +20,11 @@ pub async fn connect_to_cache(url: &str) -> eyre::Result<ConnectionManager> {
     Ok(cache)
 }
 
-/// Connect to the database
-pub async fn connect_to_database(url: &str) -> eyre::Result<PgPool> {
+/// Connect to the database, applying the run-time settings to every connection
+pub async fn connect_to_database(url: &str, settings: &[(&str, &str)]) -> eyre::Result<PgPool> {
     let options = PgConnectOptions::from_str(url)
         .wrap_err("invalid database URL format")?
+        .options(settings.iter().copied())
         .log_statements(LevelFilter::Debug);
     let db = PgPool::connect_with(options)
         .await
@@ -0,0 +1,81 @@
+use crate::util;
+use database::{PgPool, Webhook, WebhookEvent};
+use rand::distributions::{Alphanumeric, DistString};
+use tracing::info;
+use url::Url;
+
+pub async fn run(args: Args) -> eyre::Result<()> {
+    let db = util::connect_to_database(&args.database_url, &[]).await?;
+
+    match args.command {
+        Command::List => list(&db).await,
+        Command::CreatePortal { portal_url } => create_portal(&portal_url, &db).await,
+    }
+}
+
+/// Show all the webhooks
+async fn list(db: &PgPool) -> eyre::Result<()> {
+    for webhook in Webhook::all(db).await? {
+        info!(
+            id = webhook.id,
+            url = %webhook.url,
+            events = ?webhook.events,
+            enabled = webhook.enabled,
+        );
+    }
+
+    Ok(())
+}
+
+/// Subscribe the portal to the events it used to receive when it was configured with
+/// `PORTAL_URL`. Endpoints that already have a webhook are left as is.
+async fn create_portal(portal_url: &Url, db: &PgPool) -> eyre::Result<()> {
+    let existing = Webhook::all(db).await?;
+
+    let endpoints = [
+        ("/webhooks/participant", WebhookEvent::ParticipantChanged),
+        (
+            "/webhooks/event-extension",
+            WebhookEvent::EventExtensionChanged,
+        ),
+    ];
+    for (path, event) in endpoints {
+        let url = portal_url.join(path)?;
+        if existing.iter().any(|webhook| webhook.url == url.as_str()) {
+            info!(%url, "webhook already exists");
+            continue;
+        }
+
+        let secret = Alphanumeric.sample_string(&mut rand::thread_rng(), 32);
+        let webhook = Webhook::create(url.as_str(), &secret, &[event], db).await?;
+        info!(id = webhook.id, %url, "created webhook");
+    }
+
+    Ok(())
+}
+
+#[derive(clap::Args, Debug)]
+#[clap(rename_all = "kebab-case")]
+pub struct Args {
+    /// The database the webhooks are stored in
+    #[arg(short, long, env = "DATABASE_URL")]
+    database_url: String,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+#[clap(rename_all = "kebab-case")]
+enum Command {
+    /// List all the webhooks
+    List,
+    /// Subscribe the portal to participant and event extension changes
+    ///
+    /// Replaces the `PORTAL_URL` setting. Run it once when deploying a version without it. The
+    /// signing secrets can be read by admins through the `webhooks` GraphQL query.
+    CreatePortal {
+        /// The base URL of the portal, i.e. https://portal.example.com
+        portal_url: Url,
+    },
+}